@@ -0,0 +1,473 @@
+use std::{
+    fmt::Write,
+    io::BufReader,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crossterm::style::Stylize;
+
+use crate::{
+    line_reader::{BufReadExt, LineEnding},
+    search::SearchResultWithReplacement,
+    store::{self, FileStore},
+};
+
+/// The default number of unchanged lines to show either side of a change
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// The changes made (or to be made) to a single file, as structured data rather than a formatted diff
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    /// The hunks of the file with changes, in order of line number
+    pub hunks: Vec<Hunk>,
+}
+
+/// A group of nearby changed lines in a file, along with the unchanged context lines around them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    /// The line numbers, counting from 1, that the hunk spans in the original file
+    pub original_lines: Range<usize>,
+    /// The line numbers, counting from 1, that the hunk spans once the changes have been made. This is empty
+    /// if every line in the hunk is deleted, in which case it starts at the line after where the hunk was
+    pub replaced_lines: Range<usize>,
+    /// Each line of the hunk in the original file, in order
+    pub lines: Vec<HunkLine>,
+}
+
+/// A line in a `Hunk`, without its line ending
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HunkLine {
+    /// An unchanged line, shown for context
+    Context {
+        line: String,
+        line_ending: LineEnding,
+    },
+    /// A line that is replaced with `replacement`, or deleted if `replacement` is `None`. The line ending is
+    /// unchanged
+    Changed {
+        original: String,
+        replacement: Option<String>,
+        line_ending: LineEnding,
+    },
+}
+
+/// Groups the replacements made (or to be made) in a file into hunks, with up to `context_lines` unchanged
+/// lines either side of each change.
+///
+/// The file at `path` in `store` is read to provide context lines, so this should be called before the
+/// replacements are written to it. If `delete_empty_lines` is set, lines whose replacement is empty are
+/// deleted (see `FileSearcherConfig::delete_empty_lines`). Returns no hunks if there are no changes.
+///
+/// NOTE: this should only be called with replacements from the file at `path`
+pub fn hunks(
+    store: &dyn FileStore,
+    path: &Path,
+    changes: &[SearchResultWithReplacement],
+    context_lines: usize,
+    delete_empty_lines: bool,
+) -> anyhow::Result<Vec<Hunk>> {
+    if changes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let reader = BufReader::new(store.open(path)?);
+    let lines = reader
+        .lines_with_endings()
+        .map(|line_result| {
+            line_result.map(|(line, ending)| (String::from_utf8_lossy(&line).into_owned(), ending))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut changes = changes
+        .iter()
+        .filter(|c| (1..=lines.len()).contains(&c.search_result.line_number))
+        .collect::<Vec<_>>();
+    changes.sort_by_key(|c| c.search_result.line_number);
+
+    let mut hunks = vec![];
+    // The number of lines deleted in previous hunks, which shifts the start of each hunk in the new file
+    let mut num_deleted = 0;
+    for group in group_into_hunks(&changes, context_lines) {
+        let first = group[0].search_result.line_number;
+        let last = group[group.len() - 1].search_result.line_number;
+        let start = first.saturating_sub(context_lines).max(1);
+        let end = (last + context_lines).min(lines.len());
+
+        let mut group_changes = group.iter().peekable();
+        let hunk_lines = (start..=end)
+            .map(|line_number| {
+                let (line, line_ending) = lines[line_number - 1].clone();
+                match group_changes.next_if(|c| c.search_result.line_number == line_number) {
+                    Some(change) => HunkLine::Changed {
+                        original: line,
+                        replacement: (!delete_empty_lines || !change.replacement.is_empty())
+                            .then(|| change.replacement.clone()),
+                        line_ending,
+                    },
+                    None => HunkLine::Context { line, line_ending },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let num_deleted_in_hunk = hunk_lines
+            .iter()
+            .filter(|line| {
+                matches!(
+                    line,
+                    HunkLine::Changed {
+                        replacement: None,
+                        ..
+                    }
+                )
+            })
+            .count();
+        let replaced_start = start - num_deleted;
+        hunks.push(Hunk {
+            original_lines: start..end + 1,
+            replaced_lines: replaced_start..replaced_start + hunk_lines.len() - num_deleted_in_hunk,
+            lines: hunk_lines,
+        });
+        num_deleted += num_deleted_in_hunk;
+    }
+
+    Ok(hunks)
+}
+
+/// Generates a unified diff for a file from the replacements made (or to be made) in it, by formatting the
+/// hunks from `hunks`. Line endings from the original file are preserved in the diff body. Returns an empty
+/// string if there are no changes.
+///
+/// NOTE: this should only be called with replacements from the file at `path`
+pub fn unified_diff(
+    path: &Path,
+    changes: &[SearchResultWithReplacement],
+    context_lines: usize,
+    delete_empty_lines: bool,
+) -> anyhow::Result<String> {
+    let hunks = hunks(
+        &store::FILESYSTEM,
+        path,
+        changes,
+        context_lines,
+        delete_empty_lines,
+    )?;
+    Ok(format_unified_diff(path, &hunks))
+}
+
+/// Formats hunks from the file at `path` as a unified diff. Returns an empty string if there are no hunks
+pub fn format_unified_diff(path: &Path, hunks: &[Hunk]) -> String {
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let path_display = path.display();
+    let mut diff = format!("--- {path_display}\n+++ {path_display}\n");
+    for hunk in hunks {
+        let (original, replaced) = (&hunk.original_lines, &hunk.replaced_lines);
+        // An empty range starts at the line before it
+        let _ = writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            format_range(
+                original.start - usize::from(original.is_empty()),
+                original.len()
+            ),
+            format_range(
+                replaced.start - usize::from(replaced.is_empty()),
+                replaced.len()
+            )
+        );
+        for line in &hunk.lines {
+            match line {
+                HunkLine::Context { line, line_ending } => {
+                    push_diff_line(&mut diff, ' ', line, *line_ending);
+                }
+                HunkLine::Changed {
+                    original,
+                    replacement,
+                    line_ending,
+                } => {
+                    push_diff_line(&mut diff, '-', original, *line_ending);
+                    if let Some(replacement) = replacement {
+                        push_diff_line(&mut diff, '+', replacement, *line_ending);
+                    }
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Colours a diff generated by `unified_diff` with ANSI escape codes: the file headers are bold, hunk headers
+/// cyan, removed lines red and added lines green
+pub fn colorize(diff: &str) -> String {
+    let mut colored = String::with_capacity(diff.len());
+    for (idx, line) in diff.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[content.len()..];
+        // The first two lines are the `---` and `+++` headers, which can't be distinguished from removed and
+        // added lines by their prefix alone
+        let _ = match content.chars().next() {
+            _ if idx < 2 => write!(colored, "{}", content.bold()),
+            Some('@') => write!(colored, "{}", content.cyan()),
+            Some('-') => write!(colored, "{}", content.red()),
+            Some('+') => write!(colored, "{}", content.green()),
+            _ => write!(colored, "{content}"),
+        };
+        colored.push_str(ending);
+    }
+    colored
+}
+
+/// Splits sorted changes into groups whose context windows overlap
+fn group_into_hunks<'a>(
+    changes: &[&'a SearchResultWithReplacement],
+    context_lines: usize,
+) -> Vec<Vec<&'a SearchResultWithReplacement>> {
+    let mut hunks: Vec<Vec<&SearchResultWithReplacement>> = vec![];
+    for &change in changes {
+        match hunks.last_mut() {
+            Some(hunk)
+                if change.search_result.line_number
+                    <= hunk[hunk.len() - 1].search_result.line_number + 2 * context_lines + 1 =>
+            {
+                hunk.push(change);
+            }
+            _ => hunks.push(vec![change]),
+        }
+    }
+    hunks
+}
+
+fn format_range(start: usize, count: usize) -> String {
+    if count == 1 {
+        start.to_string()
+    } else {
+        format!("{start},{count}")
+    }
+}
+
+fn push_diff_line(diff: &mut String, prefix: char, line: &str, ending: LineEnding) {
+    diff.push(prefix);
+    diff.push_str(line);
+    if ending == LineEnding::None {
+        diff.push_str("\n\\ No newline at end of file\n");
+    } else {
+        diff.push_str(ending.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchResult;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn create_test_file(temp_dir: &TempDir, content: &str) -> PathBuf {
+        let file_path = temp_dir.path().join("test.txt");
+        std::fs::write(&file_path, content).unwrap();
+        file_path
+    }
+
+    fn change(
+        path: &Path,
+        line_number: usize,
+        line: &str,
+        replacement: &str,
+        line_ending: LineEnding,
+    ) -> SearchResultWithReplacement {
+        SearchResultWithReplacement {
+            search_result: SearchResult {
+                path: Some(path.to_path_buf()),
+                line_number,
+                line: line.to_string(),
+                line_ending,
+                included: true,
+                match_count: 1,
+            },
+            replacement: replacement.to_string(),
+            replace_result: None,
+        }
+    }
+
+    #[test]
+    fn test_no_changes_produces_no_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "line 1\nline 2\n");
+        assert_eq!(
+            unified_diff(&path, &[], DEFAULT_CONTEXT_LINES, false).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_single_change_with_context() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "1\n2\n3\n4\nold\n6\n7\n8\n9\n");
+        let changes = [change(&path, 5, "old", "new", LineEnding::Lf)];
+
+        let diff = unified_diff(&path, &changes, DEFAULT_CONTEXT_LINES, false).unwrap();
+        let p = path.display();
+        assert_eq!(
+            diff,
+            format!("--- {p}\n+++ {p}\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-old\n+new\n 6\n 7\n 8\n")
+        );
+    }
+
+    #[test]
+    fn test_distant_changes_produce_separate_hunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = (1..=20).map(|i| i.to_string() + "\n").collect::<String>();
+        let path = create_test_file(&temp_dir, &content);
+        let changes = [
+            change(&path, 1, "1", "one", LineEnding::Lf),
+            change(&path, 20, "20", "twenty", LineEnding::Lf),
+        ];
+
+        let diff = unified_diff(&path, &changes, DEFAULT_CONTEXT_LINES, false).unwrap();
+        let p = path.display();
+        assert_eq!(
+            diff,
+            format!(
+                "--- {p}\n+++ {p}\n@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n@@ -17,4 +17,4 @@\n 17\n 18\n 19\n-20\n+twenty\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_nearby_changes_share_a_hunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "a\nb\nc\nd\n");
+        let changes = [
+            change(&path, 1, "a", "A", LineEnding::Lf),
+            change(&path, 4, "d", "D", LineEnding::Lf),
+        ];
+
+        let diff = unified_diff(&path, &changes, DEFAULT_CONTEXT_LINES, false).unwrap();
+        let p = path.display();
+        assert_eq!(
+            diff,
+            format!("--- {p}\n+++ {p}\n@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n")
+        );
+    }
+
+    #[test]
+    fn test_custom_context_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "1\n2\n3\n4\nold\n6\n7\n8\n9\n");
+        let changes = [change(&path, 5, "old", "new", LineEnding::Lf)];
+
+        let diff = unified_diff(&path, &changes, 1, false).unwrap();
+        let p = path.display();
+        assert_eq!(
+            diff,
+            format!("--- {p}\n+++ {p}\n@@ -4,3 +4,3 @@\n 4\n-old\n+new\n 6\n")
+        );
+    }
+
+    #[test]
+    fn test_preserves_line_endings() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "foo\r\nbar\r\nbaz");
+        let changes = [
+            change(&path, 2, "bar", "BAR", LineEnding::CrLf),
+            change(&path, 3, "baz", "BAZ", LineEnding::None),
+        ];
+
+        let diff = unified_diff(&path, &changes, DEFAULT_CONTEXT_LINES, false).unwrap();
+        let p = path.display();
+        assert_eq!(
+            diff,
+            format!(
+                "--- {p}\n+++ {p}\n@@ -1,3 +1,3 @@\n foo\r\n-bar\r\n+BAR\r\n-baz\n\\ No newline at end of file\n+BAZ\n\\ No newline at end of file\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_hunks() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_test_file(&temp_dir, "1\nold\n3\nold\r\n5\n6\n7\n");
+        let changes = [
+            change(&path, 4, "old", "", LineEnding::CrLf),
+            change(&path, 2, "old", "new", LineEnding::Lf),
+        ];
+
+        let context = |line: &str| HunkLine::Context {
+            line: line.to_string(),
+            line_ending: LineEnding::Lf,
+        };
+        assert_eq!(
+            hunks(&store::FILESYSTEM, &path, &changes, 1, true).unwrap(),
+            vec![Hunk {
+                original_lines: 1..6,
+                replaced_lines: 1..5,
+                lines: vec![
+                    context("1"),
+                    HunkLine::Changed {
+                        original: "old".to_string(),
+                        replacement: Some("new".to_string()),
+                        line_ending: LineEnding::Lf,
+                    },
+                    context("3"),
+                    HunkLine::Changed {
+                        original: "old".to_string(),
+                        replacement: None,
+                        line_ending: LineEnding::CrLf,
+                    },
+                    context("5"),
+                ],
+            }]
+        );
+        assert!(
+            hunks(&store::FILESYSTEM, &path, &[], 1, true)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_delete_empty_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = (1..=12).map(|i| i.to_string() + "\n").collect::<String>();
+        let path = create_test_file(&temp_dir, &content);
+        let changes = [
+            change(&path, 2, "2", "", LineEnding::Lf),
+            change(&path, 11, "11", "eleven", LineEnding::Lf),
+        ];
+
+        let diff = unified_diff(&path, &changes, 1, true).unwrap();
+        let p = path.display();
+        assert_eq!(
+            diff,
+            format!(
+                "--- {p}\n+++ {p}\n@@ -1,3 +1,2 @@\n 1\n-2\n 3\n@@ -10,3 +9,3 @@\n 10\n-11\n+eleven\n 12\n"
+            )
+        );
+
+        let path = create_test_file(&temp_dir, "only\n");
+        let changes = [change(&path, 1, "only", "", LineEnding::Lf)];
+        let diff = unified_diff(&path, &changes, DEFAULT_CONTEXT_LINES, true).unwrap();
+        let p = path.display();
+        assert_eq!(diff, format!("--- {p}\n+++ {p}\n@@ -1 +0,0 @@\n-only\n"));
+    }
+
+    #[test]
+    fn test_colorize() {
+        let diff = "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n-old\r\n+new\r\n same\n";
+        assert_eq!(
+            colorize(diff),
+            format!(
+                "{}\n{}\n{}\n{}\r\n{}\r\n same\n",
+                "--- a.txt".bold(),
+                "+++ a.txt".bold(),
+                "@@ -1,2 +1,2 @@".cyan(),
+                "-old".red(),
+                "+new".green(),
+            )
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::bail;
+use std::str::FromStr;
+
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Text encodings that files can be read and written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// The byte order mark written at the start of files with this encoding, if they have one
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => b"",
+            Encoding::Utf16Le => UTF16_LE_BOM,
+            Encoding::Utf16Be => UTF16_BE_BOM,
+        }
+    }
+
+    /// Detects the encoding of some bytes from their byte order mark, returning `None` if there isn't one
+    pub fn from_bom(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(UTF16_LE_BOM) {
+            Some(Encoding::Utf16Le)
+        } else if bytes.starts_with(UTF16_BE_BOM) {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            _ => bail!("Unsupported encoding '{s}': expected one of utf-8, utf-16le or utf-16be"),
+        }
+    }
+}
+
+/// Text decoded from a file, along with the information needed to encode it in the same way
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: Encoding,
+    /// Whether the original bytes started with a byte order mark, which is not included in `text`
+    pub has_bom: bool,
+}
+
+impl DecodedText {
+    /// Encodes `text` in the same encoding as the original bytes, including the byte order mark if there was one
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        encode(text, self.encoding, self.has_bom)
+    }
+}
+
+/// Decodes bytes into UTF-8 text.
+///
+/// If `encoding_override` is `None`, the encoding is detected from the byte order mark, falling back to UTF-8
+/// if there isn't one.
+pub fn decode(bytes: &[u8], encoding_override: Option<Encoding>) -> anyhow::Result<DecodedText> {
+    let bom_encoding = Encoding::from_bom(bytes);
+    let encoding = encoding_override.or(bom_encoding).unwrap_or_default();
+    let has_bom = bom_encoding == Some(encoding);
+    let content = if has_bom {
+        &bytes[encoding.bom().len()..]
+    } else {
+        bytes
+    };
+
+    let text = match encoding {
+        Encoding::Utf8 => String::from_utf8(content.to_vec())?,
+        Encoding::Utf16Le => decode_utf16(content, u16::from_le_bytes)?,
+        Encoding::Utf16Be => decode_utf16(content, u16::from_be_bytes)?,
+    };
+    Ok(DecodedText {
+        text,
+        encoding,
+        has_bom,
+    })
+}
+
+fn decode_utf16(bytes: &[u8], to_u16: fn([u8; 2]) -> u16) -> anyhow::Result<String> {
+    if !bytes.len().is_multiple_of(2) {
+        bail!("Invalid UTF-16: found an odd number of bytes");
+    }
+    let units = bytes.chunks_exact(2).map(|c| to_u16([c[0], c[1]]));
+    Ok(char::decode_utf16(units).collect::<Result<String, _>>()?)
+}
+
+/// Encodes UTF-8 text into the given encoding, optionally prefixed with a byte order mark
+pub fn encode(text: &str, encoding: Encoding, include_bom: bool) -> Vec<u8> {
+    let mut bytes = if include_bom {
+        encoding.bom().to_vec()
+    } else {
+        vec![]
+    };
+    match encoding {
+        Encoding::Utf8 => bytes.extend_from_slice(text.as_bytes()),
+        Encoding::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+        Encoding::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16_le_with_bom(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        bytes
+    }
+
+    #[test]
+    fn test_decode_utf8_without_bom() {
+        let decoded = decode("hello 世界".as_bytes(), None).unwrap();
+        assert_eq!(decoded.text, "hello 世界");
+        assert_eq!(decoded.encoding, Encoding::Utf8);
+        assert!(!decoded.has_bom);
+    }
+
+    #[test]
+    fn test_decode_utf16_le_with_bom() {
+        let decoded = decode(&utf16_le_with_bom("hello\r\nworld"), None).unwrap();
+        assert_eq!(decoded.text, "hello\r\nworld");
+        assert_eq!(decoded.encoding, Encoding::Utf16Le);
+        assert!(decoded.has_bom);
+    }
+
+    #[test]
+    fn test_decode_utf16_be_with_bom() {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend("héllo".encode_utf16().flat_map(u16::to_be_bytes));
+
+        let decoded = decode(&bytes, None).unwrap();
+        assert_eq!(decoded.text, "héllo");
+        assert_eq!(decoded.encoding, Encoding::Utf16Be);
+        assert!(decoded.has_bom);
+    }
+
+    #[test]
+    fn test_decode_with_override_and_no_bom() {
+        let bytes = "hello"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+
+        let decoded = decode(&bytes, Some(Encoding::Utf16Le)).unwrap();
+        assert_eq!(decoded.text, "hello");
+        assert!(!decoded.has_bom);
+        assert!(decode(&bytes, None).unwrap().text != "hello");
+    }
+
+    #[test]
+    fn test_decode_odd_number_of_utf16_bytes() {
+        assert!(decode(&[0xFF, 0xFE, 0x68], None).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_preserves_bom() {
+        let bytes = utf16_le_with_bom("before 🚀 after");
+        let decoded = decode(&bytes, None).unwrap();
+        assert_eq!(decoded.encode(&decoded.text), bytes);
+    }
+
+    #[test]
+    fn test_encoding_from_str() {
+        assert_eq!("UTF-8".parse::<Encoding>().unwrap(), Encoding::Utf8);
+        assert_eq!("utf-16le".parse::<Encoding>().unwrap(), Encoding::Utf16Le);
+        assert_eq!("utf16be".parse::<Encoding>().unwrap(), Encoding::Utf16Be);
+        assert!("latin1".parse::<Encoding>().is_err());
+    }
+}
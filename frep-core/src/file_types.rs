@@ -0,0 +1,64 @@
+/// The built-in file types, each with the glob patterns matching files of that type. Sorted by name
+const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("cs", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("lua", &["*.lua"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("php", &["*.php"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("scala", &["*.scala", "*.sc"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// The glob patterns matching files of the type `name` (e.g. `rust` or `py`), or `None` if there is no such
+/// type
+pub fn globs_for_type(name: &str) -> Option<&'static [&'static str]> {
+    FILE_TYPES
+        .iter()
+        .find(|(type_name, _)| *type_name == name)
+        .map(|(_, globs)| *globs)
+}
+
+/// The names of all of the built-in file types, in alphabetical order
+pub fn type_names() -> impl Iterator<Item = &'static str> {
+    FILE_TYPES.iter().map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_globs_for_type() {
+        assert_eq!(globs_for_type("rust"), Some(["*.rs"].as_slice()));
+        assert_eq!(globs_for_type("py"), Some(["*.py", "*.pyi"].as_slice()));
+        assert_eq!(globs_for_type("Rust"), None);
+        assert_eq!(globs_for_type("unknown"), None);
+    }
+
+    #[test]
+    fn test_type_names_sorted() {
+        let names = type_names().collect::<Vec<_>>();
+        assert!(names.is_sorted());
+    }
+}
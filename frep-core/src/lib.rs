@@ -1,6 +1,12 @@
+pub mod diff;
+pub mod encoding;
+pub mod file_types;
 pub mod line_reader;
 pub mod replace;
 pub mod run;
 pub mod search;
+pub mod store;
+pub mod syntax;
+pub mod undo;
 pub mod utils;
 pub mod validation;
@@ -28,6 +28,17 @@ impl LineEnding {
             LineEnding::CrLf => b"\r\n",
         }
     }
+
+    /// The line ending to write in place of this one when normalizing line endings to `target`, or this line
+    /// ending if `target` is `None`. A missing line ending (i.e. on the last line of a file without a trailing
+    /// newline) is never added or removed
+    #[inline]
+    pub fn normalized(self, target: Option<LineEnding>) -> LineEnding {
+        match target {
+            Some(target) if self != LineEnding::None && target != LineEnding::None => target,
+            _ => self,
+        }
+    }
 }
 
 /// An iterator that reads lines from a `BufRead` source while preserving line endings.
@@ -130,11 +141,121 @@ pub fn split_line_ending(line: &[u8]) -> (&[u8], LineEnding) {
     }
 }
 
+/// Rewrites each line ending in `text` as `target`. See `LineEnding::normalized`
+pub fn normalize_line_endings(text: &str, target: LineEnding) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (content, ending) = split_line_ending(line.as_bytes());
+        // Only ASCII line endings are removed, so the content is still valid UTF-8
+        normalized.push_str(&line[..content.len()]);
+        normalized.push_str(ending.normalized(Some(target)).as_str());
+    }
+    normalized
+}
+
+/// The length of `text` without the line endings at its end, along with the line ending to end it with so that
+/// it ends with exactly one: `target` if set, or otherwise the last line ending in `text`, falling back to
+/// `\n` if there are none. Returns `None` if `text` is empty or contains only line endings
+pub fn trailing_line_ending(
+    text: &[u8],
+    target: Option<LineEnding>,
+) -> Option<(usize, LineEnding)> {
+    let content_end = text.iter().rposition(|&b| b != b'\n' && b != b'\r')? + 1;
+    let line_ending = match target {
+        Some(target) if target != LineEnding::None => target,
+        _ => match text.iter().rposition(|&b| b == b'\n') {
+            Some(idx) if idx > 0 && text[idx - 1] == b'\r' => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        },
+    };
+    Some((content_end, line_ending))
+}
+
+/// Makes `text` end with exactly one line ending, adding one if it has none and collapsing multiple into one.
+/// See `trailing_line_ending` for the line ending used. Text that is empty or contains only line endings is
+/// left unchanged
+pub fn ensure_trailing_newline(text: &str, target: Option<LineEnding>) -> String {
+    match trailing_line_ending(text.as_bytes(), target) {
+        // The content ends before an ASCII line ending, so is valid UTF-8
+        Some((content_end, line_ending)) => {
+            format!("{}{}", &text[..content_end], line_ending.as_str())
+        }
+        None => text.to_owned(),
+    }
+}
+
+/// The number of lines ending with each style of line ending. Lines without a line ending, such as the last
+/// line of a file, aren't counted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LineEndingCounts {
+    pub lf: usize,
+    pub crlf: usize,
+}
+
+impl LineEndingCounts {
+    /// Whether more than one style of line ending is used
+    pub fn is_mixed(self) -> bool {
+        self.lf > 0 && self.crlf > 0
+    }
+}
+
+/// Counts the line endings of each style in `reader`
+pub fn count_line_endings(reader: impl BufRead) -> std::io::Result<LineEndingCounts> {
+    let mut counts = LineEndingCounts::default();
+    for line in reader.lines_with_endings() {
+        match line?.1 {
+            LineEnding::Lf => counts.lf += 1,
+            LineEnding::CrLf => counts.crlf += 1,
+            LineEnding::None => {}
+        }
+    }
+    Ok(counts)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Cursor;
 
+    #[test]
+    fn test_count_line_endings() {
+        let counts = count_line_endings(Cursor::new("a\nb\r\nc\nd")).unwrap();
+        assert_eq!(counts, LineEndingCounts { lf: 2, crlf: 1 });
+        assert!(counts.is_mixed());
+
+        let counts = count_line_endings(Cursor::new("a\r\nb\r\n")).unwrap();
+        assert_eq!(counts, LineEndingCounts { lf: 0, crlf: 2 });
+        assert!(!counts.is_mixed());
+    }
+
+    #[test]
+    fn test_ensure_trailing_newline() {
+        assert_eq!(ensure_trailing_newline("a\nb", None), "a\nb\n");
+        assert_eq!(ensure_trailing_newline("a\r\nb", None), "a\r\nb\r\n");
+        assert_eq!(ensure_trailing_newline("a\nb\n\r\n\n", None), "a\nb\n");
+        assert_eq!(ensure_trailing_newline("a\nb\r\n", None), "a\nb\r\n");
+        assert_eq!(ensure_trailing_newline("a", None), "a\n");
+        assert_eq!(
+            ensure_trailing_newline("a\nb", Some(LineEnding::CrLf)),
+            "a\nb\r\n"
+        );
+        assert_eq!(ensure_trailing_newline("", None), "");
+        assert_eq!(ensure_trailing_newline("\n\n", None), "\n\n");
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        let text = "a\r\nb\nc\r\nd";
+        assert_eq!(normalize_line_endings(text, LineEnding::Lf), "a\nb\nc\nd");
+        assert_eq!(
+            normalize_line_endings(text, LineEnding::CrLf),
+            "a\r\nb\r\nc\r\nd"
+        );
+        assert_eq!(normalize_line_endings(text, LineEnding::None), text);
+        assert_eq!(normalize_line_endings("a\n", LineEnding::CrLf), "a\r\n");
+        assert_eq!(normalize_line_endings("", LineEnding::CrLf), "");
+    }
+
     #[test]
     fn test_split_line_ending_empty() {
         assert_eq!(split_line_ending(b""), ("".as_bytes(), LineEnding::None));
@@ -1,13 +1,297 @@
+use anyhow::{Context, bail};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use regex::bytes;
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    fmt,
     fs::{self, File},
-    io::{BufReader, BufWriter, Write},
-    path::Path,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    num::NonZero,
+    ops::Range,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
 };
 use tempfile::NamedTempFile;
 
-use crate::search::{SearchResult, SearchResultWithReplacement, SearchType};
-use crate::{line_reader::BufReadExt, search};
+use crate::encoding::{self, Encoding};
+use crate::search::{FileSearcherConfig, SearchResult, SearchResultWithReplacement, SearchType};
+use crate::store::{FileStore, FileWriter};
+use crate::syntax::{self, Language, StringTracker};
+use crate::{
+    line_reader::{self, BufReadExt},
+    search,
+};
+
+/// The error returned when a file is skipped because its contents appear to be binary
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BinaryFileError;
+
+impl fmt::Display for BinaryFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("skipped binary file")
+    }
+}
+
+impl std::error::Error for BinaryFileError {}
+
+/// The error returned when `check_unchanged` is set and a file is modified by another process after it was
+/// read, in which case the replacements are not written to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileChangedError;
+
+impl fmt::Display for FileChangedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "file was modified while replacements were being made, so they were not written",
+        )
+    }
+}
+
+impl std::error::Error for FileChangedError {}
+
+/// The error returned when a file turns out to be larger than the maximum size that can be read into memory,
+/// such as because it grew after its size was checked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileTooLargeError {
+    pub max_size: u64,
+}
+
+impl fmt::Display for FileTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "file is larger than the maximum in-memory size of {} bytes",
+            self.max_size
+        )
+    }
+}
+
+impl std::error::Error for FileTooLargeError {}
+
+/// The error returned when a file contains bytes that aren't valid UTF-8 in multiline mode with `join_lines`
+/// set, in which case the replacements are not written to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidUtf8Error {
+    /// The offset in bytes of the first invalid byte in the file
+    pub offset: usize,
+}
+
+impl fmt::Display for InvalidUtf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid UTF-8 at byte offset {}, so replacements were not written",
+            self.offset
+        )
+    }
+}
+
+impl std::error::Error for InvalidUtf8Error {}
+
+/// A problem with a file that didn't stop replacements from being made in it, but that should be reported
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplaceWarning {
+    /// The file contains bytes that aren't valid UTF-8, the first of which is at this byte offset. Lines
+    /// containing them weren't searched, and were written unchanged (see `FileSearcherConfig::lossy`)
+    InvalidUtf8 { offset: usize },
+    /// The file couldn't be replaced by renaming a temporary file over it, for this reason, so was overwritten
+    /// in place instead, which isn't atomic. This isn't reported when `FileSearcherConfig::in_place` is set
+    OverwrittenInPlace { reason: String },
+}
+
+impl fmt::Display for ReplaceWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplaceWarning::InvalidUtf8 { offset } => write!(
+                f,
+                "invalid UTF-8 at byte offset {offset}, so lines that aren't valid UTF-8 were left unchanged"
+            ),
+            ReplaceWarning::OverwrittenInPlace { reason } => {
+                write!(f, "{reason}, so the file was overwritten in place")
+            }
+        }
+    }
+}
+
+/// The outcome of replacing in a file, such as with `replace_all_in_file`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplacedFile {
+    /// The number of replacements made. See `replace_all_in_file` for how replacements are counted
+    pub num_replacements: usize,
+    /// Any problems with the file that didn't stop replacements from being made in it
+    pub warnings: Vec<ReplaceWarning>,
+}
+
+/// An external command whose output each match is replaced with, as an alternative to replacement text. The
+/// matched text is written to the command's stdin, and its stdout, with a single trailing newline removed, is
+/// used as the replacement. The command is run with `sh -c` (or `cmd /C` on Windows).
+///
+/// Spawning a process for each match is slow, so outputs are cached by matched text, with the cache shared
+/// between clones. The command is therefore run once for each distinct match, and should always produce the
+/// same output for the same input.
+#[derive(Clone, Debug)]
+pub struct ReplaceCommand {
+    command: String,
+    cache: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl ReplaceCommand {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            cache: Arc::default(),
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// The replacement for `matched`, running the command if its output for `matched` isn't already cached.
+    /// Errors if the command can't be run, exits unsuccessfully, or outputs invalid UTF-8
+    pub fn run(&self, matched: &str) -> anyhow::Result<String> {
+        if let Some(output) = self.cache.lock().unwrap().get(matched) {
+            return Ok(output.clone());
+        }
+
+        let mut child = shell_command(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run replacement command `{}`", self.command))?;
+        let mut stdin = child.stdin.take().expect("stdin should be piped");
+        // Written on a separate thread so that a command producing output before reading all of its input
+        // can't deadlock. Commands that don't read their input cause a broken pipe, which is ignored
+        let output = thread::scope(|scope| {
+            scope.spawn(move || stdin.write_all(matched.as_bytes()));
+            child.wait_with_output()
+        })?;
+        if !output.status.success() {
+            bail!(
+                "Replacement command `{command}` failed ({status}): {stderr}",
+                command = self.command,
+                status = output.status,
+                stderr = String::from_utf8_lossy(&output.stderr).trim_end(),
+            );
+        }
+
+        let mut output = String::from_utf8(output.stdout).with_context(|| {
+            format!(
+                "Replacement command `{}` output invalid UTF-8",
+                self.command
+            )
+        })?;
+        if output.ends_with('\n') {
+            output.pop();
+            if output.ends_with('\r') {
+                output.pop();
+            }
+        }
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(matched.to_owned(), output.clone());
+        Ok(output)
+    }
+}
+
+/// A command run on each file after replacements have been made in it, such as a formatter. Each `{}` in the
+/// command is replaced with the path of the file, quoted for the shell, with the path appended to the end of
+/// the command if there are none. As with `ReplaceCommand`, the command is run with `sh -c` (or `cmd /C` on
+/// Windows).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PostCommand {
+    command: String,
+}
+
+impl PostCommand {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// The command to run for the file at `path`, with the path substituted in
+    pub fn command_for(&self, path: &Path) -> String {
+        let path = shell_quote(&path.to_string_lossy());
+        if self.command.contains("{}") {
+            self.command.replace("{}", &path)
+        } else {
+            format!("{} {path}", self.command)
+        }
+    }
+
+    /// Runs the command for the file at `path`, waiting for it to finish. Errors if the command can't be run or
+    /// exits unsuccessfully
+    pub fn run(&self, path: &Path) -> anyhow::Result<()> {
+        let command = self.command_for(path);
+        let output = shell_command(&command)
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run post-replacement command `{command}`"))?;
+        if !output.status.success() {
+            bail!(
+                "Post-replacement command `{command}` failed ({status}): {stderr}",
+                status = output.status,
+                stderr = String::from_utf8_lossy(&output.stderr).trim_end(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A command that runs `command` with `sh -c`, or `cmd /C` on Windows
+fn shell_command(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut shell_command = Command::new(shell);
+    shell_command.arg(flag).arg(command);
+    shell_command
+}
+
+/// Quotes `s` so that it is passed to a command run by `shell_command` as a single argument, without being
+/// interpreted by the shell
+fn shell_quote(s: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+/// Returns a `BinaryFileError` if the contents of the file appear to be binary (see `search::is_binary_file`).
+/// The check is skipped if `include_binary` is set, or if a UTF-16 encoding is configured, since UTF-16
+/// text without a byte order mark contains NUL bytes
+pub fn check_not_binary(file_path: &Path, config: &FileSearcherConfig) -> anyhow::Result<()> {
+    let utf16 = config.encoding.is_some_and(|enc| enc != Encoding::Utf8);
+    if !config.include_binary && !utf16 && search::is_binary_file(config.store(), file_path)? {
+        return Err(BinaryFileError.into());
+    }
+    Ok(())
+}
+
+/// Returns an error if the file is UTF-16 (see `is_utf16`), as UTF-16 files are only replaced in memory, so
+/// can't be replaced line by line, such as to collect line-level results. These files would otherwise be
+/// searched as if they were UTF-8, so would appear to have no matches
+pub fn check_not_utf16(file_path: &Path, config: &FileSearcherConfig) -> anyhow::Result<()> {
+    if is_utf16(file_path, config)? {
+        bail!(
+            "UTF-16 files can't be replaced line by line, as is needed for options such as `dry_run`, `diff` and `occurrence`"
+        );
+    }
+    Ok(())
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ReplaceResult {
@@ -15,91 +299,571 @@ pub enum ReplaceResult {
     Error(String),
 }
 
+/// Writes the replacements in `results` to their file, skipping any for which `should_replace` returns
+/// `false`. The `replace_result` of each replacement that was attempted is updated, and the file is left
+/// untouched if there are no replacements to attempt, or if every line to replace has changed since it was
+/// searched. Lines replaced with empty text are removed, along with their line endings, if `delete_empty_lines`
+/// is set, and replaced lines identical to the previous line are removed if `collapse_duplicate_lines` is set,
+/// including those whose replacement leaves them unchanged.
+///
+/// Returns any warnings about the file, such as it having been overwritten in place.
+///
 /// NOTE: this should only be called with search results from the same file
 // TODO: enforce the above via types
-pub fn replace_in_file(results: &mut [SearchResultWithReplacement]) -> anyhow::Result<()> {
+pub fn replace_in_file<F>(
+    results: &mut [SearchResultWithReplacement],
+    config: &FileSearcherConfig,
+    should_replace: F,
+) -> anyhow::Result<Vec<ReplaceWarning>>
+where
+    F: Fn(&SearchResultWithReplacement) -> bool,
+{
     let file_path = match results {
         [r, ..] => r.search_result.path.clone(),
-        [] => return Ok(()),
+        [] => return Ok(vec![]),
     };
     debug_assert!(results.iter().all(|r| r.search_result.path == file_path));
 
     let mut line_map = results
         .iter_mut()
+        .filter(|res| should_replace(res))
         .map(|res| (res.search_result.line_number, res))
         .collect::<HashMap<_, _>>();
+    if line_map.is_empty() {
+        return Ok(vec![]);
+    }
 
     let file_path = file_path.expect("File path must be present when searching in files");
-    let parent_dir = file_path.parent().unwrap_or(Path::new("."));
-    let temp_output_file = NamedTempFile::new_in(parent_dir)?;
+    let store = config.store();
+    let mut output = store.create(&file_path, config)?;
+    write_replaced_lines(store.open(&file_path)?, &mut output, &mut line_map, config)?;
+
+    let replaced = results
+        .iter()
+        .any(|r| r.replace_result == Some(ReplaceResult::Success));
+    if !replaced {
+        return Ok(vec![]);
+    }
+    if config.ensure_trailing_newline {
+        ensure_trailing_newline_in_file(output.as_mut(), config.line_ending)?;
+    }
+    output.commit()
+}
 
-    // Scope the file operations so they're closed before rename
-    {
-        let input = File::open(file_path.clone())?;
-        let reader = BufReader::new(input);
-
-        let output = File::create(temp_output_file.path())?;
-        let mut writer = BufWriter::new(output);
-
-        for (idx, line_result) in reader.lines_with_endings().enumerate() {
-            let line_number = idx + 1; // Ensure line-number is 1-indexed
-            let (mut line, line_ending) = line_result?;
-            if let Some(res) = line_map.get_mut(&line_number) {
-                if line == res.search_result.line.as_bytes() {
-                    line = res.replacement.as_bytes().to_vec();
-                    res.replace_result = Some(ReplaceResult::Success);
-                } else {
-                    res.replace_result = Some(ReplaceResult::Error(
-                        "File changed since last search".to_owned(),
-                    ));
+/// Writes the lines read from `input` to `output`, with the replacements in `line_map`. Each line is only
+/// replaced if it is unchanged since it was searched
+fn write_replaced_lines(
+    input: impl Read,
+    output: impl Write,
+    line_map: &mut HashMap<usize, &mut SearchResultWithReplacement>,
+    config: &FileSearcherConfig,
+) -> anyhow::Result<()> {
+    let reader = BufReader::new(input);
+    let mut writer = BufWriter::new(output);
+    // The original contents of the previous line, only tracked when collapsing duplicate lines
+    let mut previous_line: Option<Vec<u8>> = None;
+
+    for (idx, line_result) in reader.lines_with_endings().enumerate() {
+        let line_number = idx + 1; // Ensure line-number is 1-indexed
+        let (mut line, line_ending) = line_result?;
+        let previous = if config.collapse_duplicate_lines {
+            previous_line.replace(line.clone())
+        } else {
+            None
+        };
+        if let Some(res) = line_map.get_mut(&line_number) {
+            if line == res.search_result.line.as_bytes() {
+                res.replace_result = Some(ReplaceResult::Success);
+                let is_duplicate = previous.is_some_and(|previous| previous == line);
+                if is_duplicate || (config.delete_empty_lines && res.replacement.is_empty()) {
+                    // Lines are written sequentially, so the line can be removed by not writing it
+                    continue;
                 }
+                line = res.replacement.as_bytes().to_vec();
+            } else {
+                res.replace_result = Some(ReplaceResult::Error(
+                    "File changed since last search".to_owned(),
+                ));
             }
-            line.extend(line_ending.as_bytes());
-            writer.write_all(&line)?;
         }
+        line.extend(line_ending.normalized(config.line_ending).as_bytes());
+        writer.write_all(&line)?;
+    }
 
-        writer.flush()?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A temporary file that the new contents of a file are written to before they replace the original
+struct TempOutputFile {
+    file: NamedTempFile,
+    /// Whether the temporary file is known to be in a location that can't be renamed over the original
+    in_place: bool,
+    /// Why the temporary file couldn't be created alongside the original, if that is why `in_place` is set
+    fallback_reason: Option<String>,
+}
+
+impl TempOutputFile {
+    /// Creates a temporary file in `temp_dir` from the config if set, or otherwise in the same directory as
+    /// `file_path` so that it can be renamed over the original. If a temporary file can't be created alongside
+    /// the original (e.g. because the directory is read-only), or if `in_place` is set in the config, one is
+    /// created in the system temporary directory instead, and the original is overwritten in place
+    fn new(file_path: &Path, config: &FileSearcherConfig) -> io::Result<Self> {
+        if let Some(temp_dir) = &config.temp_dir {
+            return Ok(Self {
+                file: NamedTempFile::new_in(temp_dir)?,
+                in_place: config.in_place,
+                fallback_reason: None,
+            });
+        }
+        if config.in_place {
+            return Ok(Self {
+                file: NamedTempFile::new()?,
+                in_place: true,
+                fallback_reason: None,
+            });
+        }
+
+        let parent_dir = file_path.parent().unwrap_or(Path::new("."));
+        match NamedTempFile::new_in(parent_dir) {
+            Ok(file) => Ok(Self {
+                file,
+                in_place: false,
+                fallback_reason: None,
+            }),
+            Err(e) => {
+                log::warn!(
+                    "Failed to create a temporary file in {parent_display}, so {path_display} will be overwritten in place: {e}",
+                    parent_display = parent_dir.display(),
+                    path_display = file_path.display(),
+                );
+                Ok(Self {
+                    file: NamedTempFile::new()?,
+                    in_place: true,
+                    fallback_reason: Some(format!(
+                        "failed to create a temporary file in {}: {e}",
+                        parent_dir.display()
+                    )),
+                })
+            }
+        }
+    }
+
+    /// Replaces the original file with the temporary file, by renaming the temporary file over it where
+    /// possible. If this fails (e.g. because the temporary file is on a different filesystem), the contents
+    /// are instead copied into the original file, which retains its own permissions and ownership. In that
+    /// case the modification time is restored afterwards if `keep_mtime` is set. Files with other hard links
+    /// are always overwritten in place, since renaming over them would detach them from their other links.
+    ///
+    /// Returns a `ReplaceWarning::OverwrittenInPlace` if the file was overwritten in place because the
+    /// temporary file couldn't be created alongside it or renamed over it
+    fn persist(
+        self,
+        file_path: &Path,
+        original_metadata: &fs::Metadata,
+        keep_mtime: bool,
+    ) -> io::Result<Option<ReplaceWarning>> {
+        let (temp_file, fallback_reason) = if self.in_place
+            || has_other_hard_links(original_metadata)
+        {
+            (self.file, self.fallback_reason)
+        } else {
+            match self.file.persist(file_path) {
+                Ok(_) => return Ok(None),
+                Err(e) => {
+                    log::warn!(
+                        "Failed to move temporary file to {path_display}, so it will be overwritten in place: {error}",
+                        path_display = file_path.display(),
+                        error = e.error,
+                    );
+                    let reason = format!("failed to move the temporary file over it: {}", e.error);
+                    (e.file, Some(reason))
+                }
+            }
+        };
+
+        overwrite_in_place(
+            file_path,
+            &mut temp_file.reopen()?,
+            original_metadata,
+            keep_mtime,
+        )?;
+        Ok(fallback_reason.map(|reason| ReplaceWarning::OverwrittenInPlace { reason }))
+    }
+}
+
+/// The `FileWriter` of an `FsStore`, which writes the new contents of a file to a `TempOutputFile` that then
+/// replaces the original, applying the options for writing files in the config, such as `backup_suffix`.
+///
+/// The temporary file is only created once the new contents are first written. The metadata of the original
+/// is read when the writer is created, which should be before the file is read, so that `check_unchanged`
+/// detects changes made to the file since it was read
+pub(crate) struct FsWriter<'a> {
+    /// The file to replace, which is the target of the path the writer was created with if that's a symlink
+    file_path: PathBuf,
+    original_metadata: fs::Metadata,
+    temp_file: Option<TempOutputFile>,
+    config: &'a FileSearcherConfig,
+}
+
+impl<'a> FsWriter<'a> {
+    /// Starts replacing the file at `file_path`, or the file it links to if it's a symlink (see
+    /// `resolve_symlink`)
+    pub(crate) fn new(file_path: &Path, config: &'a FileSearcherConfig) -> anyhow::Result<Self> {
+        let file_path = resolve_symlink(file_path, config.no_follow_symlink_writes)?;
+        let original_metadata = fs::metadata(&file_path)?;
+        Ok(Self {
+            file_path,
+            original_metadata,
+            temp_file: None,
+            config,
+        })
+    }
+
+    /// Takes the temporary file that the new contents are written to, creating it if it doesn't exist yet
+    fn take_temp_file(&mut self) -> io::Result<TempOutputFile> {
+        match self.temp_file.take() {
+            Some(temp_file) => Ok(temp_file),
+            None => TempOutputFile::new(&self.file_path, self.config),
+        }
+    }
+
+    fn temp_file(&mut self) -> io::Result<&mut NamedTempFile> {
+        let temp_file = self.take_temp_file()?;
+        Ok(&mut self.temp_file.insert(temp_file).file)
+    }
+}
+
+impl Read for FsWriter<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.temp_file()?.read(buf)
+    }
+}
+
+impl Write for FsWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.temp_file()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.temp_file {
+            Some(temp_file) => temp_file.file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Seek for FsWriter<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.temp_file()?.seek(pos)
+    }
+}
+
+impl FileWriter for FsWriter<'_> {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.temp_file()?.as_file().set_len(len)
+    }
+
+    fn commit(mut self: Box<Self>) -> anyhow::Result<Vec<ReplaceWarning>> {
+        let temp_file = self.take_temp_file()?;
+        let (file_path, original_metadata, config) =
+            (&self.file_path, &self.original_metadata, self.config);
+        copy_metadata(
+            original_metadata,
+            temp_file.file.as_file(),
+            config.keep_mtime,
+        )?;
+        check_unchanged(file_path, original_metadata, config)?;
+        if let Some(suffix) = &config.backup_suffix {
+            create_backup(file_path, suffix)?;
+        }
+        if let Some(undo_journal) = &config.undo_journal {
+            undo_journal.record(file_path)?;
+        }
+        let warning = temp_file.persist(file_path, original_metadata, config.keep_mtime)?;
+        Ok(warning.into_iter().collect())
+    }
+}
+
+/// Applies the options that change the contents of a file as a whole once its replacements have been made,
+/// normalizing its line endings if `line_ending` is set and ensuring it ends with a single line ending if
+/// `ensure_trailing_newline` is set
+fn finish_content(content: String, config: &FileSearcherConfig) -> String {
+    let content = match config.line_ending {
+        Some(line_ending) => line_reader::normalize_line_endings(&content, line_ending),
+        None => content,
+    };
+    if config.ensure_trailing_newline {
+        line_reader::ensure_trailing_newline(&content, config.line_ending)
+    } else {
+        content
+    }
+}
+
+/// Makes the new contents written to `file` end with exactly one line ending, as with
+/// `line_reader::ensure_trailing_newline`. The contents are read backwards from their end only as far as needed
+/// to find the line endings at their end and the line ending to use, so large files aren't read in full
+fn ensure_trailing_newline_in_file(
+    file: &mut dyn FileWriter,
+    target: Option<line_reader::LineEnding>,
+) -> io::Result<()> {
+    const CHUNK_SIZE: u64 = 8192;
+
+    let mut start = file.seek(SeekFrom::End(0))?;
+    let mut tail = vec![];
+    loop {
+        let chunk_start = start.saturating_sub(CHUNK_SIZE);
+        let chunk_len = usize::try_from(start - chunk_start).expect("chunks fit in memory");
+        let mut chunk = vec![0; chunk_len];
+        file.seek(SeekFrom::Start(chunk_start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+        start = chunk_start;
+
+        // The last line ending is only known once a `\n` is found with the byte before it, and the end of
+        // the content once a byte other than a line ending is found
+        let has_last_line_ending = tail.iter().skip(1).any(|&b| b == b'\n');
+        let has_content = tail.iter().any(|&b| b != b'\n' && b != b'\r');
+        if start == 0 || (has_last_line_ending && has_content) {
+            break;
+        }
+    }
+
+    if let Some((content_end, line_ending)) = line_reader::trailing_line_ending(&tail, target) {
+        let content_end = start + content_end as u64;
+        file.set_len(content_end)?;
+        file.seek(SeekFrom::Start(content_end))?;
+        file.write_all(line_ending.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Truncates the file at `file_path` and writes the contents of `contents` to it, so that it keeps its own
+/// permissions and ownership. If `keep_mtime` is set, the modification time is restored afterwards. Unlike
+/// renaming a temporary file over the original, this isn't atomic, so the file is left incomplete if
+/// writing is interrupted
+fn overwrite_in_place(
+    file_path: &Path,
+    contents: &mut impl Read,
+    original_metadata: &fs::Metadata,
+    keep_mtime: bool,
+) -> io::Result<()> {
+    let mut output = File::options().write(true).truncate(true).open(file_path)?;
+    io::copy(contents, &mut output)?;
+    if keep_mtime {
+        output.set_modified(original_metadata.modified()?)?;
+    }
+    Ok(())
+}
+
+/// Copies the permissions of the original file to the temporary file that will replace it. On Unix, the
+/// ownership is also copied where possible. If `keep_mtime` is set, the modification time is copied too,
+/// which is retained when the temporary file is renamed over the original
+fn copy_metadata(
+    original_metadata: &fs::Metadata,
+    temp_file: &File,
+    keep_mtime: bool,
+) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // Changing ownership usually requires elevated privileges, so failures are ignored
+        let _ = std::os::unix::fs::fchown(
+            temp_file,
+            Some(original_metadata.uid()),
+            Some(original_metadata.gid()),
+        );
+    }
+    temp_file.set_permissions(original_metadata.permissions())?;
+    if keep_mtime {
+        temp_file.set_modified(original_metadata.modified()?)?;
+    }
+    Ok(())
+}
+
+/// Returns a `FileChangedError` if `check_unchanged` is set and the size or modification time of the file no
+/// longer match `original_metadata`, which was read along with its contents
+fn check_unchanged(
+    file_path: &Path,
+    original_metadata: &fs::Metadata,
+    config: &FileSearcherConfig,
+) -> anyhow::Result<()> {
+    if !config.check_unchanged {
+        return Ok(());
+    }
+    let metadata = fs::metadata(file_path)?;
+    if metadata.len() != original_metadata.len()
+        || metadata.modified()? != original_metadata.modified()?
+    {
+        return Err(FileChangedError.into());
+    }
+    Ok(())
+}
+
+/// Whether the file has hard links other than the path it was opened through
+#[cfg(unix)]
+fn has_other_hard_links(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_other_hard_links(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Resolves `file_path` to its target if it is a symlink, so that the target is overwritten rather than the
+/// link being replaced with a regular file. If `no_follow` is set, symlinks result in an error instead
+fn resolve_symlink(file_path: &Path, no_follow: bool) -> anyhow::Result<PathBuf> {
+    if !fs::symlink_metadata(file_path)?.is_symlink() {
+        return Ok(file_path.to_path_buf());
+    }
+    if no_follow {
+        bail!(
+            "{path_display} is a symlink, so replacements were not written to it",
+            path_display = file_path.display()
+        );
     }
+    fs::canonicalize(file_path).with_context(|| {
+        format!(
+            "Failed to resolve symlink {path_display}",
+            path_display = file_path.display()
+        )
+    })
+}
+
+/// Returns the path that the backup of `file_path` is written to
+pub fn backup_path(file_path: &Path, suffix: &str) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
 
-    temp_output_file.persist(file_path)?;
+/// Copies the contents of `file_path` to a backup file, erroring if the backup file already exists
+fn create_backup(file_path: &Path, suffix: &str) -> anyhow::Result<()> {
+    let backup_path = backup_path(file_path, suffix);
+    let mut backup = match File::options()
+        .write(true)
+        .create_new(true)
+        .open(&backup_path)
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            bail!("Backup file {} already exists", backup_path.display());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    io::copy(&mut File::open(file_path)?, &mut backup)?;
     Ok(())
 }
 
-const MAX_FILE_SIZE: u64 = 100 * 1024 * 1024; // 100 MB
+/// The default maximum size of files that are replaced in memory, used when `max_in_memory_size` isn't set
+pub const DEFAULT_MAX_IN_MEMORY_SIZE: u64 = 100 * 1024 * 1024; // 100 MB
 
-fn should_replace_in_memory(path: &Path) -> Result<bool, std::io::Error> {
-    let file_size = fs::metadata(path)?.len();
-    Ok(file_size <= MAX_FILE_SIZE)
+fn should_replace_in_memory(
+    store: &dyn FileStore,
+    path: &Path,
+    max_in_memory_size: u64,
+) -> Result<bool, std::io::Error> {
+    let file_size = store.size(path)?;
+    Ok(file_size <= max_in_memory_size)
 }
 
 /// Performs search and replace operations in a file
 ///
 /// This function implements a hybrid approach to file replacements:
-/// 1. For files under the `MAX_FILE_SIZE` threshold, it attempts an in-memory replacement
+/// 1. For files under the `max_in_memory_size` threshold (100 MB by default), it attempts an in-memory replacement
 /// 2. If the file is large or in-memory replacement fails, it falls back to line-by-line chunked replacement
 ///
 /// This approach optimizes for performance while maintaining reasonable memory usage limits.
 ///
+/// UTF-16 files (detected from their byte order mark, or set via `encoding` in the config) can't be
+/// processed line by line, so are always replaced in memory. Options that require replacing line by line,
+/// such as `occurrence`, result in an error for these files (see `check_not_utf16`).
+///
 /// # Arguments
 ///
 /// * `file_path` - Path to the file to process
-/// * `search` - The search pattern (fixed string, regex, or advanced regex)
-/// * `replace` - The replacement string
+/// * `exprs` - The search patterns (fixed string, regex, or advanced regex) and their replacements, applied
+///   in order
+/// * `config` - Options controlling how the file is written, such as whether to create a backup
 ///
 /// # Returns
 ///
-/// * `Ok(true)` if replacements were made in the file
-/// * `Ok(false)` if no replacements were made (no matches found)
-/// * `Err` if any errors occurred during the operation
+/// * `Ok(replaced)` where `replaced.num_replacements` is the number of matches replaced, which is 0 if no
+///   replacements were made (no matches found), along with any warnings about the file. A line can contain
+///   several matches, although only one is replaced in each line if `occurrence` is set
+/// * `Err` if any errors occurred during the operation, or a `BinaryFileError` if the file appears to be
+///   binary (unless `include_binary` is set)
 pub fn replace_all_in_file(
     file_path: &Path,
-    search: &SearchType,
-    replace: &str,
-) -> anyhow::Result<bool> {
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+) -> anyhow::Result<ReplacedFile> {
+    check_not_binary(file_path, config)?;
+
+    // Only the Nth match in each line is replaced, so the file can't be replaced as a whole
+    if config.occurrence.is_some() {
+        return replace_chunked(file_path, exprs, config);
+    }
+    // Errors from the replacement command are reported line by line
+    if config.replace_command.is_some() {
+        return replace_chunked(file_path, exprs, config);
+    }
+    // Template tokens such as `{lineno}` are expanded for each line
+    if config.template {
+        return replace_chunked(file_path, exprs, config);
+    }
+    // Replacement functions are only applied line by line
+    if config.replacement_functions.is_some() {
+        return replace_chunked(file_path, exprs, config);
+    }
+    // Only the first matching line is replaced, after which the rest of the file is copied without being searched
+    if config.first_only {
+        return replace_chunked(file_path, exprs, config);
+    }
+    // Each line is checked against the guard and skip patterns
+    if config.filters_lines() {
+        return replace_chunked(file_path, exprs, config);
+    }
+    // String literals are tracked line by line
+    if config.skip_in_strings && Language::from_path(file_path).is_some() {
+        return replace_chunked(file_path, exprs, config);
+    }
+
+    if is_utf16(file_path, config)? {
+        return replace_in_memory(file_path, exprs, config);
+    }
+
     // Try to read into memory if not too large - if this fails, or if too large, fall back to line-by-line replacement
-    if matches!(should_replace_in_memory(file_path), Ok(true)) {
-        match replace_in_memory(file_path, search, replace) {
+    let max_in_memory_size = config
+        .max_in_memory_size
+        .unwrap_or(DEFAULT_MAX_IN_MEMORY_SIZE);
+    if matches!(
+        should_replace_in_memory(config.store(), file_path, max_in_memory_size),
+        Ok(true)
+    ) {
+        match replace_in_memory_bounded(file_path, exprs, config, max_in_memory_size) {
             Ok(replaced) => return Ok(replaced),
+            // Replacing line by line would read the file again, rather than aborting as intended
+            Err(e) if e.is::<FileChangedError>() => return Err(e),
+            // The file grew after its size was checked, such as a log file being written to
+            Err(e) if e.is::<FileTooLargeError>() => {
+                log::info!(
+                    "File {path_display} grew too large to replace in memory, so replacing line by line",
+                    path_display = file_path.display(),
+                );
+            }
+            // Replacing line by line replaces the lines that are valid UTF-8, and handles `lossy`
+            Err(e) if e.is::<std::string::FromUtf8Error>() => {
+                log::info!(
+                    "File {path_display} isn't valid UTF-8, so replacing line by line",
+                    path_display = file_path.display(),
+                );
+            }
             Err(e) => {
                 log::error!(
                     "Found error when attempting to replace in memory for file {path_display}: {e}",
@@ -109,56 +873,693 @@ pub fn replace_all_in_file(
         }
     }
 
-    replace_chunked(file_path, search, replace)
+    replace_chunked(file_path, exprs, config)
 }
 
-pub fn add_replacement(
-    search_result: SearchResult,
-    search: &SearchType,
-    replace: &str,
-) -> Option<SearchResultWithReplacement> {
-    let replacement = replacement_if_match(&search_result.line, search, replace)?;
-    Some(SearchResultWithReplacement {
-        search_result,
-        replacement,
-        replace_result: None,
-    })
-}
-
-fn replace_chunked(file_path: &Path, search: &SearchType, replace: &str) -> anyhow::Result<bool> {
-    let search_results = search::search_file(file_path, search)?;
-    if !search_results.is_empty() {
-        let mut replacement_results = search_results
-            .into_iter()
-            .map(|r| {
-                add_replacement(r, search, replace).unwrap_or_else(|| {
-                    panic!("Called add_replacement with non-matching search result")
-                })
-            })
-            .collect::<Vec<_>>();
-        replace_in_file(&mut replacement_results)?;
-        return Ok(true);
+/// Performs search and replace operations across the entire contents of a file, so that matches can span
+/// multiple lines
+///
+/// Unlike `replace_all_in_file`, there is no line-by-line fallback: files larger than `max_in_memory_size`
+/// result in an error. If `join_lines` is set in the config, files are instead read a window of lines at a
+/// time, so aren't limited in size (see `replace_windowed`).
+///
+/// # Returns
+///
+/// * `Ok(replaced)` where `replaced.num_replacements` is the number of matches replaced, as matches may span
+///   multiple lines, which is 0 if no replacements were made (no matches found), along with any warnings
+/// * `Err` if the file is too large or appears to be binary, or any other errors occurred during the operation
+pub fn replace_all_in_file_multiline(
+    file_path: &Path,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+) -> anyhow::Result<ReplacedFile> {
+    check_not_binary(file_path, config)?;
+    if config.replace_command.is_some() {
+        bail!("A replacement command can't be used in multiline mode");
+    }
+    if config.replacement_functions.is_some() {
+        bail!("Replacement functions can't be used in multiline mode");
+    }
+    if let Some(window) = config.join_lines {
+        return replace_windowed(file_path, exprs, config, window);
     }
 
-    Ok(false)
-}
-
-fn replace_in_memory(file_path: &Path, search: &SearchType, replace: &str) -> anyhow::Result<bool> {
-    let content = fs::read_to_string(file_path)?;
-    if let Some(new_content) = replacement_if_match(&content, search, replace) {
-        let parent_dir = file_path.parent().unwrap_or(Path::new("."));
-        let mut temp_file = NamedTempFile::new_in(parent_dir)?;
-        temp_file.write_all(new_content.as_bytes())?;
-        temp_file.persist(file_path)?;
-        Ok(true)
-    } else {
-        Ok(false)
+    let max_in_memory_size = config
+        .max_in_memory_size
+        .unwrap_or(DEFAULT_MAX_IN_MEMORY_SIZE);
+    if !should_replace_in_memory(config.store(), file_path, max_in_memory_size)? {
+        bail!(
+            "File is larger than the maximum in-memory size of {max_in_memory_size} bytes, so can't be replaced in multiline mode"
+        );
     }
+    replace_in_memory_bounded(file_path, exprs, config, max_in_memory_size)
 }
 
-/// Performs a search and replace operation on a string if the pattern matches
+/// Replaces matches spanning up to `window` consecutive lines, reading the file `window` lines at a time rather
+/// than into memory as a whole (see `FileSearcherConfig::join_lines`). Only a single search pattern is
+/// supported.
 ///
-/// # Arguments
+/// Each window is searched for matches starting on its first line. These are replaced along with any matches
+/// starting on the lines they extend onto, and the lines they cover are then written, so that no line is
+/// replaced twice. Otherwise, the first line is written unchanged, and the window moves on by a line. As a
+/// result, matches can't extend beyond the end of the window they start in.
+///
+/// Returns the number of matches replaced along with any warnings, or an `InvalidUtf8Error` if the file isn't
+/// valid UTF-8
+fn replace_windowed(
+    file_path: &Path,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+    window: NonZero<usize>,
+) -> anyhow::Result<ReplacedFile> {
+    let [(search, replace)] = exprs else {
+        bail!("Only a single search pattern can be used when joining lines");
+    };
+    if search.is_empty() {
+        return Ok(ReplacedFile::default());
+    }
+
+    let store = config.store();
+    let mut output = store.create(file_path, config)?;
+
+    // Scope the writer so it's flushed before the output is committed
+    let num_replaced = {
+        let mut lines = BufReader::new(store.open(file_path)?).lines_with_endings();
+        let mut writer = BufWriter::new(&mut output);
+
+        let mut num_replaced = 0;
+        let mut offset = 0;
+        // Each line in the window, including its line ending
+        let mut buffer = VecDeque::with_capacity(window.get());
+        loop {
+            while buffer.len() < window.get()
+                && let Some(line_result) = lines.next()
+            {
+                let (line, line_ending) = line_result?;
+                let mut line = String::from_utf8(line).map_err(|e| InvalidUtf8Error {
+                    offset: offset + e.utf8_error().valid_up_to(),
+                })?;
+                line.push_str(line_ending.as_str());
+                offset += line.len();
+                buffer.push_back(line);
+            }
+            if buffer.is_empty() {
+                break;
+            }
+
+            let line_ends = buffer
+                .iter()
+                .scan(0, |end, line| {
+                    *end += line.len();
+                    Some(*end)
+                })
+                .collect::<Vec<_>>();
+            let (replaced, covered_end, num_matches) = replace_in_window(
+                &buffer.iter().map(String::as_str).collect::<String>(),
+                &line_ends,
+                search,
+                replace,
+            );
+            num_replaced += num_matches;
+            let replaced = match config.line_ending {
+                Some(line_ending) => line_reader::normalize_line_endings(&replaced, line_ending),
+                None => replaced,
+            };
+            writer.write_all(replaced.as_bytes())?;
+
+            let num_covered_lines = line_ends
+                .iter()
+                .take_while(|&&end| end <= covered_end)
+                .count();
+            buffer.drain(..num_covered_lines);
+        }
+
+        writer.flush()?;
+        num_replaced
+    };
+    if num_replaced == 0 {
+        return Ok(ReplacedFile::default());
+    }
+
+    if config.ensure_trailing_newline {
+        ensure_trailing_newline_in_file(output.as_mut(), config.line_ending)?;
+    }
+    Ok(ReplacedFile {
+        num_replacements: num_replaced,
+        warnings: output.commit()?,
+    })
+}
+
+/// Replaces the matches of `search` in `window_text` for `replace_windowed` that start on its first line, or on
+/// a line that an earlier match extends onto. `line_ends` holds the offset of the end of each line in the window.
+///
+/// Returns the replaced text of the lines covered by these matches, the offset of the end of the covered lines
+/// and the number of matches replaced, which is 0 if the replacements leave the text unchanged
+fn replace_in_window(
+    window_text: &str,
+    line_ends: &[usize],
+    search: &SearchType,
+    replace: &str,
+) -> (String, usize, usize) {
+    let mut covered_end = line_ends[0];
+    let mut num_matches = 0;
+    for range in search::match_ranges(window_text, search) {
+        if range.start >= covered_end {
+            break;
+        }
+        num_matches += 1;
+        if range.end > covered_end {
+            covered_end = line_ends
+                .iter()
+                .copied()
+                .find(|&end| end >= range.end)
+                .unwrap_or(window_text.len());
+        }
+    }
+
+    let covered = &window_text[..covered_end];
+    if num_matches == 0 {
+        return (covered.to_owned(), covered_end, 0);
+    }
+    let mut replaced = match search {
+        SearchType::Fixed(fixed_str) => {
+            window_text.replacen(fixed_str.as_str(), replace, num_matches)
+        }
+        SearchType::Pattern(pattern) => pattern
+            .replacen(window_text, num_matches, replace)
+            .into_owned(),
+        SearchType::PatternAdvanced(pattern) => pattern
+            .replacen(window_text, num_matches, replace)
+            .into_owned(),
+    };
+    // Only the matches within the covered lines are replaced, so the rest of the window is unchanged
+    replaced.truncate(replaced.len() - (window_text.len() - covered_end));
+    if replaced == covered {
+        num_matches = 0;
+    }
+    (replaced, covered_end, num_matches)
+}
+
+pub fn add_replacement(
+    search_result: SearchResult,
+    exprs: &[(SearchType, String)],
+    case_transforms: bool,
+    occurrence: Option<NonZero<usize>>,
+) -> Option<SearchResultWithReplacement> {
+    let replacement =
+        replace_all_patterns(&search_result.line, exprs, case_transforms, occurrence)?;
+    Some(SearchResultWithReplacement {
+        search_result,
+        replacement,
+        replace_result: None,
+    })
+}
+
+/// Like `add_replacement`, but each match is replaced with the output of `command`. See
+/// `replace_all_patterns_with_command`
+pub fn add_replacement_with_command(
+    search_result: SearchResult,
+    exprs: &[(SearchType, String)],
+    command: &ReplaceCommand,
+) -> anyhow::Result<Option<SearchResultWithReplacement>> {
+    let Some(replacement) = replace_all_patterns_with_command(&search_result.line, exprs, command)?
+    else {
+        return Ok(None);
+    };
+    Ok(Some(SearchResultWithReplacement {
+        search_result,
+        replacement,
+        replace_result: None,
+    }))
+}
+
+/// Like `add_replacement`, but with the replacement function calls in each replacement applied. See
+/// `replace_all_patterns_with_functions`
+pub fn add_replacement_with_functions(
+    search_result: SearchResult,
+    exprs: &[(SearchType, String)],
+    functions: &ReplacementFunctions,
+) -> Option<SearchResultWithReplacement> {
+    let replacement = replace_all_patterns_with_functions(&search_result.line, exprs, functions)?;
+    Some(SearchResultWithReplacement {
+        search_result,
+        replacement,
+        replace_result: None,
+    })
+}
+
+/// Like `add_replacement`, but matches inside string literals are left unchanged. See
+/// `replace_all_patterns_outside_strings`. The `match_count` of the result only counts the matches outside
+/// string literals, as these are the ones replaced
+pub fn add_replacement_outside_strings(
+    search_result: SearchResult,
+    exprs: &[(SearchType, String)],
+    case_transforms: bool,
+    occurrence: Option<NonZero<usize>>,
+    string_tracker: &StringTracker,
+) -> Option<SearchResultWithReplacement> {
+    let replacement = replace_all_patterns_outside_strings(
+        &search_result.line,
+        exprs,
+        case_transforms,
+        occurrence,
+        string_tracker,
+    )?;
+    let match_count = count_matches_outside_strings(&search_result.line, exprs, string_tracker);
+    Some(SearchResultWithReplacement {
+        search_result: SearchResult {
+            match_count,
+            ..search_result
+        },
+        replacement,
+        replace_result: None,
+    })
+}
+
+/// The number of non-overlapping matches of each of the search patterns in `exprs` within `line` that aren't
+/// inside a string literal, as tracked by `string_tracker` at the start of the line
+fn count_matches_outside_strings(
+    line: &str,
+    exprs: &[(SearchType, String)],
+    string_tracker: &StringTracker,
+) -> usize {
+    let excluded = string_tracker.string_ranges(line);
+    exprs
+        .iter()
+        .flat_map(|(search, _)| search::match_ranges(line, search))
+        .filter(|range| !syntax::overlaps_any(range.start, range.end, &excluded))
+        .count()
+}
+
+/// Replaces the matches in `line` for `replace_chunked`, returning the replaced line along with the number of
+/// matches replaced, or `None` if it has no replacement. With `occurrence` set, lines with too few matches have
+/// no replacement. If `string_tracker` is set, only matches outside string literals are replaced, and the
+/// tracker is advanced past the line
+fn replace_line(
+    line: &str,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+    string_tracker: Option<&mut StringTracker>,
+) -> anyhow::Result<Option<(String, usize)>> {
+    // Lines excluded by the guard or skip patterns are left unchanged
+    let excluded = !config.replaces_in_line(line);
+    let with_match_count = |replacement: String| (replacement, search::count_matches(line, exprs));
+    if let Some(command) = &config.replace_command
+        && !excluded
+    {
+        return Ok(replace_all_patterns_with_command(line, exprs, command)?.map(with_match_count));
+    }
+    let Some(string_tracker) = string_tracker else {
+        if excluded {
+            return Ok(None);
+        }
+        let replacement = match &config.replacement_functions {
+            Some(functions) => replace_all_patterns_with_functions(line, exprs, functions),
+            None => replace_all_patterns(line, exprs, config.case_transforms, config.occurrence),
+        };
+        return Ok(replacement.map(with_match_count));
+    };
+    // Excluded lines are still tracked, as they may open or close string literals
+    let replacement = if excluded {
+        None
+    } else {
+        replace_all_patterns_outside_strings(
+            line,
+            exprs,
+            config.case_transforms,
+            config.occurrence,
+            string_tracker,
+        )
+        // Only the matches outside string literals are counted
+        .map(|replacement| {
+            let num_matches = count_matches_outside_strings(line, exprs, string_tracker);
+            (replacement, num_matches)
+        })
+    };
+    string_tracker.advance(line);
+    Ok(replacement)
+}
+
+/// Replaces matches in a file line by line in a single pass, writing each line to a temporary file as soon as
+/// it has been read, so that memory usage doesn't grow with the number of matching lines. The temporary file is
+/// discarded, leaving the original untouched, if there are no replacements
+///
+/// Lines that aren't valid UTF-8 are written unchanged. Unless `lossy` is set, they aren't searched, and if
+/// there are replacements in the rest of the file, the offset of the first invalid byte is reported with a
+/// `ReplaceWarning::InvalidUtf8`
+///
+/// With `first_only` set, the rest of the file is copied unchanged after the first line with a replacement,
+/// without being split into lines unless its line endings are being rewritten
+///
+/// Returns the number of matches replaced, along with any warnings
+fn replace_chunked(
+    file_path: &Path,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+) -> anyhow::Result<ReplacedFile> {
+    if exprs.iter().all(|(search, _)| search.is_empty()) {
+        return Ok(ReplacedFile::default());
+    }
+    check_not_utf16(file_path, config)?;
+
+    let mut string_tracker = Language::from_path(file_path)
+        .filter(|_| config.skip_in_strings)
+        .map(StringTracker::new);
+    let store = config.store();
+    let mut output = store.create(file_path, config)?;
+
+    // Scope the writer so it's flushed before the output is committed
+    let (num_replaced, invalid_utf8_offset) = {
+        let mut reader = BufReader::new(store.open(file_path)?);
+        let mut writer = BufWriter::new(&mut output);
+
+        let mut num_replaced = 0;
+        let mut offset = 0;
+        let mut invalid_utf8_offset = None;
+        for (line_idx, line_result) in (&mut reader).lines_with_endings().enumerate() {
+            let (line, line_ending) = line_result?;
+            let text = match str::from_utf8(&line) {
+                Ok(text) => Some(Cow::Borrowed(text)),
+                Err(_) if config.lossy => Some(String::from_utf8_lossy(&line)),
+                Err(e) => {
+                    invalid_utf8_offset.get_or_insert(offset + e.valid_up_to());
+                    None
+                }
+            };
+            offset += line.len() + line_ending.as_bytes().len();
+            let line_exprs = match text.as_deref() {
+                Some(line) if config.template && search::contains_any_search(line, exprs) => {
+                    Cow::Owned(expand_template(exprs, file_path, line_idx + 1))
+                }
+                _ => Cow::Borrowed(exprs),
+            };
+            let replacement = match text.as_deref() {
+                _ if config.first_only && num_replaced > 0 => None,
+                Some(line) => {
+                    replace_line(line, line_exprs.as_ref(), config, string_tracker.as_mut())?
+                }
+                None => None,
+            };
+            if let Some((replacement, num_matches)) = replacement {
+                writer.write_all(replacement.as_bytes())?;
+                num_replaced += config.num_replacements_in(num_matches);
+                if config.first_only {
+                    log::info!(
+                        "Replaced first match in {path_display} on line {line_number}",
+                        path_display = file_path.display(),
+                        line_number = line_idx + 1,
+                    );
+                }
+            } else {
+                writer.write_all(&line)?;
+            }
+            writer.write_all(line_ending.normalized(config.line_ending).as_bytes())?;
+            if config.first_only && num_replaced > 0 && config.line_ending.is_none() {
+                break;
+            }
+        }
+        // Copies anything left unread after stopping early, which is nothing if every line was read
+        io::copy(&mut reader, &mut writer)?;
+
+        writer.flush()?;
+        (num_replaced, invalid_utf8_offset)
+    };
+    if num_replaced == 0 {
+        return Ok(ReplacedFile::default());
+    }
+    let mut replaced = ReplacedFile {
+        num_replacements: num_replaced,
+        warnings: vec![],
+    };
+    if let Some(offset) = invalid_utf8_offset {
+        log::warn!(
+            "File {path_display} contains invalid UTF-8 at byte offset {offset}",
+            path_display = file_path.display(),
+        );
+        replaced
+            .warnings
+            .push(ReplaceWarning::InvalidUtf8 { offset });
+    }
+
+    if config.ensure_trailing_newline {
+        ensure_trailing_newline_in_file(output.as_mut(), config.line_ending)?;
+    }
+    replaced.warnings.extend(output.commit()?);
+    Ok(replaced)
+}
+
+fn is_utf16(file_path: &Path, config: &FileSearcherConfig) -> io::Result<bool> {
+    let encoding = if let Some(encoding) = config.encoding {
+        encoding
+    } else {
+        let mut bom = [0; 2];
+        let read = config.store().open(file_path)?.read(&mut bom)?;
+        Encoding::from_bom(&bom[..read]).unwrap_or_default()
+    };
+    Ok(encoding != Encoding::Utf8)
+}
+
+fn replace_in_memory(
+    file_path: &Path,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+) -> anyhow::Result<ReplacedFile> {
+    replace_in_memory_with_limit(file_path, exprs, config, None)
+}
+
+/// Like `replace_in_memory`, but returns a `FileTooLargeError`, leaving the file unchanged, if it's larger
+/// than `max_size` bytes. At most `max_size + 1` bytes are read, so this doesn't make a large allocation if
+/// the file has grown since its size was checked
+fn replace_in_memory_bounded(
+    file_path: &Path,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+    max_size: u64,
+) -> anyhow::Result<ReplacedFile> {
+    replace_in_memory_with_limit(file_path, exprs, config, Some(max_size))
+}
+
+/// Replaces the entire contents of a file in memory, counting the matches replaced in the original contents.
+/// If `max_size` is set, files larger than this are left unchanged and result in a `FileTooLargeError` (see
+/// `read_bounded`)
+fn replace_in_memory_with_limit(
+    file_path: &Path,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+    max_size: Option<u64>,
+) -> anyhow::Result<ReplacedFile> {
+    let store = config.store();
+    let mut output = store.create(file_path, config)?;
+    let bytes = match max_size {
+        Some(max_size) => read_bounded(store, file_path, max_size)?,
+        None => store.read(file_path)?,
+    };
+    let decoded = encoding::decode(&bytes, config.encoding)?;
+    let Some(new_content) =
+        replace_all_patterns(&decoded.text, exprs, config.case_transforms, None)
+    else {
+        return Ok(ReplacedFile::default());
+    };
+    let new_content = finish_content(new_content, config);
+    output.write_all(&decoded.encode(&new_content))?;
+    Ok(ReplacedFile {
+        num_replacements: search::count_matches(&decoded.text, exprs),
+        warnings: output.commit()?,
+    })
+}
+
+/// The bytes at the start of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Whether the file at `path` in `store` is gzip-compressed, judging by its `.gz` extension or, failing that,
+/// by whether it starts with the gzip magic bytes
+pub fn is_gzip_file(store: &dyn FileStore, path: &Path) -> io::Result<bool> {
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+    {
+        return Ok(true);
+    }
+    let mut magic = [0; GZIP_MAGIC.len()];
+    match store.open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == GZIP_MAGIC),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// The option in `config` that requires replacing line by line, which isn't supported for compressed files, as
+/// they are replaced as a whole
+fn line_by_line_option(config: &FileSearcherConfig) -> Option<&'static str> {
+    [
+        (config.occurrence.is_some(), "occurrence"),
+        (config.replace_command.is_some(), "replace_command"),
+        (config.template, "template"),
+        (
+            config.replacement_functions.is_some(),
+            "replacement_functions",
+        ),
+        (config.first_only, "first_only"),
+        (config.only_matching_lines.is_some(), "only_matching_lines"),
+        (config.skip_lines.is_some(), "skip_lines"),
+        (config.skip_in_strings, "skip_in_strings"),
+        (config.join_lines.is_some(), "join_lines"),
+        (config.diff, "diff"),
+        (config.collect_line_results, "collect_line_results"),
+        (config.max_count.is_some(), "max_count"),
+        (config.max_per_file.is_some(), "max_per_file"),
+        (config.delete_empty_lines, "delete_empty_lines"),
+        (config.collapse_duplicate_lines, "collapse_duplicate_lines"),
+        (config.preserve_indent, "preserve_indent"),
+    ]
+    .into_iter()
+    .find_map(|(set, option)| set.then_some(option))
+}
+
+/// Performs search and replace operations on the decompressed contents of a gzip-compressed file, which is
+/// then compressed again at `compression_level` from the config.
+///
+/// The contents are replaced in memory as a whole, so `max_in_memory_size` applies to the decompressed size,
+/// and larger files result in a `FileTooLargeError`. Options that require replacing line by line, such as
+/// `occurrence` or `diff`, aren't supported and result in an error. When performing a dry run, the matches
+/// are counted without the file being written.
+///
+/// # Returns
+///
+/// * `Ok(replaced)` where `replaced.num_replacements` is the number of matches replaced, which is 0 if no
+///   replacements were made (no matches found), along with any warnings
+/// * `Err` if the file isn't valid gzip, or any other errors occurred during the operation
+pub fn replace_all_in_gzip_file(
+    file_path: &Path,
+    exprs: &[(SearchType, String)],
+    config: &FileSearcherConfig,
+) -> anyhow::Result<ReplacedFile> {
+    if let Some(option) = line_by_line_option(config) {
+        bail!("`{option}` isn't supported for compressed files");
+    }
+
+    let store = config.store();
+    let mut output = store.create(file_path, config)?;
+    let max_size = config
+        .max_in_memory_size
+        .unwrap_or(DEFAULT_MAX_IN_MEMORY_SIZE);
+    let bytes = read_bounded_from(GzDecoder::new(store.open(file_path)?), max_size)
+        .with_context(|| format!("Failed to decompress {}", file_path.display()))?;
+    let decoded = encoding::decode(&bytes, config.encoding)?;
+    let Some(new_content) =
+        replace_all_patterns(&decoded.text, exprs, config.case_transforms, None)
+    else {
+        return Ok(ReplacedFile::default());
+    };
+    let num_replacements = search::count_matches(&decoded.text, exprs);
+    if config.dry_run {
+        return Ok(ReplacedFile {
+            num_replacements,
+            warnings: vec![],
+        });
+    }
+
+    let new_content = finish_content(new_content, config);
+    let level = config
+        .compression_level
+        .map_or_else(Compression::default, Compression::new);
+    let mut encoder = GzEncoder::new(&mut output, level);
+    encoder.write_all(&decoded.encode(&new_content))?;
+    encoder.finish()?;
+    Ok(ReplacedFile {
+        num_replacements,
+        warnings: output.commit()?,
+    })
+}
+
+/// Expands the template tokens in each of the replacements in `exprs`, for a match on line `line_number` of the
+/// file at `path` (see `FileSearcherConfig::template`):
+///
+/// * `{path}` - the path of the file, as found when searching the directory
+/// * `{filename}` - the name of the file, without its directory
+/// * `{lineno}` - the number of the line, counting from 1
+///
+/// Any other braces are left unchanged. The expanded values are inserted verbatim, so `$` is escaped in the
+/// replacements for regex patterns to avoid it being treated as a capture group reference
+pub fn expand_template(
+    exprs: &[(SearchType, String)],
+    path: &Path,
+    line_number: usize,
+) -> Vec<(SearchType, String)> {
+    let path_str = path.display().to_string();
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let line_number = line_number.to_string();
+
+    exprs
+        .iter()
+        .map(|(search, replace)| {
+            let escape = |value: &str| match search {
+                SearchType::Fixed(_) => value.to_owned(),
+                _ => value.replace('$', "$$"),
+            };
+            let tokens = [
+                ("{path}", escape(&path_str)),
+                ("{filename}", escape(&filename)),
+                ("{lineno}", line_number.clone()),
+            ];
+
+            let mut expanded = String::with_capacity(replace.len());
+            let mut rest = replace.as_str();
+            while let Some(start) = rest.find('{') {
+                expanded.push_str(&rest[..start]);
+                rest = &rest[start..];
+                if let Some((token, value)) =
+                    tokens.iter().find(|(token, _)| rest.starts_with(token))
+                {
+                    expanded.push_str(value);
+                    rest = &rest[token.len()..];
+                } else {
+                    expanded.push('{');
+                    rest = &rest[1..];
+                }
+            }
+            expanded.push_str(rest);
+            (search.clone(), expanded)
+        })
+        .collect()
+}
+
+/// Restores the leading whitespace of `line` at the start of `replacement`, if the replacement doesn't already
+/// start with it, in place of any other leading whitespace in the replacement. This keeps the indentation of
+/// lines whose replacement doesn't include it, such as when replacing a whole line. Empty replacements are left
+/// empty, so that deleted lines don't become whitespace
+pub fn with_preserved_indent(line: &str, replacement: String) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    if indent.is_empty() || replacement.is_empty() || replacement.starts_with(indent) {
+        replacement
+    } else {
+        format!("{indent}{}", replacement.trim_start())
+    }
+}
+
+/// Reads the contents of a file in `store`, returning a `FileTooLargeError` if it's larger than `max_size`
+/// bytes. Rather than trusting the file's size, which may be out of date by the time the file is read, at most
+/// `max_size` bytes are read, followed by a check for any more
+fn read_bounded(store: &dyn FileStore, file_path: &Path, max_size: u64) -> anyhow::Result<Vec<u8>> {
+    read_bounded_from(store.open(file_path)?, max_size)
+}
+
+/// Like `read_bounded`, but reads from `reader`
+fn read_bounded_from(mut reader: impl Read, max_size: u64) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.by_ref().take(max_size).read_to_end(&mut bytes)?;
+    if reader.read(&mut [0])? > 0 {
+        return Err(FileTooLargeError { max_size }.into());
+    }
+    Ok(bytes)
+}
+
+/// Performs a search and replace operation on a string if the pattern matches
+///
+/// # Arguments
 ///
 /// * `line` - The string to search within
 /// * `search` - The search pattern (fixed string, regex, or advanced regex)
@@ -185,71 +1586,548 @@ pub fn replacement_if_match(line: &str, search: &SearchType, replace: &str) -> O
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct ReplaceStats {
-    pub num_successes: usize,
-    pub errors: Vec<SearchResultWithReplacement>,
+/// Like `replacement_if_match`, but applies case-transform escapes in the replacement after capture groups
+/// have been expanded for each regex match:
+///
+/// * `\U` - uppercase all following characters, until `\L` or `\E`
+/// * `\L` - lowercase all following characters, until `\U` or `\E`
+/// * `\E` - end the case transformation started by `\U` or `\L`
+/// * `\u` - uppercase the next character
+/// * `\l` - lowercase the next character
+///
+/// For instance, replacing `(\w+)_id` with `\U$1\E_ID` turns `user_id` into `USER_ID`. A `\U` or `\L`
+/// without a matching `\E` applies until the end of the replacement, and `\\` produces a literal
+/// backslash. Escapes are not applied when searching with fixed strings.
+pub fn replacement_if_match_with_case_transforms(
+    line: &str,
+    search: &SearchType,
+    replace: &str,
+) -> Option<String> {
+    replacement_if_match_with(line, search, replace, apply_case_transforms)
 }
 
-pub fn calculate_statistics<I>(results: I) -> ReplaceStats
-where
-    I: IntoIterator<Item = SearchResultWithReplacement>,
-{
-    let mut num_successes = 0;
-    let mut errors = vec![];
-
-    results.into_iter().for_each(|res| {
-        assert!(
-            res.search_result.included,
-            "Expected only included results, found {res:?}"
-        );
-        match &res.replace_result {
-            Some(ReplaceResult::Success) => {
-                num_successes += 1;
-            }
-            None => {
-                let mut res = res.clone();
-                res.replace_result = Some(ReplaceResult::Error(
-                    "Failed to find search result in file".to_owned(),
-                ));
-                errors.push(res);
-            }
-            Some(ReplaceResult::Error(_)) => {
-                errors.push(res.clone());
-            }
-        }
-    });
+/// Like `replacement_if_match`, but `finish` is applied to the replacement for each regex match after capture
+/// groups have been expanded. Replacements for fixed strings are inserted as they are
+fn replacement_if_match_with(
+    line: &str,
+    search: &SearchType,
+    replace: &str,
+    finish: impl Fn(&str) -> String,
+) -> Option<String> {
+    if line.is_empty() || search.is_empty() {
+        return None;
+    }
 
-    ReplaceStats {
-        num_successes,
-        errors,
+    if search::contains_search(line, search) {
+        let replacement = match search {
+            SearchType::Fixed(fixed_str) => line.replace(fixed_str, replace),
+            SearchType::Pattern(pattern) => pattern
+                .replace_all(line, |caps: &regex::Captures<'_>| {
+                    let mut expanded = String::new();
+                    caps.expand(replace, &mut expanded);
+                    finish(&expanded)
+                })
+                .to_string(),
+            SearchType::PatternAdvanced(pattern) => pattern
+                .replace_all(line, |caps: &fancy_regex::Captures<'_>| {
+                    let mut expanded = String::new();
+                    caps.expand(replace, &mut expanded);
+                    finish(&expanded)
+                })
+                .to_string(),
+        };
+        Some(replacement)
+    } else {
+        None
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::line_reader::LineEnding;
-    use crate::search::{SearchResult, SearchType, search_file};
-    use regex::Regex;
-    use std::path::PathBuf;
-    use tempfile::TempDir;
+/// Like `replacement_if_match` (or `replacement_if_match_with_case_transforms` if `case_transforms` is set),
+/// but only replaces the `occurrence`th match in the string, counting from 1
+///
+/// # Returns
+///
+/// * `Some(String)` containing the string with the replacement made if there are at least `occurrence`
+///   matches
+/// * `None` if there are fewer matches
+pub fn replacement_if_nth_match(
+    line: &str,
+    search: &SearchType,
+    replace: &str,
+    occurrence: NonZero<usize>,
+    case_transforms: bool,
+) -> Option<String> {
+    if line.is_empty() || search.is_empty() {
+        return None;
+    }
 
-    mod test_helpers {
-        use crate::search::SearchType;
+    let finish = |expanded: String| {
+        if case_transforms {
+            apply_case_transforms(&expanded)
+        } else {
+            expanded
+        }
+    };
+    let index = occurrence.get() - 1;
+    let (start, end, replacement) = match search {
+        SearchType::Fixed(fixed_str) => {
+            let (start, matched) = line.match_indices(fixed_str.as_str()).nth(index)?;
+            (start, start + matched.len(), replace.to_owned())
+        }
+        SearchType::Pattern(pattern) => {
+            let caps = pattern.captures_iter(line).nth(index)?;
+            let matched = caps.get(0)?;
+            let mut expanded = String::new();
+            caps.expand(replace, &mut expanded);
+            (matched.start(), matched.end(), finish(expanded))
+        }
+        SearchType::PatternAdvanced(pattern) => {
+            let caps = pattern.captures_iter(line).nth(index)?.ok()?;
+            let matched = caps.get(0)?;
+            let mut expanded = String::new();
+            caps.expand(replace, &mut expanded);
+            (matched.start(), matched.end(), finish(expanded))
+        }
+    };
 
-        pub fn create_fixed_search(term: &str) -> SearchType {
-            SearchType::Fixed(term.to_string())
+    let mut result = String::with_capacity(line.len() - (end - start) + replacement.len());
+    result.push_str(&line[..start]);
+    result.push_str(&replacement);
+    result.push_str(&line[end..]);
+    Some(result)
+}
+
+/// Escapes each `$` in the replacements for regex patterns in `exprs` as `$$`, so that the replacement text is
+/// inserted verbatim rather than having capture group references such as `$1` or `${name}` expanded.
+/// Replacements for fixed strings are already inserted verbatim, so are left unchanged
+pub fn make_replacements_literal(exprs: &mut [(SearchType, String)]) {
+    for (search, replace) in exprs {
+        if !matches!(search, SearchType::Fixed(_)) {
+            *replace = replace.replace('$', "$$");
         }
     }
+}
 
-    // Helper functions
-    fn create_search_result_with_replacement(
-        path: &str,
-        line_number: usize,
-        line: &str,
-        replacement: &str,
-        included: bool,
+/// Applies each search pattern and replacement in `exprs` to the string in turn, so that later patterns
+/// are matched against the output of earlier replacements. Case-transform escapes are applied if
+/// `case_transforms` is set (see `replacement_if_match_with_case_transforms`), and if `occurrence` is set
+/// only that match of each pattern is replaced (see `replacement_if_nth_match`).
+///
+/// Replacements that leave the string unchanged, such as replacing `foo` with `foo`, are treated as if there
+/// were no match, so that files aren't rewritten with identical contents.
+///
+/// # Returns
+///
+/// * `Some(String)` containing the string with replacements if any of the patterns matched and the result
+///   differs from the original string
+/// * `None` if none of the patterns matched, or the replacements left the string unchanged
+pub fn replace_all_patterns(
+    line: &str,
+    exprs: &[(SearchType, String)],
+    case_transforms: bool,
+    occurrence: Option<NonZero<usize>>,
+) -> Option<String> {
+    let replacement_if_match = |line: &str, search: &SearchType, replace: &str| match occurrence {
+        Some(occurrence) => {
+            replacement_if_nth_match(line, search, replace, occurrence, case_transforms)
+        }
+        None if case_transforms => replacement_if_match_with_case_transforms(line, search, replace),
+        None => replacement_if_match(line, search, replace),
+    };
+
+    let mut result: Option<String> = None;
+    for (search, replace) in exprs {
+        let current = result.as_deref().unwrap_or(line);
+        if let Some(replaced) = replacement_if_match(current, search, replace) {
+            result = Some(replaced);
+        }
+    }
+    result.filter(|result| result != line)
+}
+
+/// Like `replace_all_patterns`, but for lines that aren't necessarily valid UTF-8. Replacements are inserted
+/// verbatim for fixed strings, and with capture groups expanded otherwise, as with `bytes::Regex::replace_all`
+/// (see `validation::parse_search_bytes`)
+pub fn replace_all_patterns_bytes(
+    line: &[u8],
+    exprs: &[(bytes::Regex, Vec<u8>)],
+) -> Option<Vec<u8>> {
+    if line.is_empty() {
+        return None;
+    }
+
+    let mut result: Option<Vec<u8>> = None;
+    for (search, replace) in exprs {
+        let current = result.as_deref().unwrap_or(line);
+        if let Cow::Owned(replaced) = search.replace_all(current, replace.as_slice()) {
+            result = Some(replaced);
+        }
+    }
+    result.filter(|result| result != line)
+}
+
+/// Like `replace_all_patterns`, but each match is replaced with the output of `command` for the matched text
+/// (see `ReplaceCommand`), rather than with the replacement text of its pattern. Case transforms and
+/// `occurrence` aren't supported. Errors if the command fails for any match
+pub fn replace_all_patterns_with_command(
+    line: &str,
+    exprs: &[(SearchType, String)],
+    command: &ReplaceCommand,
+) -> anyhow::Result<Option<String>> {
+    let mut result: Option<String> = None;
+    for (search, _) in exprs {
+        let current = result.as_deref().unwrap_or(line);
+        if current.is_empty() {
+            break;
+        }
+        let ranges = search::match_ranges(current, search);
+        if ranges.is_empty() {
+            continue;
+        }
+
+        let mut replaced = String::with_capacity(current.len());
+        let mut last_end = 0;
+        for range in ranges {
+            replaced.push_str(&current[last_end..range.start]);
+            replaced.push_str(&command.run(&current[range.clone()])?);
+            last_end = range.end;
+        }
+        replaced.push_str(&current[last_end..]);
+        result = Some(replaced);
+    }
+    Ok(result.filter(|result| result != line))
+}
+
+/// Like `replace_all_patterns`, but with the replacement function calls in each replacement applied after
+/// capture groups have been expanded for each match (see `ReplacementFunctions`). As with case transforms,
+/// function calls aren't applied when searching with fixed strings, and `occurrence` isn't supported
+pub fn replace_all_patterns_with_functions(
+    line: &str,
+    exprs: &[(SearchType, String)],
+    functions: &ReplacementFunctions,
+) -> Option<String> {
+    let mut result: Option<String> = None;
+    for (search, replace) in exprs {
+        let current = result.as_deref().unwrap_or(line);
+        if let Some(replaced) =
+            replacement_if_match_with(current, search, replace, |text| functions.apply(text))
+        {
+            result = Some(replaced);
+        }
+    }
+    result.filter(|result| result != line)
+}
+
+/// Like `replace_all_patterns`, but matches inside string literals are left unchanged. `string_tracker`
+/// must be at the start of `line` (see `syntax::StringTracker`), and is used to locate the string literals
+/// again after each pattern is applied, since earlier replacements can move them
+pub fn replace_all_patterns_outside_strings(
+    line: &str,
+    exprs: &[(SearchType, String)],
+    case_transforms: bool,
+    occurrence: Option<NonZero<usize>>,
+    string_tracker: &StringTracker,
+) -> Option<String> {
+    let mut result: Option<String> = None;
+    for (search, replace) in exprs {
+        let current = result.as_deref().unwrap_or(line);
+        let excluded = string_tracker.string_ranges(current);
+        if let Some(replaced) = replacement_outside_ranges(
+            current,
+            search,
+            replace,
+            case_transforms,
+            occurrence,
+            &excluded,
+        ) {
+            result = Some(replaced);
+        }
+    }
+    result.filter(|result| result != line)
+}
+
+/// Like `replacement_if_match` (or `replacement_if_nth_match` if `occurrence` is set), but matches overlapping
+/// any of the `excluded` byte ranges are skipped, and aren't counted towards `occurrence`
+fn replacement_outside_ranges(
+    line: &str,
+    search: &SearchType,
+    replace: &str,
+    case_transforms: bool,
+    occurrence: Option<NonZero<usize>>,
+    excluded: &[Range<usize>],
+) -> Option<String> {
+    if line.is_empty() || search.is_empty() {
+        return None;
+    }
+
+    let finish = |expanded: String| {
+        if case_transforms {
+            apply_case_transforms(&expanded)
+        } else {
+            expanded
+        }
+    };
+    let matches: Vec<(usize, usize, String)> = match search {
+        SearchType::Fixed(fixed_str) => line
+            .match_indices(fixed_str.as_str())
+            .map(|(start, matched)| (start, start + matched.len(), replace.to_owned()))
+            .collect(),
+        SearchType::Pattern(pattern) => pattern
+            .captures_iter(line)
+            .filter_map(|caps| {
+                let matched = caps.get(0)?;
+                let mut expanded = String::new();
+                caps.expand(replace, &mut expanded);
+                Some((matched.start(), matched.end(), finish(expanded)))
+            })
+            .collect(),
+        SearchType::PatternAdvanced(pattern) => pattern
+            .captures_iter(line)
+            .filter_map(|caps| {
+                let caps = caps.ok()?;
+                let matched = caps.get(0)?;
+                let mut expanded = String::new();
+                caps.expand(replace, &mut expanded);
+                Some((matched.start(), matched.end(), finish(expanded)))
+            })
+            .collect(),
+    };
+
+    let mut matches = matches
+        .into_iter()
+        .filter(|(start, end, _)| !syntax::overlaps_any(*start, *end, excluded));
+    let matches: Vec<_> = match occurrence {
+        Some(occurrence) => matches.nth(occurrence.get() - 1).into_iter().collect(),
+        None => matches.collect(),
+    };
+    if matches.is_empty() {
+        return None;
+    }
+
+    let mut result = String::with_capacity(line.len());
+    let mut last_end = 0;
+    for (start, end, replacement) in matches {
+        result.push_str(&line[last_end..start]);
+        result.push_str(&replacement);
+        last_end = end;
+    }
+    result.push_str(&line[last_end..]);
+    Some(result)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Case {
+    Upper,
+    Lower,
+}
+
+impl Case {
+    fn push(self, c: char, dst: &mut String) {
+        match self {
+            Case::Upper => dst.extend(c.to_uppercase()),
+            Case::Lower => dst.extend(c.to_lowercase()),
+        }
+    }
+}
+
+/// Applies the `\U`, `\L`, `\E`, `\u` and `\l` escapes in `text`. Other escapes are left as they are
+fn apply_case_transforms(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut span_case = None;
+    let mut next_char_case = None;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.peek() {
+                Some('U') => span_case = Some(Case::Upper),
+                Some('L') => span_case = Some(Case::Lower),
+                Some('E') => span_case = None,
+                Some('u') => next_char_case = Some(Case::Upper),
+                Some('l') => next_char_case = Some(Case::Lower),
+                Some('\\') => result.push('\\'),
+                _ => {
+                    result.push(c);
+                    continue;
+                }
+            }
+            chars.next();
+            continue;
+        }
+
+        match next_char_case.take().or(span_case) {
+            Some(case) => case.push(c, &mut result),
+            None => result.push(c),
+        }
+    }
+    result
+}
+
+/// A function that can be called by name within replacement text. See `ReplacementFunctions`
+pub type ReplacementFunction = fn(&str) -> String;
+
+/// Named functions that can be called within replacement text as `\{name:text\}`, such as `\{upper:$1\}`,
+/// with the call replaced by the function's output for `text`. Calls are applied to the replacement for each
+/// match, after capture groups have been expanded, and calls to unregistered functions are left as they are.
+///
+/// The built-in functions are `uppercase`, `lowercase`, `trim`, `reverse` and `base64`, and further functions,
+/// or other names for the built-in ones, can be registered with `register`.
+#[derive(Clone, Debug)]
+pub struct ReplacementFunctions {
+    functions: HashMap<String, ReplacementFunction>,
+}
+
+impl Default for ReplacementFunctions {
+    fn default() -> Self {
+        let builtins: [(&str, ReplacementFunction); 5] = [
+            ("uppercase", str::to_uppercase),
+            ("lowercase", str::to_lowercase),
+            ("trim", |text| text.trim().to_owned()),
+            ("reverse", |text| text.chars().rev().collect()),
+            ("base64", |text| base64_encode(text.as_bytes())),
+        ];
+        Self {
+            functions: builtins
+                .into_iter()
+                .map(|(name, function)| (name.to_owned(), function))
+                .collect(),
+        }
+    }
+}
+
+impl ReplacementFunctions {
+    /// Registers `function` under `name`, replacing any function already registered with that name
+    pub fn register(&mut self, name: impl Into<String>, function: ReplacementFunction) {
+        self.functions.insert(name.into(), function);
+    }
+
+    pub fn get(&self, name: &str) -> Option<ReplacementFunction> {
+        self.functions.get(name).copied()
+    }
+
+    /// The names of the registered functions, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self
+            .functions
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// Replaces each `\{name:text\}` in `text` with the output of the function registered as `name`. Calls
+    /// can't be nested, and a call without a closing `\}` is left as it is
+    pub fn apply(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(r"\{") {
+            result.push_str(&rest[..start]);
+            let call = &rest[start + 2..];
+            let Some(end) = call.find(r"\}") else {
+                rest = &rest[start..];
+                break;
+            };
+            match call[..end]
+                .split_once(':')
+                .and_then(|(name, arg)| Some((self.get(name)?, arg)))
+            {
+                Some((function, arg)) => result.push_str(&function(arg)),
+                None => result.push_str(&rest[start..start + 2 + end + 2]),
+            }
+            rest = &call[end + 2..];
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+/// Encodes `bytes` as standard base64, with padding
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0, |n, (idx, &byte)| {
+            n | (usize::from(byte) << (16 - 8 * idx))
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(n >> (18 - 6 * idx)) & 0x3f]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReplaceStats {
+    pub num_successes: usize,
+    pub errors: Vec<SearchResultWithReplacement>,
+}
+
+pub fn calculate_statistics<I>(results: I) -> ReplaceStats
+where
+    I: IntoIterator<Item = SearchResultWithReplacement>,
+{
+    let mut num_successes = 0;
+    let mut errors = vec![];
+
+    results.into_iter().for_each(|res| {
+        assert!(
+            res.search_result.included,
+            "Expected only included results, found {res:?}"
+        );
+        match &res.replace_result {
+            Some(ReplaceResult::Success) => {
+                num_successes += 1;
+            }
+            None => {
+                let mut res = res.clone();
+                res.replace_result = Some(ReplaceResult::Error(
+                    "Failed to find search result in file".to_owned(),
+                ));
+                errors.push(res);
+            }
+            Some(ReplaceResult::Error(_)) => {
+                errors.push(res.clone());
+            }
+        }
+    });
+
+    ReplaceStats {
+        num_successes,
+        errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_reader::LineEnding;
+    use crate::search::{SearchResult, SearchType, search_file};
+    use crate::store::FILESYSTEM;
+    use regex::Regex;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    mod test_helpers {
+        use crate::search::SearchType;
+
+        pub fn create_fixed_search(term: &str) -> SearchType {
+            SearchType::Fixed(term.to_string())
+        }
+    }
+
+    // Helper functions
+    fn create_search_result_with_replacement(
+        path: &str,
+        line_number: usize,
+        line: &str,
+        replacement: &str,
+        included: bool,
         replace_result: Option<ReplaceResult>,
     ) -> SearchResultWithReplacement {
         SearchResultWithReplacement {
@@ -259,6 +2137,7 @@ mod tests {
                 line: line.to_string(),
                 line_ending: LineEnding::Lf,
                 included,
+                match_count: 1,
             },
             replacement: replacement.to_string(),
             replace_result,
@@ -271,425 +2150,2165 @@ mod tests {
         file_path
     }
 
-    fn assert_file_content(file_path: &Path, expected_content: &str) {
-        let content = std::fs::read_to_string(file_path).unwrap();
-        assert_eq!(content, expected_content);
-    }
+    fn assert_file_content(file_path: &Path, expected_content: &str) {
+        let content = std::fs::read_to_string(file_path).unwrap();
+        assert_eq!(content, expected_content);
+    }
+
+    fn fixed_search(pattern: &str) -> SearchType {
+        SearchType::Fixed(pattern.to_string())
+    }
+
+    fn regex_search(pattern: &str) -> SearchType {
+        SearchType::Pattern(Regex::new(pattern).unwrap())
+    }
+
+    // Tests for replace_in_file
+    #[test]
+    fn test_replace_in_file_success() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "line 1\nold text\nline 3\nold text\nline 5\n",
+        );
+
+        // Create search results
+        let mut results = vec![
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                2,
+                "old text",
+                "new text",
+                true,
+                None,
+            ),
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                4,
+                "old text",
+                "new text",
+                true,
+                None,
+            ),
+        ];
+
+        // Perform replacement
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |_| true);
+        assert!(result.is_ok());
+
+        // Verify replacements were marked as successful
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
+        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+
+        // Verify file content
+        assert_file_content(&file_path, "line 1\nnew text\nline 3\nnew text\nline 5\n");
+    }
+
+    #[test]
+    fn test_replace_in_file_with_filter() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old 1\nold 2\nold 3\n");
+
+        let mut results = [
+            (1, "old 1", "new 1"),
+            (2, "old 2", "new 2"),
+            (3, "old 3", "new 3"),
+        ]
+        .into_iter()
+        .map(|(line_number, line, replacement)| {
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                line_number,
+                line,
+                replacement,
+                true,
+                None,
+            )
+        })
+        .collect::<Vec<_>>();
+
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |res| {
+            res.search_result.line_number != 2
+        });
+        assert!(result.is_ok());
+
+        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
+        assert_eq!(results[1].replace_result, None);
+        assert_eq!(results[2].replace_result, Some(ReplaceResult::Success));
+        assert_file_content(&file_path, "new 1\nold 2\nnew 3\n");
+
+        // The file isn't rewritten when every replacement is filtered out
+        let result = replace_in_file(&mut results, &backup_config(), |_| false);
+        assert!(result.is_ok());
+        assert!(!temp_dir.path().join("test.txt.bak").exists());
+    }
+
+    #[test]
+    fn test_replace_in_file_success_no_final_newline() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "line 1\nold text\nline 3\nold text\nline 5",
+        );
+
+        // Create search results
+        let mut results = vec![
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                2,
+                "old text",
+                "new text",
+                true,
+                None,
+            ),
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                4,
+                "old text",
+                "new text",
+                true,
+                None,
+            ),
+        ];
+
+        // Perform replacement
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |_| true);
+        assert!(result.is_ok());
+
+        // Verify replacements were marked as successful
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
+        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+
+        // Verify file content
+        let new_content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(new_content, "line 1\nnew text\nline 3\nnew text\nline 5");
+    }
+
+    #[test]
+    fn test_replace_in_file_delete_empty_lines() {
+        let config = FileSearcherConfig {
+            delete_empty_lines: true,
+            ..FileSearcherConfig::default()
+        };
+        // Each case deletes `old`, removing the line it's on, and also deletes `old` in `old 4`, which is left
+        // as a non-empty line
+        for (content, line_number, expected) in [
+            ("old\nline 2\nline 3\nold 4\n", 1, "line 2\nline 3\n 4\n"),
+            ("line 1\nold\nline 3\nold 4\n", 2, "line 1\nline 3\n 4\n"),
+            ("line 1\nline 2\nold 4\nold", 4, "line 1\nline 2\n 4\n"),
+        ] {
+            let temp_dir = TempDir::new().unwrap();
+            let file_path = create_test_file(&temp_dir, "test.txt", content);
+            let old_4_line_number = content.lines().position(|l| l == "old 4").unwrap() + 1;
+
+            let mut results = vec![
+                create_search_result_with_replacement(
+                    file_path.to_str().unwrap(),
+                    line_number,
+                    "old",
+                    "",
+                    true,
+                    None,
+                ),
+                create_search_result_with_replacement(
+                    file_path.to_str().unwrap(),
+                    old_4_line_number,
+                    "old 4",
+                    " 4",
+                    true,
+                    None,
+                ),
+            ];
+
+            let result = replace_in_file(&mut results, &config, |_| true);
+            assert!(result.is_ok());
+            assert!(
+                results
+                    .iter()
+                    .all(|r| r.replace_result == Some(ReplaceResult::Success))
+            );
+            assert_file_content(&file_path, expected);
+        }
+    }
+
+    #[test]
+    fn test_replace_in_file_collapse_duplicate_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "line 1\n\n\n\nline 2\n\nline 3\n\n\n\n",
+        );
+        // Line 10 is identical to line 9, but isn't replaced, so is kept
+        let mut results = [2, 3, 4, 6, 8, 9]
+            .into_iter()
+            .map(|line_number| {
+                create_search_result_with_replacement(
+                    file_path.to_str().unwrap(),
+                    line_number,
+                    "",
+                    "",
+                    true,
+                    None,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let config = FileSearcherConfig {
+            collapse_duplicate_lines: true,
+            ..FileSearcherConfig::default()
+        };
+        replace_in_file(&mut results, &config, |_| true).unwrap();
+        assert!(
+            results
+                .iter()
+                .all(|r| r.replace_result == Some(ReplaceResult::Success))
+        );
+        assert_file_content(&file_path, "line 1\n\nline 2\n\nline 3\n\n\n");
+    }
+
+    #[test]
+    fn test_replace_in_file_success_windows_newlines() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "line 1\r\nold text\r\nline 3\r\nold text\r\nline 5\r\n",
+        );
+
+        // Create search results
+        let mut results = vec![
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                2,
+                "old text",
+                "new text",
+                true,
+                None,
+            ),
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                4,
+                "old text",
+                "new text",
+                true,
+                None,
+            ),
+        ];
+
+        // Perform replacement
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |_| true);
+        assert!(result.is_ok());
+
+        // Verify replacements were marked as successful
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
+        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+
+        // Verify file content
+        let new_content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(
+            new_content,
+            "line 1\r\nnew text\r\nline 3\r\nnew text\r\nline 5\r\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_in_file_success_mixed_newlines() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "\n\r\nline 1\nold text\r\nline 3\nline 4\r\nline 5\r\n\n\n",
+        );
+
+        // Create search results
+        let mut results = vec![
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                4,
+                "old text",
+                "new text",
+                true,
+                None,
+            ),
+            create_search_result_with_replacement(
+                file_path.to_str().unwrap(),
+                7,
+                "line 5",
+                "updated line 5",
+                true,
+                None,
+            ),
+        ];
+
+        // Perform replacement
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |_| true);
+        assert!(result.is_ok());
+
+        // Verify replacements were marked as successful
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
+        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+
+        // Verify file content
+        let new_content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(
+            new_content,
+            "\n\r\nline 1\nnew text\r\nline 3\nline 4\r\nupdated line 5\r\n\n\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_in_file_line_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "line 1\nactual text\nline 3\n");
+
+        // Create search result with mismatching line
+        let mut results = vec![create_search_result_with_replacement(
+            file_path.to_str().unwrap(),
+            2,
+            "expected text",
+            "new text",
+            true,
+            None,
+        )];
+
+        // Perform replacement
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |_| true);
+        assert!(result.is_ok());
+
+        // Verify replacement was marked as error
+        assert_eq!(
+            results[0].replace_result,
+            Some(ReplaceResult::Error(
+                "File changed since last search".to_owned()
+            ))
+        );
+
+        // Verify file content is unchanged
+        let new_content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(new_content, "line 1\nactual text\nline 3\n");
+    }
+
+    #[test]
+    fn test_replace_in_file_nonexistent_file() {
+        let mut results = vec![create_search_result_with_replacement(
+            "/nonexistent/path/file.txt",
+            1,
+            "old",
+            "new",
+            true,
+            None,
+        )];
+
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |_| true);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_replace_directory_errors() {
+        let mut results = vec![create_search_result_with_replacement(
+            "/", 0, "foo", "bar", true, None,
+        )];
+
+        let result = replace_in_file(&mut results, &FileSearcherConfig::default(), |_| true);
+        assert!(result.is_err());
+    }
+
+    // Tests for replace_in_memory
+    #[test]
+    fn test_replace_in_memory() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Test with fixed string
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "This is a test.\nIt contains search_term that should be replaced.\nMultiple lines with search_term here.",
+        );
+
+        let result = replace_in_memory(
+            &file_path,
+            &[(fixed_search("search_term"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 2); // Should return the number of matches replaced
+
+        assert_file_content(
+            &file_path,
+            "This is a test.\nIt contains replacement that should be replaced.\nMultiple lines with replacement here.",
+        );
+
+        // Test with regex pattern
+        let regex_path = create_test_file(
+            &temp_dir,
+            "regex_test.txt",
+            "Number: 123, Code: 456, ID: 789",
+        );
+
+        let result = replace_in_memory(
+            &regex_path,
+            &[(regex_search(r"\d{3}"), "XXX".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 3);
+
+        assert_file_content(&regex_path, "Number: XXX, Code: XXX, ID: XXX");
+    }
+
+    #[test]
+    fn test_replace_in_memory_no_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "no_match.txt",
+            "This is a test file with no matches.",
+        );
+
+        let result = replace_in_memory(
+            &file_path,
+            &[(fixed_search("nonexistent"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 0); // Should return 0 for no modifications
+
+        // Verify file content unchanged
+        assert_file_content(&file_path, "This is a test file with no matches.");
+    }
+
+    #[test]
+    fn test_replace_in_memory_empty_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "empty.txt", "");
+
+        let result = replace_in_memory(
+            &file_path,
+            &[(fixed_search("anything"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 0);
+
+        // Verify file still empty
+        assert_file_content(&file_path, "");
+    }
+
+    #[test]
+    fn test_replace_in_memory_nonexistent_file() {
+        let result = replace_in_memory(
+            Path::new("/nonexistent/path/file.txt"),
+            &[(fixed_search("test"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    // Tests for replace_chunked
+    #[test]
+    fn test_replace_chunked() {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Test with fixed string
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "This is line one.\nThis contains search_pattern to replace.\nAnother line with search_pattern here.\nFinal line.",
+        );
+
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("search_pattern"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 2); // Check that replacement happened
+
+        assert_file_content(
+            &file_path,
+            "This is line one.\nThis contains replacement to replace.\nAnother line with replacement here.\nFinal line.",
+        );
+
+        // Test with regex pattern
+        let regex_path = create_test_file(
+            &temp_dir,
+            "regex.txt",
+            "Line with numbers: 123 and 456.\nAnother line with 789.",
+        );
+
+        let result = replace_chunked(
+            &regex_path,
+            &[(regex_search(r"\d{3}"), "XXX".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 3);
+
+        assert_file_content(
+            &regex_path,
+            "Line with numbers: XXX and XXX.\nAnother line with XXX.",
+        );
+    }
+
+    #[test]
+    fn test_replace_chunked_no_match() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "This is a test file with no matching patterns.",
+        );
+
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("nonexistent"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 0);
+
+        // Verify file content unchanged
+        assert_file_content(&file_path, "This is a test file with no matching patterns.");
+    }
+
+    #[test]
+    fn test_replace_chunked_empty_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "empty.txt", "");
+
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("anything"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 0);
+
+        // Verify file still empty
+        assert_file_content(&file_path, "");
+    }
+
+    #[test]
+    fn test_replace_chunked_nonexistent_file() {
+        let result = replace_chunked(
+            Path::new("/nonexistent/path/file.txt"),
+            &[(fixed_search("test"), "replacement".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_replace_chunked_invalid_utf8() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        std::fs::write(&file_path, b"old 1\r\n\xff old\nold 2").unwrap();
+
+        // Lines that aren't valid UTF-8 are written unchanged, and reported with the offset of the first invalid
+        // byte
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert_eq!(
+            result.unwrap(),
+            ReplacedFile {
+                num_replacements: 2,
+                warnings: vec![ReplaceWarning::InvalidUtf8 { offset: 7 }],
+            }
+        );
+        assert_eq!(
+            std::fs::read(&file_path).unwrap(),
+            b"new 1\r\n\xff old\nnew 2"
+        );
+
+        // Files without replacements aren't reported
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("nonexistent"), "new".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert_eq!(result.unwrap(), ReplacedFile::default());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        // Invalid bytes are only replaced on lines with replacements
+        let lossy_config = FileSearcherConfig {
+            lossy: true,
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("2"), "3".to_string())],
+            &lossy_config,
+        );
+        assert_eq!(
+            result.unwrap(),
+            ReplacedFile {
+                num_replacements: 1,
+                warnings: vec![],
+            }
+        );
+        assert_eq!(
+            std::fs::read(&file_path).unwrap(),
+            b"new 1\r\n\xff old\nnew 3"
+        );
+
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &lossy_config,
+        );
+        assert_eq!(result.unwrap().num_replacements, 1);
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "new 1\r\n\u{fffd} new\nnew 3"
+        );
+    }
+
+    #[test]
+    fn test_replace_all_in_file_invalid_utf8() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        std::fs::write(&file_path, b"old\n\xe9t\xe9 old\n").unwrap();
+
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert_eq!(
+            result.unwrap(),
+            ReplacedFile {
+                num_replacements: 1,
+                warnings: vec![ReplaceWarning::InvalidUtf8 { offset: 4 }],
+            }
+        );
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"new\n\xe9t\xe9 old\n");
+    }
+
+    fn write_gzip(path: &Path, contents: &str) {
+        let mut encoder = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+        encoder.write_all(contents.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    fn read_gzip(path: &Path) -> String {
+        let mut contents = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn test_replace_all_in_gzip_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.log.gz");
+        // Detected from the magic bytes, without a `.gz` extension
+        let no_extension = temp_dir.path().join("test.log");
+        write_gzip(&file_path, "old 1\nkeep\nold 2 old\n");
+        write_gzip(&no_extension, "old\n");
+        std::fs::write(temp_dir.path().join("plain.txt"), "old\n").unwrap();
+        assert!(is_gzip_file(&FILESYSTEM, &file_path).unwrap());
+        assert!(is_gzip_file(&FILESYSTEM, &no_extension).unwrap());
+        assert!(!is_gzip_file(&FILESYSTEM, &temp_dir.path().join("plain.txt")).unwrap());
+
+        let exprs = [(fixed_search("old"), "new".to_string())];
+        let dry_run = FileSearcherConfig {
+            dry_run: true,
+            ..FileSearcherConfig::default()
+        };
+        assert_eq!(
+            replace_all_in_gzip_file(&file_path, &exprs, &dry_run)
+                .unwrap()
+                .num_replacements,
+            3
+        );
+        assert_eq!(read_gzip(&file_path), "old 1\nkeep\nold 2 old\n");
+
+        let config = FileSearcherConfig {
+            compression_level: Some(9),
+            ..FileSearcherConfig::default()
+        };
+        assert_eq!(
+            replace_all_in_gzip_file(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            3
+        );
+        assert_eq!(read_gzip(&file_path), "new 1\nkeep\nnew 2 new\n");
+        assert_eq!(
+            replace_all_in_gzip_file(&no_extension, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            1
+        );
+        assert_eq!(read_gzip(&no_extension), "new\n");
+    }
+
+    #[test]
+    fn test_replace_all_in_gzip_file_too_large() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.gz");
+        // Compresses to far fewer than 100 bytes, but the limit applies to the decompressed size
+        write_gzip(&file_path, &"old\n".repeat(100));
+
+        let config = FileSearcherConfig {
+            max_in_memory_size: Some(100),
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_gzip_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &config,
+        );
+        assert!(result.unwrap_err().is::<FileTooLargeError>());
+        assert_eq!(read_gzip(&file_path), "old\n".repeat(100));
+
+        let config = FileSearcherConfig {
+            diff: true,
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_gzip_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &config,
+        );
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("`diff` isn't supported")
+        );
+    }
+
+    // Tests for replace_all_in_file
+    #[test]
+    fn test_replace_all_in_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "test.txt",
+            "This is a test file.\nIt has some content to replace.\nThe word replace should be replaced.",
+        );
+
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("replace"), "modify".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().num_replacements, 3);
+
+        assert_file_content(
+            &file_path,
+            "This is a test file.\nIt has some content to modify.\nThe word modify should be modifyd.",
+        );
+    }
+
+    fn utf16_le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_replace_all_in_file_utf16_with_bom() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("utf16.txt");
+        let mut content = vec![0xFF, 0xFE];
+        content.extend(utf16_le("line 1\r\nold text\r\n"));
+        std::fs::write(&file_path, &content).unwrap();
+
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &FileSearcherConfig::default(),
+        );
+        assert_eq!(result.unwrap().num_replacements, 1);
+
+        let mut expected = vec![0xFF, 0xFE];
+        expected.extend(utf16_le("line 1\r\nnew text\r\n"));
+        assert_eq!(std::fs::read(&file_path).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_replace_all_in_file_utf16_encoding_override() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("utf16.txt");
+        std::fs::write(&file_path, utf16_le("old text")).unwrap();
+
+        let config = FileSearcherConfig {
+            encoding: Some(Encoding::Utf16Le),
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &config,
+        );
+        assert_eq!(result.unwrap().num_replacements, 1);
+
+        assert_eq!(std::fs::read(&file_path).unwrap(), utf16_le("new text"));
+    }
+
+    #[test]
+    fn test_replace_all_in_file_utf16_line_by_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("utf16.txt");
+        let mut content = vec![0xFF, 0xFE];
+        content.extend(utf16_le("old old\r\n"));
+        std::fs::write(&file_path, &content).unwrap();
+
+        // Rather than being searched as if it were UTF-8, and so appearing to have no matches
+        let config = FileSearcherConfig {
+            occurrence: NonZero::new(2),
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &config,
+        );
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("UTF-16 files can't be replaced line by line")
+        );
+        assert_eq!(std::fs::read(&file_path).unwrap(), content);
+    }
+
+    #[test]
+    fn test_should_replace_in_memory_threshold() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "0123456789");
+
+        assert!(
+            should_replace_in_memory(&FILESYSTEM, &file_path, DEFAULT_MAX_IN_MEMORY_SIZE).unwrap()
+        );
+        assert!(should_replace_in_memory(&FILESYSTEM, &file_path, 10).unwrap());
+        assert!(!should_replace_in_memory(&FILESYSTEM, &file_path, 9).unwrap());
+    }
+
+    #[test]
+    fn test_replace_in_memory_bounded() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old 1\nold 2\n");
+        let exprs = [(fixed_search("old"), "new".to_string())];
+
+        // Simulates the file having grown past the threshold after its size was checked
+        let err = replace_in_memory_bounded(&file_path, &exprs, &FileSearcherConfig::default(), 11)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<FileTooLargeError>(),
+            Some(&FileTooLargeError { max_size: 11 })
+        );
+        assert_file_content(&file_path, "old 1\nold 2\n");
+
+        let result =
+            replace_in_memory_bounded(&file_path, &exprs, &FileSearcherConfig::default(), 12);
+        assert_eq!(result.unwrap().num_replacements, 2);
+        assert_file_content(&file_path, "new 1\nnew 2\n");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_above_in_memory_threshold() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\r\nline 2\nold");
+
+        let config = FileSearcherConfig {
+            max_in_memory_size: Some(1),
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &config,
+        );
+        assert_eq!(result.unwrap().num_replacements, 2);
+
+        assert_file_content(&file_path, "new text\r\nline 2\nnew");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_binary() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.dat", "old\0\nold\n");
+        let exprs = [(fixed_search("old"), "new".to_string())];
+
+        let result = replace_all_in_file(&file_path, &exprs, &FileSearcherConfig::default());
+        assert!(result.unwrap_err().is::<BinaryFileError>());
+        assert_file_content(&file_path, "old\0\nold\n");
+
+        let config = FileSearcherConfig {
+            include_binary: true,
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_file(&file_path, &exprs, &config);
+        assert_eq!(result.unwrap().num_replacements, 2);
+        assert_file_content(&file_path, "new\0\nnew\n");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_line_ending() {
+        let temp_dir = TempDir::new().unwrap();
+        let exprs = [(fixed_search("old"), "new".to_string())];
+
+        for max_in_memory_size in [None, Some(1)] {
+            let file_path =
+                create_test_file(&temp_dir, "test.txt", "old\r\nkeep\nkeep\r\nold\nlast");
+            let config = FileSearcherConfig {
+                line_ending: Some(LineEnding::Lf),
+                max_in_memory_size,
+                ..FileSearcherConfig::default()
+            };
+            assert_eq!(
+                replace_all_in_file(&file_path, &exprs, &config)
+                    .unwrap()
+                    .num_replacements,
+                2
+            );
+            assert_file_content(&file_path, "new\nkeep\nkeep\nnew\nlast");
+
+            let file_path = create_test_file(&temp_dir, "test.txt", "old\nkeep\r\nkeep\n");
+            let config = FileSearcherConfig {
+                line_ending: Some(LineEnding::CrLf),
+                max_in_memory_size,
+                ..FileSearcherConfig::default()
+            };
+            assert_eq!(
+                replace_all_in_file(&file_path, &exprs, &config)
+                    .unwrap()
+                    .num_replacements,
+                1
+            );
+            assert_file_content(&file_path, "new\r\nkeep\r\nkeep\r\n");
+        }
+
+        // Files without replacements are left untouched
+        let file_path = create_test_file(&temp_dir, "test.txt", "keep\r\nkeep\n");
+        let config = FileSearcherConfig {
+            line_ending: Some(LineEnding::Lf),
+            ..FileSearcherConfig::default()
+        };
+        assert_eq!(
+            replace_all_in_file(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            0
+        );
+        assert_file_content(&file_path, "keep\r\nkeep\n");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_ensure_trailing_newline() {
+        let temp_dir = TempDir::new().unwrap();
+        let exprs = [(fixed_search("old"), "new".to_string())];
+
+        // Replaced in memory, and line by line
+        for max_in_memory_size in [None, Some(1)] {
+            let config = FileSearcherConfig {
+                ensure_trailing_newline: true,
+                max_in_memory_size,
+                ..FileSearcherConfig::default()
+            };
+            let file_path = create_test_file(&temp_dir, "test.txt", "old\r\nlast");
+            assert_eq!(
+                replace_all_in_file(&file_path, &exprs, &config)
+                    .unwrap()
+                    .num_replacements,
+                1
+            );
+            assert_file_content(&file_path, "new\r\nlast\r\n");
+
+            let file_path = create_test_file(&temp_dir, "test.txt", "old\nlast\n\n\n");
+            assert_eq!(
+                replace_all_in_file(&file_path, &exprs, &config)
+                    .unwrap()
+                    .num_replacements,
+                1
+            );
+            assert_file_content(&file_path, "new\nlast\n");
+
+            // Files without replacements are left untouched
+            let file_path = create_test_file(&temp_dir, "test.txt", "keep\nlast");
+            assert_eq!(
+                replace_all_in_file(&file_path, &exprs, &config)
+                    .unwrap()
+                    .num_replacements,
+                0
+            );
+            assert_file_content(&file_path, "keep\nlast");
+        }
+    }
+
+    #[test]
+    fn test_ensure_trailing_newline_in_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let ensure = |contents: &str, target| {
+            let file_path = create_test_file(&temp_dir, "test.txt", "");
+            let config = FileSearcherConfig::default();
+            let mut output = FILESYSTEM.create(&file_path, &config).unwrap();
+            output.write_all(contents.as_bytes()).unwrap();
+            ensure_trailing_newline_in_file(output.as_mut(), target).unwrap();
+            output.commit().unwrap();
+            fs::read_to_string(&file_path).unwrap()
+        };
+
+        assert_eq!(ensure("a\r\nb", None), "a\r\nb\r\n");
+        assert_eq!(ensure("a\nb\r\n\n", Some(LineEnding::CrLf)), "a\nb\r\n");
+        assert_eq!(ensure("", None), "");
+        // The file is read back across several chunks
+        let newlines = "\r\n".repeat(10_000);
+        assert_eq!(ensure(&format!("a{newlines}"), None), "a\r\n");
+        let long_line = "b".repeat(20_000);
+        assert_eq!(
+            ensure(&format!("a\r\n{long_line}"), None),
+            format!("a\r\n{long_line}\r\n")
+        );
+    }
+
+    #[test]
+    fn test_replace_all_in_file_occurrence() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old old old\r\nold\nold old");
+
+        let config = FileSearcherConfig {
+            occurrence: NonZero::new(2),
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &config,
+        );
+        assert_eq!(result.unwrap().num_replacements, 2);
+
+        assert_file_content(&file_path, "old new old\r\nold\nold new");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_first_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "keep\nold 1\r\nold 2\nold 3");
+
+        let config = FileSearcherConfig {
+            first_only: true,
+            ..FileSearcherConfig::default()
+        };
+        let exprs = [(fixed_search("old"), "new".to_string())];
+        assert_eq!(
+            replace_all_in_file(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "keep\nnew 1\r\nold 2\nold 3");
+
+        // Line endings after the replaced line are still rewritten
+        let config = FileSearcherConfig {
+            line_ending: Some(LineEnding::Lf),
+            ..config
+        };
+        assert_eq!(
+            replace_all_in_file(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "keep\nnew 1\nnew 2\nold 3");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_only_matching_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old\nprod old\nold prod\nold");
+
+        let config = FileSearcherConfig {
+            only_matching_lines: Some(regex_search("prod")),
+            ..FileSearcherConfig::default()
+        };
+        let exprs = [(fixed_search("old"), "new".to_string())];
+        assert_eq!(
+            replace_all_in_file(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            2
+        );
+        assert_file_content(&file_path, "old\nprod new\nnew prod\nold");
+
+        // The guard pattern also applies when replacing with a command
+        let config = FileSearcherConfig {
+            only_matching_lines: Some(fixed_search("new")),
+            replace_command: Some(ReplaceCommand::new("tr a-z A-Z")),
+            ..FileSearcherConfig::default()
+        };
+        let exprs = [(regex_search("new|old"), String::new())];
+        assert_eq!(
+            replace_all_in_file(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            2
+        );
+        assert_file_content(&file_path, "old\nprod NEW\nNEW prod\nold");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_multiline() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old\ntext\nold\n");
+
+        let search = fixed_search("old\ntext");
+        let config = FileSearcherConfig::default();
+        assert_eq!(
+            replace_all_in_file_multiline(
+                &file_path,
+                &[(search.clone(), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "new\nold\n");
+        assert_eq!(
+            replace_all_in_file_multiline(
+                &file_path,
+                &[(search.clone(), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            0
+        );
+
+        let config = FileSearcherConfig {
+            max_in_memory_size: Some(1),
+            ..FileSearcherConfig::default()
+        };
+        let result = replace_all_in_file_multiline(
+            &file_path,
+            &[(fixed_search("old"), "".to_string())],
+            &config,
+        );
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("can't be replaced in multiline mode")
+        );
+        assert_file_content(&file_path, "new\nold\n");
+    }
+
+    #[test]
+    fn test_replace_all_in_file_join_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(
+            &temp_dir,
+            "query.sql",
+            "SELECT a,\n  b\nFROM t;\nSELECT c FROM u;\nSELECT d\n\n\n\nFROM v;\n",
+        );
+        let config = FileSearcherConfig {
+            join_lines: NonZero::new(3),
+            ..FileSearcherConfig::default()
+        };
+
+        // Matches spanning more lines than the window are left unchanged
+        let exprs = [(
+            regex_search("(?s)SELECT(.*?)FROM"),
+            "PICK${1}FROM".to_string(),
+        )];
+        assert_eq!(
+            replace_all_in_file_multiline(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            2
+        );
+        assert_file_content(
+            &file_path,
+            "PICK a,\n  b\nFROM t;\nPICK c FROM u;\nSELECT d\n\n\n\nFROM v;\n",
+        );
+
+        // Lines covered by a replacement aren't searched again in later windows
+        std::fs::write(&file_path, "x\nx\nx\n").unwrap();
+        let exprs = [(fixed_search("x\nx"), "y".to_string())];
+        assert_eq!(
+            replace_all_in_file_multiline(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "y\nx\n");
+
+        let exprs = [
+            (fixed_search("x"), "y".to_string()),
+            (fixed_search("y"), "z".to_string()),
+        ];
+        assert!(replace_all_in_file_multiline(&file_path, &exprs, &config).is_err());
+        assert_file_content(&file_path, "y\nx\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permissions_preserved() {
+        use std::os::unix::fs::PermissionsExt;
 
-    fn fixed_search(pattern: &str) -> SearchType {
-        SearchType::Fixed(pattern.to_string())
-    }
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "script.sh", "echo old\n");
+        std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o755)).unwrap();
 
-    fn regex_search(pattern: &str) -> SearchType {
-        SearchType::Pattern(Regex::new(pattern).unwrap())
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        let config = FileSearcherConfig::default();
+        assert_eq!(
+            replace_in_memory(
+                &file_path,
+                &[(fixed_search("old"), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "echo new\n");
+        assert_eq!(mode(&file_path), 0o755);
+
+        assert_eq!(
+            replace_chunked(
+                &file_path,
+                &[(fixed_search("new"), "newer".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "echo newer\n");
+        assert_eq!(mode(&file_path), 0o755);
     }
 
-    // Tests for replace_in_file
+    #[cfg(unix)]
     #[test]
-    fn test_replace_in_file_success() {
+    fn test_read_only_parent_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
         let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "line 1\nold text\nline 3\nold text\nline 5\n",
-        );
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
+        let set_dir_mode = |mode| {
+            std::fs::set_permissions(temp_dir.path(), std::fs::Permissions::from_mode(mode))
+                .unwrap();
+        };
+        set_dir_mode(0o555);
 
-        // Create search results
-        let mut results = vec![
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                2,
-                "old text",
-                "new text",
-                true,
-                None,
-            ),
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                4,
-                "old text",
-                "new text",
-                true,
-                None,
-            ),
-        ];
+        let config = FileSearcherConfig::default();
+        let in_memory_result = replace_in_memory(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &config,
+        );
+        let chunked_result = replace_chunked(
+            &file_path,
+            &[(fixed_search("new"), "newer".to_string())],
+            &config,
+        );
+        let entries = std::fs::read_dir(temp_dir.path()).unwrap().count();
+        set_dir_mode(0o755);
 
-        // Perform replacement
-        let result = replace_in_file(&mut results);
-        assert!(result.is_ok());
+        assert_eq!(in_memory_result.unwrap().num_replacements, 1);
+        assert_eq!(chunked_result.unwrap().num_replacements, 1);
+        assert_file_content(&file_path, "newer text\n");
+        assert_eq!(entries, 1);
+    }
 
-        // Verify replacements were marked as successful
-        assert_eq!(results.len(), 2);
-        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
-        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+    #[test]
+    fn test_temp_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let other_temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
 
-        // Verify file content
-        assert_file_content(&file_path, "line 1\nnew text\nline 3\nnew text\nline 5\n");
+        let config = FileSearcherConfig {
+            temp_dir: Some(other_temp_dir.path().to_path_buf()),
+            ..FileSearcherConfig::default()
+        };
+        assert_eq!(
+            replace_in_memory(
+                &file_path,
+                &[(fixed_search("old"), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_eq!(
+            replace_chunked(
+                &file_path,
+                &[(fixed_search("new"), "newer".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "newer text\n");
+        assert_eq!(std::fs::read_dir(other_temp_dir.path()).unwrap().count(), 0);
     }
 
     #[test]
-    fn test_replace_in_file_success_no_final_newline() {
+    fn test_check_unchanged() {
         let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "line 1\nold text\nline 3\nold text\nline 5",
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
+        let original_metadata = std::fs::metadata(&file_path).unwrap();
+        let config = FileSearcherConfig {
+            check_unchanged: true,
+            ..FileSearcherConfig::default()
+        };
+
+        assert!(check_unchanged(&file_path, &original_metadata, &config).is_ok());
+
+        std::fs::write(&file_path, "changed text\n").unwrap();
+        let result = check_unchanged(&file_path, &original_metadata, &config);
+        assert!(result.unwrap_err().is::<FileChangedError>());
+        assert!(
+            check_unchanged(
+                &file_path,
+                &original_metadata,
+                &FileSearcherConfig::default()
+            )
+            .is_ok()
         );
+    }
 
-        // Create search results
-        let mut results = vec![
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                2,
-                "old text",
-                "new text",
-                true,
-                None,
-            ),
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                4,
-                "old text",
-                "new text",
-                true,
-                None,
-            ),
-        ];
+    #[test]
+    fn test_temp_output_file_in_place() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let original_metadata = std::fs::metadata(&file_path).unwrap();
+
+        let mut temp_file = TempOutputFile {
+            file: NamedTempFile::new().unwrap(),
+            in_place: true,
+            fallback_reason: None,
+        };
+        temp_file.file.write_all(b"new\n").unwrap();
+        let warning = temp_file
+            .persist(&file_path, &original_metadata, true)
+            .unwrap();
 
-        // Perform replacement
-        let result = replace_in_file(&mut results);
-        assert!(result.is_ok());
+        assert_eq!(warning, None);
+        assert_file_content(&file_path, "new\n");
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().modified().unwrap(),
+            mtime
+        );
 
-        // Verify replacements were marked as successful
-        assert_eq!(results.len(), 2);
-        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
-        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+        // Falling back to overwriting in place is reported
+        let mut temp_file = TempOutputFile {
+            file: NamedTempFile::new().unwrap(),
+            in_place: true,
+            fallback_reason: Some("read-only directory".to_owned()),
+        };
+        temp_file.file.write_all(b"newer\n").unwrap();
+        let warning = temp_file
+            .persist(&file_path, &original_metadata, true)
+            .unwrap();
 
-        // Verify file content
-        let new_content = std::fs::read_to_string(&file_path).unwrap();
-        assert_eq!(new_content, "line 1\nnew text\nline 3\nnew text\nline 5");
+        assert_eq!(
+            warning,
+            Some(ReplaceWarning::OverwrittenInPlace {
+                reason: "read-only directory".to_owned()
+            })
+        );
+        assert_file_content(&file_path, "newer\n");
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_replace_in_file_success_windows_newlines() {
+    fn test_symlink_writes() {
+        use std::os::unix::fs::symlink;
+
         let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "line 1\r\nold text\r\nline 3\r\nold text\r\nline 5\r\n",
-        );
+        let target_path = create_test_file(&temp_dir, "target.txt", "old text\n");
+        let link_path = temp_dir.path().join("link.txt");
+        symlink(&target_path, &link_path).unwrap();
 
-        // Create search results
-        let mut results = vec![
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                2,
-                "old text",
-                "new text",
-                true,
-                None,
-            ),
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                4,
-                "old text",
-                "new text",
-                true,
-                None,
-            ),
-        ];
+        let config = FileSearcherConfig::default();
+        assert_eq!(
+            replace_in_memory(
+                &link_path,
+                &[(fixed_search("old"), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_eq!(
+            replace_chunked(
+                &link_path,
+                &[(fixed_search("new"), "newer".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_file_content(&target_path, "newer text\n");
+        assert!(
+            std::fs::symlink_metadata(&link_path)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
 
-        // Perform replacement
-        let result = replace_in_file(&mut results);
-        assert!(result.is_ok());
+        let config = FileSearcherConfig {
+            no_follow_symlink_writes: true,
+            ..FileSearcherConfig::default()
+        };
+        let err = replace_all_in_file(
+            &link_path,
+            &[(fixed_search("newer"), "newest".to_string())],
+            &config,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("is a symlink"));
+        assert_file_content(&target_path, "newer text\n");
+
+        // A symlink to itself can't be resolved
+        let loop_path = temp_dir.path().join("loop.txt");
+        symlink(&loop_path, &loop_path).unwrap();
+        assert!(resolve_symlink(&loop_path, false).is_err());
+    }
 
-        // Verify replacements were marked as successful
-        assert_eq!(results.len(), 2);
-        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
-        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+    #[cfg(unix)]
+    #[test]
+    fn test_hard_links_preserved() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
+        let link_path = temp_dir.path().join("link.txt");
+        std::fs::hard_link(&file_path, &link_path).unwrap();
 
-        // Verify file content
-        let new_content = std::fs::read_to_string(&file_path).unwrap();
+        let config = FileSearcherConfig::default();
         assert_eq!(
-            new_content,
-            "line 1\r\nnew text\r\nline 3\r\nnew text\r\nline 5\r\n"
+            replace_in_memory(
+                &file_path,
+                &[(fixed_search("old"), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_eq!(
+            replace_chunked(
+                &link_path,
+                &[(fixed_search("new"), "newer".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
         );
+        assert_file_content(&file_path, "newer text\n");
+        assert_file_content(&link_path, "newer text\n");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 2);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_replace_in_file_success_mixed_newlines() {
+    fn test_in_place() {
+        use std::os::unix::fs::MetadataExt;
+
         let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "\n\r\nline 1\nold text\r\nline 3\nline 4\r\nline 5\r\n\n\n",
+        let other_temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
+        let inode = std::fs::metadata(&file_path).unwrap().ino();
+
+        let config = FileSearcherConfig {
+            in_place: true,
+            temp_dir: Some(other_temp_dir.path().to_path_buf()),
+            ..FileSearcherConfig::default()
+        };
+        assert_eq!(
+            replace_in_memory(
+                &file_path,
+                &[(fixed_search("old"), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_eq!(
+            replace_chunked(
+                &file_path,
+                &[(fixed_search("new"), "newer".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
         );
+        // The original file is overwritten rather than having a temporary file renamed over it
+        assert_file_content(&file_path, "newer text\n");
+        assert_eq!(std::fs::metadata(&file_path).unwrap().ino(), inode);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        assert_eq!(std::fs::read_dir(other_temp_dir.path()).unwrap().count(), 0);
+    }
 
-        // Create search results
-        let mut results = vec![
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                4,
-                "old text",
-                "new text",
-                true,
-                None,
-            ),
-            create_search_result_with_replacement(
-                file_path.to_str().unwrap(),
-                7,
-                "line 5",
-                "updated line 5",
-                true,
-                None,
-            ),
-        ];
+    #[test]
+    fn test_keep_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
 
-        // Perform replacement
-        let result = replace_in_file(&mut results);
-        assert!(result.is_ok());
+        let modified = |path: &Path| std::fs::metadata(path).unwrap().modified().unwrap();
 
-        // Verify replacements were marked as successful
-        assert_eq!(results.len(), 2);
-        assert_eq!(results[0].replace_result, Some(ReplaceResult::Success));
-        assert_eq!(results[1].replace_result, Some(ReplaceResult::Success));
+        let config = FileSearcherConfig {
+            keep_mtime: true,
+            ..FileSearcherConfig::default()
+        };
+        assert_eq!(
+            replace_in_memory(
+                &file_path,
+                &[(fixed_search("old"), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "new text\n");
+        assert_eq!(modified(&file_path), mtime);
 
-        // Verify file content
-        let new_content = std::fs::read_to_string(&file_path).unwrap();
         assert_eq!(
-            new_content,
-            "\n\r\nline 1\nnew text\r\nline 3\nline 4\r\nupdated line 5\r\n\n\n"
+            replace_chunked(
+                &file_path,
+                &[(fixed_search("new"), "newer".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
+        );
+        assert_file_content(&file_path, "newer text\n");
+        assert_eq!(modified(&file_path), mtime);
+
+        let config = FileSearcherConfig::default();
+        assert_eq!(
+            replace_in_memory(
+                &file_path,
+                &[(fixed_search("newer"), "new".to_string())],
+                &config
+            )
+            .unwrap()
+            .num_replacements,
+            1
         );
+        assert_ne!(modified(&file_path), mtime);
     }
 
     #[test]
-    fn test_replace_in_file_line_mismatch() {
-        let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(&temp_dir, "test.txt", "line 1\nactual text\nline 3\n");
+    fn test_replacement_functions() {
+        let mut functions = ReplacementFunctions::default();
+        assert_eq!(
+            functions.apply(r"\{uppercase:abc\}-\{reverse:abc\}"),
+            "ABC-cba"
+        );
+        assert_eq!(functions.apply(r"[\{trim:  a b \}]"), "[a b]");
+        assert_eq!(functions.apply(r"\{base64:hello\}"), "aGVsbG8=");
+        // Unknown functions and unterminated calls are left as they are
+        assert_eq!(
+            functions.apply(r"\{upper:abc\} \{abc\}"),
+            r"\{upper:abc\} \{abc\}"
+        );
+        assert_eq!(functions.apply(r"a \{lowercase:B"), r"a \{lowercase:B");
 
-        // Create search result with mismatching line
-        let mut results = vec![create_search_result_with_replacement(
-            file_path.to_str().unwrap(),
-            2,
-            "expected text",
-            "new text",
-            true,
-            None,
-        )];
+        functions.register("upper", str::to_uppercase);
+        assert_eq!(functions.apply(r"\{upper:abc\}"), "ABC");
+        assert_eq!(
+            functions.names(),
+            vec![
+                "base64",
+                "lowercase",
+                "reverse",
+                "trim",
+                "upper",
+                "uppercase"
+            ]
+        );
+    }
 
-        // Perform replacement
-        let result = replace_in_file(&mut results);
-        assert!(result.is_ok());
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(&[0xff, 0xfe]), "//4=");
+    }
 
-        // Verify replacement was marked as error
+    #[test]
+    fn test_replace_all_patterns_with_functions() {
+        let functions = ReplacementFunctions::default();
+        let exprs = [(
+            regex_search(r"(\w+)_id"),
+            r"\{uppercase:$1\}_ID".to_string(),
+        )];
         assert_eq!(
-            results[0].replace_result,
-            Some(ReplaceResult::Error(
-                "File changed since last search".to_owned()
-            ))
+            replace_all_patterns_with_functions("user_id, group_id", &exprs, &functions),
+            Some("USER_ID, GROUP_ID".to_string())
+        );
+        assert_eq!(
+            replace_all_patterns_with_functions("no match", &exprs, &functions),
+            None
         );
 
-        // Verify file content is unchanged
-        let new_content = std::fs::read_to_string(&file_path).unwrap();
-        assert_eq!(new_content, "line 1\nactual text\nline 3\n");
+        // Calls are only applied to the replacements, not to text in the line that wasn't matched
+        let exprs = [(regex_search("x"), r"\{reverse:ab\}".to_string())];
+        assert_eq!(
+            replace_all_patterns_with_functions(r"x \{reverse:cd\}", &exprs, &functions),
+            Some(r"ba \{reverse:cd\}".to_string())
+        );
     }
 
     #[test]
-    fn test_replace_in_file_nonexistent_file() {
-        let mut results = vec![create_search_result_with_replacement(
-            "/nonexistent/path/file.txt",
-            1,
-            "old",
-            "new",
-            true,
-            None,
-        )];
+    fn test_apply_case_transforms() {
+        assert_eq!(apply_case_transforms(r"\Ufoo\E_bar"), "FOO_bar");
+        assert_eq!(apply_case_transforms(r"\LFOO\UBar\E baz"), "fooBAR baz");
+        assert_eq!(apply_case_transforms(r"\ufoo \lBAR"), "Foo bAR");
+        assert_eq!(apply_case_transforms(r"\u\LfOO bAR"), "Foo bar");
+        assert_eq!(
+            apply_case_transforms(r"\Lstraße \Ustraße"),
+            "straße STRASSE"
+        );
+        // Unterminated and trailing sequences
+        assert_eq!(apply_case_transforms(r"\Ufoo bar"), "FOO BAR");
+        assert_eq!(apply_case_transforms(r"foo\U"), "foo");
+        assert_eq!(apply_case_transforms(r"foo\u"), "foo");
+        assert_eq!(apply_case_transforms("foo\\"), "foo\\");
+        // Other escapes are left as they are
+        assert_eq!(apply_case_transforms(r"a\nb\\Uc"), r"a\nb\Uc");
+    }
 
-        let result = replace_in_file(&mut results);
-        assert!(result.is_err());
+    #[test]
+    fn test_replacement_if_match_named_groups() {
+        let pattern = r"(?P<year>\d{4})-(?P<month>\d{2})";
+        for search in [
+            regex_search(pattern),
+            SearchType::PatternAdvanced(fancy_regex::Regex::new(pattern).unwrap()),
+        ] {
+            assert_eq!(
+                replacement_if_match("Date: 2024-03", &search, "${month}/${year}"),
+                Some("Date: 03/2024".to_string())
+            );
+            assert_eq!(
+                replacement_if_match("2024-03", &search, "$month/$year"),
+                Some("03/2024".to_string())
+            );
+            // Undefined group names expand to an empty string
+            assert_eq!(
+                replacement_if_match("2024-03", &search, "${day}/${month}"),
+                Some("/03".to_string())
+            );
+            assert_eq!(
+                replacement_if_match_with_case_transforms("2024-03", &search, r"\U${month}x"),
+                Some("03X".to_string())
+            );
+            assert_eq!(
+                replacement_if_nth_match(
+                    "2023-01 2024-03",
+                    &search,
+                    "${year}.${month}",
+                    NonZero::new(2).unwrap(),
+                    false
+                ),
+                Some("2023-01 2024.03".to_string())
+            );
+        }
     }
 
     #[test]
-    fn test_replace_directory_errors() {
-        let mut results = vec![create_search_result_with_replacement(
-            "/", 0, "foo", "bar", true, None,
-        )];
+    fn test_with_preserved_indent() {
+        let preserve =
+            |line, replacement: &str| with_preserved_indent(line, replacement.to_string());
+        assert_eq!(preserve("    key = 1", "key = 2"), "    key = 2");
+        assert_eq!(preserve("\t\tkey = 1", "key = 2"), "\t\tkey = 2");
+        assert_eq!(preserve(" \tkey = 1", "key = 2"), " \tkey = 2");
+        // Replacements that already start with the indentation are unchanged
+        assert_eq!(preserve("    key = 1", "    key = 2"), "    key = 2");
+        assert_eq!(preserve("\tkey = 1", "\t\tkey = 2"), "\t\tkey = 2");
+        // Other leading whitespace is replaced with the original indentation
+        assert_eq!(preserve("\tkey = 1", "  key = 2"), "\tkey = 2");
+        assert_eq!(preserve("key = 1", "  key = 2"), "  key = 2");
+        assert_eq!(preserve("    key = 1", ""), "");
+    }
 
-        let result = replace_in_file(&mut results);
-        assert!(result.is_err());
+    #[test]
+    fn test_expand_template() {
+        let path = Path::new("src/cost$.rs");
+        let exprs = [
+            (
+                fixed_search("TODO"),
+                "TODO({filename}:{lineno})".to_string(),
+            ),
+            (
+                regex_search("x"),
+                "{path} {lineno} {line} {{path}".to_string(),
+            ),
+        ];
+        let expanded = expand_template(&exprs, path, 12)
+            .into_iter()
+            .map(|(_, replace)| replace)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            expanded,
+            vec![
+                "TODO(cost$.rs:12)".to_string(),
+                "src/cost$$.rs 12 {line} {src/cost$$.rs".to_string(),
+            ]
+        );
     }
 
-    // Tests for replace_in_memory
     #[test]
-    fn test_replace_in_memory() {
-        let temp_dir = TempDir::new().unwrap();
+    fn test_make_replacements_literal() {
+        for replacement in ["$1", "${1}", "$$", "cost: $5", "${name}"] {
+            let mut exprs = [
+                (regex_search(r"(\w+)"), replacement.to_string()),
+                (
+                    SearchType::PatternAdvanced(fancy_regex::Regex::new(r"(\w+)").unwrap()),
+                    replacement.to_string(),
+                ),
+                (fixed_search("foo"), replacement.to_string()),
+            ];
+            make_replacements_literal(&mut exprs);
+            for (search, replace) in &exprs {
+                assert_eq!(
+                    replacement_if_match("foo", search, replace),
+                    Some(replacement.to_string())
+                );
+            }
+        }
+    }
 
-        // Test with fixed string
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "This is a test.\nIt contains search_term that should be replaced.\nMultiple lines with search_term here.",
+    #[test]
+    fn test_replacement_if_match_with_case_transforms() {
+        let search = regex_search(r"(\w+)_id");
+        assert_eq!(
+            replacement_if_match_with_case_transforms("user_id, group_id", &search, r"\U$1\E_ID"),
+            Some("USER_ID, GROUP_ID".to_string())
+        );
+        assert_eq!(
+            replacement_if_match("user_id", &search, r"\U$1\E_ID"),
+            Some(r"\Uuser\E_ID".to_string())
         );
 
-        let result = replace_in_memory(&file_path, &fixed_search("search_term"), "replacement");
-        assert!(result.is_ok());
-        assert!(result.unwrap()); // Should return true for modifications
+        let search =
+            SearchType::PatternAdvanced(fancy_regex::Regex::new(r"(\w+)(?= world)").unwrap());
+        assert_eq!(
+            replacement_if_match_with_case_transforms("hello world", &search, r"\u$1"),
+            Some("Hello world".to_string())
+        );
 
-        assert_file_content(
-            &file_path,
-            "This is a test.\nIt contains replacement that should be replaced.\nMultiple lines with replacement here.",
+        // Case transforms only apply to the replaced text
+        let search = regex_search("b+");
+        assert_eq!(
+            replacement_if_match_with_case_transforms(r"a\Ubbb c", &search, r"\U$0"),
+            Some(r"a\UBBB c".to_string())
         );
 
-        // Test with regex pattern
-        let regex_path = create_test_file(
-            &temp_dir,
-            "regex_test.txt",
-            "Number: 123, Code: 456, ID: 789",
+        assert_eq!(
+            replacement_if_match_with_case_transforms("foo", &fixed_search("foo"), r"\Ubar"),
+            Some(r"\Ubar".to_string())
         );
+    }
 
-        let result = replace_in_memory(&regex_path, &regex_search(r"\d{3}"), "XXX");
-        assert!(result.is_ok());
-        assert!(result.unwrap());
+    #[test]
+    fn test_replace_all_patterns() {
+        let exprs = [
+            (fixed_search("foo"), "bar".to_string()),
+            (fixed_search("bar"), "baz".to_string()),
+        ];
+        // Later patterns see the output of earlier replacements
+        assert_eq!(
+            replace_all_patterns("foo bar", &exprs, false, None),
+            Some("baz baz".to_string())
+        );
+        assert_eq!(
+            replace_all_patterns("bar", &exprs, false, None),
+            Some("baz".to_string())
+        );
+        assert_eq!(replace_all_patterns("qux", &exprs, false, None), None);
+        assert_eq!(replace_all_patterns("foo", &[], false, None), None);
 
-        assert_file_content(&regex_path, "Number: XXX, Code: XXX, ID: XXX");
+        let exprs = [
+            (regex_search(r"(\w+)_id"), r"\U$1\E_ID".to_string()),
+            (fixed_search("USER"), "ACCOUNT".to_string()),
+        ];
+        assert_eq!(
+            replace_all_patterns("user_id", &exprs, true, None),
+            Some("ACCOUNT_ID".to_string())
+        );
     }
 
     #[test]
-    fn test_replace_in_memory_no_match() {
-        let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(
-            &temp_dir,
-            "no_match.txt",
-            "This is a test file with no matches.",
-        );
+    fn test_replace_all_patterns_unchanged() {
+        let exprs = [(fixed_search("foo"), "foo".to_string())];
+        assert_eq!(replace_all_patterns("foo bar", &exprs, false, None), None);
 
-        let result = replace_in_memory(&file_path, &fixed_search("nonexistent"), "replacement");
-        assert!(result.is_ok());
-        assert!(!result.unwrap()); // Should return false for no modifications
+        let exprs = [(regex_search(r"(\w+)"), "$1".to_string())];
+        assert_eq!(replace_all_patterns("foo bar", &exprs, false, None), None);
 
-        // Verify file content unchanged
-        assert_file_content(&file_path, "This is a test file with no matches.");
+        // Later patterns can undo the replacements of earlier ones
+        let exprs = [
+            (fixed_search("foo"), "bar".to_string()),
+            (fixed_search("bar"), "foo".to_string()),
+        ];
+        assert_eq!(replace_all_patterns("foo", &exprs, false, None), None);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_replace_in_memory_empty_file() {
-        let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(&temp_dir, "empty.txt", "");
+    fn test_replace_command() {
+        let command = ReplaceCommand::new("tr a-z A-Z");
+        assert_eq!(command.run("hello").unwrap(), "HELLO");
+
+        // A single trailing newline is removed
+        let command = ReplaceCommand::new("printf 'a\\n\\n'");
+        assert_eq!(command.run("").unwrap(), "a\n");
+
+        // The command is run once for each distinct match
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let log_path = temp_dir.path().join("log.txt");
+        let command = ReplaceCommand::new(format!("cat >> {}; echo x", log_path.display()));
+        assert_eq!(command.clone().run("one").unwrap(), "x");
+        assert_eq!(command.run("one").unwrap(), "x");
+        assert_eq!(command.run("two").unwrap(), "x");
+        assert_eq!(fs::read_to_string(&log_path).unwrap(), "onetwo");
+
+        let command = ReplaceCommand::new("echo oops >&2; exit 3");
+        let error = command.run("hello").unwrap_err().to_string();
+        assert!(error.contains("failed"), "{error}");
+        assert!(error.contains("oops"), "{error}");
+    }
 
-        let result = replace_in_memory(&file_path, &fixed_search("anything"), "replacement");
-        assert!(result.is_ok());
-        assert!(!result.unwrap());
+    #[cfg(unix)]
+    #[test]
+    fn test_post_command() {
+        let command = PostCommand::new("wc -l < {} > {}.count");
+        assert_eq!(
+            command.command_for(Path::new("it's.txt")),
+            r"wc -l < 'it'\''s.txt' > 'it'\''s.txt'.count"
+        );
+        assert_eq!(
+            PostCommand::new("rustfmt").command_for(Path::new("src/lib.rs")),
+            "rustfmt 'src/lib.rs'"
+        );
 
-        // Verify file still empty
-        assert_file_content(&file_path, "");
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("it's a file.txt");
+        fs::write(&file_path, "one\ntwo\n").unwrap();
+        command.run(&file_path).unwrap();
+        let count_path = temp_dir.path().join("it's a file.txt.count");
+        assert_eq!(fs::read_to_string(&count_path).unwrap().trim(), "2");
+
+        let error = PostCommand::new("echo oops >&2; exit 3")
+            .run(&file_path)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("failed"), "{error}");
+        assert!(error.contains("oops"), "{error}");
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_replace_in_memory_nonexistent_file() {
-        let result = replace_in_memory(
-            Path::new("/nonexistent/path/file.txt"),
-            &fixed_search("test"),
-            "replacement",
+    fn test_replace_all_patterns_with_command() {
+        let command = ReplaceCommand::new("tr a-z A-Z");
+        let exprs = [
+            (regex_search(r"\bf\w+"), String::new()),
+            (fixed_search("bar"), String::new()),
+        ];
+        assert_eq!(
+            replace_all_patterns_with_command("foo bar fizz", &exprs, &command).unwrap(),
+            Some("FOO BAR FIZZ".to_string())
         );
-        assert!(result.is_err());
+        assert_eq!(
+            replace_all_patterns_with_command("qux", &exprs, &command).unwrap(),
+            None
+        );
+        // Output identical to the match is treated as no replacement
+        assert_eq!(
+            replace_all_patterns_with_command(
+                "FOO",
+                &[(regex_search("F+"), String::new())],
+                &command
+            )
+            .unwrap(),
+            None
+        );
+
+        let command = ReplaceCommand::new("exit 1");
+        assert!(replace_all_patterns_with_command("foo", &exprs, &command).is_err());
     }
 
-    // Tests for replace_chunked
     #[test]
-    fn test_replace_chunked() {
-        let temp_dir = TempDir::new().unwrap();
+    fn test_replace_all_patterns_outside_strings() {
+        let tracker = StringTracker::new(Language::Rust);
+        let line = r#"let foo = format!("foo {}", foo);"#;
 
-        // Test with fixed string
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "This is line one.\nThis contains search_pattern to replace.\nAnother line with search_pattern here.\nFinal line.",
+        let exprs = [(fixed_search("foo"), "bar".to_string())];
+        assert_eq!(
+            replace_all_patterns_outside_strings(line, &exprs, false, None, &tracker),
+            Some(r#"let bar = format!("foo {}", bar);"#.to_string())
+        );
+        assert_eq!(
+            replace_all_patterns_outside_strings(line, &exprs, false, NonZero::new(2), &tracker),
+            Some(r#"let foo = format!("foo {}", bar);"#.to_string())
+        );
+        assert_eq!(
+            replace_all_patterns_outside_strings(r#"x("foo")"#, &exprs, false, None, &tracker),
+            None
         );
 
-        let result = replace_chunked(&file_path, &fixed_search("search_pattern"), "replacement");
-        assert!(result.is_ok());
-        assert!(result.unwrap()); // Check that replacement happened
+        let exprs = [(regex_search(r"\b(f\w+)"), r"\U$1".to_string())];
+        assert_eq!(
+            replace_all_patterns_outside_strings(line, &exprs, true, None, &tracker),
+            Some(r#"let FOO = FORMAT!("foo {}", FOO);"#.to_string())
+        );
 
-        assert_file_content(
-            &file_path,
-            "This is line one.\nThis contains replacement to replace.\nAnother line with replacement here.\nFinal line.",
+        // Strings are located again after each pattern, as earlier replacements can move them
+        let exprs = [
+            (fixed_search("format"), "formatted_string".to_string()),
+            (regex_search(r"foo \{"), "baz {".to_string()),
+            (fixed_search("foo"), "bar".to_string()),
+        ];
+        assert_eq!(
+            replace_all_patterns_outside_strings(line, &exprs, false, None, &tracker),
+            Some(r#"let bar = formatted_string!("foo {}", bar);"#.to_string())
         );
 
-        // Test with regex pattern
-        let regex_path = create_test_file(
-            &temp_dir,
-            "regex.txt",
-            "Line with numbers: 123 and 456.\nAnother line with 789.",
+        // Strings continuing from previous lines are skipped
+        let mut tracker = StringTracker::new(Language::Rust);
+        tracker.advance(r#"let s = "start"#);
+        let exprs = [(fixed_search("foo"), "bar".to_string())];
+        assert_eq!(
+            replace_all_patterns_outside_strings(r#"foo" + foo"#, &exprs, false, None, &tracker),
+            Some(r#"foo" + bar"#.to_string())
         );
+    }
 
-        let result = replace_chunked(&regex_path, &regex_search(r"\d{3}"), "XXX");
-        assert!(result.is_ok());
-        assert!(result.unwrap());
+    #[test]
+    fn test_replace_all_in_file_skip_in_strings() {
+        let temp_dir = TempDir::new().unwrap();
+        let rust_file = create_test_file(&temp_dir, "main.rs", "let foo = \"foo\nfoo\";\nfoo();\n");
+        let text_file = create_test_file(&temp_dir, "notes.txt", "foo \"foo\"\n");
+        let config = FileSearcherConfig {
+            skip_in_strings: true,
+            ..FileSearcherConfig::default()
+        };
+        let exprs = [(fixed_search("foo"), "bar".to_string())];
 
-        assert_file_content(
-            &regex_path,
-            "Line with numbers: XXX and XXX.\nAnother line with XXX.",
+        assert_eq!(
+            replace_all_in_file(&rust_file, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            2
         );
+        assert_file_content(&rust_file, "let bar = \"foo\nfoo\";\nbar();\n");
+        assert_eq!(
+            replace_all_in_file(&text_file, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            2
+        );
+        assert_file_content(&text_file, "bar \"bar\"\n");
     }
 
     #[test]
-    fn test_replace_chunked_no_match() {
+    fn test_replace_all_in_file_unchanged() {
         let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "This is a test file with no matching patterns.",
+        let file_path = create_test_file(&temp_dir, "test.txt", "foo\r\nbar foo\n");
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let exprs = [(fixed_search("foo"), "foo".to_string())];
+        let config = FileSearcherConfig::default();
+        assert_eq!(
+            replace_in_memory(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            0
+        );
+        assert_eq!(
+            replace_chunked(&file_path, &exprs, &config)
+                .unwrap()
+                .num_replacements,
+            0
         );
 
-        let result = replace_chunked(&file_path, &fixed_search("nonexistent"), "replacement");
-        assert!(result.is_ok());
-        assert!(!result.unwrap());
+        assert_file_content(&file_path, "foo\r\nbar foo\n");
+        assert_eq!(
+            std::fs::metadata(&file_path).unwrap().modified().unwrap(),
+            mtime
+        );
+    }
 
-        // Verify file content unchanged
-        assert_file_content(&file_path, "This is a test file with no matching patterns.");
+    #[test]
+    fn test_replacement_if_nth_match() {
+        let second = NonZero::new(2).unwrap();
+        assert_eq!(
+            replacement_if_nth_match("foo foo foo", &fixed_search("foo"), "bar", second, false),
+            Some("foo bar foo".to_string())
+        );
+        assert_eq!(
+            replacement_if_nth_match("foo", &fixed_search("foo"), "bar", second, false),
+            None
+        );
+        assert_eq!(
+            replacement_if_nth_match(
+                "a1 b2 c3",
+                &regex_search(r"(\w)(\d)"),
+                "$2$1",
+                second,
+                false
+            ),
+            Some("a1 2b c3".to_string())
+        );
+        assert_eq!(
+            replacement_if_nth_match(
+                "a1 b2 c3",
+                &SearchType::PatternAdvanced(fancy_regex::Regex::new(r"(\w)(?=\d)").unwrap()),
+                r"\U$1",
+                NonZero::new(3).unwrap(),
+                true
+            ),
+            Some("a1 b2 C3".to_string())
+        );
+        assert_eq!(
+            replacement_if_nth_match("", &fixed_search("foo"), "bar", second, false),
+            None
+        );
+    }
+
+    fn backup_config() -> FileSearcherConfig {
+        FileSearcherConfig {
+            backup_suffix: Some(".bak".to_string()),
+            ..FileSearcherConfig::default()
+        }
     }
 
     #[test]
-    fn test_replace_chunked_empty_file() {
+    fn test_replace_in_memory_creates_backup() {
         let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(&temp_dir, "empty.txt", "");
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\nline 2\n");
 
-        let result = replace_chunked(&file_path, &fixed_search("anything"), "replacement");
-        assert!(result.is_ok());
-        assert!(!result.unwrap());
+        let result = replace_in_memory(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &backup_config(),
+        );
+        assert_eq!(result.unwrap().num_replacements, 1);
 
-        // Verify file still empty
-        assert_file_content(&file_path, "");
+        assert_file_content(&file_path, "new text\nline 2\n");
+        assert_file_content(&temp_dir.path().join("test.txt.bak"), "old text\nline 2\n");
     }
 
     #[test]
-    fn test_replace_chunked_nonexistent_file() {
+    fn test_replace_chunked_creates_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\nline 2\n");
+
         let result = replace_chunked(
-            Path::new("/nonexistent/path/file.txt"),
-            &fixed_search("test"),
-            "replacement",
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &backup_config(),
         );
-        assert!(result.is_err());
+        assert_eq!(result.unwrap().num_replacements, 1);
+
+        assert_file_content(&file_path, "new text\nline 2\n");
+        assert_file_content(&temp_dir.path().join("test.txt.bak"), "old text\nline 2\n");
     }
 
-    // Tests for replace_all_in_file
     #[test]
-    fn test_replace_all_in_file() {
+    fn test_no_backup_without_replacements() {
         let temp_dir = TempDir::new().unwrap();
-        let file_path = create_test_file(
-            &temp_dir,
-            "test.txt",
-            "This is a test file.\nIt has some content to replace.\nThe word replace should be replaced.",
+        let file_path = create_test_file(&temp_dir, "test.txt", "some text\n");
+
+        let result = replace_all_in_file(
+            &file_path,
+            &[(fixed_search("missing"), "new".to_string())],
+            &backup_config(),
         );
+        assert_eq!(result.unwrap().num_replacements, 0);
 
-        let result = replace_all_in_file(&file_path, &fixed_search("replace"), "modify");
-        assert!(result.is_ok());
-        assert!(result.unwrap());
+        assert!(!temp_dir.path().join("test.txt.bak").exists());
+    }
 
-        assert_file_content(
+    #[test]
+    fn test_no_backup_when_file_changed_since_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "line 1\nactual text\n");
+
+        let mut results = vec![create_search_result_with_replacement(
+            file_path.to_str().unwrap(),
+            2,
+            "expected text",
+            "new text",
+            true,
+            None,
+        )];
+        replace_in_file(&mut results, &backup_config(), |_| true).unwrap();
+
+        assert!(!temp_dir.path().join("test.txt.bak").exists());
+    }
+
+    #[test]
+    fn test_existing_backup_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_test_file(&temp_dir, "test.txt", "old text\n");
+        let backup_path = create_test_file(&temp_dir, "test.txt.bak", "existing backup\n");
+
+        let result = replace_in_memory(
             &file_path,
-            "This is a test file.\nIt has some content to modify.\nThe word modify should be modifyd.",
+            &[(fixed_search("old"), "new".to_string())],
+            &backup_config(),
+        );
+        assert!(result.is_err());
+        let result = replace_chunked(
+            &file_path,
+            &[(fixed_search("old"), "new".to_string())],
+            &backup_config(),
         );
+        assert!(result.is_err());
+
+        assert_file_content(&file_path, "old text\n");
+        assert_file_content(&backup_path, "existing backup\n");
     }
 
     #[test]
@@ -705,7 +4324,9 @@ mod tests {
         let results = search_file(temp_file.path(), &search)
             .unwrap()
             .into_iter()
-            .filter_map(|r| add_replacement(r, &search, replacement))
+            .filter_map(|r| {
+                add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+            })
             .collect::<Vec<_>>();
 
         assert_eq!(results.len(), 1);
@@ -716,7 +4337,9 @@ mod tests {
         let results = search_file(temp_file.path(), &search)
             .unwrap()
             .into_iter()
-            .filter_map(|r| add_replacement(r, &search, replacement))
+            .filter_map(|r| {
+                add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+            })
             .collect::<Vec<_>>();
 
         assert_eq!(results.len(), 1);
@@ -744,7 +4367,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 1);
@@ -768,7 +4393,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 3);
@@ -793,7 +4420,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 0);
@@ -812,7 +4441,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 2);
@@ -836,7 +4467,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 1);
@@ -855,7 +4488,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 0);
@@ -872,7 +4507,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 3);
@@ -902,7 +4539,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 1);
@@ -922,7 +4561,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 0);
@@ -947,7 +4588,9 @@ mod tests {
             let results = search_file(temp_file.path(), &search)
                 .unwrap()
                 .into_iter()
-                .filter_map(|r| add_replacement(r, &search, replacement))
+                .filter_map(|r| {
+                    add_replacement(r, &[(search.clone(), replacement.to_string())], false, None)
+                })
                 .collect::<Vec<_>>();
 
             assert_eq!(results.len(), 10); // Lines 0, 100, 200, ..., 900
@@ -999,9 +4642,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1017,9 +4666,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1035,9 +4690,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1057,9 +4718,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1075,9 +4742,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1093,9 +4766,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1111,9 +4790,15 @@ mod tests {
                         search_text: "café",
                         fixed_strings: true,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "restaurant",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1133,9 +4818,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1151,9 +4842,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1169,9 +4866,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1191,9 +4894,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1209,9 +4918,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1227,9 +4942,15 @@ mod tests {
                         search_text: "world",
                         fixed_strings: true,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1256,9 +4977,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1275,9 +5002,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1294,9 +5027,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1317,9 +5056,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1336,9 +5081,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1355,9 +5106,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "NUM",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1374,9 +5131,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: true,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "XX",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1398,9 +5161,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1417,9 +5186,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1436,9 +5211,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: true,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1459,9 +5240,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1478,9 +5265,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "earth",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1497,9 +5290,15 @@ mod tests {
                         search_text: re_str,
                         fixed_strings: false,
                         match_whole_word: false,
+                        match_whole_line: false,
                         match_case: false,
                         replacement_text: "XXX-XX-XXXX",
                         advanced_regex: false,
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1524,10 +5323,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: true,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: true,
                         replacement_text: "domain",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1547,10 +5352,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: true,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: true,
                         replacement_text: "report",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1566,10 +5377,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: true,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: true,
                         replacement_text: "earth",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1589,10 +5406,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: true,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: false,
                         replacement_text: "domain",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1612,10 +5435,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: true,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: false,
                         replacement_text: "earth",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1635,10 +5464,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: false,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: true,
                         replacement_text: "XX:XX",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1658,10 +5493,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: false,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: true,
                         replacement_text: "earth",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1681,10 +5522,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: false,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: false,
                         replacement_text: "ERROR",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1704,10 +5551,16 @@ mod tests {
                     let search_config = SearchConfig {
                         search_text: re_str,
                         match_whole_word: false,
+                        match_whole_line: false,
                         fixed_strings: false,
                         advanced_regex: true,
                         match_case: false,
                         replacement_text: "GREEK",
+                        multiline: false,
+                        dotall: false,
+                        additional_exprs: &[],
+                        word_chars: "",
+                        strict_captures: false,
                     };
                     let parsed = test_helpers::must_parse_search_config(search_config);
 
@@ -1725,9 +5578,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1742,9 +5601,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1755,9 +5620,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1772,9 +5643,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1785,9 +5662,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1798,9 +5681,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1815,9 +5704,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: true,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1828,9 +5723,15 @@ mod tests {
                 search_text: "wOrld",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: true,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1845,9 +5746,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1858,9 +5765,15 @@ mod tests {
                 search_text: "",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1875,9 +5788,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1888,9 +5807,15 @@ mod tests {
                 search_text: "world",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1905,9 +5830,15 @@ mod tests {
                 search_text: "(world)",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1918,9 +5849,15 @@ mod tests {
                 search_text: "world.*",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "ea+rth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1936,9 +5873,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "NEW",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1949,9 +5892,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "NEW",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1967,9 +5916,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "hi earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1980,9 +5935,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "hi earth",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -1998,9 +5959,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2011,9 +5978,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2024,9 +5997,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2037,9 +6016,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2056,9 +6041,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "TEST",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2070,9 +6061,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "TEST",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2084,9 +6081,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "TEST",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2102,9 +6105,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2115,9 +6124,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2133,9 +6148,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "NEW",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2146,9 +6167,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "NEW",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2164,9 +6191,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2182,9 +6215,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "NEW",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2200,9 +6239,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "NEW",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2218,9 +6263,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2231,9 +6282,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2249,9 +6306,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2267,9 +6330,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
@@ -2280,9 +6349,15 @@ mod tests {
                 search_text: re_str,
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 replacement_text: "X",
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let parsed = test_helpers::must_parse_search_config(search_config);
             assert_eq!(
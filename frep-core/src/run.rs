@@ -1,40 +1,779 @@
-use std::io::Cursor;
+use std::{
+    fmt,
+    io::Cursor,
+    num::NonZero,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::style::Stylize;
 
 use crate::{
-    line_reader::BufReadExt,
-    replace::replacement_if_match,
-    search::{FileSearcher, ParsedDirConfig, ParsedSearchConfig},
+    line_reader::{self, BufReadExt},
+    replace::{self, ReplaceResult, ReplaceStats, replace_all_patterns},
+    search::{
+        self, FileReplaceOutcome, FileReplacements, FileSearcher, FileSearcherConfig,
+        FilterDecision, ParsedDirConfig, ParsedSearchConfig, ProgressCallback, SearchResult,
+        SearchType,
+    },
+    utils,
     validation::{
-        DirConfig, SearchConfig, SimpleErrorHandler, ValidationResult,
+        self, DirConfig, SearchConfig, SimpleErrorHandler, ValidationResult,
         validate_search_configuration,
     },
 };
 
+/// The outcome of a find-and-replace across files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunSummary {
+    /// The number of files that had replacements performed in them (or would have, when performing a dry run)
+    pub num_files_replaced: usize,
+    /// The replacements in each file, sorted by path. Only populated when performing a dry run, or when
+    /// `diff` or `collect_line_results` is set in the `FileSearcherConfig`
+    pub files: Vec<FileReplacements>,
+    /// Whether this was a dry run, in which case no files were modified
+    pub dry_run: bool,
+    /// Whether diffs were generated, in which case they are displayed in place of the summary
+    pub diff: bool,
+    /// The number of files skipped because their contents appear to be binary
+    pub num_binary_files_skipped: usize,
+    /// The files that couldn't be processed because of an error, along with the error, sorted by path. Files
+    /// are left unchanged when an error occurs
+    pub errors: Vec<(PathBuf, String)>,
+    /// The files and directories that were skipped because permission to read or write them was denied, along
+    /// with the error, sorted by path. Unlike `errors`, these don't indicate a failure
+    pub skipped: Vec<(PathBuf, String)>,
+    /// The paths of the files that were modified, sorted. Always empty when performing a dry run
+    pub modified_paths: Vec<PathBuf>,
+    /// The number of replacements made in each file that had replacements performed in it (or would have,
+    /// when performing a dry run), sorted by path
+    pub replacement_counts: Vec<(PathBuf, usize)>,
+    /// The number of matches left unchanged in each file because of the `max_per_file` limit in the
+    /// `FileSearcherConfig`, for the files that had any, sorted by path
+    pub over_limit: Vec<(PathBuf, usize)>,
+    /// The original and new path of each file or directory that was renamed (or would have been, when
+    /// performing a dry run), sorted by original path. Only populated when `rename` is set in the
+    /// `FileSearcherConfig`
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+    /// The files with replacements that use a mix of `\n` and `\r\n` line endings, sorted. Only populated when
+    /// `warn_mixed_endings` is set in the `FileSearcherConfig`
+    pub mixed_line_endings: Vec<PathBuf>,
+    /// Problems with files that didn't stop replacements from being made in them, such as invalid UTF-8,
+    /// sorted by path
+    pub warnings: Vec<(PathBuf, replace::ReplaceWarning)>,
+    /// Aggregate metrics for the run, such as the number of files searched and the time taken
+    pub stats: RunStats,
+}
+
+impl RunSummary {
+    /// Whether any files had replacements performed in them (or would have, when performing a dry run)
+    pub fn has_replacements(&self) -> bool {
+        self.num_files_replaced > 0
+    }
+
+    /// The total number of matches replaced across all files in `files`, which may be more than the number of
+    /// lines with replacements as a line can contain several matches
+    pub fn num_matches(&self) -> usize {
+        self.files.iter().map(|f| f.num_replacements).sum()
+    }
+
+    /// Serializes the summary as JSON, including the line-level replacements in each file
+    pub fn to_json(&self) -> String {
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                let lines = file
+                    .replacements
+                    .iter()
+                    .map(|res| {
+                        let error = match &res.replace_result {
+                            Some(ReplaceResult::Error(error)) => utils::json_string(error),
+                            _ => "null".to_owned(),
+                        };
+                        format!(
+                            r#"{{"line_number":{line_number},"before":{before},"after":{after},"error":{error}}}"#,
+                            line_number = res.search_result.line_number,
+                            before = utils::json_string(&res.search_result.line),
+                            after = utils::json_string(&res.replacement),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!(
+                    r#"{{"path":{path},"num_matches":{num_matches},"lines":[{lines}]}}"#,
+                    path = utils::json_string(&file.path.to_string_lossy()),
+                    num_matches = file.num_replacements,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            r#"{{"num_files_replaced":{num_files_replaced},"num_matches":{num_matches},"dry_run":{dry_run},"files":[{files}],"errors":[{errors}],"skipped":[{skipped}],"warnings":[{warnings}]}}"#,
+            num_files_replaced = self.num_files_replaced,
+            num_matches = self.num_matches(),
+            dry_run = self.dry_run,
+            errors = path_errors_json(&self.errors),
+            skipped = path_errors_json(&self.skipped),
+            warnings = path_warnings_json(&self.warnings),
+        )
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_files_replaced = self.num_files_replaced;
+        if self.diff {
+            for file in &self.files {
+                f.write_str(file.diff.as_deref().unwrap_or_default())?;
+            }
+            Ok(())
+        } else if self.dry_run {
+            let contexts = self.files.iter().filter_map(|file| file.context.as_deref());
+            write_contexts(f, contexts)?;
+            let num_matches = self.num_matches();
+            writeln!(
+                f,
+                "Dry run: {num_files_replaced} file{files_suffix}, {num_matches} match{matches_suffix} would be updated",
+                files_suffix = if num_files_replaced != 1 { "s" } else { "" },
+                matches_suffix = if num_matches != 1 { "es" } else { "" },
+            )?;
+            write_renamed(f, &self.renamed, "Would rename")?;
+            write_over_limit(f, &self.over_limit)?;
+            write_mixed_line_endings(f, &self.mixed_line_endings)?;
+            write_warnings(f, &self.warnings)?;
+            write_binary_files_skipped(f, self.num_binary_files_skipped)?;
+            write_skipped(f, &self.skipped)?;
+            write_errors(f, &self.errors)
+        } else {
+            write_success(f, num_files_replaced)?;
+            write_renamed(f, &self.renamed, "Renamed")?;
+            write_mixed_line_endings(f, &self.mixed_line_endings)?;
+            write_warnings(f, &self.warnings)?;
+            write_binary_files_skipped(f, self.num_binary_files_skipped)?;
+            write_skipped(f, &self.skipped)?;
+            write_errors(f, &self.errors)
+        }
+    }
+}
+
+/// Aggregate metrics for a find-and-replace across files, such as for benchmarking
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// The number of files that were searched, including those with no matches and those that couldn't be
+    /// processed because of an error
+    pub num_files_searched: usize,
+    /// The number of files that were skipped because they appear to be binary, or their size or modification
+    /// time is out of range. Files excluded by globs, extensions or ignore files are never visited, so aren't
+    /// counted
+    pub num_files_skipped: usize,
+    /// The number of files that had replacements performed in them (or would have, when performing a dry run)
+    pub num_files_replaced: usize,
+    /// The total number of replacements made (or that would be made, when performing a dry run). See
+    /// `replace::replace_all_in_file` for how replacements are counted
+    pub num_matches: usize,
+    /// The time taken to search and replace in files, excluding parsing the configuration
+    pub elapsed: Duration,
+}
+
+impl RunStats {
+    /// The number of files found that weren't excluded by globs, extensions or ignore files, whether or not they
+    /// were then searched
+    pub fn num_files_found(&self) -> usize {
+        self.num_files_searched + self.num_files_skipped
+    }
+}
+
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Files searched: {}", self.num_files_searched)?;
+        writeln!(f, "Files skipped: {}", self.num_files_skipped)?;
+        writeln!(f, "Files with replacements: {}", self.num_files_replaced)?;
+        writeln!(f, "Matches: {}", self.num_matches)?;
+        writeln!(f, "Elapsed: {:.2?}", self.elapsed)
+    }
+}
+
+/// Serializes a list of paths and their errors as the elements of a JSON array
+fn path_errors_json(path_errors: &[(PathBuf, String)]) -> String {
+    path_errors
+        .iter()
+        .map(|(path, error)| {
+            format!(
+                r#"{{"path":{path},"error":{error}}}"#,
+                path = utils::json_string(&path.to_string_lossy()),
+                error = utils::json_string(error),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Serializes a list of paths and their warnings as the elements of a JSON array
+fn path_warnings_json(path_warnings: &[(PathBuf, replace::ReplaceWarning)]) -> String {
+    path_warnings
+        .iter()
+        .map(|(path, warning)| {
+            format!(
+                r#"{{"path":{path},"warning":{warning}}}"#,
+                path = utils::json_string(&path.to_string_lossy()),
+                warning = utils::json_string(&warning.to_string()),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn write_contexts<'a>(
+    f: &mut fmt::Formatter<'_>,
+    contexts: impl Iterator<Item = &'a str>,
+) -> fmt::Result {
+    for (i, context) in contexts.enumerate() {
+        if i > 0 {
+            f.write_str("--\n")?;
+        }
+        f.write_str(context)?;
+    }
+    Ok(())
+}
+
+fn write_success(f: &mut fmt::Formatter<'_>, num_files_replaced: usize) -> fmt::Result {
+    writeln!(
+        f,
+        "Success: {num_files_replaced} file{prefix} updated",
+        prefix = if num_files_replaced != 1 { "s" } else { "" },
+    )
+}
+
+fn write_renamed(
+    f: &mut fmt::Formatter<'_>,
+    renamed: &[(PathBuf, PathBuf)],
+    verb: &str,
+) -> fmt::Result {
+    if !renamed.is_empty() {
+        writeln!(
+            f,
+            "{verb} {num_renamed} path{prefix}:",
+            num_renamed = renamed.len(),
+            prefix = if renamed.len() != 1 { "s" } else { "" },
+        )?;
+        for (from, to) in renamed {
+            writeln!(f, "  {} -> {}", from.display(), to.display())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_over_limit(f: &mut fmt::Formatter<'_>, over_limit: &[(PathBuf, usize)]) -> fmt::Result {
+    if !over_limit.is_empty() {
+        let num_matches = over_limit.iter().map(|(_, n)| n).sum::<usize>();
+        writeln!(
+            f,
+            "Skipped {num_matches} match{matches_suffix} in {num_files} file{files_suffix} due to per-file limit:",
+            matches_suffix = if num_matches != 1 { "es" } else { "" },
+            num_files = over_limit.len(),
+            files_suffix = if over_limit.len() != 1 { "s" } else { "" },
+        )?;
+        for (path, num_over_limit) in over_limit {
+            writeln!(f, "  {}: {num_over_limit}", path.display())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_binary_files_skipped(f: &mut fmt::Formatter<'_>, num_skipped: usize) -> fmt::Result {
+    if num_skipped > 0 {
+        writeln!(
+            f,
+            "Skipped {num_skipped} binary file{prefix}",
+            prefix = if num_skipped != 1 { "s" } else { "" },
+        )?;
+    }
+    Ok(())
+}
+
+fn write_mixed_line_endings(f: &mut fmt::Formatter<'_>, paths: &[PathBuf]) -> fmt::Result {
+    if !paths.is_empty() {
+        writeln!(
+            f,
+            "Warning: {num_paths} file{prefix} with mixed line endings:",
+            num_paths = paths.len(),
+            prefix = if paths.len() != 1 { "s" } else { "" },
+        )?;
+        for path in paths {
+            writeln!(f, "  {}", path.display())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_warnings(
+    f: &mut fmt::Formatter<'_>,
+    warnings: &[(PathBuf, replace::ReplaceWarning)],
+) -> fmt::Result {
+    for (path, warning) in warnings {
+        writeln!(f, "Warning: {}: {warning}", path.display())?;
+    }
+    Ok(())
+}
+
+fn write_skipped(f: &mut fmt::Formatter<'_>, skipped: &[(PathBuf, String)]) -> fmt::Result {
+    if !skipped.is_empty() {
+        writeln!(
+            f,
+            "Skipped {num_skipped} file{prefix} (permission denied):",
+            num_skipped = skipped.len(),
+            prefix = if skipped.len() != 1 { "s" } else { "" },
+        )?;
+        for (path, error) in skipped {
+            writeln!(f, "  {}: {error}", path.display())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_errors(f: &mut fmt::Formatter<'_>, errors: &[(PathBuf, String)]) -> fmt::Result {
+    if !errors.is_empty() {
+        writeln!(
+            f,
+            "Failed to process {num_errors} file{prefix}:",
+            num_errors = errors.len(),
+            prefix = if errors.len() != 1 { "s" } else { "" },
+        )?;
+        for (path, error) in errors {
+            writeln!(f, "  {}: {error}", path.display())?;
+        }
+    }
+    Ok(())
+}
+
+/// Statistics about the replacements made by a find-and-replace across files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplaceSummary {
+    /// The number of files that had replacements performed in them
+    pub num_files_replaced: usize,
+    /// The total number of matches replaced across all files, which may be more than the number of lines
+    /// replaced as a line can contain several matches
+    pub num_matches: usize,
+    /// The number of lines successfully replaced, along with the lines that couldn't be replaced in each file
+    pub stats: ReplaceStats,
+    /// The files that couldn't be processed at all because of an error, along with the error, sorted by path
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+impl fmt::Display for ReplaceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_success(f, self.num_files_replaced)?;
+        write_errors(f, &self.errors)
+    }
+}
+
+/// The error returned when the number of matches that would be replaced differs from
+/// `FileSearcherConfig::expect_count`, in which case no files are modified
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnexpectedCountError {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for UnexpectedCountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Expected {expected} replacement{suffix} but found {found}, so no files were modified",
+            expected = self.expected,
+            suffix = if self.expected != 1 { "s" } else { "" },
+            found = self.found,
+        )
+    }
+}
+
+impl std::error::Error for UnexpectedCountError {}
+
 // Perform a find-and-replace recursively in a given directory
 pub fn find_and_replace(
     search_config: SearchConfig<'_>,
     dir_config: DirConfig<'_>,
 ) -> anyhow::Result<String> {
+    let summary =
+        find_and_replace_with_config(search_config, dir_config, FileSearcherConfig::default())?;
+    Ok(summary.to_string())
+}
+
+/// Perform a find-and-replace recursively in a given directory, returning statistics about the replacements
+/// made rather than a formatted message.
+///
+/// Line-level results are collected in order to report errors, so UTF-16 files are not supported.
+pub fn find_and_replace_with_stats(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+) -> anyhow::Result<ReplaceSummary> {
+    let searcher_config = FileSearcherConfig {
+        collect_line_results: true,
+        ..FileSearcherConfig::default()
+    };
+    let summary = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+
+    Ok(ReplaceSummary {
+        num_files_replaced: summary.num_files_replaced,
+        num_matches: summary.num_matches(),
+        stats: replace::calculate_statistics(
+            summary.files.into_iter().flat_map(|file| file.replacements),
+        ),
+        errors: summary.errors,
+    })
+}
+
+/// Perform a find-and-replace in the single file at `path`, without walking a directory, returning whether any
+/// replacements were made. The search config is validated once, and the file is then replaced as by
+/// `replace::replace_all_in_file` (or `replace::replace_all_in_file_multiline` in multiline mode), so this is
+/// useful for benchmarking replacement in isolation. Unlike when walking a directory, the file is processed even
+/// if it is hidden, ignored or has a binary extension, although files whose contents appear to be binary result
+/// in an error
+pub fn replace_file(path: &Path, search_config: SearchConfig<'_>) -> anyhow::Result<bool> {
+    let (parsed_search_config, _) = parse_config(search_config, None)?;
+    let exprs = parsed_search_config.exprs();
+    let searcher_config = FileSearcherConfig::default();
+    let num_replaced = if parsed_search_config.multiline {
+        replace::replace_all_in_file_multiline(path, &exprs, &searcher_config)?.num_replacements
+    } else {
+        replace::replace_all_in_file(path, &exprs, &searcher_config)?.num_replacements
+    };
+    Ok(num_replaced > 0)
+}
+
+/// Perform a find-and-replace recursively in a given directory, with additional options such as dry-run
+pub fn find_and_replace_with_config(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+    searcher_config: FileSearcherConfig,
+) -> anyhow::Result<RunSummary> {
+    find_and_replace_with_progress(search_config, dir_config, searcher_config, None)
+}
+
+/// Like `find_and_replace_with_config`, but calls `on_file_processed` after each file is processed with its
+/// path and the number of replacements made in it. See `FileSearcher::walk_files_and_replace`.
+///
+/// If `expect_count` is set in the `FileSearcherConfig`, the matches that would be replaced are counted with a
+/// dry run before any files are modified, and an `UnexpectedCountError` is returned if the count differs. Files
+/// modified by other processes in between may still be replaced a different number of times
+pub fn find_and_replace_with_progress(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+    searcher_config: FileSearcherConfig,
+    on_file_processed: Option<ProgressCallback<'_>>,
+) -> anyhow::Result<RunSummary> {
+    let (parsed_search_config, parsed_dir_config) = parse_config(search_config, Some(dir_config))?;
+    let (dry_run, diff) = (searcher_config.dry_run, searcher_config.diff);
+    let expect_count = searcher_config.expect_count;
+    if expect_count.is_some() && parsed_search_config.multiline {
+        anyhow::bail!("The expected number of replacements can't be checked in multiline mode");
+    }
+    let searcher = FileSearcher::with_config(
+        parsed_search_config,
+        parsed_dir_config.expect("Found None dir_config when search_type is Files"),
+        searcher_config,
+    );
+    let start = Instant::now();
+    // A dry run is checked once it has completed, rather than being performed twice
+    if let Some(expected) = expect_count
+        && !dry_run
+    {
+        let found = searcher.count_replacements();
+        if found != expected {
+            return Err(UnexpectedCountError { expected, found }.into());
+        }
+    }
+    let result = searcher.walk_files_and_replace(None, on_file_processed);
+    let num_matches = result.replacement_counts.iter().map(|(_, n)| n).sum();
+    if let Some(expected) = expect_count
+        && num_matches != expected
+        && dry_run
+    {
+        return Err(UnexpectedCountError {
+            expected,
+            found: num_matches,
+        }
+        .into());
+    }
+    let stats = RunStats {
+        num_files_searched: result.num_files_searched,
+        num_files_skipped: result.num_binary_files_skipped + result.num_files_filtered,
+        num_files_replaced: result.num_files_replaced,
+        num_matches,
+        elapsed: start.elapsed(),
+    };
+
+    Ok(RunSummary {
+        num_files_replaced: result.num_files_replaced,
+        files: result.files,
+        dry_run,
+        diff,
+        num_binary_files_skipped: result.num_binary_files_skipped,
+        errors: result.errors,
+        skipped: result.skipped,
+        modified_paths: result.modified_paths,
+        replacement_counts: result.replacement_counts,
+        over_limit: result.over_limit,
+        renamed: result.renamed,
+        mixed_line_endings: result.mixed_line_endings,
+        warnings: result.warnings,
+        stats,
+    })
+}
+
+/// Like `find_and_replace_with_config`, but rather than collecting a summary, calls `on_outcome` with the
+/// outcome of each file as soon as it has been processed, such as to report results incrementally. See
+/// `FileSearcher::walk_files_and_replace_streaming`: in particular, files aren't renamed when `rename` is set,
+/// and `post_command` isn't run.
+///
+/// `on_outcome` is called on the thread that called this function. If it returns an error, no further files
+/// are processed and the error is returned
+pub fn find_and_replace_streaming(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+    searcher_config: FileSearcherConfig,
+    mut on_outcome: impl FnMut(FileReplaceOutcome) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let (parsed_search_config, parsed_dir_config) = parse_config(search_config, Some(dir_config))?;
+    let searcher = FileSearcher::with_config(
+        parsed_search_config,
+        parsed_dir_config.expect("Found None dir_config when search_type is Files"),
+        searcher_config,
+    );
+    let cancelled = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        let (searcher, cancelled_ref) = (&searcher, &cancelled);
+        scope
+            .spawn(move || searcher.walk_files_and_replace_streaming(Some(cancelled_ref), &sender));
+
+        for outcome in receiver {
+            if let Err(e) = on_outcome(outcome) {
+                cancelled.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The number of lines matching a search in each file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountSummary {
+    /// The number of matching lines in each file with at least one match, sorted by path
+    pub file_counts: Vec<(PathBuf, usize)>,
+    /// The total number of matches across all files, which may be more than the number of matching lines as
+    /// a line can contain several matches
+    pub num_matches: usize,
+    /// The matching lines with surrounding context for each file with at least one match, sorted by path and
+    /// formatted by `search::format_context`. Only populated when `before_context` or `after_context` is set
+    pub contexts: Vec<String>,
+    /// Whether file paths are displayed in bold. Set from `color` in the `FileSearcherConfig`, which also
+    /// highlights the matches in `contexts`
+    pub color: bool,
+}
+
+impl CountSummary {
+    /// The total number of matching lines across all files
+    pub fn total(&self) -> usize {
+        self.file_counts.iter().map(|(_, count)| count).sum()
+    }
+}
+
+impl fmt::Display for CountSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_contexts(f, self.contexts.iter().map(String::as_str))?;
+        for (path, count) in &self.file_counts {
+            if self.color {
+                writeln!(f, "{}:{count}", path.display().to_string().bold())?;
+            } else {
+                writeln!(f, "{}:{count}", path.display())?;
+            }
+        }
+        let total = self.total();
+        let num_files = self.file_counts.len();
+        writeln!(
+            f,
+            "Total: {num_matches} match{matches_suffix} across {total} line{lines_suffix} in {num_files} file{files_suffix}",
+            num_matches = self.num_matches,
+            matches_suffix = if self.num_matches != 1 { "es" } else { "" },
+            lines_suffix = if total != 1 { "s" } else { "" },
+            files_suffix = if num_files != 1 { "s" } else { "" },
+        )
+    }
+}
+
+/// Find the lines matching a search recursively in a given directory, without performing any replacements.
+/// The results are sorted by path and then by line number
+pub fn find_matches(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+) -> anyhow::Result<Vec<SearchResult>> {
     let (parsed_search_config, parsed_dir_config) = parse_config(search_config, Some(dir_config))?;
     let searcher = FileSearcher::new(
         parsed_search_config,
         parsed_dir_config.expect("Found None dir_config when search_type is Files"),
     );
-    let num_files_replaced = searcher.walk_files_and_replace(None);
+    Ok(searcher.walk_files_and_collect(None))
+}
 
-    Ok(format!(
-        "Success: {num_files_replaced} file{prefix} updated\n",
-        prefix = if num_files_replaced != 1 { "s" } else { "" },
-    ))
+/// Find the files in a given directory whose paths, relative to the directory, match a search. File contents
+/// are neither searched nor replaced. The paths are sorted
+pub fn find_matching_paths(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+    searcher_config: FileSearcherConfig,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let (parsed_search_config, parsed_dir_config) = parse_config(search_config, Some(dir_config))?;
+    let searcher = FileSearcher::with_config(
+        parsed_search_config,
+        parsed_dir_config.expect("Found None dir_config when search_type is Files"),
+        searcher_config,
+    );
+    Ok(searcher.walk_files_and_match_paths(None))
+}
+
+/// List the files in a given directory that would be searched when replacing, relative to the directory
+/// containing them, without reading their contents. The paths are sorted. See
+/// `FileSearcher::walk_files_and_list_paths`
+pub fn list_files(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+    searcher_config: FileSearcherConfig,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let (parsed_search_config, parsed_dir_config) = parse_config(search_config, Some(dir_config))?;
+    let searcher = FileSearcher::with_config(
+        parsed_search_config,
+        parsed_dir_config.expect("Found None dir_config when search_type is Files"),
+        searcher_config,
+    );
+    Ok(searcher.walk_files_and_list_paths(None))
+}
+
+/// Reports whether each file in a given directory would be searched, along with the glob, extension or ignore
+/// rule responsible, sorted by path. See `FileSearcher::explain_filters`
+pub fn explain_filters(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+) -> anyhow::Result<Vec<(PathBuf, FilterDecision)>> {
+    let (parsed_search_config, parsed_dir_config) = parse_config(search_config, Some(dir_config))?;
+    let searcher = FileSearcher::new(
+        parsed_search_config,
+        parsed_dir_config.expect("Found None dir_config when search_type is Files"),
+    );
+    Ok(searcher.explain_filters())
+}
+
+/// Count the lines matching a search recursively in a given directory, without performing any replacements
+pub fn count_matches(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+) -> anyhow::Result<CountSummary> {
+    count_matches_with_config(search_config, dir_config, FileSearcherConfig::default())
+}
+
+/// Count the lines matching a search recursively in a given directory, with additional options such as
+/// the number of context lines to display around each match
+pub fn count_matches_with_config(
+    search_config: SearchConfig<'_>,
+    dir_config: DirConfig<'_>,
+    searcher_config: FileSearcherConfig,
+) -> anyhow::Result<CountSummary> {
+    let (parsed_search_config, parsed_dir_config) = parse_config(search_config, Some(dir_config))?;
+    let (before, after, color) = (
+        searcher_config.before_context,
+        searcher_config.after_context,
+        searcher_config.color,
+    );
+    let searcher = FileSearcher::with_config(
+        parsed_search_config,
+        parsed_dir_config.expect("Found None dir_config when search_type is Files"),
+        searcher_config,
+    );
+
+    if before.is_none() && after.is_none() {
+        let counts = searcher.walk_files_and_count(None);
+        return Ok(CountSummary {
+            num_matches: counts.iter().map(|(_, _, num_matches)| num_matches).sum(),
+            file_counts: counts
+                .into_iter()
+                .map(|(path, num_lines, _)| (path, num_lines))
+                .collect(),
+            contexts: vec![],
+            color,
+        });
+    }
+
+    let files = searcher.walk_files_with_context(None, before.unwrap_or(0), after.unwrap_or(0));
+    Ok(CountSummary {
+        file_counts: files
+            .iter()
+            .map(|(path, results)| (path.clone(), results.len()))
+            .collect(),
+        num_matches: files
+            .iter()
+            .flat_map(|(_, results)| results)
+            .map(|r| r.search_result.match_count)
+            .sum(),
+        contexts: files
+            .iter()
+            .map(|(_, results)| {
+                if color {
+                    search::format_context_highlighted(results, searcher.exprs())
+                } else {
+                    search::format_context(results)
+                }
+            })
+            .collect(),
+        color,
+    })
 }
 
 /// Perform a find-and-replace in a string slice
 pub fn find_and_replace_text(
     content: &str,
     search_config: SearchConfig<'_>,
+) -> anyhow::Result<String> {
+    find_and_replace_text_with_config(content, search_config, &FileSearcherConfig::default())
+}
+
+/// Perform a find-and-replace in a string slice, with additional options such as case transforms. Options
+/// that only apply to files, such as `dry_run`, are ignored
+pub fn find_and_replace_text_with_config(
+    content: &str,
+    search_config: SearchConfig<'_>,
+    searcher_config: &FileSearcherConfig,
 ) -> anyhow::Result<String> {
     let (parsed_search_config, _) = parse_config(search_config, None)?;
+    let mut exprs = parsed_search_config.exprs();
+    if searcher_config.literal_replacement {
+        replace::make_replacements_literal(&mut exprs);
+    }
+    let case_transforms = searcher_config.case_transforms;
+    if parsed_search_config.multiline && searcher_config.replace_command.is_some() {
+        anyhow::bail!("A replacement command can't be used in multiline mode");
+    }
+    if parsed_search_config.multiline && searcher_config.replacement_functions.is_some() {
+        anyhow::bail!("Replacement functions can't be used in multiline mode");
+    }
+    if parsed_search_config.multiline {
+        let result = replace_all_patterns(content, &exprs, case_transforms, None)
+            .unwrap_or_else(|| content.to_owned());
+        return Ok(match searcher_config.line_ending {
+            Some(line_ending) => line_reader::normalize_line_endings(&result, line_ending),
+            None => result,
+        });
+    }
+
     let mut result = String::with_capacity(content.len());
+    let mut has_replaced = false;
 
     let cursor = Cursor::new(content);
 
@@ -43,17 +782,141 @@ pub fn find_and_replace_text(
 
         let line = String::from_utf8(line_bytes)?;
 
-        if let Some(replaced_line) = replacement_if_match(
-            &line,
-            &parsed_search_config.search,
-            &parsed_search_config.replace,
-        ) {
-            result.push_str(&replaced_line);
+        let replaced_line = match &searcher_config.replace_command {
+            _ if searcher_config.first_only && has_replaced => None,
+            _ if !searcher_config.replaces_in_line(&line) => None,
+            Some(command) => replace::replace_all_patterns_with_command(&line, &exprs, command)?,
+            None => match &searcher_config.replacement_functions {
+                Some(functions) => {
+                    replace::replace_all_patterns_with_functions(&line, &exprs, functions)
+                }
+                None => {
+                    replace_all_patterns(&line, &exprs, case_transforms, searcher_config.occurrence)
+                }
+            },
+        };
+        if let Some(replaced_line) = replaced_line {
+            has_replaced = true;
+            if searcher_config.preserve_indent {
+                result.push_str(&replace::with_preserved_indent(&line, replaced_line));
+            } else {
+                result.push_str(&replaced_line);
+            }
         } else {
             result.push_str(&line);
         }
 
-        result.push_str(line_ending.as_str());
+        result.push_str(line_ending.normalized(searcher_config.line_ending).as_str());
+    }
+
+    Ok(result)
+}
+
+/// A search that has been parsed once, so that it can be matched against and applied to many lines without
+/// the `SearchConfig` being validated again each time, such as when embedding frep-core in another tool
+#[derive(Clone, Debug)]
+pub struct CompiledSearch {
+    exprs: Vec<(SearchType, String)>,
+    case_transforms: bool,
+    occurrence: Option<NonZero<usize>>,
+    preserve_indent: bool,
+}
+
+impl CompiledSearch {
+    /// Parses `search_config`, returning an error if it is invalid, such as if the search text isn't a valid
+    /// regex
+    pub fn new(search_config: SearchConfig<'_>) -> anyhow::Result<Self> {
+        Self::with_config(search_config, &FileSearcherConfig::default())
+    }
+
+    /// Like `new`, but with the options in `searcher_config` that apply to individual lines: `case_transforms`,
+    /// `occurrence`, `literal_replacement` and `preserve_indent`. Other options are ignored
+    pub fn with_config(
+        search_config: SearchConfig<'_>,
+        searcher_config: &FileSearcherConfig,
+    ) -> anyhow::Result<Self> {
+        let (parsed_search_config, _) = parse_config(search_config, None)?;
+        let mut exprs = parsed_search_config.exprs();
+        if searcher_config.literal_replacement {
+            replace::make_replacements_literal(&mut exprs);
+        }
+        Ok(Self {
+            exprs,
+            case_transforms: searcher_config.case_transforms,
+            occurrence: searcher_config.occurrence,
+            preserve_indent: searcher_config.preserve_indent,
+        })
+    }
+
+    /// The search patterns and their replacements, in the order they are applied. Empty search patterns are
+    /// omitted
+    pub fn exprs(&self) -> &[(SearchType, String)] {
+        &self.exprs
+    }
+
+    /// Whether `line` contains a match of any of the search patterns. Note that `replace_line` may still
+    /// return `None` for a matching line, such as when the replacement leaves it unchanged
+    pub fn matches(&self, line: &str) -> bool {
+        search::contains_any_search(line, &self.exprs)
+    }
+
+    /// `line` with the matches of each search pattern replaced in turn, or `None` if nothing matched or the
+    /// replacements left the line unchanged. See `replace::replace_all_patterns`
+    pub fn replace_line(&self, line: &str) -> Option<String> {
+        let replacement =
+            replace_all_patterns(line, &self.exprs, self.case_transforms, self.occurrence)?;
+        Some(if self.preserve_indent {
+            replace::with_preserved_indent(line, replacement)
+        } else {
+            replacement
+        })
+    }
+}
+
+/// Perform a find-and-replace in a byte slice, which unlike `find_and_replace_text` doesn't need to be valid
+/// UTF-8, such as Latin-1 encoded text. Bytes that aren't part of a match are left unchanged. See
+/// `validation::parse_search_bytes` for the search options that are supported
+pub fn find_and_replace_bytes(
+    content: &[u8],
+    search_config: &SearchConfig<'_>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut exprs = vec![];
+    let expr_texts = std::iter::once((search_config.search_text, search_config.replacement_text))
+        .chain(
+            search_config
+                .additional_exprs
+                .iter()
+                .map(|(search, replace)| (search.as_str(), replace.as_str())),
+        );
+    for (search_text, replacement_text) in expr_texts {
+        let expr_config = SearchConfig {
+            search_text,
+            replacement_text,
+            ..search_config.clone()
+        };
+        if let Some(search) = validation::parse_search_bytes(&expr_config)? {
+            let replace = if search_config.fixed_strings {
+                replacement_text.replace('$', "$$")
+            } else {
+                replacement_text.to_owned()
+            };
+            exprs.push((search, replace.into_bytes()));
+        }
+    }
+
+    if search_config.multiline {
+        return Ok(replace::replace_all_patterns_bytes(content, &exprs)
+            .unwrap_or_else(|| content.to_vec()));
+    }
+
+    let mut result = Vec::with_capacity(content.len());
+    for line_result in Cursor::new(content).lines_with_endings() {
+        let (line, line_ending) = line_result?;
+        match replace::replace_all_patterns_bytes(&line, &exprs) {
+            Some(replaced_line) => result.extend_from_slice(&replaced_line),
+            None => result.extend_from_slice(&line),
+        }
+        result.extend_from_slice(line_ending.as_bytes());
     }
 
     Ok(result)
@@ -1,21 +1,64 @@
-use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, BufReader, Read};
 use std::num::NonZero;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
 use std::thread::{self};
+use std::time::{Duration, SystemTime};
 
+use anyhow::Context;
 use content_inspector::{ContentType, inspect};
+use crossterm::style::Stylize;
 use fancy_regex::Regex as FancyRegex;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::overrides::Override;
-use ignore::{WalkBuilder, WalkState};
+use ignore::{Match, WalkBuilder, WalkState};
 use regex::Regex;
 
 use crate::{
-    line_reader::{BufReadExt, LineEnding},
+    diff,
+    encoding::Encoding,
+    line_reader::{self, BufReadExt, LineEnding},
     replace::{self, ReplaceResult},
+    store::{self, FileStore},
+    syntax::{Language, StringTracker},
+    undo::UndoJournal,
+    utils,
 };
 
+/// The error returned when searching a file takes longer than `FileSearcherConfig::timeout`, such as because
+/// an advanced regex backtracks catastrophically on its contents
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeoutError {
+    pub timeout: Duration,
+    /// The search patterns that were being matched
+    pub patterns: Vec<String>,
+}
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "timed out after {timeout}ms matching {patterns}",
+            timeout = self.timeout.as_millis(),
+            patterns = self
+                .patterns
+                .iter()
+                .map(|pattern| format!("`{pattern}`"))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SearchResult {
     pub path: Option<PathBuf>,
@@ -24,6 +67,10 @@ pub struct SearchResult {
     pub line: String,
     pub line_ending: LineEnding,
     pub included: bool,
+    /// The number of non-overlapping matches of the search patterns within the line, counted separately for
+    /// each pattern. Once a replacement has been added with `replace::add_replacement_outside_strings`, only
+    /// matches outside string literals are counted
+    pub match_count: usize,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -66,18 +113,48 @@ pub enum SearchType {
 
 impl SearchType {
     pub fn is_empty(&self) -> bool {
-        let str = match &self {
-            SearchType::Pattern(r) => &r.to_string(),
-            SearchType::PatternAdvanced(r) => &r.to_string(),
+        self.as_str().is_empty()
+    }
+
+    /// The fixed string or regex pattern being searched for
+    pub fn as_str(&self) -> &str {
+        match &self {
+            SearchType::Pattern(r) => r.as_str(),
+            SearchType::PatternAdvanced(r) => r.as_str(),
             SearchType::Fixed(s) => s,
-        };
-        str.is_empty()
+        }
     }
 }
 
+/// A line surrounding a search result, displayed for context
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContextLine {
+    /// 1-indexed
+    pub line_number: usize,
+    pub line: String,
+}
+
+/// A search result along with the lines surrounding it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchResultWithContext {
+    pub search_result: SearchResult,
+    /// The non-matching lines directly before the result, in order
+    pub context_before: Vec<ContextLine>,
+    /// The non-matching lines directly after the result, in order
+    pub context_after: Vec<ContextLine>,
+}
+
 /// A function that processes search results for a file and determines whether to continue searching.
 type FileVisitor = Box<dyn FnMut(Vec<SearchResult>) -> WalkState + Send>;
 
+/// A function called after each file is processed when replacing, with the path of the file and the number
+/// of replacements made in it. See `FileSearcher::walk_files_and_replace`
+pub type ProgressCallback<'a> = &'a mut (dyn FnMut(&Path, usize) + Send);
+
+/// A function that processes each entry found when walking a directory.
+type EntryVisitor<'s> =
+    Box<dyn FnMut(Result<ignore::DirEntry, ignore::Error>) -> WalkState + Send + 's>;
+
 impl FileSearcher {
     pub fn search(&self) -> &SearchType {
         &self.search_config.search
@@ -86,6 +163,11 @@ impl FileSearcher {
     pub fn replace(&self) -> &String {
         &self.search_config.replace
     }
+
+    /// All of the patterns to search for along with their replacements, in the order they are applied
+    pub fn exprs(&self) -> &[(SearchType, String)] {
+        &self.exprs
+    }
 }
 
 /// Options for regex pattern conversion
@@ -103,48 +185,722 @@ pub struct ParsedSearchConfig {
     pub search: SearchType,
     /// The text to replace matches with
     pub replace: String,
+    /// Whether to replace across the entire contents of each file rather than line by line. Line-level
+    /// results (i.e. `dry_run`, `diff` and `collect_line_results`) aren't supported in this mode
+    pub multiline: bool,
+    /// Further patterns to search for, along with the text to replace their matches with. These are applied
+    /// in order after `search` and `replace`, so each sees the output of the replacements before it
+    pub additional_exprs: Vec<(SearchType, String)>,
+}
+
+impl ParsedSearchConfig {
+    /// `search` and `replace` followed by `additional_exprs`, in the order they should be applied. Empty
+    /// search patterns are omitted
+    pub fn exprs(&self) -> Vec<(SearchType, String)> {
+        std::iter::once((self.search.clone(), self.replace.clone()))
+            .chain(self.additional_exprs.iter().cloned())
+            .filter(|(search, _)| !search.is_empty())
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct ParsedDirConfig {
     /// Configuration for file inclusion/exclusion patterns
     pub overrides: Override,
-    /// The root directory to start searching from
-    pub root_dir: PathBuf,
-    /// Whether to include hidden files/directories in the search
+    /// The glob patterns that `overrides` was built from, in order, with exclude patterns starting with `!`.
+    /// These are only used to report which glob matched a file in `FileSearcher::explain_filters`
+    pub override_globs: Vec<String>,
+    /// The root directories to start searching from, which are walked in order. These shouldn't overlap, as
+    /// files inside more than one of them are processed once for each
+    pub root_dirs: Vec<PathBuf>,
+    /// Whether to include hidden files/directories in the search. This is independent of `respect_ignore`, so
+    /// hidden files matched by ignore files are still skipped
     pub include_hidden: bool,
+    /// Whether to follow symbolic links when walking the directory. Symlink loops are detected and skipped
+    pub follow_symlinks: bool,
+    /// The maximum depth to descend into directories, where a depth of 1 only includes files directly in
+    /// a root directory. If `None`, there is no limit
+    pub max_depth: Option<usize>,
+    /// Whether to skip files matched by ignore files, such as `.gitignore`, `.ignore`, `.frepignore` and the
+    /// global gitignore
+    pub respect_ignore: bool,
+    /// Patterns from additional ignore files, in gitignore syntax and matched relative to the first of
+    /// `root_dirs`. Unlike `.gitignore` files, these are applied even if `respect_ignore` is `false`
+    pub custom_ignore: Gitignore,
+    /// If set, only files with one of these extensions (without the leading dot) are processed. This is
+    /// checked before any other filtering, so is faster than an equivalent include glob in large directories
+    pub extensions: Option<Vec<String>>,
+}
+
+/// The order in which to process files. See `FileSearcherConfig::sort`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileOrder {
+    /// By path
+    Path,
+    /// By size, smallest first
+    Size,
+    /// By last modification time, oldest first
+    Modified,
+}
+
+/// Options controlling how `FileSearcher` performs replacements
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct FileSearcherConfig {
+    /// Whether to collect the replacements that would be made, rather than writing them to disk
+    pub dry_run: bool,
+    /// Whether to generate a unified diff for each file with replacements
+    pub diff: bool,
+    /// If set, the original contents of each file are copied to a file with this suffix appended to its path
+    /// before the file is overwritten. Files whose names end with this suffix are not searched
+    pub backup_suffix: Option<String>,
+    /// Whether to collect line-level results for each file with replacements. This is slower than
+    /// replacing without collecting results, so is disabled by default
+    pub collect_line_results: bool,
+    /// The encoding to read and write files with. If `None`, this is detected from each file's byte order
+    /// mark, falling back to UTF-8. Note that UTF-16 files are only supported when replacing without
+    /// line-level results (i.e. not with `dry_run`, `diff` or `collect_line_results`), and are reported as
+    /// errors otherwise (see `replace::check_not_utf16`)
+    pub encoding: Option<Encoding>,
+    /// Files larger than this (in bytes) are replaced line by line rather than being read into memory.
+    /// If `None`, `replace::DEFAULT_MAX_IN_MEMORY_SIZE` is used
+    pub max_in_memory_size: Option<u64>,
+    /// If set, only files whose size (in bytes) is at least the first bound and at most the second are
+    /// searched, with `None` leaving that side of the range unbounded. Symlinked files are sized by the
+    /// file they link to
+    pub size_filter: Option<(Option<u64>, Option<u64>)>,
+    /// If set, only files last modified at or after the first bound and before the second are searched, with
+    /// `None` leaving that side of the range unbounded. As with `size_filter`, symlinked files use the
+    /// modification time of the file they link to
+    pub modified_filter: Option<(Option<SystemTime>, Option<SystemTime>)>,
+    /// The number of threads to process files with. If `None`, this defaults to the number of logical CPUs
+    /// (up to a maximum of 12). With a single thread, files are processed sequentially in order of path
+    pub threads: Option<NonZero<usize>>,
+    /// If set, every file in the root directories is found before any are processed, and they are then processed
+    /// sequentially in this order, with ties broken by path, such as for `max_count` to replace in the same files
+    /// on each run. This is slower than processing files in parallel as they are found, and `threads` is ignored.
+    /// Files whose metadata can't be read are processed first when sorting by size or modification time
+    pub sort: Option<FileOrder>,
+    /// Whether to apply case-transform escapes (such as `\U` and `\E`) in the replacement text when searching
+    /// with regex. See `replace::replacement_if_match_with_case_transforms`
+    pub case_transforms: bool,
+    /// Whether to preserve the modification time of each file that has replacements made in it
+    pub keep_mtime: bool,
+    /// The number of lines of context to show before each match. When performing a dry run without `diff`,
+    /// matching lines are formatted with context only if this or `after_context` is set. Diffs use the
+    /// larger of the two, falling back to `diff::DEFAULT_CONTEXT_LINES` if neither is set
+    pub before_context: Option<usize>,
+    /// The number of lines of context to show after each match. See `before_context`
+    pub after_context: Option<usize>,
+    /// The maximum number of matches to replace across all files. Once this many matches have been replaced,
+    /// no further files are searched. Lines are replaced as a whole, so a line is left unchanged if replacing
+    /// all of its matches would exceed the limit. Files are replaced line by line when this is set, so as with
+    /// `dry_run`, UTF-16 files are not supported. Not supported in multiline mode
+    pub max_count: Option<usize>,
+    /// The maximum number of matches to replace in each file. As with `max_count`, lines are replaced as a
+    /// whole, so replacing stops at the first line whose matches would exceed the limit. The matches in any
+    /// further lines are left unchanged, and are counted in `FileReplacements::num_over_limit`. Files are
+    /// replaced line by line when this is set, so as with `dry_run`, UTF-16 files are not supported. Not
+    /// supported in multiline mode
+    pub max_per_file: Option<usize>,
+    /// If set, no files are modified unless exactly this many matches would be replaced across all files, such
+    /// as to guard against a pattern matching more or fewer times than intended. The matches that would be
+    /// replaced are first counted with a dry run (see `FileSearcher::count_replacements`). This is only checked
+    /// by `run::find_and_replace_with_config` and `run::find_and_replace_with_progress`. Not supported in
+    /// multiline mode
+    pub expect_count: Option<usize>,
+    /// If set, only the Nth match of each search pattern in a line is replaced, counting from 1, and lines
+    /// with fewer matches are left unchanged. If `None`, all matches are replaced. Files are replaced line
+    /// by line when this is set. Ignored in multiline mode
+    pub occurrence: Option<NonZero<usize>>,
+    /// Whether to replace in files that appear to be binary, rather than skipping them. This includes files
+    /// with binary extensions such as `png`, and files containing NUL bytes near the start (see
+    /// `is_binary_file`)
+    pub include_binary: bool,
+    /// Whether to report files that appear to be binary as errors in `WalkReplaceResult::errors`, rather than
+    /// skipping them, such as to catch binary files accidentally matched by the include globs. This only
+    /// applies when replacing, and has no effect if `include_binary` is set
+    pub error_on_binary: bool,
+    /// The directory to write temporary files to before they replace the files being modified. If `None`,
+    /// temporary files are written alongside the files being modified, falling back to the system temporary
+    /// directory if that isn't possible. Files are overwritten in place when the temporary file can't be
+    /// renamed over them, such as when it is on a different filesystem, which is reported with a
+    /// `replace::ReplaceWarning::OverwrittenInPlace`
+    pub temp_dir: Option<PathBuf>,
+    /// Whether to overwrite files in place rather than renaming a temporary file over them, such as on networked
+    /// filesystems where renaming is slow or unreliable. Files replaced in memory are written directly, and
+    /// otherwise the new contents are written to a temporary file in `temp_dir` (or the system temporary
+    /// directory) and then copied over the original. This isn't atomic: if writing is interrupted, the file
+    /// is left incomplete and its original contents are lost, unless `backup_suffix` is set
+    pub in_place: bool,
+    /// Whether to make each file that has replacements made in it end with exactly one line ending, adding one
+    /// if it has none and collapsing multiple into one. The line ending used is `line_ending` if set, or
+    /// otherwise the last one in the file. Files without replacements are left unchanged
+    pub ensure_trailing_newline: bool,
+    /// Whether to check each file searched when replacing for a mix of line ending styles, such as `\n` and
+    /// `\r\n`, reporting them in `WalkReplaceResult::mixed_line_endings`. The file is checked before any
+    /// replacements are made in it, which requires reading it again
+    pub warn_mixed_endings: bool,
+    /// Whether to highlight the context lines and diffs in results with ANSI colours. See
+    /// `format_context_highlighted` and `diff::colorize`
+    pub color: bool,
+    /// Whether to insert the replacement text verbatim when searching with regex, rather than expanding
+    /// capture group references such as `$1`. See `replace::make_replacements_literal`
+    pub literal_replacement: bool,
+    /// If set, every line ending in each file that has replacements made in it is rewritten as this, including on
+    /// lines without replacements. Lines without a line ending are left unchanged, as is the case when this
+    /// is `LineEnding::None`. If `None`, line endings are preserved
+    pub line_ending: Option<LineEnding>,
+    /// Whether to error when a file with replacements is a symlink, rather than writing the replacements to
+    /// the file it links to. Symlinked files are only searched when `DirConfig::follow_symlinks` is set
+    pub no_follow_symlink_writes: bool,
+    /// Whether to check that the size and modification time of each file are unchanged just before the
+    /// replacements are written to it, erroring with `replace::FileChangedError` for that file if another
+    /// process has modified it since it was read
+    pub check_unchanged: bool,
+    /// Whether to leave matches inside string literals unchanged, in files whose language is supported (see
+    /// `syntax::Language::from_path`). Other files are replaced as usual. Files in supported languages are
+    /// replaced line by line when this is set. Not supported in multiline mode
+    pub skip_in_strings: bool,
+    /// Whether to also apply the search patterns to the names of files and directories, renaming those whose
+    /// names change once the contents of all files have been replaced. Nothing is renamed when performing a
+    /// dry run, but the renames that would be made are still reported
+    pub rename: bool,
+    /// The maximum time to spend searching each file. Each file is first searched on a separate thread, and if
+    /// this takes longer than the timeout, the file is left unchanged and reported with a `TimeoutError`, and no
+    /// further files are processed. The file is also left unchanged and reported with the error if an advanced
+    /// regex fails to match, such as by exceeding its backtracking limit, rather than the failed match being
+    /// skipped. Otherwise, replacements are made as usual, so this adds the cost of an extra search of each file
+    pub timeout: Option<Duration>,
+    /// If set, each match is replaced with the output of this command rather than with the replacement text
+    /// (see `replace::ReplaceCommand`), and `case_transforms`, `occurrence` and `skip_in_strings` are ignored.
+    /// Files are replaced line by line when this is set. Not supported in multiline mode
+    pub replace_command: Option<replace::ReplaceCommand>,
+    /// If set, calls such as `\{upper:$1\}` to the functions registered here are applied to the replacement
+    /// for each match (see `replace::ReplacementFunctions`), and `case_transforms`, `occurrence` and
+    /// `skip_in_strings` are ignored. Files are replaced line by line when this is set. Not supported in
+    /// multiline mode
+    pub replacement_functions: Option<replace::ReplacementFunctions>,
+    /// Whether to remove lines that are left empty by a replacement, including their line endings, rather than
+    /// leaving blank lines behind. Lines without a replacement are never removed, even if empty. Files are
+    /// replaced line by line when this is set. Not supported in multiline mode
+    pub delete_empty_lines: bool,
+    /// Whether to collapse runs of consecutive identical lines matching the search into a single line, such as
+    /// repeated separator comments or blank lines in generated files, even if the replacement leaves them
+    /// unchanged. Lines are compared before being replaced, and the first line of each run is kept. Files are
+    /// replaced line by line when this is set, and the lines are only collapsed when they are written, so dry
+    /// runs and diffs don't show them being removed. Not supported in multiline mode
+    pub collapse_duplicate_lines: bool,
+    /// Whether to keep the leading whitespace of each line with a replacement, restoring it if the replacement
+    /// doesn't start with it (see `replace::with_preserved_indent`). Files are replaced line by line when this
+    /// is set. Not supported in multiline mode
+    pub preserve_indent: bool,
+    /// Whether to replace only the first line with a match in each file, leaving any later matches unchanged.
+    /// When replacing without line-level results, the rest of the file is copied without being searched, so
+    /// as with `occurrence`, files are always replaced line by line. Not supported in multiline mode
+    pub first_only: bool,
+    /// If set, replacements are only made in lines that also contain a match of this pattern, such as to only
+    /// replace `port` on lines mentioning `production`. Other matching lines are left unchanged, and aren't
+    /// included in line-level results. Files are replaced line by line when this is set. Not supported in
+    /// multiline mode
+    pub only_matching_lines: Option<SearchType>,
+    /// If set, replacements aren't made in lines that contain a match of this pattern, such as to leave
+    /// commented-out lines (`^\s*//`) unchanged. This takes precedence over `only_matching_lines`, and as with
+    /// it, files are replaced line by line when this is set. Not supported in multiline mode
+    pub skip_lines: Option<SearchType>,
+    /// Whether to expand the tokens `{path}`, `{filename}` and `{lineno}` in the replacement text with the path
+    /// and name of each file and the number of each matching line (see `replace::expand_template`). Files are
+    /// replaced line by line when this is set. Not supported in multiline mode, and ignored when replacing
+    /// with `replace_command` or renaming files
+    pub template: bool,
+    /// Whether to match lines that aren't valid UTF-8 after replacing each invalid byte sequence with U+FFFD
+    /// (see `String::from_utf8_lossy`), rather than writing them unchanged and reporting the first invalid byte
+    /// with `replace::ReplaceWarning::InvalidUtf8`. Only lines with replacements are rewritten, so invalid bytes
+    /// in other lines are kept. This only applies when replacing without line-level results (see
+    /// `replace::replace_all_in_file`): otherwise, lines that aren't valid UTF-8 are skipped. Not supported in
+    /// multiline mode
+    pub lossy: bool,
+    /// If set, this command is run on each file once replacements have been written to it, such as to format
+    /// it (see `replace::PostCommand`). Files are processed one at a time, and a file for which the command
+    /// fails is reported in `WalkReplaceResult::errors`, without stopping the remaining files from being
+    /// processed. Not run when performing a dry run, or by `walk_files_and_replace_streaming`
+    pub post_command: Option<replace::PostCommand>,
+    /// If set in multiline mode, files are searched this many consecutive lines at a time rather than being
+    /// read into memory as a whole, so matches can span at most this many lines (see
+    /// `replace::replace_all_in_file_multiline`). Only a single search pattern is supported in this case
+    pub join_lines: Option<NonZero<usize>>,
+    /// If set, the original contents of each file are recorded in this journal just before the file is
+    /// overwritten, so that the run can be reverted with `undo::undo`
+    pub undo_journal: Option<UndoJournal>,
+    /// Whether to search and replace within gzip-compressed files (detected from their `.gz` extension or
+    /// their contents), which are decompressed, replaced as a whole and then compressed again (see
+    /// `replace::replace_all_in_gzip_file`). Otherwise, they are skipped as binary files. This only applies
+    /// when replacing, not when searching or counting matches
+    pub search_compressed: bool,
+    /// The gzip compression level used when compressing files again, from 0 (no compression) to 9 (best
+    /// compression). Defaults to 6 if not set
+    pub compression_level: Option<u32>,
+    /// If set, the files in this store are replaced rather than the files in the directories being walked, such
+    /// as to replace in an in-memory virtual filesystem. Only the include and exclude globs and extensions filter
+    /// the files in the store, files aren't renamed and `post_command` isn't run. Options for how files are
+    /// written, such as `backup_suffix` or `undo_journal`, only apply if the store's `FileStore::create` applies
+    /// them
+    pub file_store: Option<Arc<dyn FileStore>>,
+}
+
+impl FileSearcherConfig {
+    /// The store that files are read from and written to, which is `file_store` if set, or otherwise the
+    /// filesystem
+    pub fn store(&self) -> &dyn FileStore {
+        self.file_store.as_deref().unwrap_or(&store::FILESYSTEM)
+    }
+
+    /// Whether replacements can be made in `line`, if it matches the search patterns. See
+    /// `only_matching_lines` and `skip_lines`
+    pub fn replaces_in_line(&self, line: &str) -> bool {
+        self.skip_lines
+            .as_ref()
+            .is_none_or(|skip| !contains_search(line, skip))
+            && self
+                .only_matching_lines
+                .as_ref()
+                .is_none_or(|guard| contains_search(line, guard))
+    }
+
+    /// Whether any lines are excluded from replacement by `only_matching_lines` or `skip_lines`
+    pub fn filters_lines(&self) -> bool {
+        self.only_matching_lines.is_some() || self.skip_lines.is_some()
+    }
+
+    /// The number of matches replaced in a line containing `match_count` matches, which is all of them unless
+    /// `occurrence` is set (and not ignored because of `replace_command` or `replacement_functions`), in which
+    /// case only one is
+    pub fn num_replacements_in(&self, match_count: usize) -> usize {
+        if self.occurrence.is_some()
+            && self.replace_command.is_none()
+            && self.replacement_functions.is_none()
+        {
+            match_count.min(1)
+        } else {
+            match_count
+        }
+    }
+}
+
+/// The replacements made (or to be made) in a single file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReplacements {
+    pub path: PathBuf,
+    /// The line-level replacements, sorted by line number
+    pub replacements: Vec<SearchResultWithReplacement>,
+    /// The number of matches replaced (or to be replaced) in `replacements`, which can be more than the number
+    /// of lines as a line can contain several matches. See `FileSearcherConfig::num_replacements_in`
+    pub num_replacements: usize,
+    /// A unified diff of the replacements. Only populated when `diff` is set
+    pub diff: Option<String>,
+    /// The matching lines with surrounding context, formatted by `format_context`. Only populated when
+    /// performing a dry run without `diff`, and with `before_context` or `after_context` set
+    pub context: Option<String>,
+    /// The number of matches left unchanged because of `FileSearcherConfig::max_per_file`
+    pub num_over_limit: usize,
+}
+
+/// The outcome of processing a single file in `FileSearcher::walk_files_and_replace_streaming`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileReplaceOutcome {
+    /// The path of the file, or of the directory for `FileReplaceResult::PermissionDenied`
+    pub path: PathBuf,
+    pub result: FileReplaceResult,
+    /// Whether the file used more than one style of line ending before any replacements were made in it. Only
+    /// checked when `FileSearcherConfig::warn_mixed_endings` is set
+    pub mixed_line_endings: bool,
+    /// Problems with the file that didn't stop replacements from being made in it, such as invalid UTF-8
+    pub warnings: Vec<replace::ReplaceWarning>,
 }
 
+impl FileReplaceOutcome {
+    /// The outcome for a file, or directory, that wasn't replaced in, such as because it was filtered out
+    fn unprocessed(path: &Path, result: FileReplaceResult) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            result,
+            mixed_line_endings: false,
+            warnings: vec![],
+        }
+    }
+
+    /// Serializes the outcome as a single line of JSON, such as for newline-delimited JSON output. Files that
+    /// were searched give the number of replacements and whether the file was modified, which it never is when
+    /// `dry_run` is set, along with any warnings, and files that couldn't be processed give the error. Returns
+    /// `None` for files that were skipped as binary or filtered out
+    pub fn to_json(&self, dry_run: bool) -> Option<String> {
+        let path = utils::json_string(&self.path.to_string_lossy());
+        match &self.result {
+            FileReplaceResult::Replaced {
+                num_replacements, ..
+            } => {
+                let warnings = if self.warnings.is_empty() {
+                    String::new()
+                } else {
+                    let warnings = self
+                        .warnings
+                        .iter()
+                        .map(|warning| utils::json_string(&warning.to_string()))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    format!(r#","warnings":[{warnings}]"#)
+                };
+                Some(format!(
+                    r#"{{"path":{path},"matches":{num_replacements},"modified":{modified}{warnings}}}"#,
+                    modified = *num_replacements > 0 && !dry_run,
+                ))
+            }
+            FileReplaceResult::PermissionDenied(error) | FileReplaceResult::Error(error) => {
+                Some(format!(
+                    r#"{{"path":{path},"error":{error}}}"#,
+                    error = utils::json_string(error)
+                ))
+            }
+            FileReplaceResult::BinarySkipped | FileReplaceResult::Filtered => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileReplaceResult {
+    /// The file was searched, and had this many replacements made in it (or would have, when performing a dry
+    /// run), which is 0 if there were no matches. The line-level replacements are included if they were
+    /// collected, as described in `WalkReplaceResult::files`
+    Replaced {
+        num_replacements: usize,
+        file_replacements: Option<FileReplacements>,
+    },
+    /// The file was skipped because its contents appear to be binary
+    BinarySkipped,
+    /// The file was skipped without being read, because it has a binary extension (such as `png`), or its size
+    /// or modification time is outside `size_filter` or `modified_filter`
+    Filtered,
+    /// The file or directory was skipped because permission to read or write it was denied
+    PermissionDenied(String),
+    /// The file couldn't be processed because of this error, and was left unchanged
+    Error(String),
+}
+
+/// The outcome of `FileSearcher::walk_files_and_replace`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WalkReplaceResult {
+    /// The number of files that had replacements performed in them (or would have, when performing a dry run)
+    pub num_files_replaced: usize,
+    /// The replacements in each file, sorted by path. Only populated when performing a dry run, or when
+    /// `diff` or `collect_line_results` is set
+    pub files: Vec<FileReplacements>,
+    /// The number of files skipped because their contents appear to be binary
+    pub num_binary_files_skipped: usize,
+    /// The number of files that were searched, including those with no matches and those that couldn't be
+    /// processed because of an error
+    pub num_files_searched: usize,
+    /// The number of files skipped without being read, as described in `FileReplaceResult::Filtered`. Files
+    /// excluded by globs, extensions or ignore files are never visited, so aren't counted
+    pub num_files_filtered: usize,
+    /// The files that couldn't be processed because of an error, along with the error, sorted by path
+    pub errors: Vec<(PathBuf, String)>,
+    /// The files and directories that were skipped because permission to read or write them was denied,
+    /// along with the error, sorted by path. These are not included in `errors`
+    pub skipped: Vec<(PathBuf, String)>,
+    /// The paths of the files that were modified, sorted. Always empty when performing a dry run. If files or
+    /// their parent directories were renamed, these are the new paths
+    pub modified_paths: Vec<PathBuf>,
+    /// The number of replacements made in each file that had replacements performed in it (or would have,
+    /// when performing a dry run), sorted by path. As with `modified_paths`, files that were renamed have
+    /// their new paths. See `replace::replace_all_in_file` for how replacements are counted
+    pub replacement_counts: Vec<(PathBuf, usize)>,
+    /// The number of matches left unchanged in each file because of `max_per_file`, for the files that had
+    /// any, sorted by path. As with `modified_paths`, files that were renamed have their new paths
+    pub over_limit: Vec<(PathBuf, usize)>,
+    /// The original and new path of each file or directory that was renamed (or would have been, when
+    /// performing a dry run), sorted by original path. Only populated when `rename` is set
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+    /// The files with replacements that used more than one style of line ending, sorted by path. Only
+    /// populated when `warn_mixed_endings` is set
+    pub mixed_line_endings: Vec<PathBuf>,
+    /// The warnings reported for each file, as described in `FileReplaceOutcome::warnings`, sorted by path.
+    /// Files that were renamed keep their original paths
+    pub warnings: Vec<(PathBuf, replace::ReplaceWarning)>,
+}
+
+/// Whether a file is searched, and why, as reported by `FileSearcher::explain_filters`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// The file is searched. Holds the include glob that matched the file, if there is one
+    Included(Option<String>),
+    /// The file doesn't have one of the configured `extensions`
+    ExcludedByExtension,
+    /// The file, or a directory containing it, matched this exclude glob (starting with `!`). If `None`, there
+    /// are include globs and the file didn't match any of them
+    ExcludedByGlob(Option<String>),
+    /// The file, or a directory containing it, matched a pattern in one of the additional ignore files (see
+    /// `ParsedDirConfig::custom_ignore`)
+    ExcludedByIgnoreFile {
+        pattern: String,
+        ignore_file: Option<PathBuf>,
+    },
+    /// The file is ignored by an ignore file found when walking the directories, such as a `.gitignore`,
+    /// `.ignore` or `.frepignore` file, or by the global gitignore
+    ExcludedByIgnoreRules,
+}
+
+impl std::fmt::Display for FilterDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterDecision::Included(Some(glob)) => write!(f, "included by glob '{glob}'"),
+            FilterDecision::Included(None) => write!(f, "included"),
+            FilterDecision::ExcludedByExtension => {
+                write!(
+                    f,
+                    "excluded, as it doesn't have one of the given extensions"
+                )
+            }
+            FilterDecision::ExcludedByGlob(Some(glob)) => write!(f, "excluded by glob '{glob}'"),
+            FilterDecision::ExcludedByGlob(None) => {
+                write!(f, "excluded, as it doesn't match any of the include globs")
+            }
+            FilterDecision::ExcludedByIgnoreFile {
+                pattern,
+                ignore_file: Some(ignore_file),
+            } => write!(f, "excluded by '{pattern}' in {}", ignore_file.display()),
+            FilterDecision::ExcludedByIgnoreFile {
+                pattern,
+                ignore_file: None,
+            } => write!(f, "excluded by '{pattern}'"),
+            FilterDecision::ExcludedByIgnoreRules => {
+                write!(f, "excluded by an ignore file, such as a .gitignore")
+            }
+        }
+    }
+}
+
+/// The name of frep-specific ignore files, which use gitignore syntax and apply to the directory they are in,
+/// so that files can be excluded from frep without affecting git
+pub const FREP_IGNORE_FILENAME: &str = ".frepignore";
+
 #[derive(Clone, Debug)]
 pub struct FileSearcher {
     search_config: ParsedSearchConfig,
     dir_config: ParsedDirConfig,
+    config: FileSearcherConfig,
+    /// See `ParsedSearchConfig::exprs`
+    exprs: Vec<(SearchType, String)>,
 }
 
 impl FileSearcher {
     pub fn new(search_config: ParsedSearchConfig, dir_config: ParsedDirConfig) -> Self {
+        Self::with_config(search_config, dir_config, FileSearcherConfig::default())
+    }
+
+    pub fn with_config(
+        search_config: ParsedSearchConfig,
+        dir_config: ParsedDirConfig,
+        config: FileSearcherConfig,
+    ) -> Self {
+        let mut exprs = search_config.exprs();
+        if config.literal_replacement {
+            replace::make_replacements_literal(&mut exprs);
+        }
         Self {
             search_config,
             dir_config,
+            config,
+            exprs,
         }
     }
 
-    fn build_walker(&self) -> ignore::WalkParallel {
-        let num_threads = thread::available_parallelism()
-            .map(NonZero::get)
-            .unwrap_or(4)
-            .min(12);
+    fn num_threads(&self) -> usize {
+        self.config.threads.map_or_else(
+            || {
+                thread::available_parallelism()
+                    .map(NonZero::get)
+                    .unwrap_or(4)
+                    .min(12)
+            },
+            NonZero::get,
+        )
+    }
 
-        WalkBuilder::new(&self.dir_config.root_dir)
+    /// Walks each of the configured root directories, calling a visitor created by `make_visitor` on each entry.
+    ///
+    /// Entries are visited in parallel, unless configured to use a single thread, in which case they are
+    /// visited sequentially in order of file name so that the results are deterministic. If `sort` is set, every
+    /// entry is collected first and they are then visited sequentially in that order, after any walk errors.
+    fn walk<'s, F>(&self, mut make_visitor: F)
+    where
+        F: FnMut() -> EntryVisitor<'s>,
+    {
+        let Some(mut builder) = self.walk_builder(true) else {
+            return;
+        };
+
+        let num_threads = self.num_threads();
+        if let Some(order) = self.config.sort {
+            let mut entries = vec![];
+            let mut errors = vec![];
+            for result in builder.build() {
+                match result {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => errors.push(e),
+                }
+            }
+            sort_entries(&mut entries, order);
+
+            let mut visitor = make_visitor();
+            for result in errors
+                .into_iter()
+                .map(Err)
+                .chain(entries.into_iter().map(Ok))
+            {
+                if matches!(visitor(result), WalkState::Quit) {
+                    break;
+                }
+            }
+        } else if num_threads == 1 {
+            let mut visitor = make_visitor();
+            for result in builder.sort_by_file_name(Ord::cmp).build() {
+                if matches!(visitor(result), WalkState::Quit) {
+                    break;
+                }
+            }
+        } else {
+            builder
+                .threads(num_threads)
+                .build_parallel()
+                .run(make_visitor);
+        }
+    }
+
+    /// A `WalkBuilder` for the configured root directories, or `None` if there aren't any. If `apply_filters`
+    /// is `false`, globs and ignore files are not applied, so that every file is visited other than hidden files
+    /// (unless `include_hidden` is set)
+    fn walk_builder(&self, apply_filters: bool) -> Option<WalkBuilder> {
+        let mut root_dirs = self.dir_config.root_dirs.iter();
+        let mut builder = WalkBuilder::new(root_dirs.next()?);
+        for root_dir in root_dirs {
+            builder.add(root_dir);
+        }
+        let respect_ignore = apply_filters && self.dir_config.respect_ignore;
+        if respect_ignore {
+            builder.add_custom_ignore_filename(FREP_IGNORE_FILENAME);
+        }
+        builder
             .hidden(!self.dir_config.include_hidden)
-            .overrides(self.dir_config.overrides.clone())
-            .threads(num_threads)
-            .build_parallel()
+            .follow_links(self.dir_config.follow_symlinks)
+            .max_depth(self.dir_config.max_depth)
+            .ignore(respect_ignore)
+            .git_ignore(respect_ignore)
+            .git_global(respect_ignore)
+            .git_exclude(respect_ignore)
+            .parents(respect_ignore);
+        if apply_filters {
+            builder.overrides(self.dir_config.overrides.clone());
+            if !self.dir_config.custom_ignore.is_empty() {
+                let custom_ignore = self.dir_config.custom_ignore.clone();
+                builder.filter_entry(move |entry| {
+                    let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+                    !custom_ignore.matched(entry.path(), is_dir).is_ignore()
+                });
+            }
+        }
+        Some(builder)
+    }
+
+    /// Reports whether each file in the root directories is searched, along with the glob, extension or ignore
+    /// rule responsible, such as to debug globs that don't match as expected. Every file is visited, including
+    /// those in ignored directories, so this can be slow for large directories. Hidden files are only included
+    /// if `include_hidden` is set, and filters that are checked once a file is found (such as `size_filter`) are
+    /// not considered.
+    ///
+    /// Returns each file along with the decision, sorted by path.
+    pub fn explain_filters(&self) -> Vec<(PathBuf, FilterDecision)> {
+        let walk_files = |apply_filters| {
+            self.walk_builder(apply_filters)
+                .into_iter()
+                .flat_map(|builder| builder.build())
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+                .map(ignore::DirEntry::into_path)
+        };
+        let included = walk_files(true).collect::<HashSet<_>>();
+
+        // The same globs as the overrides, but without inverting them, so that the matching glob is available
+        let mut override_globs = GitignoreBuilder::new(self.dir_config.overrides.path());
+        for glob in &self.dir_config.override_globs {
+            let _ = override_globs.add_line(None, glob);
+        }
+        let override_globs = override_globs
+            .build()
+            .unwrap_or_else(|_| Gitignore::empty());
+
+        let mut decisions = walk_files(false)
+            .map(|path| {
+                let decision =
+                    self.filter_decision(&path, included.contains(&path), &override_globs);
+                (path, decision)
+            })
+            .collect::<Vec<_>>();
+        decisions.sort_by(|(a, _), (b, _)| a.cmp(b));
+        decisions
+    }
+
+    /// Whether the file at `path` is searched, and why. `included` is whether it was found when walking the
+    /// directories with filters applied. `override_globs` contains the override globs in gitignore form, so
+    /// ignore matches are include globs and whitelist matches are exclude globs
+    fn filter_decision(
+        &self,
+        path: &Path,
+        included: bool,
+        override_globs: &Gitignore,
+    ) -> FilterDecision {
+        if !self.has_included_extension(path) {
+            return FilterDecision::ExcludedByExtension;
+        }
+        if included {
+            return FilterDecision::Included(match override_globs.matched(path, false) {
+                Match::Ignore(glob) => Some(glob.original().to_owned()),
+                _ => None,
+            });
+        }
+
+        let root_dir = self
+            .dir_config
+            .root_dirs
+            .iter()
+            .find(|root_dir| path.starts_with(root_dir));
+        // The file itself is checked first, followed by each directory containing it
+        let paths = std::iter::once((path, false)).chain(
+            path.ancestors()
+                .skip(1)
+                .take_while(|dir| root_dir.is_some_and(|root_dir| *dir != root_dir.as_path()))
+                .map(|dir| (dir, true)),
+        );
+        for (path, is_dir) in paths {
+            if let Match::Whitelist(glob) = override_globs.matched(path, is_dir) {
+                return FilterDecision::ExcludedByGlob(Some(glob.original().to_owned()));
+            }
+            if !is_dir && self.dir_config.overrides.matched(path, false).is_ignore() {
+                return FilterDecision::ExcludedByGlob(None);
+            }
+            if let Match::Ignore(glob) = self.dir_config.custom_ignore.matched(path, is_dir) {
+                return FilterDecision::ExcludedByIgnoreFile {
+                    pattern: glob.original().to_owned(),
+                    ignore_file: glob.from().map(Path::to_path_buf),
+                };
+            }
+        }
+        FilterDecision::ExcludedByIgnoreRules
     }
 
     /// Walks through files in the configured directory and processes matches.
     ///
-    /// This method traverses the filesystem starting from the `root_dir` specified in the `FileSearcher`,
+    /// This method traverses the filesystem starting from each of the `root_dirs` specified in the `FileSearcher`,
     /// respecting the configured overrides (include/exclude patterns) and hidden file settings.
     /// It uses parallel processing when possible for better performance.
     ///
@@ -164,17 +920,25 @@ impl FileSearcher {
     ///     path::PathBuf,
     /// };
     /// use regex::Regex;
-    /// use ignore::{WalkState, overrides::Override};
+    /// use ignore::{WalkState, gitignore::Gitignore, overrides::Override};
     /// use frep_core::search::{FileSearcher, ParsedSearchConfig, ParsedDirConfig, SearchResult, SearchType};
     ///
     /// let search_config = ParsedSearchConfig {
     ///     search: SearchType::Pattern(Regex::new("pattern").unwrap()),
     ///     replace: "replacement".to_string(),
+    ///     multiline: false,
+    ///     additional_exprs: vec![],
     /// };
     /// let dir_config = ParsedDirConfig {
     ///     overrides: Override::empty(),
-    ///     root_dir: PathBuf::from("."),
+    ///     override_globs: vec![],
+    ///     root_dirs: vec![PathBuf::from(".")],
     ///     include_hidden: false,
+    ///     follow_symlinks: false,
+    ///     max_depth: None,
+    ///     respect_ignore: true,
+    ///     custom_ignore: Gitignore::empty(),
+    ///     extensions: None,
     /// };
     /// let searcher = FileSearcher::new(search_config, dir_config);
     /// let cancelled = AtomicBool::new(false);
@@ -194,16 +958,40 @@ impl FileSearcher {
     ///     Ok(())
     /// }
     /// ```
-    pub fn walk_files<F>(&self, cancelled: Option<&AtomicBool>, mut file_handler: F)
+    pub fn walk_files<F>(&self, cancelled: Option<&AtomicBool>, file_handler: F)
     where
         F: FnMut() -> FileVisitor + Send,
+    {
+        self.walk_files_using(
+            cancelled,
+            |path| {
+                Ok(self
+                    .search_file(path, 0, 0)?
+                    .into_iter()
+                    .map(|r| r.search_result)
+                    .collect())
+            },
+            file_handler,
+        );
+    }
+
+    /// Walks through files in the configured directory, searching each file with `search_file` and passing
+    /// any results to the visitors created by `file_handler`
+    fn walk_files_using<R, S, F>(
+        &self,
+        cancelled: Option<&AtomicBool>,
+        search_file: S,
+        mut file_handler: F,
+    ) where
+        S: Fn(&Path) -> anyhow::Result<Vec<R>> + Sync,
+        F: FnMut() -> Box<dyn FnMut(Vec<R>) -> WalkState + Send> + Send,
     {
         if let Some(cancelled) = cancelled {
             cancelled.store(false, Ordering::Relaxed);
         }
 
-        let walker = self.build_walker();
-        walker.run(|| {
+        let search_file = &search_file;
+        self.walk(|| {
             let mut on_file_found = file_handler();
             Box::new(move |result| {
                 if let Some(cancelled) = cancelled
@@ -216,8 +1004,12 @@ impl FileSearcher {
                     return WalkState::Continue;
                 };
 
-                if is_searchable(&entry) {
-                    let results = match search_file(entry.path(), &self.search_config.search) {
+                if self.has_included_extension(entry.path())
+                    && is_searchable(&entry)
+                    && self.has_size_in_range(entry.path())
+                    && self.has_modified_in_range(entry.path())
+                {
+                    let results = match search_file(entry.path()) {
                         Ok(r) => r,
                         Err(e) => {
                             log::warn!(
@@ -237,30 +1029,633 @@ impl FileSearcher {
         });
     }
 
+    /// Walks through files in the configured directory and counts the lines matching the search pattern,
+    /// without performing any replacements.
+    ///
+    /// # Parameters
+    ///
+    /// * `cancelled` - An optional atomic boolean that can be used to signal cancellation from another thread.
+    ///   If this is set to `true` during execution, the search will stop as soon as possible.
+    ///
+    /// # Returns
+    ///
+    /// The number of matching lines and the total number of matches in each file with at least one match,
+    /// sorted by path.
+    pub fn walk_files_and_count(
+        &self,
+        cancelled: Option<&AtomicBool>,
+    ) -> Vec<(PathBuf, usize, usize)> {
+        let counts = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        self.walk_files(cancelled, || {
+            let counts = counts.clone();
+            Box::new(move |results| {
+                if let Some(path) = results.first().and_then(|r| r.path.clone()) {
+                    let num_matches = results.iter().map(|r| r.match_count).sum();
+                    counts
+                        .lock()
+                        .unwrap()
+                        .push((path, results.len(), num_matches));
+                }
+                WalkState::Continue
+            })
+        });
+
+        let mut counts = std::mem::take(&mut *counts.lock().unwrap());
+        counts.sort();
+        counts
+    }
+
+    /// Walks through files in the configured directory and finds the lines matching the search pattern, without
+    /// performing any replacements.
+    ///
+    /// # Parameters
+    ///
+    /// * `cancelled` - An optional atomic boolean that can be used to signal cancellation from another thread.
+    ///   If this is set to `true` during execution, the search will stop as soon as possible.
+    ///
+    /// # Returns
+    ///
+    /// The matching lines across all files, sorted by path and then by line number.
+    pub fn walk_files_and_collect(&self, cancelled: Option<&AtomicBool>) -> Vec<SearchResult> {
+        let files = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        self.walk_files(cancelled, || {
+            let files = files.clone();
+            Box::new(move |results| {
+                files.lock().unwrap().push(results);
+                WalkState::Continue
+            })
+        });
+
+        let mut files = std::mem::take(&mut *files.lock().unwrap());
+        files.sort_by(|a, b| a.first().map(|r| &r.path).cmp(&b.first().map(|r| &r.path)));
+        files.into_iter().flatten().collect()
+    }
+
+    /// Walks through files in the configured directory and finds the files whose paths, relative to the root
+    /// directory containing them, match the search pattern. File contents are not read.
+    ///
+    /// # Parameters
+    ///
+    /// * `cancelled` - An optional atomic boolean that can be used to signal cancellation from another thread.
+    ///   If this is set to `true` during execution, the search will stop as soon as possible.
+    ///
+    /// # Returns
+    ///
+    /// The paths of the matching files, sorted.
+    pub fn walk_files_and_match_paths(&self, cancelled: Option<&AtomicBool>) -> Vec<PathBuf> {
+        let paths = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        self.walk(|| {
+            let paths = paths.clone();
+
+            Box::new(move |result| {
+                if let Some(cancelled) = cancelled
+                    && cancelled.load(Ordering::Relaxed)
+                {
+                    return WalkState::Quit;
+                }
+
+                let Ok(entry) = result else {
+                    return WalkState::Continue;
+                };
+
+                if !entry.file_type().is_some_and(|ft| ft.is_file())
+                    || !self.has_included_extension(entry.path())
+                    || !self.has_size_in_range(entry.path())
+                    || !self.has_modified_in_range(entry.path())
+                {
+                    return WalkState::Continue;
+                }
+                let relative_path = self
+                    .dir_config
+                    .root_dirs
+                    .iter()
+                    .find_map(|root_dir| entry.path().strip_prefix(root_dir).ok())
+                    .unwrap_or(entry.path());
+                if relative_path
+                    .to_str()
+                    .is_some_and(|relative_path| contains_any_search(relative_path, self.exprs()))
+                {
+                    paths.lock().unwrap().push(entry.into_path());
+                }
+                WalkState::Continue
+            })
+        });
+
+        let mut paths = std::mem::take(&mut *paths.lock().unwrap());
+        paths.sort();
+        paths
+    }
+
+    /// Walks through files in the configured directory and lists those that would be searched when replacing,
+    /// after applying the glob, ignore, hidden, extension, binary, size and modification time filters. File
+    /// contents are not read, other than to detect compressed files when `search_compressed` is set.
+    ///
+    /// # Parameters
+    ///
+    /// * `cancelled` - An optional atomic boolean that can be used to signal cancellation from another thread.
+    ///   If this is set to `true` during execution, the walk will stop as soon as possible.
+    ///
+    /// # Returns
+    ///
+    /// The paths of the files, relative to the root directory containing them, sorted.
+    pub fn walk_files_and_list_paths(&self, cancelled: Option<&AtomicBool>) -> Vec<PathBuf> {
+        let paths = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        self.walk(|| {
+            let paths = paths.clone();
+
+            Box::new(move |result| {
+                if let Some(cancelled) = cancelled
+                    && cancelled.load(Ordering::Relaxed)
+                {
+                    return WalkState::Quit;
+                }
+
+                let Ok(entry) = result else {
+                    return WalkState::Continue;
+                };
+
+                let path = entry.path();
+                if !entry.file_type().is_some_and(|ft| ft.is_file())
+                    || !self.has_included_extension(path)
+                    || self.is_backup_file(path)
+                    || !(self.config.include_binary
+                        || !is_likely_binary(path)
+                        || self.is_compressed(path))
+                    || !self.has_size_in_range(path)
+                    || !self.has_modified_in_range(path)
+                {
+                    return WalkState::Continue;
+                }
+                let relative_path = self
+                    .dir_config
+                    .root_dirs
+                    .iter()
+                    .find_map(|root_dir| path.strip_prefix(root_dir).ok())
+                    .unwrap_or(path);
+                paths.lock().unwrap().push(relative_path.to_path_buf());
+                WalkState::Continue
+            })
+        });
+
+        let mut paths = std::mem::take(&mut *paths.lock().unwrap());
+        paths.sort();
+        paths
+    }
+
+    /// Walks through files in the configured directory and finds the lines matching the search pattern, along
+    /// with up to `before` and `after` lines of context around each match, without performing any replacements.
+    ///
+    /// # Parameters
+    ///
+    /// * `cancelled` - An optional atomic boolean that can be used to signal cancellation from another thread.
+    ///   If this is set to `true` during execution, the search will stop as soon as possible.
+    ///
+    /// # Returns
+    ///
+    /// The matching lines in each file with at least one match, sorted by path.
+    pub fn walk_files_with_context(
+        &self,
+        cancelled: Option<&AtomicBool>,
+        before: usize,
+        after: usize,
+    ) -> Vec<(PathBuf, Vec<SearchResultWithContext>)> {
+        let files = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        self.walk_files_using(
+            cancelled,
+            |path| self.search_file(path, before, after),
+            || {
+                let files = files.clone();
+                Box::new(move |results: Vec<SearchResultWithContext>| {
+                    if let Some(path) = results.first().and_then(|r| r.search_result.path.clone()) {
+                        files.lock().unwrap().push((path, results));
+                    }
+                    WalkState::Continue
+                })
+            },
+        );
+
+        let mut files = std::mem::take(&mut *files.lock().unwrap());
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+    }
+
     /// Walks through files in the configured directory and replaces matches.
     ///
-    /// This method traverses the filesystem starting from the `root_dir` specified in the `FileSearcher`,
+    /// This method traverses the filesystem starting from each of the `root_dirs` specified in the `FileSearcher`,
     /// respecting the configured overrides (include/exclude patterns) and hidden file settings.
     /// It replaces all matches of the search pattern with the replacement text in each file.
     ///
+    /// If `dry_run` is set in the `FileSearcherConfig`, no files are modified: instead, the replacements
+    /// that would have been made are collected and returned. If `diff` is set, a unified diff of each
+    /// file with replacements is also returned. If `rename` is set, files and directories whose names match
+    /// are then renamed (see `rename_paths`). If `post_command` is set, it is run on each file with replacements
+    /// before anything is renamed.
+    ///
+    /// This collects the outcomes sent by `walk_files_and_replace_streaming`, which can be used instead to
+    /// handle the outcome of each file as soon as it has been processed.
+    ///
     /// # Parameters
     ///
     /// * `cancelled` - An optional atomic boolean that can be used to signal cancellation from another thread.
     ///   If this is set to `true` during execution, the search will stop as soon as possible.
     ///
+    /// * `on_file_processed` - An optional callback, invoked after each file is processed with its path and
+    ///   the number of replacements made in it (or that would be made, when performing a dry run), which is
+    ///   0 if there were no matches or an error occurred. This can be used to report progress. The callback
+    ///   is called on the thread that called this method, and must not panic.
+    ///
     /// # Returns
     ///
-    /// The number of files that had replacements performed in them.
-    pub fn walk_files_and_replace(&self, cancelled: Option<&AtomicBool>) -> usize {
+    /// A `WalkReplaceResult` containing the number of files that had replacements performed in them, along
+    /// with the line-level replacements in each file if requested.
+    pub fn walk_files_and_replace(
+        &self,
+        cancelled: Option<&AtomicBool>,
+        mut on_file_processed: Option<ProgressCallback<'_>>,
+    ) -> WalkReplaceResult {
+        let mut result = WalkReplaceResult::default();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            scope.spawn(move || self.walk_files_and_replace_streaming(cancelled, &sender));
+
+            for FileReplaceOutcome {
+                path,
+                result: file_result,
+                mixed_line_endings,
+                warnings,
+            } in receiver
+            {
+                if mixed_line_endings {
+                    result.mixed_line_endings.push(path.clone());
+                }
+                result
+                    .warnings
+                    .extend(warnings.into_iter().map(|warning| (path.clone(), warning)));
+                let num_replacements = match file_result {
+                    FileReplaceResult::Replaced {
+                        num_replacements,
+                        file_replacements,
+                    } => {
+                        result.num_files_searched += 1;
+                        if let Some(file_replacements) = file_replacements {
+                            if file_replacements.num_over_limit > 0 {
+                                result
+                                    .over_limit
+                                    .push((path.clone(), file_replacements.num_over_limit));
+                            }
+                            result.files.push(file_replacements);
+                        }
+                        num_replacements
+                    }
+                    FileReplaceResult::BinarySkipped => {
+                        result.num_binary_files_skipped += 1;
+                        0
+                    }
+                    FileReplaceResult::Filtered => {
+                        // Filtered files aren't processed, so progress isn't reported for them
+                        result.num_files_filtered += 1;
+                        continue;
+                    }
+                    FileReplaceResult::PermissionDenied(error) => {
+                        result.skipped.push((path.clone(), error));
+                        0
+                    }
+                    FileReplaceResult::Error(error) => {
+                        result.num_files_searched += 1;
+                        result.errors.push((path.clone(), error));
+                        0
+                    }
+                };
+
+                if num_replacements > 0 {
+                    result.num_files_replaced += 1;
+                    result
+                        .replacement_counts
+                        .push((path.clone(), num_replacements));
+                    if !self.config.dry_run {
+                        result.modified_paths.push(path.clone());
+                        if let Some(post_command) = &self.config.post_command
+                            && self.config.file_store.is_none()
+                            && let Err(e) = post_command.run(&path)
+                        {
+                            log::error!(
+                                "Post-replacement command failed for {}: {e}",
+                                path.display()
+                            );
+                            result.errors.push((path.clone(), format!("{e:#}")));
+                        }
+                    }
+                }
+                if let Some(on_file_processed) = on_file_processed.as_deref_mut() {
+                    on_file_processed(&path, num_replacements);
+                }
+            }
+        });
+
+        result.files.sort_by(|a, b| a.path.cmp(&b.path));
+        result.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+
+        if self.config.rename && self.config.file_store.is_none() {
+            let (new_names, renamed) = self.rename_paths(cancelled, &mut result.errors);
+            if !self.config.dry_run {
+                for path in &mut result.modified_paths {
+                    *path = renamed_path(path, &new_names);
+                }
+                for (path, _) in result
+                    .replacement_counts
+                    .iter_mut()
+                    .chain(&mut result.over_limit)
+                {
+                    *path = renamed_path(path, &new_names);
+                }
+            }
+            result.renamed = renamed;
+        }
+        result.errors.sort_by(|a, b| a.0.cmp(&b.0));
+        result.modified_paths.sort();
+        result.replacement_counts.sort();
+        result.mixed_line_endings.sort();
+        result.warnings.sort_by(|a, b| a.0.cmp(&b.0));
+        result.over_limit.sort();
+
+        result
+    }
+
+    /// Like `walk_files_and_replace`, but rather than collecting the results, sends the outcome of each file to
+    /// `sender` as soon as it has been processed, such as to update a UI incrementally. Outcomes are sent in
+    /// the order in which files finish processing, which is only deterministic with a single thread. Returns
+    /// once all files have been processed.
+    ///
+    /// Unlike `walk_files_and_replace`, files and directories are not renamed when `rename` is set. Outcomes
+    /// that can't be sent because the receiver has been dropped are discarded.
+    pub fn walk_files_and_replace_streaming(
+        &self,
+        cancelled: Option<&AtomicBool>,
+        sender: &mpsc::Sender<FileReplaceOutcome>,
+    ) {
         if let Some(cancelled) = cancelled {
             cancelled.store(false, Ordering::Relaxed);
         }
+        let remaining = self.config.max_count.map(AtomicUsize::new);
+        let remaining = remaining.as_ref();
+        let timed_out = AtomicBool::new(false);
+        let timed_out = &timed_out;
+
+        if let Some(file_store) = &self.config.file_store {
+            self.replace_in_store(file_store.as_ref(), cancelled, remaining, timed_out, sender);
+            return;
+        }
+
+        self.walk(|| {
+            let sender = sender.clone();
+            let send = move |outcome| {
+                let _ = sender.send(outcome);
+            };
+
+            Box::new(move |result| {
+                if let Some(cancelled) = cancelled
+                    && cancelled.load(Ordering::Relaxed)
+                {
+                    return WalkState::Quit;
+                }
+
+                let entry = match result {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        if let Some(io_error) = e.io_error()
+                            && io_error.kind() == io::ErrorKind::PermissionDenied
+                            && let Some(path) = walk_error_path(&e)
+                        {
+                            log::warn!("Skipped {}: {io_error}", path.display());
+                            send(FileReplaceOutcome::unprocessed(
+                                path,
+                                FileReplaceResult::PermissionDenied(io_error.to_string()),
+                            ));
+                        }
+                        return WalkState::Continue;
+                    }
+                };
+
+                if !self.has_included_extension(entry.path())
+                    || !entry.file_type().is_some_and(|ft| ft.is_file())
+                    || self.is_backup_file(entry.path())
+                {
+                    return WalkState::Continue;
+                }
+                if self.is_filtered(entry.path()) {
+                    send(FileReplaceOutcome::unprocessed(
+                        entry.path(),
+                        FileReplaceResult::Filtered,
+                    ));
+                    return WalkState::Continue;
+                }
+                if remaining.is_some_and(|remaining| remaining.load(Ordering::Relaxed) == 0)
+                    || timed_out.load(Ordering::Relaxed)
+                {
+                    return WalkState::Quit;
+                }
+
+                send(self.replace_in_path(entry.path(), remaining, timed_out));
+                WalkState::Continue
+            })
+        });
+    }
+
+    /// Replaces in each file in `store`, one at a time, sending the outcome of each to `sender` as with
+    /// `walk_files_and_replace_streaming`. Files excluded by the globs or extensions are filtered out
+    fn replace_in_store(
+        &self,
+        store: &dyn FileStore,
+        cancelled: Option<&AtomicBool>,
+        remaining: Option<&AtomicUsize>,
+        timed_out: &AtomicBool,
+        sender: &mpsc::Sender<FileReplaceOutcome>,
+    ) {
+        let paths = match store.paths() {
+            Ok(paths) => paths,
+            Err(e) => {
+                log::error!("Failed to list the files in the file store: {e}");
+                return;
+            }
+        };
+
+        for path in paths {
+            if cancelled.is_some_and(|cancelled| cancelled.load(Ordering::Relaxed)) {
+                return;
+            }
+            let outcome = if self.dir_config.overrides.matched(&path, false).is_ignore()
+                || !self.has_included_extension(&path)
+            {
+                FileReplaceOutcome::unprocessed(&path, FileReplaceResult::Filtered)
+            } else if remaining.is_some_and(|remaining| remaining.load(Ordering::Relaxed) == 0)
+                || timed_out.load(Ordering::Relaxed)
+            {
+                return;
+            } else {
+                self.replace_in_path(&path, remaining, timed_out)
+            };
+            let _ = sender.send(outcome);
+        }
+    }
+
+    /// Whether the file at `path` is skipped by `walk_files_and_replace_streaming` without being replaced in,
+    /// because of its binary extension (unless `error_on_binary` is set), its size or its modification time
+    fn is_filtered(&self, path: &Path) -> bool {
+        let has_binary_extension = !self.config.include_binary
+            && !self.config.error_on_binary
+            && is_likely_binary(path)
+            && !self.is_compressed(path);
+        has_binary_extension || !self.has_size_in_range(path) || !self.has_modified_in_range(path)
+    }
+
+    /// Replaces in the file at `path` for `walk_files_and_replace_streaming`, decrementing `remaining` by the
+    /// number of lines replaced when `max_count` is set, and setting `timed_out` if the search times out
+    fn replace_in_path(
+        &self,
+        path: &Path,
+        remaining: Option<&AtomicUsize>,
+        timed_out: &AtomicBool,
+    ) -> FileReplaceOutcome {
+        let compressed = self.is_compressed(path);
+        let has_binary_extension =
+            !self.config.include_binary && !compressed && is_likely_binary(path);
+        // Checked before replacing, as the line endings may be normalized when replacing
+        let mixed_line_endings = self.config.warn_mixed_endings
+            && !has_binary_extension
+            && !compressed
+            && has_mixed_line_endings(self.config.store(), path);
+        let result = if has_binary_extension {
+            Err(replace::BinaryFileError.into())
+        } else if compressed {
+            replace::replace_all_in_gzip_file(path, self.exprs(), &self.config)
+                .map(|replaced| (replaced.num_replacements, None, replaced.warnings))
+        } else if let Err(e) = self.search_within_timeout(path) {
+            Err(e)
+        } else if self.search_config.multiline {
+            replace::replace_all_in_file_multiline(path, self.exprs(), &self.config)
+                .map(|replaced| (replaced.num_replacements, None, replaced.warnings))
+        } else if self.replaces_line_by_line() {
+            self.replace_line_by_line(path, remaining)
+                .map(|(file_replacements, warnings)| {
+                    let num_replacements = file_replacements
+                        .as_ref()
+                        .map_or(0, |file_replacements| file_replacements.num_replacements);
+                    (num_replacements, file_replacements, warnings)
+                })
+        } else {
+            replace::replace_all_in_file(path, self.exprs(), &self.config)
+                .map(|replaced| (replaced.num_replacements, None, replaced.warnings))
+        };
+        let mut warnings = vec![];
+        let result = match result {
+            Ok((num_replacements, file_replacements, file_warnings)) => {
+                warnings = file_warnings;
+                FileReplaceResult::Replaced {
+                    num_replacements,
+                    file_replacements,
+                }
+            }
+            Err(e) if e.is::<replace::BinaryFileError>() => self.binary_file_result(path),
+            Err(e) if e.is::<TimeoutError>() => {
+                log::error!("Aborting after timeout in {}: {e}", path.display());
+                timed_out.store(true, Ordering::Relaxed);
+                FileReplaceResult::Error(format!("{e:#}"))
+            }
+            Err(e) if is_permission_denied(&e) => {
+                log::warn!("Skipped {}: {e:#}", path.display());
+                FileReplaceResult::PermissionDenied(format!("{e:#}"))
+            }
+            Err(e) => {
+                log::error!(
+                    "Found error when performing replacement in {path_display}: {e}",
+                    path_display = path.display()
+                );
+                FileReplaceResult::Error(format!("{e:#}"))
+            }
+        };
+        // Only files with replacements are reported
+        let mixed_line_endings = mixed_line_endings
+            && matches!(result, FileReplaceResult::Replaced { num_replacements, .. } if num_replacements > 0);
+        FileReplaceOutcome {
+            path: path.to_path_buf(),
+            result,
+            mixed_line_endings,
+            warnings,
+        }
+    }
 
-        let num_files_replaced_in = std::sync::Arc::new(AtomicUsize::new(0));
+    /// Computes the changes that would be made to each file as structured hunks, without modifying any files,
+    /// such as for an editor integration to apply the edits itself. This performs a dry run (see
+    /// `walk_files_and_replace`), with the same context lines around each change as a diff. Files and
+    /// directories are not renamed, and files whose hunks can't be computed (such as because they were deleted
+    /// during the run) are logged and omitted.
+    ///
+    /// Returns the changes to each file with replacements, sorted by path.
+    pub fn compute_changes(&self) -> Vec<diff::FileChange> {
+        let searcher = FileSearcher {
+            config: FileSearcherConfig {
+                dry_run: true,
+                diff: false,
+                before_context: None,
+                after_context: None,
+                rename: false,
+                ..self.config.clone()
+            },
+            ..self.clone()
+        };
+        let context_lines = self.diff_context_lines();
+
+        searcher
+            .walk_files_and_replace(None, None)
+            .files
+            .into_iter()
+            .filter_map(|file| {
+                match diff::hunks(
+                    self.config.store(),
+                    &file.path,
+                    &file.replacements,
+                    context_lines,
+                    self.config.delete_empty_lines,
+                ) {
+                    Ok(hunks) => Some(diff::FileChange {
+                        path: file.path,
+                        hunks,
+                    }),
+                    Err(e) => {
+                        log::error!(
+                            "Failed to compute changes for {path_display}: {e}",
+                            path_display = file.path.display()
+                        );
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
 
-        let walker = self.build_walker();
-        walker.run(|| {
-            let counter = num_files_replaced_in.clone();
+    /// Applies the search patterns to the name of each file and directory in the configured directory, renaming
+    /// those whose names change (unless performing a dry run). Entries are renamed deepest first, so that
+    /// renaming a directory doesn't change the paths of the entries within it that are yet to be renamed. An
+    /// entry isn't renamed if its new path already exists, and any errors are added to `errors`.
+    ///
+    /// Returns the new name of each renamed entry keyed by its original path, along with the original and new
+    /// path of each renamed entry sorted by original path. New paths include the new names of any renamed
+    /// parent directories.
+    fn rename_paths(
+        &self,
+        cancelled: Option<&AtomicBool>,
+        errors: &mut Vec<(PathBuf, String)>,
+    ) -> (HashMap<PathBuf, String>, Vec<(PathBuf, PathBuf)>) {
+        let candidates = std::sync::Arc::new(Mutex::new(Vec::new()));
+
+        self.walk(|| {
+            let candidates = candidates.clone();
 
             Box::new(move |result| {
                 if let Some(cancelled) = cancelled
@@ -273,27 +1668,497 @@ impl FileSearcher {
                     return WalkState::Continue;
                 };
 
-                if is_searchable(&entry) {
-                    match replace::replace_all_in_file(entry.path(), self.search(), self.replace())
-                    {
-                        Ok(replaced_in_file) => {
-                            if replaced_in_file {
-                                counter.fetch_add(1, Ordering::Relaxed);
+                let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+                if entry.depth() == 0
+                    || (!is_dir && !self.has_included_extension(entry.path()))
+                    || self.is_backup_file(entry.path())
+                {
+                    return WalkState::Continue;
+                }
+                let new_name = entry.file_name().to_str().and_then(|name| {
+                    replace::replace_all_patterns(
+                        name,
+                        self.exprs(),
+                        self.config.case_transforms,
+                        self.config.occurrence,
+                    )
+                });
+                if let Some(new_name) = new_name {
+                    candidates
+                        .lock()
+                        .unwrap()
+                        .push((entry.into_path(), new_name));
+                }
+                WalkState::Continue
+            })
+        });
+
+        let mut candidates = std::mem::take(&mut *candidates.lock().unwrap());
+        candidates.sort_by(|(a, _), (b, _)| {
+            b.components()
+                .count()
+                .cmp(&a.components().count())
+                .then_with(|| a.cmp(b))
+        });
+
+        let mut new_names = HashMap::new();
+        let mut new_paths = HashSet::new();
+        for (path, new_name) in candidates {
+            let new_path = path.with_file_name(&new_name);
+            let result = if new_name.is_empty() || new_name.contains(std::path::is_separator) {
+                Err(format!("'{new_name}' is not a valid file name"))
+            } else if new_paths.contains(&new_path) || fs::symlink_metadata(&new_path).is_ok() {
+                Err(format!("{} already exists", new_path.display()))
+            } else if self.config.dry_run {
+                Ok(())
+            } else {
+                fs::rename(&path, &new_path).map_err(|e| e.to_string())
+            };
+
+            match result {
+                Ok(()) => {
+                    new_paths.insert(new_path);
+                    new_names.insert(path, new_name);
+                }
+                Err(e) => {
+                    log::error!(
+                        "Failed to rename {path_display}: {e}",
+                        path_display = path.display()
+                    );
+                    errors.push((path, format!("Failed to rename: {e}")));
+                }
+            }
+        }
+
+        let mut renamed = new_names
+            .keys()
+            .map(|path| (path.clone(), renamed_path(path, &new_names)))
+            .collect::<Vec<_>>();
+        renamed.sort();
+        (new_names, renamed)
+    }
+
+    /// Whether the path has one of the configured `extensions`, or `true` if these aren't set. This only
+    /// inspects the path, so is checked before anything else that requires reading the file's metadata
+    fn has_included_extension(&self, path: &Path) -> bool {
+        self.dir_config
+            .extensions
+            .as_ref()
+            .is_none_or(|extensions| {
+                path.extension()
+                    .is_some_and(|ext| extensions.iter().any(|e| ext == e.as_str()))
+            })
+    }
+
+    /// Searches the file on a separate thread, returning a `TimeoutError` if this takes longer than the
+    /// configured `timeout`, or the error if an advanced regex fails to match (see `try_count_matches`). The
+    /// search continues in the background after timing out, but as it only reads the file, it has no effect.
+    /// Does nothing if `timeout` isn't set
+    fn search_within_timeout(&self, path: &Path) -> anyhow::Result<()> {
+        let Some(timeout) = self.config.timeout else {
+            return Ok(());
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let path = path.to_path_buf();
+        let exprs = self.exprs().to_vec();
+        let multiline = self.search_config.multiline;
+        let file_store = self.config.file_store.clone();
+        thread::spawn(move || {
+            let store = file_store.as_deref().unwrap_or(&store::FILESYSTEM);
+            let match_error = OnceCell::new();
+            // Errors reading the file are reported when it is replaced, so are ignored here
+            if multiline {
+                if let Ok(bytes) = store.read(&path)
+                    && let Err(e) = try_count_matches(&String::from_utf8_lossy(&bytes), &exprs)
+                {
+                    let _ = match_error.set(e);
+                }
+            } else {
+                let count_line_matches = |line: &str| {
+                    // The rest of the file is still read once a match has failed, but isn't matched
+                    if match_error.get().is_some() {
+                        return 0;
+                    }
+                    try_count_matches(line, &exprs).unwrap_or_else(|e| {
+                        let _ = match_error.set(e);
+                        0
+                    })
+                };
+                let _ = search_file_matching(store, &path, count_line_matches, 0, 0);
+            }
+            let _ = sender.send(match_error.into_inner());
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(Some(match_error)) => Err(match_error),
+            Ok(None) | Err(mpsc::RecvTimeoutError::Disconnected) => Ok(()),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(TimeoutError {
+                timeout,
+                patterns: self
+                    .exprs()
+                    .iter()
+                    .map(|(search, _)| search.as_str().to_owned())
+                    .collect(),
+            }
+            .into()),
+        }
+    }
+
+    /// Whether the size of the file is within the configured `size_filter`, or `true` if this isn't set. Files
+    /// whose metadata can't be read are included, so that the error is reported when they are processed
+    fn has_size_in_range(&self, path: &Path) -> bool {
+        let Some((min_size, max_size)) = self.config.size_filter else {
+            return true;
+        };
+        fs::metadata(path).map_or(true, |metadata| {
+            let len = metadata.len();
+            min_size.is_none_or(|min_size| len >= min_size)
+                && max_size.is_none_or(|max_size| len <= max_size)
+        })
+    }
+
+    fn has_modified_in_range(&self, path: &Path) -> bool {
+        let Some((after, before)) = self.config.modified_filter else {
+            return true;
+        };
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_or(true, |modified| {
+                after.is_none_or(|after| modified >= after)
+                    && before.is_none_or(|before| modified < before)
+            })
+    }
+
+    /// The outcome for a file whose contents appear to be binary, which is an error if `error_on_binary` is set
+    fn binary_file_result(&self, path: &Path) -> FileReplaceResult {
+        if self.config.error_on_binary {
+            log::error!("Found binary file {}", path.display());
+            FileReplaceResult::Error("file appears to be binary".to_owned())
+        } else {
+            log::info!("Skipped binary file {}", path.display());
+            FileReplaceResult::BinarySkipped
+        }
+    }
+
+    /// Whether `path` is a gzip-compressed file to be decompressed before replacing, if `search_compressed` is
+    /// set. Files that can't be read are treated as uncompressed, so the error is reported when replacing
+    fn is_compressed(&self, path: &Path) -> bool {
+        self.config.search_compressed
+            && replace::is_gzip_file(self.config.store(), path).unwrap_or(false)
+    }
+
+    fn is_backup_file(&self, path: &Path) -> bool {
+        self.config.backup_suffix.as_ref().is_some_and(|suffix| {
+            path.as_os_str()
+                .to_string_lossy()
+                .ends_with(suffix.as_str())
+        })
+    }
+
+    /// The number of unchanged lines to show either side of each change in a diff. See
+    /// `FileSearcherConfig::before_context`
+    fn diff_context_lines(&self) -> usize {
+        match (self.config.before_context, self.config.after_context) {
+            (None, None) => diff::DEFAULT_CONTEXT_LINES,
+            (before, after) => before.max(after).unwrap_or_default(),
+        }
+    }
+
+    /// The number of matches that `walk_files_and_replace` would replace across all files, counted with a dry
+    /// run so that no files are modified. Not supported in multiline mode, where dry runs aren't supported
+    pub fn count_replacements(&self) -> usize {
+        let searcher = FileSearcher {
+            config: FileSearcherConfig {
+                dry_run: true,
+                diff: false,
+                collect_line_results: false,
+                before_context: None,
+                after_context: None,
+                rename: false,
+                ..self.config.clone()
+            },
+            ..self.clone()
+        };
+        searcher
+            .walk_files_and_replace(None, None)
+            .replacement_counts
+            .iter()
+            .map(|(_, num_replacements)| num_replacements)
+            .sum()
+    }
+
+    /// Whether line-level results are needed, rather than replacing the whole file at once
+    fn replaces_line_by_line(&self) -> bool {
+        self.config.dry_run
+            || self.config.diff
+            || self.config.collect_line_results
+            || self.config.max_count.is_some()
+            || self.config.max_per_file.is_some()
+            || self.config.delete_empty_lines
+            || self.config.collapse_duplicate_lines
+            || self.config.preserve_indent
+    }
+
+    /// Searches a file for the lines to replace, along with up to `before` lines preceding and `after` lines
+    /// following each. See `search_file_with_context_for_exprs`, although lines excluded by
+    /// `only_matching_lines` or `skip_lines` aren't matched
+    fn search_file(
+        &self,
+        path: &Path,
+        before: usize,
+        after: usize,
+    ) -> anyhow::Result<Vec<SearchResultWithContext>> {
+        if self.exprs().is_empty() {
+            return Ok(vec![]);
+        }
+        search_file_matching(
+            self.config.store(),
+            path,
+            |line| {
+                if self.config.replaces_in_line(line) {
+                    count_matches(line, self.exprs())
+                } else {
+                    0
+                }
+            },
+            before,
+            after,
+        )
+    }
+
+    /// Finds the replacements to be made in a file line by line, generating a diff if configured to do so.
+    /// Unless performing a dry run, the replacements are then written to the file.
+    ///
+    /// At most `max_per_file` matches are replaced, if set. If `remaining` is set, at most that many matches
+    /// are replaced, and `remaining` is reduced by the number of matches replaced.
+    ///
+    /// Returns `None` if there are no replacements to be made, along with any warnings from writing the file.
+    fn replace_line_by_line(
+        &self,
+        path: &Path,
+        remaining: Option<&AtomicUsize>,
+    ) -> anyhow::Result<(Option<FileReplacements>, Vec<replace::ReplaceWarning>)> {
+        replace::check_not_binary(path, &self.config)?;
+        replace::check_not_utf16(path, &self.config)?;
+
+        let (before, after) = (self.config.before_context, self.config.after_context);
+        let mut num_over_limit = 0;
+        // Returns the number of matching lines to replace, given the number of matches in each
+        let mut truncate_to_limits = |match_counts: &[usize]| {
+            let mut limit = match_counts.len();
+            if let Some(max_per_file) = self.config.max_per_file {
+                limit = lines_within(match_counts, max_per_file);
+                num_over_limit = match_counts[limit..].iter().sum();
+            }
+            if self.config.first_only {
+                limit = limit.min(1);
+            }
+            if let Some(remaining) = remaining {
+                limit = take_lines_up_to(remaining, &match_counts[..limit]);
+            }
+            limit
+        };
+        let (search_results, context) =
+            if self.config.dry_run && !self.config.diff && (before.is_some() || after.is_some()) {
+                let mut results =
+                    self.search_file(path, before.unwrap_or(0), after.unwrap_or(0))?;
+                let match_counts = results
+                    .iter()
+                    .map(|r| self.config.num_replacements_in(r.search_result.match_count))
+                    .collect::<Vec<_>>();
+                results.truncate(truncate_to_limits(&match_counts));
+                let context = if self.config.color {
+                    format_context_highlighted(&results, self.exprs())
+                } else {
+                    format_context(&results)
+                };
+                let results = results.into_iter().map(|r| r.search_result).collect();
+                (results, Some(context))
+            } else {
+                let mut results = self
+                    .search_file(path, 0, 0)?
+                    .into_iter()
+                    .map(|r| r.search_result)
+                    .collect::<Vec<_>>();
+                let match_counts = results
+                    .iter()
+                    .map(|r| self.config.num_replacements_in(r.match_count))
+                    .collect::<Vec<_>>();
+                results.truncate(truncate_to_limits(&match_counts));
+                (results, None)
+            };
+
+        let mut replacements = self.replacements_for(path, search_results)?;
+        if replacements.is_empty() {
+            return Ok((None, vec![]));
+        }
+        if self.config.preserve_indent {
+            for res in &mut replacements {
+                res.replacement = replace::with_preserved_indent(
+                    &res.search_result.line,
+                    std::mem::take(&mut res.replacement),
+                );
+            }
+        }
+
+        let diff = if self.config.diff {
+            let hunks = diff::hunks(
+                self.config.store(),
+                path,
+                &replacements,
+                self.diff_context_lines(),
+                self.config.delete_empty_lines,
+            )?;
+            let diff = diff::format_unified_diff(path, &hunks);
+            Some(if self.config.color {
+                diff::colorize(&diff)
+            } else {
+                diff
+            })
+        } else {
+            None
+        };
+        let warnings = if self.config.dry_run {
+            vec![]
+        } else {
+            replace::replace_in_file(&mut replacements, &self.config, |_| true)?
+        };
+
+        let num_replacements = replacements
+            .iter()
+            .map(|res| {
+                self.config
+                    .num_replacements_in(res.search_result.match_count)
+            })
+            .sum();
+        let file_replacements = FileReplacements {
+            path: path.to_path_buf(),
+            replacements,
+            num_replacements,
+            diff,
+            context,
+            num_over_limit,
+        };
+        Ok((Some(file_replacements), warnings))
+    }
+
+    /// The replacements for the matching lines in `search_results` from the file at `path`, in line order. This
+    /// includes the lines to remove as duplicates when `collapse_duplicate_lines` is set, and excludes lines
+    /// left without a replacement, such as those with too few matches when `occurrence` is set
+    fn replacements_for(
+        &self,
+        path: &Path,
+        search_results: Vec<SearchResult>,
+    ) -> anyhow::Result<Vec<SearchResultWithReplacement>> {
+        // With `template` set, each line has its own replacements, with tokens such as `{lineno}` expanded
+        let line_exprs = |r: &SearchResult| {
+            if self.config.template {
+                Cow::Owned(replace::expand_template(self.exprs(), path, r.line_number))
+            } else {
+                Cow::Borrowed(self.exprs())
+            }
+        };
+        let string_trackers = match Language::from_path(path) {
+            Some(language) if self.config.skip_in_strings => Some(StringTracker::for_each_line(
+                language,
+                BufReader::new(self.config.store().open(path)?),
+            )?),
+            _ => None,
+        };
+        let duplicate_lines = if self.config.collapse_duplicate_lines {
+            duplicate_lines(&search_results)
+        } else {
+            vec![]
+        };
+        let mut replacements = if let Some(command) = &self.config.replace_command {
+            search_results
+                .into_iter()
+                .filter_map(|r| {
+                    replace::add_replacement_with_command(r, self.exprs(), command).transpose()
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+        } else {
+            search_results
+                .into_iter()
+                .filter_map(|r| {
+                    if let Some(string_trackers) = &string_trackers {
+                        let string_tracker = string_trackers.get(r.line_number - 1)?;
+                        let exprs = line_exprs(&r);
+                        replace::add_replacement_outside_strings(
+                            r,
+                            &exprs,
+                            self.config.case_transforms,
+                            self.config.occurrence,
+                            string_tracker,
+                        )
+                    } else {
+                        let exprs = line_exprs(&r);
+                        match &self.config.replacement_functions {
+                            Some(functions) => {
+                                replace::add_replacement_with_functions(r, &exprs, functions)
                             }
-                        }
-                        Err(e) => {
-                            log::error!(
-                                "Found error when performing replacement in {path_display}: {e}",
-                                path_display = entry.path().display()
-                            );
+                            None => replace::add_replacement(
+                                r,
+                                &exprs,
+                                self.config.case_transforms,
+                                self.config.occurrence,
+                            ),
                         }
                     }
-                }
-                WalkState::Continue
-            })
-        });
+                })
+                .collect::<Vec<_>>()
+        };
+        // Duplicate lines are removed even if the replacement leaves them unchanged
+        for duplicate in duplicate_lines {
+            if let Err(idx) = replacements
+                .binary_search_by_key(&duplicate.search_result.line_number, |res| {
+                    res.search_result.line_number
+                })
+            {
+                replacements.insert(idx, duplicate);
+            }
+        }
+        Ok(replacements)
+    }
+}
+
+/// Replacements leaving each line in `search_results` unchanged that is identical to the matching line before
+/// it, so that it's removed by `replace::replace_in_file` when `collapse_duplicate_lines` is set
+fn duplicate_lines(search_results: &[SearchResult]) -> Vec<SearchResultWithReplacement> {
+    search_results
+        .windows(2)
+        .filter(|pair| {
+            pair[1].line_number == pair[0].line_number + 1 && pair[1].line == pair[0].line
+        })
+        .map(|pair| SearchResultWithReplacement {
+            search_result: pair[1].clone(),
+            replacement: pair[1].line.clone(),
+            replace_result: None,
+        })
+        .collect()
+}
+
+/// The number of leading lines, with `match_counts` matches in each, whose matches total at most `max_matches`
+fn lines_within(match_counts: &[usize], max_matches: usize) -> usize {
+    match_counts
+        .iter()
+        .scan(0, |total, &count| {
+            *total += count;
+            Some(*total)
+        })
+        .take_while(|&total| total <= max_matches)
+        .count()
+}
 
-        num_files_replaced_in.load(Ordering::Relaxed)
+/// Atomically subtracts the matches in the leading lines of `match_counts` that fit within `remaining` from
+/// it, returning the number of lines taken (see `lines_within`)
+fn take_lines_up_to(remaining: &AtomicUsize, match_counts: &[usize]) -> usize {
+    let taken = |r: usize| lines_within(match_counts, r);
+    match remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |r| {
+        Some(r - match_counts[..taken(r)].iter().sum::<usize>())
+    }) {
+        Ok(previous) | Err(previous) => taken(previous),
     }
 }
 
@@ -312,6 +2177,97 @@ fn is_likely_binary(path: &Path) -> bool {
         })
 }
 
+/// Whether the file at `path` uses more than one style of line ending. Files that can't be read are treated as
+/// not having mixed line endings, as the error is reported when replacing
+fn has_mixed_line_endings(store: &dyn FileStore, path: &Path) -> bool {
+    match store
+        .open(path)
+        .and_then(|file| line_reader::count_line_endings(BufReader::new(file)))
+    {
+        Ok(counts) => counts.is_mixed(),
+        Err(e) => {
+            log::warn!(
+                "Failed to check the line endings of {}: {e}",
+                path.display()
+            );
+            false
+        }
+    }
+}
+
+/// Whether `e` was caused by permission to read or write a file being denied
+fn is_permission_denied(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+    })
+}
+
+/// Sorts `entries` in `order`, breaking ties by path. Entries whose metadata can't be read sort first
+fn sort_entries(entries: &mut [ignore::DirEntry], order: FileOrder) {
+    match order {
+        FileOrder::Path => entries.sort_by(|a, b| a.path().cmp(b.path())),
+        FileOrder::Size => entries.sort_by_cached_key(|entry| {
+            (
+                fs::metadata(entry.path())
+                    .ok()
+                    .map(|metadata| metadata.len()),
+                entry.path().to_path_buf(),
+            )
+        }),
+        FileOrder::Modified => entries.sort_by_cached_key(|entry| {
+            (
+                fs::metadata(entry.path())
+                    .and_then(|metadata| metadata.modified())
+                    .ok(),
+                entry.path().to_path_buf(),
+            )
+        }),
+    }
+}
+
+/// The path of the file or directory that caused an error when walking, if known
+fn walk_error_path(e: &ignore::Error) -> Option<&Path> {
+    match e {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } => walk_error_path(err),
+        _ => None,
+    }
+}
+
+/// The path that `path` has after each of its components in `new_names` (keyed by the original path up to and
+/// including that component) has been renamed
+fn renamed_path(path: &Path, new_names: &HashMap<PathBuf, String>) -> PathBuf {
+    let mut original = PathBuf::new();
+    let mut renamed = PathBuf::new();
+    for component in path.components() {
+        original.push(component);
+        match new_names.get(&original) {
+            Some(new_name) => renamed.push(new_name),
+            None => renamed.push(component),
+        }
+    }
+    renamed
+}
+
+/// The number of bytes at the start of a file that are inspected to determine whether it is binary
+const BINARY_PROBE_SIZE: usize = 8192;
+
+/// Whether the contents of a file in `store` appear to be binary, based on whether there are NUL bytes near
+/// the start of the file. Files starting with a UTF-16 byte order mark are not considered binary
+pub fn is_binary_file(store: &dyn FileStore, path: &Path) -> io::Result<bool> {
+    let mut probe = [0u8; BINARY_PROBE_SIZE];
+    let read = store.open(path)?.read(&mut probe)?;
+    Ok(is_binary(&probe[..read]))
+}
+
+/// Whether `contents` appear to be binary, judging by the start of the contents as with `is_binary_file`
+pub fn is_binary(contents: &[u8]) -> bool {
+    let probe = &contents[..contents.len().min(BINARY_PROBE_SIZE)];
+    matches!(inspect(probe), ContentType::BINARY)
+}
+
 fn is_searchable(entry: &ignore::DirEntry) -> bool {
     entry.file_type().is_some_and(|ft| ft.is_file()) && !is_likely_binary(entry.path())
 }
@@ -324,22 +2280,129 @@ pub fn contains_search(line: &str, search: &SearchType) -> bool {
     }
 }
 
+/// Whether the line contains any of the search patterns in `exprs`
+pub fn contains_any_search(line: &str, exprs: &[(SearchType, String)]) -> bool {
+    exprs
+        .iter()
+        .any(|(search, _)| contains_search(line, search))
+}
+
+/// The total number of non-overlapping matches of each of the search patterns in `exprs` within `text`
+pub fn count_matches(text: &str, exprs: &[(SearchType, String)]) -> usize {
+    exprs
+        .iter()
+        .map(|(search, _)| count_search_matches(text, search))
+        .sum()
+}
+
+/// Like `count_matches`, but returns an error if an advanced regex fails to match, such as by exceeding its
+/// backtracking limit, rather than skipping the failed match
+fn try_count_matches(text: &str, exprs: &[(SearchType, String)]) -> anyhow::Result<usize> {
+    exprs
+        .iter()
+        .map(|(search, _)| match search {
+            SearchType::PatternAdvanced(pattern) => pattern
+                .find_iter(text)
+                .try_fold(0, |count, found| {
+                    found.map(|_| count + 1).map_err(anyhow::Error::from)
+                })
+                .with_context(|| format!("Failed to match `{}`", search.as_str())),
+            search => Ok(count_search_matches(text, search)),
+        })
+        .sum()
+}
+
+/// The number of non-overlapping matches of `search` within `text`
+pub fn count_search_matches(text: &str, search: &SearchType) -> usize {
+    match search {
+        SearchType::Fixed(fixed_str) => text.matches(fixed_str.as_str()).count(),
+        SearchType::Pattern(pattern) => pattern.find_iter(text).count(),
+        SearchType::PatternAdvanced(pattern) => pattern.find_iter(text).flatten().count(),
+    }
+}
+
 pub fn search_file(path: &Path, search: &SearchType) -> anyhow::Result<Vec<SearchResult>> {
+    Ok(search_file_with_context(path, search, 0, 0)?
+        .into_iter()
+        .map(|r| r.search_result)
+        .collect())
+}
+
+/// Like `search_file`, but finds the lines matching any of the search patterns in `exprs`
+pub fn search_file_for_exprs(
+    path: &Path,
+    exprs: &[(SearchType, String)],
+) -> anyhow::Result<Vec<SearchResult>> {
+    Ok(search_file_with_context_for_exprs(path, exprs, 0, 0)?
+        .into_iter()
+        .map(|r| r.search_result)
+        .collect())
+}
+
+/// Searches a file, retaining up to `before` lines preceding and `after` lines following each match.
+///
+/// Matching lines are never included as context, so the context of adjacent matches may be shorter than
+/// requested. Context lines may be shared between the results for nearby matches: use `format_context`
+/// to display them with overlapping regions merged.
+pub fn search_file_with_context(
+    path: &Path,
+    search: &SearchType,
+    before: usize,
+    after: usize,
+) -> anyhow::Result<Vec<SearchResultWithContext>> {
     if search.is_empty() {
         return Ok(vec![]);
     }
-    let mut file = File::open(path)?;
+    search_file_matching(
+        &store::FILESYSTEM,
+        path,
+        |line| count_search_matches(line, search),
+        before,
+        after,
+    )
+}
+
+/// Like `search_file_with_context`, but finds the lines matching any of the search patterns in `exprs`
+pub fn search_file_with_context_for_exprs(
+    path: &Path,
+    exprs: &[(SearchType, String)],
+    before: usize,
+    after: usize,
+) -> anyhow::Result<Vec<SearchResultWithContext>> {
+    if exprs.iter().all(|(search, _)| search.is_empty()) {
+        return Ok(vec![]);
+    }
+    search_file_matching(
+        &store::FILESYSTEM,
+        path,
+        |line| count_matches(line, exprs),
+        before,
+        after,
+    )
+}
+
+/// Searches a file in `store` line by line, using `count_matches` to find the number of matches within each
+/// line
+fn search_file_matching(
+    store: &dyn FileStore,
+    path: &Path,
+    count_matches: impl Fn(&str) -> usize,
+    before: usize,
+    after: usize,
+) -> anyhow::Result<Vec<SearchResultWithContext>> {
+    let mut file = store.open(path)?;
 
     // Fast upfront binary sniff (8 KiB)
-    let mut probe = [0u8; 8192];
+    let mut probe = [0u8; BINARY_PROBE_SIZE];
     let read = file.read(&mut probe).unwrap_or(0);
     if matches!(inspect(&probe[..read]), ContentType::BINARY) {
         return Ok(Vec::new());
     }
-    file.seek(SeekFrom::Start(0))?;
 
-    let reader = BufReader::with_capacity(16384, file);
-    let mut results = Vec::new();
+    let reader = BufReader::with_capacity(16384, (&probe[..read]).chain(file));
+    let mut results: Vec<SearchResultWithContext> = Vec::new();
+    let mut context_before = VecDeque::with_capacity(before);
+    let mut remaining_after = 0;
 
     let mut read_errors = 0;
 
@@ -364,23 +2427,164 @@ pub fn search_file(path: &Path, search: &SearchType) -> anyhow::Result<Vec<Searc
             }
         };
 
-        if let Ok(line) = String::from_utf8(line_bytes)
-            && contains_search(&line, search)
-        {
-            let result = SearchResult {
-                path: Some(path.to_path_buf()),
-                line_number,
-                line,
-                line_ending,
-                included: true,
-            };
-            results.push(result);
+        let line = String::from_utf8(line_bytes);
+        let match_count = line.as_deref().map_or(0, &count_matches);
+        match line {
+            Ok(line) if match_count > 0 => {
+                results.push(SearchResultWithContext {
+                    search_result: SearchResult {
+                        path: Some(path.to_path_buf()),
+                        line_number,
+                        line,
+                        line_ending,
+                        included: true,
+                        match_count,
+                    },
+                    context_before: context_before.drain(..).collect(),
+                    context_after: Vec::new(),
+                });
+                remaining_after = after;
+            }
+            line if before > 0 || remaining_after > 0 => {
+                let line = ContextLine {
+                    line_number,
+                    line: line
+                        .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()),
+                };
+                if remaining_after > 0
+                    && let Some(result) = results.last_mut()
+                {
+                    result.context_after.push(line.clone());
+                    remaining_after -= 1;
+                }
+                if before > 0 {
+                    if context_before.len() == before {
+                        context_before.pop_front();
+                    }
+                    context_before.push_back(line);
+                }
+            }
+            _ => {}
         }
     }
 
     Ok(results)
 }
 
+/// Formats search results along with their context lines in the style of `grep`, i.e. `path:line_number:line`
+/// for matching lines and `path-line_number-line` for context lines. Context shared between adjacent results
+/// is only displayed once, and non-contiguous groups of lines are separated by `--`.
+pub fn format_context(results: &[SearchResultWithContext]) -> String {
+    write_context(results, None)
+}
+
+/// Like `format_context`, but with file paths in bold and the matches of the search patterns in `exprs` within
+/// matching lines in red
+pub fn format_context_highlighted(
+    results: &[SearchResultWithContext],
+    exprs: &[(SearchType, String)],
+) -> String {
+    write_context(results, Some(exprs))
+}
+
+fn write_context(
+    results: &[SearchResultWithContext],
+    highlight: Option<&[(SearchType, String)]>,
+) -> String {
+    let mut formatted = String::new();
+    let mut last_line_number = None;
+    for result in results {
+        let path = result.search_result.path.clone().unwrap_or_default();
+        let lines = result
+            .context_before
+            .iter()
+            .map(|l| (l.line_number, l.line.as_str(), '-'))
+            .chain(std::iter::once((
+                result.search_result.line_number,
+                result.search_result.line.as_str(),
+                ':',
+            )))
+            .chain(
+                result
+                    .context_after
+                    .iter()
+                    .map(|l| (l.line_number, l.line.as_str(), '-')),
+            );
+        for (line_number, line, separator) in lines {
+            match last_line_number {
+                Some(last) if line_number <= last => continue,
+                Some(last) if line_number > last + 1 => formatted.push_str("--\n"),
+                _ => {}
+            }
+            match highlight {
+                Some(exprs) => {
+                    let line = if separator == ':' {
+                        highlight_matches(line, exprs)
+                    } else {
+                        line.to_owned()
+                    };
+                    let _ = writeln!(
+                        formatted,
+                        "{path}{separator}{line_number}{separator}{line}",
+                        path = path.display().to_string().bold()
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        formatted,
+                        "{path}{separator}{line_number}{separator}{line}",
+                        path = path.display()
+                    );
+                }
+            }
+            last_line_number = Some(line_number);
+        }
+    }
+    formatted
+}
+
+/// Colours each match of the search patterns in `exprs` within `line` red. Overlapping matches are merged
+fn highlight_matches(line: &str, exprs: &[(SearchType, String)]) -> String {
+    let mut ranges = exprs
+        .iter()
+        .flat_map(|(search, _)| match_ranges(line, search))
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut highlighted = String::with_capacity(line.len());
+    let mut end = 0;
+    for range in ranges {
+        let start = range.start.max(end);
+        if start >= range.end {
+            continue;
+        }
+        highlighted.push_str(&line[end..start]);
+        let _ = write!(highlighted, "{}", line[start..range.end].red());
+        end = range.end;
+    }
+    highlighted.push_str(&line[end..]);
+    highlighted
+}
+
+/// The byte ranges of each non-overlapping match of `search` in `line`
+pub(crate) fn match_ranges(line: &str, search: &SearchType) -> Vec<Range<usize>> {
+    if search.is_empty() {
+        return vec![];
+    }
+    match search {
+        SearchType::Fixed(fixed_str) => line
+            .match_indices(fixed_str.as_str())
+            .map(|(start, matched)| start..start + matched.len())
+            .collect(),
+        SearchType::Pattern(pattern) => pattern.find_iter(line).map(|m| m.range()).collect(),
+        SearchType::PatternAdvanced(pattern) => pattern
+            .find_iter(line)
+            .filter_map(Result::ok)
+            .map(|m| m.start()..m.end())
+            .collect(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -400,6 +2604,7 @@ mod tests {
                     line: "test line".to_string(),
                     line_ending: LineEnding::Lf,
                     included: true,
+                    match_count: 1,
                 },
                 replacement: "replacement".to_string(),
                 replace_result,
@@ -888,5 +3093,683 @@ mod tests {
             assert!(is_likely_binary(Path::new(".hidden.png")));
             assert!(!is_likely_binary(Path::new(".hidden.txt")));
         }
+
+        #[test]
+        fn test_single_thread_walks_in_order() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            for path in ["b.txt", "a.txt", "sub/c.txt"] {
+                let path = temp_dir.path().join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, "needle\n").unwrap();
+            }
+
+            let searcher = FileSearcher::with_config(
+                ParsedSearchConfig {
+                    search: test_helpers::create_fixed_search("needle"),
+                    replace: "pin".to_string(),
+                    multiline: false,
+                    additional_exprs: vec![],
+                },
+                ParsedDirConfig {
+                    overrides: Override::empty(),
+                    override_globs: vec![],
+                    root_dirs: vec![temp_dir.path().to_path_buf()],
+                    include_hidden: false,
+                    follow_symlinks: false,
+                    max_depth: None,
+                    respect_ignore: true,
+                    custom_ignore: Gitignore::empty(),
+                    extensions: None,
+                },
+                FileSearcherConfig {
+                    threads: NonZero::new(1),
+                    ..FileSearcherConfig::default()
+                },
+            );
+
+            let visited = std::sync::Arc::new(Mutex::new(Vec::new()));
+            searcher.walk_files(None, || {
+                let visited = visited.clone();
+                Box::new(move |results| {
+                    visited
+                        .lock()
+                        .unwrap()
+                        .push(results[0].path.clone().unwrap());
+                    WalkState::Continue
+                })
+            });
+            assert_eq!(
+                *visited.lock().unwrap(),
+                vec![
+                    temp_dir.path().join("a.txt"),
+                    temp_dir.path().join("b.txt"),
+                    temp_dir.path().join("sub/c.txt"),
+                ]
+            );
+
+            let result = searcher.walk_files_and_replace(None, None);
+            assert_eq!(result.num_files_replaced, 3);
+            assert_eq!(
+                std::fs::read_to_string(temp_dir.path().join("sub/c.txt")).unwrap(),
+                "pin\n"
+            );
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_reports_progress() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            std::fs::write(temp_dir.path().join("a.txt"), "needle\nneedle\nhay\n").unwrap();
+            std::fs::write(temp_dir.path().join("b.txt"), "hay\n").unwrap();
+
+            let searcher = FileSearcher::with_config(
+                ParsedSearchConfig {
+                    search: test_helpers::create_fixed_search("needle"),
+                    replace: "pin".to_string(),
+                    multiline: false,
+                    additional_exprs: vec![],
+                },
+                ParsedDirConfig {
+                    overrides: Override::empty(),
+                    override_globs: vec![],
+                    root_dirs: vec![temp_dir.path().to_path_buf()],
+                    include_hidden: false,
+                    follow_symlinks: false,
+                    max_depth: None,
+                    respect_ignore: true,
+                    custom_ignore: Gitignore::empty(),
+                    extensions: None,
+                },
+                FileSearcherConfig {
+                    threads: NonZero::new(1),
+                    ..FileSearcherConfig::default()
+                },
+            );
+
+            let mut processed = vec![];
+            let result = searcher.walk_files_and_replace(
+                None,
+                Some(&mut |path: &Path, num_replacements| {
+                    processed.push((path.to_path_buf(), num_replacements));
+                }),
+            );
+            assert_eq!(result.num_files_replaced, 1);
+            assert_eq!(
+                processed,
+                vec![
+                    (temp_dir.path().join("a.txt"), 2),
+                    (temp_dir.path().join("b.txt"), 0),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_max_count() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            for (name, content) in [
+                ("a.txt", "needle 1\nneedle 2\n"),
+                ("b.txt", "needle 1 needle\nhay\nneedle 2\n"),
+                ("c.txt", "needle 1\n"),
+            ] {
+                std::fs::write(temp_dir.path().join(name), content).unwrap();
+            }
+
+            let searcher = |max_count| {
+                FileSearcher::with_config(
+                    ParsedSearchConfig {
+                        search: test_helpers::create_fixed_search("needle"),
+                        replace: "pin".to_string(),
+                        multiline: false,
+                        additional_exprs: vec![],
+                    },
+                    ParsedDirConfig {
+                        overrides: Override::empty(),
+                        override_globs: vec![],
+                        root_dirs: vec![temp_dir.path().to_path_buf()],
+                        include_hidden: false,
+                        follow_symlinks: false,
+                        max_depth: None,
+                        respect_ignore: true,
+                        custom_ignore: Gitignore::empty(),
+                        extensions: None,
+                    },
+                    FileSearcherConfig {
+                        threads: NonZero::new(1),
+                        max_count: Some(max_count),
+                        ..FileSearcherConfig::default()
+                    },
+                )
+            };
+
+            // A line whose matches would exceed the limit is left unchanged
+            let result = searcher(3).walk_files_and_replace(None, None);
+            assert_eq!(result.num_files_replaced, 2);
+            assert_eq!(
+                result.replacement_counts,
+                vec![
+                    (temp_dir.path().join("a.txt"), 2),
+                    (temp_dir.path().join("c.txt"), 1),
+                ]
+            );
+            let read = |name| std::fs::read_to_string(temp_dir.path().join(name)).unwrap();
+            assert_eq!(read("a.txt"), "pin 1\npin 2\n");
+            assert_eq!(read("b.txt"), "needle 1 needle\nhay\nneedle 2\n");
+            assert_eq!(read("c.txt"), "pin 1\n");
+
+            // Each match counts towards the limit, rather than each line
+            let result = searcher(2).walk_files_and_replace(None, None);
+            assert_eq!(result.num_files_replaced, 1);
+            assert_eq!(read("b.txt"), "pin 1 pin\nhay\nneedle 2\n");
+            assert_eq!(read("c.txt"), "pin 1\n");
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_sorted() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            for (name, content) in [
+                ("a.txt", "needle 1\nneedle 2\n"),
+                ("b.txt", "needle 1\nhay\nneedle 2\n"),
+                ("c.txt", "needle 1\n"),
+            ] {
+                std::fs::write(temp_dir.path().join(name), content).unwrap();
+            }
+
+            let searcher = |sort| {
+                FileSearcher::with_config(
+                    ParsedSearchConfig {
+                        search: test_helpers::create_fixed_search("needle"),
+                        replace: "pin".to_string(),
+                        multiline: false,
+                        additional_exprs: vec![],
+                    },
+                    ParsedDirConfig {
+                        overrides: Override::empty(),
+                        override_globs: vec![],
+                        root_dirs: vec![temp_dir.path().to_path_buf()],
+                        include_hidden: false,
+                        follow_symlinks: false,
+                        max_depth: None,
+                        respect_ignore: true,
+                        custom_ignore: Gitignore::empty(),
+                        extensions: None,
+                    },
+                    FileSearcherConfig {
+                        dry_run: true,
+                        threads: NonZero::new(4),
+                        sort: Some(sort),
+                        max_count: Some(2),
+                        ..FileSearcherConfig::default()
+                    },
+                )
+            };
+
+            // Files are processed in order, even with multiple threads
+            let (sender, receiver) = mpsc::channel();
+            searcher(FileOrder::Path).walk_files_and_replace_streaming(None, &sender);
+            drop(sender);
+            assert_eq!(
+                receiver
+                    .iter()
+                    .map(|outcome| outcome.path)
+                    .collect::<Vec<_>>(),
+                vec![temp_dir.path().join("a.txt")]
+            );
+
+            // The smallest file is replaced first, so the limit is reached part way through the next smallest
+            let result = searcher(FileOrder::Size).walk_files_and_replace(None, None);
+            assert_eq!(
+                result
+                    .files
+                    .iter()
+                    .map(|file| (file.path.clone(), file.replacements.len()))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (temp_dir.path().join("a.txt"), 1),
+                    (temp_dir.path().join("c.txt"), 1),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_max_per_file() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            for (name, content) in [
+                ("a.txt", "needle 1\nneedle 2\nneedle 3\n"),
+                ("b.txt", "needle 1 needle\nhay\nneedle 2\n"),
+                ("c.txt", "needle 1\n"),
+            ] {
+                std::fs::write(temp_dir.path().join(name), content).unwrap();
+            }
+
+            let searcher = FileSearcher::with_config(
+                ParsedSearchConfig {
+                    search: test_helpers::create_fixed_search("needle"),
+                    replace: "pin".to_string(),
+                    multiline: false,
+                    additional_exprs: vec![],
+                },
+                ParsedDirConfig {
+                    overrides: Override::empty(),
+                    override_globs: vec![],
+                    root_dirs: vec![temp_dir.path().to_path_buf()],
+                    include_hidden: false,
+                    follow_symlinks: false,
+                    max_depth: None,
+                    respect_ignore: true,
+                    custom_ignore: Gitignore::empty(),
+                    extensions: None,
+                },
+                FileSearcherConfig {
+                    max_per_file: Some(2),
+                    ..FileSearcherConfig::default()
+                },
+            );
+
+            let result = searcher.walk_files_and_replace(None, None);
+            assert_eq!(result.num_files_replaced, 3);
+            assert_eq!(
+                result.replacement_counts,
+                vec![
+                    (temp_dir.path().join("a.txt"), 2),
+                    (temp_dir.path().join("b.txt"), 2),
+                    (temp_dir.path().join("c.txt"), 1),
+                ]
+            );
+            // Each match counts towards the limit, rather than each line
+            assert_eq!(
+                result.over_limit,
+                vec![
+                    (temp_dir.path().join("a.txt"), 1),
+                    (temp_dir.path().join("b.txt"), 1),
+                ]
+            );
+            let read = |name| std::fs::read_to_string(temp_dir.path().join(name)).unwrap();
+            assert_eq!(read("a.txt"), "pin 1\npin 2\nneedle 3\n");
+            assert_eq!(read("b.txt"), "pin 1 pin\nhay\nneedle 2\n");
+            assert_eq!(read("c.txt"), "pin 1\n");
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_streaming() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            for (name, content) in [
+                ("a.txt", "needle 1\nneedle 2\n"),
+                ("b.txt", "hay\n"),
+                ("c.dat", "needle\0"),
+                ("d.png", "needle\n"),
+            ] {
+                std::fs::write(temp_dir.path().join(name), content).unwrap();
+            }
+
+            let searcher = FileSearcher::with_config(
+                ParsedSearchConfig {
+                    search: test_helpers::create_fixed_search("needle"),
+                    replace: "pin".to_string(),
+                    multiline: false,
+                    additional_exprs: vec![],
+                },
+                ParsedDirConfig {
+                    overrides: Override::empty(),
+                    override_globs: vec![],
+                    root_dirs: vec![temp_dir.path().to_path_buf()],
+                    include_hidden: false,
+                    follow_symlinks: false,
+                    max_depth: None,
+                    respect_ignore: true,
+                    custom_ignore: Gitignore::empty(),
+                    extensions: None,
+                },
+                FileSearcherConfig {
+                    threads: NonZero::new(1),
+                    ..FileSearcherConfig::default()
+                },
+            );
+
+            let (sender, receiver) = mpsc::channel();
+            searcher.walk_files_and_replace_streaming(None, &sender);
+            drop(sender);
+            let outcomes = receiver.into_iter().collect::<Vec<_>>();
+            assert_eq!(
+                outcomes,
+                vec![
+                    FileReplaceOutcome {
+                        path: temp_dir.path().join("a.txt"),
+                        result: FileReplaceResult::Replaced {
+                            num_replacements: 2,
+                            file_replacements: None,
+                        },
+                        mixed_line_endings: false,
+                        warnings: vec![],
+                    },
+                    FileReplaceOutcome {
+                        path: temp_dir.path().join("b.txt"),
+                        result: FileReplaceResult::Replaced {
+                            num_replacements: 0,
+                            file_replacements: None,
+                        },
+                        mixed_line_endings: false,
+                        warnings: vec![],
+                    },
+                    FileReplaceOutcome {
+                        path: temp_dir.path().join("c.dat"),
+                        result: FileReplaceResult::BinarySkipped,
+                        mixed_line_endings: false,
+                        warnings: vec![],
+                    },
+                    FileReplaceOutcome {
+                        path: temp_dir.path().join("d.png"),
+                        result: FileReplaceResult::Filtered,
+                        mixed_line_endings: false,
+                        warnings: vec![],
+                    },
+                ]
+            );
+            assert_eq!(
+                std::fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+                "pin 1\npin 2\n"
+            );
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_error_on_binary() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            for (name, content) in [
+                ("a.txt", "needle\n"),
+                ("c.dat", "needle\0"),
+                ("d.png", "needle\n"),
+            ] {
+                std::fs::write(temp_dir.path().join(name), content).unwrap();
+            }
+
+            let searcher = FileSearcher::with_config(
+                ParsedSearchConfig {
+                    search: test_helpers::create_fixed_search("needle"),
+                    replace: "pin".to_string(),
+                    multiline: false,
+                    additional_exprs: vec![],
+                },
+                ParsedDirConfig {
+                    overrides: Override::empty(),
+                    override_globs: vec![],
+                    root_dirs: vec![temp_dir.path().to_path_buf()],
+                    include_hidden: false,
+                    follow_symlinks: false,
+                    max_depth: None,
+                    respect_ignore: true,
+                    custom_ignore: Gitignore::empty(),
+                    extensions: None,
+                },
+                FileSearcherConfig {
+                    error_on_binary: true,
+                    ..FileSearcherConfig::default()
+                },
+            );
+
+            // Files with binary contents or extensions are both reported
+            let result = searcher.walk_files_and_replace(None, None);
+            assert_eq!(result.num_binary_files_skipped, 0);
+            assert_eq!(
+                result.errors,
+                vec![
+                    (
+                        temp_dir.path().join("c.dat"),
+                        "file appears to be binary".to_owned()
+                    ),
+                    (
+                        temp_dir.path().join("d.png"),
+                        "file appears to be binary".to_owned()
+                    ),
+                ]
+            );
+            assert_eq!(
+                std::fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+                "pin\n"
+            );
+        }
+
+        #[test]
+        fn test_compute_changes() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            for (name, content) in [("a.txt", "needle 1\nhay\nneedle 2\n"), ("b.txt", "hay\n")] {
+                std::fs::write(temp_dir.path().join(name), content).unwrap();
+            }
+
+            let searcher = FileSearcher::with_config(
+                ParsedSearchConfig {
+                    search: test_helpers::create_fixed_search("needle"),
+                    replace: "pin".to_string(),
+                    multiline: false,
+                    additional_exprs: vec![],
+                },
+                ParsedDirConfig {
+                    overrides: Override::empty(),
+                    override_globs: vec![],
+                    root_dirs: vec![temp_dir.path().to_path_buf()],
+                    include_hidden: false,
+                    follow_symlinks: false,
+                    max_depth: None,
+                    respect_ignore: true,
+                    custom_ignore: Gitignore::empty(),
+                    extensions: None,
+                },
+                FileSearcherConfig {
+                    before_context: Some(0),
+                    ..FileSearcherConfig::default()
+                },
+            );
+
+            let changed = |original: &str, replacement: &str| diff::HunkLine::Changed {
+                original: original.to_string(),
+                replacement: Some(replacement.to_string()),
+                line_ending: LineEnding::Lf,
+            };
+            assert_eq!(
+                searcher.compute_changes(),
+                vec![diff::FileChange {
+                    path: temp_dir.path().join("a.txt"),
+                    hunks: vec![
+                        diff::Hunk {
+                            original_lines: 1..2,
+                            replaced_lines: 1..2,
+                            lines: vec![changed("needle 1", "pin 1")],
+                        },
+                        diff::Hunk {
+                            original_lines: 3..4,
+                            replaced_lines: 3..4,
+                            lines: vec![changed("needle 2", "pin 2")],
+                        },
+                    ],
+                }]
+            );
+            // No files are modified
+            assert_eq!(
+                std::fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+                "needle 1\nhay\nneedle 2\n"
+            );
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_timeout() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            // Large enough that searching can't finish within a zero timeout
+            let content = "needle\n".repeat(10_000);
+            for name in ["a.txt", "b.txt"] {
+                std::fs::write(temp_dir.path().join(name), &content).unwrap();
+            }
+
+            let searcher = |timeout| {
+                FileSearcher::with_config(
+                    ParsedSearchConfig {
+                        search: test_helpers::create_fixed_search("needle"),
+                        replace: "pin".to_string(),
+                        multiline: false,
+                        additional_exprs: vec![],
+                    },
+                    ParsedDirConfig {
+                        overrides: Override::empty(),
+                        override_globs: vec![],
+                        root_dirs: vec![temp_dir.path().to_path_buf()],
+                        include_hidden: false,
+                        follow_symlinks: false,
+                        max_depth: None,
+                        respect_ignore: true,
+                        custom_ignore: Gitignore::empty(),
+                        extensions: None,
+                    },
+                    FileSearcherConfig {
+                        threads: NonZero::new(1),
+                        timeout: Some(timeout),
+                        ..FileSearcherConfig::default()
+                    },
+                )
+            };
+            let read = |name| std::fs::read_to_string(temp_dir.path().join(name)).unwrap();
+
+            // No further files are processed after the first times out
+            let result = searcher(Duration::ZERO).walk_files_and_replace(None, None);
+            assert_eq!(result.num_files_replaced, 0);
+            assert_eq!(
+                result.errors,
+                vec![(
+                    temp_dir.path().join("a.txt"),
+                    "timed out after 0ms matching `needle`".to_owned()
+                )]
+            );
+            assert_eq!(read("a.txt"), content);
+            assert_eq!(read("b.txt"), content);
+
+            let result = searcher(Duration::from_secs(60)).walk_files_and_replace(None, None);
+            assert_eq!(result.num_files_replaced, 2);
+            assert!(result.errors.is_empty());
+            assert_eq!(read("a.txt"), "pin\n".repeat(10_000));
+        }
+
+        #[test]
+        fn test_walk_files_and_replace_timeout_match_error() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            let content = "the the cat\n";
+            std::fs::write(temp_dir.path().join("a.txt"), content).unwrap();
+
+            // Exceeding the backtracking limit fails the match, which is reported rather than skipped
+            let search = fancy_regex::RegexBuilder::new(r"(\w+) \1")
+                .backtrack_limit(1)
+                .build()
+                .unwrap();
+            let searcher = FileSearcher::with_config(
+                ParsedSearchConfig {
+                    search: SearchType::PatternAdvanced(search),
+                    replace: "$1".to_string(),
+                    multiline: false,
+                    additional_exprs: vec![],
+                },
+                ParsedDirConfig {
+                    overrides: Override::empty(),
+                    override_globs: vec![],
+                    root_dirs: vec![temp_dir.path().to_path_buf()],
+                    include_hidden: false,
+                    follow_symlinks: false,
+                    max_depth: None,
+                    respect_ignore: true,
+                    custom_ignore: Gitignore::empty(),
+                    extensions: None,
+                },
+                FileSearcherConfig {
+                    timeout: Some(Duration::from_secs(60)),
+                    ..FileSearcherConfig::default()
+                },
+            );
+            let result = searcher.walk_files_and_replace(None, None);
+            assert_eq!(result.num_files_replaced, 0);
+            assert_eq!(
+                result.errors,
+                vec![(
+                    temp_dir.path().join("a.txt"),
+                    r"Failed to match `(\w+) \1`: Error executing regex: Max limit for backtracking count exceeded"
+                        .to_owned()
+                )]
+            );
+            assert_eq!(
+                std::fs::read_to_string(temp_dir.path().join("a.txt")).unwrap(),
+                content
+            );
+        }
+    }
+
+    mod context_tests {
+        use super::*;
+
+        #[test]
+        fn test_search_file_with_context() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            let path = temp_dir.path().join("test.txt");
+            std::fs::write(&path, "1\n2\nfoo\n4\n5\n6\nfoo\n").unwrap();
+
+            let results =
+                search_file_with_context(&path, &test_helpers::create_fixed_search("foo"), 1, 2)
+                    .unwrap();
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].search_result.line_number, 3);
+            assert_eq!(
+                results[0].context_before,
+                vec![ContextLine {
+                    line_number: 2,
+                    line: "2".to_string()
+                }]
+            );
+            assert_eq!(
+                results[0]
+                    .context_after
+                    .iter()
+                    .map(|l| l.line_number)
+                    .collect::<Vec<_>>(),
+                vec![4, 5]
+            );
+            assert_eq!(results[1].search_result.line_number, 7);
+            assert_eq!(results[1].context_before.len(), 1);
+            assert!(results[1].context_after.is_empty());
+        }
+
+        #[test]
+        fn test_format_context_merges_overlapping_regions() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            let path = temp_dir.path().join("test.txt");
+            std::fs::write(&path, "foo\n2\nfoo\n4\n5\n6\n7\nfoo\n").unwrap();
+
+            let results =
+                search_file_with_context(&path, &test_helpers::create_fixed_search("foo"), 1, 1)
+                    .unwrap();
+            let p = path.display();
+            assert_eq!(
+                format_context(&results),
+                format!("{p}:1:foo\n{p}-2-2\n{p}:3:foo\n{p}-4-4\n--\n{p}-7-7\n{p}:8:foo\n")
+            );
+        }
+
+        #[test]
+        fn test_format_context_highlighted() {
+            let temp_dir = tempfile::TempDir::new().unwrap();
+            let path = temp_dir.path().join("test.txt");
+            std::fs::write(&path, "a foo, foo\nbar\n").unwrap();
+
+            let exprs = [(test_helpers::create_fixed_search("foo"), String::new())];
+            let results = search_file_with_context_for_exprs(&path, &exprs, 0, 1).unwrap();
+            let p = path.display().to_string().bold();
+            assert_eq!(
+                format_context_highlighted(&results, &exprs),
+                format!("{p}:1:a {foo}, {foo}\n{p}-2-bar\n", foo = "foo".red())
+            );
+        }
+
+        #[test]
+        fn test_highlight_matches_merges_overlapping_matches() {
+            let exprs = [
+                (test_helpers::create_fixed_search("abc"), String::new()),
+                (test_helpers::create_fixed_search("bcd"), String::new()),
+            ];
+            assert_eq!(
+                highlight_matches("xabcdx", &exprs),
+                format!("x{}{}x", "abc".red(), "d".red())
+            );
+            assert_eq!(highlight_matches("xyz", &exprs), "xyz");
+        }
     }
 }
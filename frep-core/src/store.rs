@@ -0,0 +1,287 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tempfile::NamedTempFile;
+
+use crate::{
+    replace::{self, ReplaceWarning},
+    search::FileSearcherConfig,
+};
+
+/// A source of files to search and replace in, which all files are read from and written to (see
+/// `FileSearcherConfig::store`). This is the filesystem by default, but can be set with
+/// `FileSearcherConfig::file_store` to replace in the files of another store, such as an in-memory virtual
+/// filesystem or an archive, rather than walking the directories.
+///
+/// Only `paths`, `read` and `write` need to be implemented, in which case files are read and written as a
+/// whole. Files may be processed from multiple threads at once.
+pub trait FileStore: fmt::Debug + Send + Sync {
+    /// The paths of the files in the store
+    fn paths(&self) -> io::Result<Vec<PathBuf>>;
+
+    /// The contents of the file at `path`
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Replaces the contents of the file at `path` with `contents`
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    /// Opens the file at `path` to read its contents incrementally, so that large files needn't be held in
+    /// memory. By default, the contents are read in full with `read`
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(io::Cursor::new(self.read(path)?)))
+    }
+
+    /// The size of the file at `path` in bytes. By default, the contents are read in full with `read`
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.read(path)?.len() as u64)
+    }
+
+    /// Starts replacing the contents of the file at `path`, which are only replaced once the returned writer
+    /// is committed. By default, the new contents are held in memory until then, and are written with `write`.
+    ///
+    /// `config` holds the options for how files are written, such as `backup_suffix` and `in_place`, which
+    /// stores may ignore if they don't apply
+    fn create<'a>(
+        &'a self,
+        path: &Path,
+        _config: &'a FileSearcherConfig,
+    ) -> anyhow::Result<Box<dyn FileWriter + 'a>> {
+        Ok(Box::new(BufferedWriter {
+            store: self,
+            path: path.to_path_buf(),
+            contents: io::Cursor::new(vec![]),
+        }))
+    }
+}
+
+/// The new contents of a file in a `FileStore`, which replace the file's contents when committed. The contents
+/// written can be read back, such as to check how they end. If the writer is dropped without being
+/// committed, the file is left unchanged
+pub trait FileWriter: Read + Write + Seek {
+    /// Truncates or extends the new contents to `len` bytes
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+
+    /// Replaces the contents of the file with the new contents, returning any warnings about how it was
+    /// written
+    fn commit(self: Box<Self>) -> anyhow::Result<Vec<ReplaceWarning>>;
+}
+
+/// The default `FileWriter` of a `FileStore`, holding the new contents in memory until they are written with
+/// `FileStore::write`
+struct BufferedWriter<'a, S: ?Sized> {
+    store: &'a S,
+    path: PathBuf,
+    contents: io::Cursor<Vec<u8>>,
+}
+
+impl<S: ?Sized> Read for BufferedWriter<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.contents.read(buf)
+    }
+}
+
+impl<S: ?Sized> Write for BufferedWriter<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.contents.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: ?Sized> Seek for BufferedWriter<'_, S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.contents.seek(pos)
+    }
+}
+
+impl<S: FileStore + ?Sized> FileWriter for BufferedWriter<'_, S> {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let len = usize::try_from(len).map_err(io::Error::other)?;
+        self.contents.get_mut().resize(len, 0);
+        Ok(())
+    }
+
+    fn commit(self: Box<Self>) -> anyhow::Result<Vec<ReplaceWarning>> {
+        self.store.write(&self.path, self.contents.get_ref())?;
+        Ok(vec![])
+    }
+}
+
+/// The filesystem, with paths used as given (i.e. relative to the current directory unless absolute), which
+/// is the store used unless `FileSearcherConfig::file_store` is set
+pub static FILESYSTEM: FsStore = FsStore {
+    root: PathBuf::new(),
+};
+
+/// A `FileStore` backed by the files within a directory on the filesystem. Paths are relative to the
+/// directory, and hidden and ignored files are included.
+///
+/// Files are replaced by writing their new contents to a temporary file, which is then renamed over the
+/// original, keeping its permissions. The options for writing files in `FileSearcherConfig`, such as
+/// `backup_suffix`, `in_place` and `check_unchanged`, are applied when replacing files (see
+/// `replace::FsWriter`)
+#[derive(Clone, Debug)]
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl FileStore for FsStore {
+    fn paths(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = vec![];
+        for entry in ignore::WalkBuilder::new(&self.root)
+            .standard_filters(false)
+            .build()
+        {
+            let entry = entry.map_err(io::Error::other)?;
+            if entry.file_type().is_some_and(|ft| ft.is_file())
+                && let Ok(path) = entry.path().strip_prefix(&self.root)
+            {
+                paths.push(path.to_path_buf());
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.root.join(path))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let path = self.root.join(path);
+        let parent = path.parent().unwrap_or(Path::new("."));
+        let mut temp_file = NamedTempFile::new_in(parent)?;
+        temp_file.write_all(contents)?;
+        temp_file
+            .as_file()
+            .set_permissions(fs::metadata(&path)?.permissions())?;
+        temp_file.persist(&path)?;
+        Ok(())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(self.root.join(path))?))
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(self.root.join(path))?.len())
+    }
+
+    fn create<'a>(
+        &'a self,
+        path: &Path,
+        config: &'a FileSearcherConfig,
+    ) -> anyhow::Result<Box<dyn FileWriter + 'a>> {
+        Ok(Box::new(replace::FsWriter::new(
+            &self.root.join(path),
+            config,
+        )?))
+    }
+}
+
+/// A `FileStore` holding its files in memory, such as for testing
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new<P: Into<PathBuf>, C: Into<Vec<u8>>>(
+        files: impl IntoIterator<Item = (P, C)>,
+    ) -> Self {
+        Self {
+            files: Mutex::new(
+                files
+                    .into_iter()
+                    .map(|(path, contents)| (path.into(), contents.into()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The contents of each file, sorted by path
+    pub fn files(&self) -> BTreeMap<PathBuf, Vec<u8>> {
+        self.files.lock().unwrap().clone()
+    }
+}
+
+impl FileStore for MemoryStore {
+    fn paths(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(self.files.lock().unwrap().keys().cloned().collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found in store"))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        match self.files.lock().unwrap().get_mut(path) {
+            Some(file) => {
+                *file = contents.to_vec();
+                Ok(())
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "file not found in store",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fs_store() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("nested")).unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+        fs::write(temp_dir.path().join("nested/.hidden"), "hidden").unwrap();
+
+        let store = FsStore::new(temp_dir.path());
+        assert_eq!(
+            store.paths().unwrap(),
+            vec![PathBuf::from("a.txt"), PathBuf::from("nested/.hidden")]
+        );
+        store.write(Path::new("nested/.hidden"), b"new").unwrap();
+        assert_eq!(store.read(Path::new("nested/.hidden")).unwrap(), b"new");
+        assert!(store.write(Path::new("missing.txt"), b"new").is_err());
+        assert!(!temp_dir.path().join("missing.txt").exists());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new([("a.txt", "a"), ("b.txt", "b")]);
+        assert_eq!(
+            store.paths().unwrap(),
+            vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]
+        );
+        store.write(Path::new("a.txt"), b"new").unwrap();
+        assert_eq!(store.read(Path::new("a.txt")).unwrap(), b"new");
+        assert_eq!(
+            store.read(Path::new("c.txt")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(store.write(Path::new("c.txt"), b"new").is_err());
+    }
+}
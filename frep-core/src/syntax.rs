@@ -0,0 +1,425 @@
+use std::{io, ops::Range, path::Path};
+
+use crate::line_reader::BufReadExt;
+
+/// A language whose string literals can be detected, so that matches inside them can be skipped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    Json,
+}
+
+impl Language {
+    /// The language of the file at `path` based on its extension, or `None` if the language isn't supported
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Language::Rust),
+            "py" | "pyi" => Some(Language::Python),
+            "json" => Some(Language::Json),
+            _ => None,
+        }
+    }
+}
+
+/// The delimiter that closes a string literal: `count` repetitions of `quote`, followed by `hashes` `#`
+/// characters (as in Rust raw strings)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct StringDelimiter {
+    quote: u8,
+    count: usize,
+    hashes: usize,
+    /// Whether a backslash escapes the following character, so that an escaped quote doesn't close the string
+    escapes: bool,
+    /// Whether the string can continue onto the next line without the line break being escaped
+    spans_lines: bool,
+}
+
+impl StringDelimiter {
+    fn plain(quote: u8, spans_lines: bool) -> Self {
+        Self {
+            quote,
+            count: 1,
+            hashes: 0,
+            escapes: true,
+            spans_lines,
+        }
+    }
+
+    /// The index just past the delimiter if it starts at `i`
+    fn closes_at(&self, bytes: &[u8], i: usize) -> Option<usize> {
+        let quotes_end = i + self.count;
+        let end = quotes_end + self.hashes;
+        let closes = bytes.get(i..quotes_end)?.iter().all(|&b| b == self.quote)
+            && bytes.get(quotes_end..end)?.iter().all(|&b| b == b'#');
+        closes.then_some(end)
+    }
+}
+
+/// What the scanner is inside of at a given point in a file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Code,
+    String(StringDelimiter),
+    /// A Rust block comment, which can be nested
+    BlockComment {
+        depth: usize,
+    },
+}
+
+/// Tracks which parts of each line in a file are inside string literals, carrying over strings (and comments)
+/// that span multiple lines.
+///
+/// This is a lightweight tokenizer rather than a full parser: comments are recognised only so that quotes
+/// within them are ignored, and interpolated expressions in strings (such as in Python f-strings) are
+/// treated as part of the string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StringTracker {
+    language: Language,
+    state: State,
+}
+
+impl StringTracker {
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            state: State::Code,
+        }
+    }
+
+    /// The byte ranges of `line` that are inside string literals, including their quotes, given the strings
+    /// left open by the lines before it. `line` must not include its line ending
+    pub fn string_ranges(&self, line: &str) -> Vec<Range<usize>> {
+        self.scan(line).0
+    }
+
+    /// Moves past `line`, so that the tracker applies to the following line
+    pub fn advance(&mut self, line: &str) {
+        self.state = self.scan(line).1;
+    }
+
+    /// The tracker at the start of each line read from `reader`. Lines that aren't valid UTF-8 are skipped
+    /// over without changing the state
+    pub fn for_each_line(language: Language, reader: impl io::BufRead) -> io::Result<Vec<Self>> {
+        let mut tracker = Self::new(language);
+        let mut trackers = vec![];
+        for line in reader.lines_with_endings() {
+            let (line, _) = line?;
+            trackers.push(tracker);
+            if let Ok(line) = str::from_utf8(&line) {
+                tracker.advance(line);
+            }
+        }
+        Ok(trackers)
+    }
+
+    fn scan(&self, line: &str) -> (Vec<Range<usize>>, State) {
+        let bytes = line.as_bytes();
+        let mut ranges = vec![];
+        let mut state = self.state;
+        let mut string_start = 0;
+        let mut escaped_line_break = false;
+
+        // All delimiters are ASCII, so the line can be scanned byte by byte without splitting characters
+        let mut i = 0;
+        while i < bytes.len() {
+            match state {
+                State::Code => {
+                    if self.starts_line_comment(bytes, i) {
+                        break;
+                    } else if self.language == Language::Rust && bytes[i..].starts_with(b"/*") {
+                        state = State::BlockComment { depth: 1 };
+                        i += 2;
+                    } else if let Some((delimiter, len)) = self.string_opening(bytes, i) {
+                        state = State::String(delimiter);
+                        string_start = i;
+                        i += len;
+                    } else if self.language == Language::Rust && bytes[i] == b'\'' {
+                        i = skip_char_literal(line, i);
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::String(delimiter) => {
+                    if delimiter.escapes && bytes[i] == b'\\' {
+                        escaped_line_break = i + 1 == bytes.len();
+                        i += 2;
+                    } else if let Some(end) = delimiter.closes_at(bytes, i) {
+                        ranges.push(string_start..end);
+                        state = State::Code;
+                        i = end;
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::BlockComment { depth } => {
+                    if bytes[i..].starts_with(b"/*") {
+                        state = State::BlockComment { depth: depth + 1 };
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        state = if depth == 1 {
+                            State::Code
+                        } else {
+                            State::BlockComment { depth: depth - 1 }
+                        };
+                        i += 2;
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+        }
+
+        if let State::String(delimiter) = state {
+            ranges.push(string_start..bytes.len());
+            if !delimiter.spans_lines && !escaped_line_break {
+                state = State::Code;
+            }
+        }
+        (ranges, state)
+    }
+
+    fn starts_line_comment(&self, bytes: &[u8], i: usize) -> bool {
+        match self.language {
+            Language::Rust => bytes[i..].starts_with(b"//"),
+            Language::Python => bytes[i] == b'#',
+            Language::Json => false,
+        }
+    }
+
+    /// The delimiter closing the string literal opening at `i`, if any, along with the length of the opening
+    fn string_opening(&self, bytes: &[u8], i: usize) -> Option<(StringDelimiter, usize)> {
+        match self.language {
+            Language::Rust => {
+                if bytes[i] == b'"' {
+                    return Some((StringDelimiter::plain(b'"', true), 1));
+                }
+                // Raw strings, optionally prefixed by `b` or `c`, such as `r"..."` or `br#"..."#`
+                let is_word_start = |i: usize| i == 0 || !is_identifier_byte(bytes[i - 1]);
+                let raw_prefix = bytes[i] == b'r'
+                    && (is_word_start(i)
+                        || (matches!(bytes[i - 1], b'b' | b'c') && is_word_start(i - 1)));
+                if !raw_prefix {
+                    return None;
+                }
+                let hashes = bytes[i + 1..].iter().take_while(|&&b| b == b'#').count();
+                (bytes.get(i + 1 + hashes) == Some(&b'"')).then_some((
+                    StringDelimiter {
+                        quote: b'"',
+                        count: 1,
+                        hashes,
+                        escapes: false,
+                        spans_lines: true,
+                    },
+                    hashes + 2,
+                ))
+            }
+            Language::Python => {
+                let quote = bytes[i];
+                if quote != b'"' && quote != b'\'' {
+                    return None;
+                }
+                if bytes[i..].starts_with(&[quote; 3]) {
+                    Some((
+                        StringDelimiter {
+                            count: 3,
+                            ..StringDelimiter::plain(quote, true)
+                        },
+                        3,
+                    ))
+                } else {
+                    Some((StringDelimiter::plain(quote, false), 1))
+                }
+            }
+            Language::Json => {
+                (bytes[i] == b'"').then_some((StringDelimiter::plain(b'"', false), 1))
+            }
+        }
+    }
+}
+
+fn is_identifier_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// The index just past the Rust character literal starting at `i`, or just past the quote if it instead
+/// starts a lifetime or label such as `'a`
+fn skip_char_literal(line: &str, i: usize) -> usize {
+    let rest = &line[i + 1..];
+    if let Some(escaped) = rest.strip_prefix('\\') {
+        // Skip the escaped character, so that `'\''` is handled
+        let escaped_len = escaped.chars().next().map_or(0, char::len_utf8);
+        return match escaped[escaped_len..].find('\'') {
+            Some(end) => i + 2 + escaped_len + end + 1,
+            None => line.len(),
+        };
+    }
+    match rest.chars().next() {
+        Some(c) if rest[c.len_utf8()..].starts_with('\'') => i + 1 + c.len_utf8() + 1,
+        _ => i + 1,
+    }
+}
+
+/// Whether the match spanning `start..end` overlaps any of `ranges`. Empty matches overlap a range if they
+/// are within it
+pub fn overlaps_any(start: usize, end: usize, ranges: &[Range<usize>]) -> bool {
+    let end = end.max(start + 1);
+    ranges
+        .iter()
+        .any(|range| start < range.end && end > range.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings<'a>(tracker: &StringTracker, line: &'a str) -> Vec<&'a str> {
+        tracker
+            .string_ranges(line)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect()
+    }
+
+    fn strings_by_line(language: Language, text: &str) -> Vec<Vec<&str>> {
+        let mut tracker = StringTracker::new(language);
+        text.lines()
+            .map(|line| {
+                let line_strings = strings(&tracker, line);
+                tracker.advance(line);
+                line_strings
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_language_from_path() {
+        assert_eq!(
+            Language::from_path(Path::new("src/main.rs")),
+            Some(Language::Rust)
+        );
+        assert_eq!(
+            Language::from_path(Path::new("script.py")),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            Language::from_path(Path::new("data.json")),
+            Some(Language::Json)
+        );
+        assert_eq!(Language::from_path(Path::new("notes.txt")), None);
+        assert_eq!(Language::from_path(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn test_rust_strings() {
+        let tracker = StringTracker::new(Language::Rust);
+        assert_eq!(
+            strings(&tracker, r#"let name = "foo" + "b\"ar";"#),
+            vec![r#""foo""#, r#""b\"ar""#]
+        );
+        assert_eq!(
+            strings(&tracker, r###"let raw = r#"a "quoted" foo"# + br"x";"###),
+            vec![r###"r#"a "quoted" foo"#"###, r#"r"x""#]
+        );
+        assert_eq!(
+            strings(&tracker, r#"let c = '"'; foo("a")"#),
+            vec![r#""a""#]
+        );
+        assert_eq!(
+            strings(&tracker, r#"fn f<'a>(x: &'a str) -> &'a str { "foo" }"#),
+            vec![r#""foo""#]
+        );
+        assert_eq!(strings(&tracker, r#"let c = '\''; "s""#), vec![r#""s""#]);
+        assert_eq!(
+            strings(&tracker, r#"foo(); // "comment""#),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            strings(&tracker, r#"/* "comment" */ "string""#),
+            vec![r#""string""#]
+        );
+        assert_eq!(strings(&tracker, r#"for_"x""#), vec![r#""x""#]);
+    }
+
+    #[test]
+    fn test_rust_multiline() {
+        let text = "let s = \"first\nsecond\" + foo;\n/* outer /* nested */\n\"still a comment\" */ \"string\"";
+        assert_eq!(
+            strings_by_line(Language::Rust, text),
+            vec![
+                vec!["\"first"],
+                vec!["second\""],
+                vec![],
+                vec!["\"string\""]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_strings() {
+        let text = "x = 'foo' + \"b'ar\"  # 'comment'\ndoc = \"\"\"first\nfoo \"quoted\"\n\"\"\" + f\"{foo}\"\ns = 'unterminated\nfoo = 1";
+        assert_eq!(
+            strings_by_line(Language::Python, text),
+            vec![
+                vec!["'foo'", "\"b'ar\""],
+                vec!["\"\"\"first"],
+                vec!["foo \"quoted\""],
+                vec!["\"\"\"", "\"{foo}\""],
+                vec!["'unterminated"],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_escaped_line_break() {
+        let text = "s = 'first \\\nfoo' + foo";
+        assert_eq!(
+            strings_by_line(Language::Python, text),
+            vec![vec!["'first \\"], vec!["foo'"]]
+        );
+    }
+
+    #[test]
+    fn test_json_strings() {
+        let text = "{\"foo\": \"b\\\"ar\", \"n\": 1,\n\"unterminated\n\"x\": foo}";
+        assert_eq!(
+            strings_by_line(Language::Json, text),
+            vec![
+                vec!["\"foo\"", "\"b\\\"ar\"", "\"n\""],
+                vec!["\"unterminated"],
+                vec!["\"x\""],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_for_each_line() {
+        let trackers =
+            StringTracker::for_each_line(Language::Rust, "let s = \"a\nb\";\nfoo\n".as_bytes())
+                .unwrap();
+        let lines = ["let s = \"a", "b\";", "foo"];
+        assert_eq!(trackers.len(), 3);
+        assert_eq!(
+            lines
+                .iter()
+                .zip(&trackers)
+                .map(|(line, tracker)| tracker.string_ranges(line))
+                .collect::<Vec<_>>(),
+            vec![vec![8..10], vec![0..2], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_overlaps_any() {
+        let ranges = [2..5, 8..10];
+        assert!(overlaps_any(3, 4, &ranges));
+        assert!(overlaps_any(0, 3, &ranges));
+        assert!(overlaps_any(4, 9, &ranges));
+        assert!(!overlaps_any(5, 8, &ranges));
+        assert!(!overlaps_any(0, 2, &ranges));
+        assert!(overlaps_any(2, 2, &ranges));
+        assert!(!overlaps_any(5, 5, &ranges));
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::{Context, bail};
+use std::{
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tempfile::NamedTempFile;
+
+/// The name of the file in each journal listing the files recorded in it
+const MANIFEST_FILENAME: &str = "manifest";
+
+/// A journal of the original contents of each file modified during a run, so that the modifications can be
+/// reverted with `undo`.
+///
+/// Each journal is a directory within the journals directory, named so that journals sort by the time they
+/// were created. The original contents of each file are copied into the journal before the file is
+/// overwritten, and the path of the file is then appended to the journal's manifest, so a file is only listed
+/// once its contents have been recorded in full. The directory is only created once the first file is
+/// recorded. Clones share the same journal, and files can be recorded from multiple threads.
+#[derive(Clone, Debug)]
+pub struct UndoJournal {
+    dir: PathBuf,
+    /// The paths recorded so far, in order
+    recorded: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl UndoJournal {
+    /// A new journal within `journals_dir`, which is more recent than any journal already in it
+    pub fn new(journals_dir: &Path) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        Self {
+            dir: journals_dir.join(format!("{timestamp:020}-{}", std::process::id())),
+            recorded: Arc::default(),
+        }
+    }
+
+    /// The directory containing the journal, which doesn't exist until a file has been recorded
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Records the current contents of the file at `path`, which should be called just before it is
+    /// overwritten. Files already recorded in this journal are skipped, so that the earliest contents are
+    /// restored
+    pub fn record(&self, path: &Path) -> anyhow::Result<()> {
+        let path = path.canonicalize()?;
+        let path_str = path
+            .to_str()
+            .filter(|path| !path.contains('\n'))
+            .with_context(|| {
+                format!(
+                    "{} can't be recorded in the undo journal, as its path isn't valid UTF-8 or contains a newline",
+                    path.display()
+                )
+            })?;
+
+        let mut recorded = self.recorded.lock().unwrap();
+        if recorded.contains(&path) {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        fs::copy(&path, self.dir.join(recorded.len().to_string())).with_context(|| {
+            format!(
+                "Failed to record {} in the undo journal at {}",
+                path.display(),
+                self.dir.display()
+            )
+        })?;
+        let mut manifest = fs::File::options()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(MANIFEST_FILENAME))?;
+        writeln!(manifest, "{path_str}")?;
+        manifest.sync_all()?;
+        recorded.push(path);
+        Ok(())
+    }
+}
+
+/// The most recent journal in `journals_dir`, or `None` if there are no journals
+pub fn latest_journal(journals_dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let entries = match fs::read_dir(journals_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut journals = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            journals.push(entry.path());
+        }
+    }
+    Ok(journals.into_iter().max())
+}
+
+/// The outcome of undoing a journal
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UndoSummary {
+    /// The files restored to their original contents, in the order in which they were recorded
+    pub restored: Vec<PathBuf>,
+    /// The files that couldn't be restored, along with the error for each
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+impl fmt::Display for UndoSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_restored = self.restored.len();
+        writeln!(
+            f,
+            "Restored {num_restored} file{suffix}",
+            suffix = if num_restored != 1 { "s" } else { "" },
+        )?;
+        if !self.errors.is_empty() {
+            writeln!(
+                f,
+                "Failed to restore {num_errors} file{suffix}:",
+                num_errors = self.errors.len(),
+                suffix = if self.errors.len() != 1 { "s" } else { "" },
+            )?;
+            for (path, error) in &self.errors {
+                writeln!(f, "  {}: {error}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Restores each file recorded in the journal at `journal_dir` to its original contents.
+///
+/// Each file is replaced atomically, so is either fully restored or left unchanged, and a file that can't be
+/// restored doesn't stop the others from being restored. Once every file has been restored, the journal is
+/// removed; otherwise it is kept, so that restoring can be retried. Files modified since the journal was
+/// written are overwritten, and journals shouldn't be undone by multiple processes at once.
+pub fn undo(journal_dir: &Path) -> anyhow::Result<UndoSummary> {
+    let manifest_path = journal_dir.join(MANIFEST_FILENAME);
+    let manifest = fs::read_to_string(&manifest_path).with_context(|| {
+        format!(
+            "Failed to read undo journal manifest {}",
+            manifest_path.display()
+        )
+    })?;
+
+    let mut summary = UndoSummary::default();
+    for (idx, path) in manifest.lines().enumerate() {
+        let path = PathBuf::from(path);
+        match restore(&journal_dir.join(idx.to_string()), &path) {
+            Ok(()) => summary.restored.push(path),
+            Err(e) => summary.errors.push((path, format!("{e:#}"))),
+        }
+    }
+
+    if summary.errors.is_empty() {
+        fs::remove_dir_all(journal_dir)?;
+    }
+    Ok(summary)
+}
+
+/// Replaces the file at `path` with the contents and permissions of `original`
+fn restore(original: &Path, path: &Path) -> anyhow::Result<()> {
+    let Some(parent) = path.parent() else {
+        bail!("{} has no parent directory", path.display());
+    };
+    let mut temp_file = NamedTempFile::new_in(parent)?;
+    std::io::copy(&mut fs::File::open(original)?, temp_file.as_file_mut())?;
+    temp_file
+        .as_file()
+        .set_permissions(fs::metadata(original)?.permissions())?;
+    temp_file.persist(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_undo() {
+        let temp_dir = TempDir::new().unwrap();
+        let journals_dir = temp_dir.path().join("journals");
+        let first = temp_dir.path().join("first.txt");
+        let second = temp_dir.path().join("second.txt");
+        fs::write(&first, "first original").unwrap();
+        fs::write(&second, "second original").unwrap();
+
+        assert_eq!(latest_journal(&journals_dir).unwrap(), None);
+        let journal = UndoJournal::new(&journals_dir);
+        journal.record(&first).unwrap();
+        fs::write(&first, "first modified").unwrap();
+        journal.clone().record(&second).unwrap();
+        fs::write(&second, "second modified").unwrap();
+        // Only the earliest contents of each file are kept
+        journal.record(&first).unwrap();
+        fs::write(&first, "first modified again").unwrap();
+
+        let latest = latest_journal(&journals_dir).unwrap().unwrap();
+        assert_eq!(latest, journal.dir());
+        let summary = undo(&latest).unwrap();
+        assert_eq!(
+            summary.restored,
+            vec![
+                first.canonicalize().unwrap(),
+                second.canonicalize().unwrap()
+            ]
+        );
+        assert!(summary.errors.is_empty());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first original");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second original");
+        assert!(!latest.exists());
+    }
+
+    #[test]
+    fn test_undo_partial_failure() {
+        let temp_dir = TempDir::new().unwrap();
+        let journals_dir = temp_dir.path().join("journals");
+        let kept = temp_dir.path().join("kept.txt");
+        let nested_dir = temp_dir.path().join("nested");
+        let removed = nested_dir.join("removed.txt");
+        fs::create_dir(&nested_dir).unwrap();
+        fs::write(&kept, "kept original").unwrap();
+        fs::write(&removed, "removed original").unwrap();
+
+        let journal = UndoJournal::new(&journals_dir);
+        journal.record(&removed).unwrap();
+        journal.record(&kept).unwrap();
+        fs::write(&kept, "kept modified").unwrap();
+        fs::remove_dir_all(&nested_dir).unwrap();
+
+        // Files that can't be restored don't stop the others, and the journal is kept so undo can be retried
+        let summary = undo(journal.dir()).unwrap();
+        assert_eq!(summary.restored, vec![kept.canonicalize().unwrap()]);
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(fs::read_to_string(&kept).unwrap(), "kept original");
+        assert!(journal.dir().exists());
+
+        fs::create_dir(&nested_dir).unwrap();
+        let summary = undo(journal.dir()).unwrap();
+        assert_eq!(summary.restored.len(), 2);
+        assert_eq!(fs::read_to_string(&removed).unwrap(), "removed original");
+        assert!(!journal.dir().exists());
+    }
+}
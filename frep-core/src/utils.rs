@@ -1,3 +1,5 @@
+use std::fmt::Write;
+
 use anyhow::Error;
 use ignore::overrides::OverrideBuilder;
 
@@ -5,16 +7,53 @@ pub fn is_regex_error(e: &Error) -> bool {
     e.downcast_ref::<regex::Error>().is_some() || e.downcast_ref::<fancy_regex::Error>().is_some()
 }
 
-pub fn add_overrides(
+/// Adds each of the glob patterns in `globs` to `overrides`, prepended with `prefix` (e.g. `!` to exclude files
+/// matching the globs), and records each pattern added in `added`. Patterns are trimmed, and empty patterns are
+/// skipped
+pub fn add_overrides<'a>(
     overrides: &mut OverrideBuilder,
-    files: &str,
+    globs: impl IntoIterator<Item = &'a str>,
     prefix: &str,
+    added: &mut Vec<String>,
 ) -> anyhow::Result<()> {
-    for file in files.split(',') {
+    for file in globs {
         let file = file.trim();
         if !file.is_empty() {
-            overrides.add(&format!("{prefix}{file}"))?;
+            let glob = format!("{prefix}{file}");
+            overrides.add(&glob)?;
+            added.push(glob);
         }
     }
     Ok(())
 }
+
+/// The glob patterns in the contents of a glob file, with one pattern per line. Lines starting with `#` are
+/// comments, and are skipped along with blank lines. Patterns starting with `!` exclude files, as with
+/// `OverrideBuilder::add`
+pub fn glob_file_patterns(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Formats a string as a JSON string literal, including the surrounding quotes
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", u32::from(c));
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -1,12 +1,20 @@
+use anyhow::{Context, bail};
 use crossterm::style::Stylize;
 use fancy_regex::Regex as FancyRegex;
-use ignore::overrides::OverrideBuilder;
-use regex::Regex;
-use std::path::PathBuf;
+use ignore::{gitignore::GitignoreBuilder, overrides::OverrideBuilder};
+use regex::{Regex, bytes};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use crate::search::{ParsedDirConfig, ParsedSearchConfig, SearchType};
-use crate::utils;
+use crate::{file_types, utils};
 
+/// The text to search for and replace it with, and options controlling how the search is performed.
+///
+/// Prefer constructing this with `SearchConfig::builder`, which fills in defaults for any options that aren't
+/// set and so doesn't need updating when new options are added.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct SearchConfig<'a> {
@@ -15,20 +23,179 @@ pub struct SearchConfig<'a> {
     pub fixed_strings: bool,
     pub advanced_regex: bool,
     pub match_whole_word: bool,
+    /// Whether the search text must match an entire line, so that the replacement replaces the whole line.
+    /// Regex patterns are anchored with `^` and `$`, while fixed strings are compared against the line with
+    /// leading and trailing whitespace trimmed
+    pub match_whole_line: bool,
     pub match_case: bool,
+    /// Whether to match against the entire contents of each file rather than line by line, allowing matches
+    /// to span multiple lines. Regex patterns are compiled with the `m` flag, so `^` and `$` match at the start
+    /// and end of each line. See `dotall` to also have `.` match newlines
+    pub multiline: bool,
+    /// Whether `.` matches newlines in regex patterns, by compiling them with the `s` flag. Lines never
+    /// contain newlines when matching line by line, so this only has an effect in `multiline` mode
+    pub dotall: bool,
+    /// Further search and replacement text pairs, applied in order after `search_text` and
+    /// `replacement_text`. These are parsed using the same options as `search_text`
+    pub additional_exprs: &'a [(String, String)],
+    /// Characters to treat as part of words when `match_whole_word` is set, in addition to letters, digits and
+    /// underscores, which include non-ASCII characters such as `é` and `日` as with `\w`. For instance, with `-`
+    /// included, `foo` doesn't match within `foo-bar`
+    pub word_chars: &'a str,
+    /// Whether to check that each capture group referenced in the replacement text, such as `$3` or `${name}`,
+    /// exists in the search pattern, reporting a validation error otherwise rather than expanding the reference
+    /// to empty text. Fixed strings aren't checked, as their replacement text isn't expanded
+    pub strict_captures: bool,
+}
+
+impl<'a> SearchConfig<'a> {
+    /// Creates a builder for a `SearchConfig` that replaces `search_text` with `replacement_text`. See
+    /// `SearchConfigBuilder` for the defaults used
+    pub fn builder(search_text: &'a str, replacement_text: &'a str) -> SearchConfigBuilder<'a> {
+        SearchConfigBuilder::new(search_text, replacement_text)
+    }
+}
+
+/// Builds a `SearchConfig`. By default, the search text is treated as a regex (without advanced features
+/// such as lookarounds), matching is case-sensitive and not restricted to whole words, and matching is
+/// performed line by line
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SearchConfigBuilder<'a> {
+    config: SearchConfig<'a>,
+}
+
+impl<'a> SearchConfigBuilder<'a> {
+    pub fn new(search_text: &'a str, replacement_text: &'a str) -> Self {
+        Self {
+            config: SearchConfig {
+                search_text,
+                replacement_text,
+                fixed_strings: false,
+                advanced_regex: false,
+                match_whole_word: false,
+                match_whole_line: false,
+                match_case: true,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
+            },
+        }
+    }
+
+    /// Whether to treat the search text as a fixed string rather than a regex
+    pub fn fixed_strings(mut self, fixed_strings: bool) -> Self {
+        self.config.fixed_strings = fixed_strings;
+        self
+    }
+
+    /// Whether to enable advanced regex features such as lookarounds and backreferences
+    pub fn advanced_regex(mut self, advanced_regex: bool) -> Self {
+        self.config.advanced_regex = advanced_regex;
+        self
+    }
+
+    /// Whether to only match whole words
+    pub fn match_whole_word(mut self, match_whole_word: bool) -> Self {
+        self.config.match_whole_word = match_whole_word;
+        self
+    }
+
+    /// Whether to only match entire lines. See `SearchConfig::match_whole_line`
+    pub fn match_whole_line(mut self, match_whole_line: bool) -> Self {
+        self.config.match_whole_line = match_whole_line;
+        self
+    }
+
+    /// Whether matching is case-sensitive
+    pub fn match_case(mut self, match_case: bool) -> Self {
+        self.config.match_case = match_case;
+        self
+    }
+
+    /// Whether to match against the entire contents of each file. See `SearchConfig::multiline`
+    pub fn multiline(mut self, multiline: bool) -> Self {
+        self.config.multiline = multiline;
+        self
+    }
+
+    /// Whether `.` matches newlines. See `SearchConfig::dotall`
+    pub fn dotall(mut self, dotall: bool) -> Self {
+        self.config.dotall = dotall;
+        self
+    }
+
+    /// Further search and replacement text pairs. See `SearchConfig::additional_exprs`
+    pub fn additional_exprs(mut self, additional_exprs: &'a [(String, String)]) -> Self {
+        self.config.additional_exprs = additional_exprs;
+        self
+    }
+
+    /// Additional characters that form part of words when matching whole words. See `SearchConfig::word_chars`
+    pub fn word_chars(mut self, word_chars: &'a str) -> Self {
+        self.config.word_chars = word_chars;
+        self
+    }
+
+    /// Whether to check capture group references in the replacement text. See `SearchConfig::strict_captures`
+    pub fn strict_captures(mut self, strict_captures: bool) -> Self {
+        self.config.strict_captures = strict_captures;
+        self
+    }
+
+    pub fn build(self) -> SearchConfig<'a> {
+        self.config
+    }
+}
+
+/// Which globs take precedence for files matching both an include glob and an exclude glob
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GlobPrecedence {
+    /// Files matching an include glob are processed, even if they also match an exclude glob
+    Include,
+    /// Files matching an exclude glob are skipped, even if they also match an include glob
+    #[default]
+    Exclude,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DirConfig<'a> {
     pub include_globs: Option<&'a str>,
     pub exclude_globs: Option<&'a str>,
-    pub directory: PathBuf,
+    /// A file containing further glob patterns, one per line, that are combined with `include_globs` and
+    /// `exclude_globs`. Patterns starting with `!` exclude files, and lines starting with `#` are comments
+    pub glob_file: Option<&'a Path>,
+    /// Names of built-in file types (see `file_types::globs_for_type`) that file paths must match one of
+    pub include_types: &'a [String],
+    /// Names of built-in file types that file paths must not match
+    pub exclude_types: &'a [String],
+    /// Additional ignore files, in gitignore syntax, whose patterns are matched relative to the first of
+    /// `directories`. These are applied even if `respect_ignore` is `false`
+    pub ignore_files: &'a [PathBuf],
+    /// Whether `include_globs` and `include_types` or `exclude_globs` and `exclude_types` take precedence for
+    /// files matching both. Patterns in `glob_file` are applied after all of these, in order, so take precedence
+    /// over them
+    pub glob_precedence: GlobPrecedence,
+    /// If set, only files with one of these extensions are processed, such as `rs` or `.rs`. This is checked
+    /// before any glob matching, and composes with the other filters
+    pub extensions: Option<Vec<String>>,
+    /// The directories to search, of which there must be at least one. Directories that are inside (or the same
+    /// as) another of the directories are skipped, so that no file is processed twice. Glob patterns containing
+    /// a `/` are matched relative to the first directory
+    pub directories: Vec<PathBuf>,
     pub include_hidden: bool,
+    pub follow_symlinks: bool,
+    pub max_depth: Option<usize>,
+    pub respect_ignore: bool,
 }
 pub trait ValidationErrorHandler {
     fn handle_search_text_error(&mut self, error: &str, detail: &str);
+    fn handle_replacement_text_error(&mut self, error: &str, detail: &str);
     fn handle_include_files_error(&mut self, error: &str, detail: &str);
     fn handle_exclude_files_error(&mut self, error: &str, detail: &str);
+    fn handle_glob_file_error(&mut self, error: &str, detail: &str);
+    fn handle_ignore_file_error(&mut self, error: &str, detail: &str);
 }
 
 /// Collects errors into an array
@@ -66,6 +233,10 @@ impl ValidationErrorHandler for SimpleErrorHandler {
         self.push_error("Failed to parse search text", detail);
     }
 
+    fn handle_replacement_text_error(&mut self, _error: &str, detail: &str) {
+        self.push_error("Invalid replacement text", detail);
+    }
+
     fn handle_include_files_error(&mut self, _error: &str, detail: &str) {
         self.push_error("Failed to parse include globs", detail);
     }
@@ -73,6 +244,14 @@ impl ValidationErrorHandler for SimpleErrorHandler {
     fn handle_exclude_files_error(&mut self, _error: &str, detail: &str) {
         self.push_error("Failed to parse exclude globs", detail);
     }
+
+    fn handle_glob_file_error(&mut self, _error: &str, detail: &str) {
+        self.push_error("Failed to parse glob file", detail);
+    }
+
+    fn handle_ignore_file_error(&mut self, _error: &str, detail: &str) {
+        self.push_error("Failed to parse ignore file", detail);
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -101,6 +280,7 @@ pub fn validate_search_configuration<H: ValidationErrorHandler>(
     error_handler: &mut H,
 ) -> anyhow::Result<ValidationResult<(ParsedSearchConfig, Option<ParsedDirConfig>)>> {
     let search_pattern = parse_search_text_with_error_handler(&search_config, error_handler)?;
+    let additional_exprs = parse_additional_exprs(&search_config, error_handler)?;
 
     let parsed_dir_config = match dir_config {
         Some(dir_config) => {
@@ -112,12 +292,15 @@ pub fn validate_search_configuration<H: ValidationErrorHandler>(
 
     if let (
         ValidationResult::Success(search_pattern),
+        ValidationResult::Success(additional_exprs),
         ValidationResult::Success(parsed_dir_config),
-    ) = (search_pattern, parsed_dir_config)
+    ) = (search_pattern, additional_exprs, parsed_dir_config)
     {
         let search_config = ParsedSearchConfig {
             search: search_pattern,
             replace: search_config.replacement_text.to_owned(),
+            multiline: search_config.multiline,
+            additional_exprs,
         };
         Ok(ValidationResult::Success((
             search_config,
@@ -129,14 +312,24 @@ pub fn validate_search_configuration<H: ValidationErrorHandler>(
 }
 
 pub fn parse_search_text(config: &SearchConfig<'_>) -> anyhow::Result<SearchType> {
-    if !config.match_whole_word && config.match_case {
+    if config.search_text.is_empty() {
+        // Avoid wrapping an empty search in flags, which would then match everywhere
+        return Ok(SearchType::Fixed(String::new()));
+    }
+    if !config.match_whole_word && !config.match_whole_line && config.match_case {
         // No conversion required
         let search = if config.fixed_strings {
             SearchType::Fixed(config.search_text.to_string())
         } else if config.advanced_regex {
-            SearchType::PatternAdvanced(FancyRegex::new(config.search_text)?)
+            SearchType::PatternAdvanced(FancyRegex::new(&with_flags(
+                config.search_text.to_owned(),
+                config,
+            ))?)
         } else {
-            SearchType::Pattern(Regex::new(config.search_text)?)
+            SearchType::Pattern(Regex::new(&with_flags(
+                config.search_text.to_owned(),
+                config,
+            ))?)
         };
         Ok(search)
     } else {
@@ -150,11 +343,16 @@ pub fn parse_search_text(config: &SearchConfig<'_>) -> anyhow::Result<SearchType
         };
 
         if config.match_whole_word {
-            search_regex_str = format!(r"(?<![a-zA-Z0-9_]){search_regex_str}(?![a-zA-Z0-9_])");
+            let word_chars = word_char_class(config.word_chars);
+            search_regex_str = format!(r"(?<!{word_chars}){search_regex_str}(?!{word_chars})");
+        }
+        if config.match_whole_line {
+            search_regex_str = anchor_to_line(&search_regex_str, config.fixed_strings, false);
         }
         if !config.match_case {
             search_regex_str = format!(r"(?i){search_regex_str}");
         }
+        search_regex_str = with_flags(search_regex_str, config);
 
         // Shouldn't fail as we have already verified that the regex is valid, so `unwrap` here is fine.
         // (Any issues will likely be with the padding we are doing in this function.)
@@ -163,15 +361,121 @@ pub fn parse_search_text(config: &SearchConfig<'_>) -> anyhow::Result<SearchType
     }
 }
 
+/// Like `parse_search_text`, but builds a regex that matches against bytes rather than strings, so that content
+/// which isn't valid UTF-8 can be searched. Returns `None` if the search text is empty.
+///
+/// `regex::bytes` doesn't support lookarounds or backreferences, so patterns using advanced regex features
+/// and whole-word matching result in an error. As with `regex::bytes::Regex`, Unicode mode is enabled by
+/// default, so to match arbitrary bytes with `.` the pattern should disable it with `(?-u)`.
+pub fn parse_search_bytes(config: &SearchConfig<'_>) -> anyhow::Result<Option<bytes::Regex>> {
+    if config.search_text.is_empty() {
+        return Ok(None);
+    }
+    if config.match_whole_word {
+        bail!("Whole-word matching is not supported when searching bytes");
+    }
+
+    let mut pattern = if config.fixed_strings {
+        regex::escape(config.search_text)
+    } else {
+        config.search_text.to_owned()
+    };
+    if config.match_whole_line {
+        pattern = anchor_to_line(&pattern, config.fixed_strings, true);
+    }
+    if !config.match_case {
+        pattern = format!("(?i){pattern}");
+    }
+    match bytes::Regex::new(&with_flags(pattern, config)) {
+        Ok(regex) => Ok(Some(regex)),
+        Err(e) if config.advanced_regex => Err(anyhow::Error::new(e)
+            .context("Advanced regex features are not supported when searching bytes")),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A regex character class matching letters, digits and underscores, along with each of the characters in
+/// `extra_chars`. This is Unicode-aware, as with `\w` and `\b` in regex patterns, so that whole words are matched
+/// consistently whether searching with fixed strings or regex, and `caf` doesn't match within `café`
+fn word_char_class(extra_chars: &str) -> String {
+    let mut class = String::from(r"[\w");
+    for c in extra_chars.chars() {
+        class.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+    }
+    class.push(']');
+    class
+}
+
+/// Anchors `pattern` so that it only matches an entire line. Fixed strings (already escaped) may be
+/// surrounded by whitespace other than line breaks, which is included in the match so that the whole line is
+/// replaced.
+///
+/// Files are searched as a whole when replaced in memory, so the anchors match at each line break rather than
+/// only at the start and end of the text, and the `\r` of a `\r\n` line ending isn't included in the match.
+/// fancy-regex doesn't support CRLF mode, so a lookahead is used for this unless building a `bytes` regex,
+/// which doesn't support lookarounds
+fn anchor_to_line(pattern: &str, fixed_strings: bool, bytes: bool) -> String {
+    let pattern = if fixed_strings {
+        format!(r"[^\S\r\n]*{pattern}[^\S\r\n]*")
+    } else {
+        format!("(?:{pattern})")
+    };
+    if bytes {
+        format!("(?mR:^){pattern}(?mR:$)")
+    } else {
+        format!(r"(?m:^){pattern}(?=\r?(?m:$))")
+    }
+}
+
+/// Prepends the `m` and `s` flags to `pattern` if `multiline` and `dotall` are set, respectively
+fn with_flags(pattern: String, config: &SearchConfig<'_>) -> String {
+    let flags = match (config.multiline, config.dotall) {
+        (true, true) => "(?ms)",
+        (true, false) => "(?m)",
+        (false, true) => "(?s)",
+        (false, false) => return pattern,
+    };
+    format!("{flags}{pattern}")
+}
+
 fn parse_search_text_with_error_handler<H: ValidationErrorHandler>(
     config: &SearchConfig<'_>,
     error_handler: &mut H,
 ) -> anyhow::Result<ValidationResult<SearchType>> {
     match parse_search_text(config) {
-        Ok(pattern) => Ok(ValidationResult::Success(pattern)),
+        Ok(pattern) => {
+            if config.strict_captures && !config.fixed_strings {
+                let missing = missing_capture_groups(&pattern, config.replacement_text);
+                if !missing.is_empty() {
+                    error_handler.handle_replacement_text_error(
+                        "Replacement references missing capture groups",
+                        &format!(
+                            "The replacement text {replacement:?} references capture groups that don't exist in the search pattern: {missing}",
+                            replacement = config.replacement_text,
+                            missing = missing.join(", "),
+                        ),
+                    );
+                    return Ok(ValidationResult::ValidationErrors);
+                }
+            }
+            Ok(ValidationResult::Success(pattern))
+        }
         Err(e) => {
             if utils::is_regex_error(&e) {
-                error_handler.handle_search_text_error("Couldn't parse regex", &e.to_string());
+                let message = e.to_string();
+                let kind = regex_error_kind(&message);
+                // The kind is included in the detail, as error handlers such as `SimpleErrorHandler` only show that
+                let mut detail = format!("{}\n\n{message}", kind.description());
+                if !config.advanced_regex
+                    && (matches!(
+                        kind,
+                        RegexErrorKind::Lookaround | RegexErrorKind::Backreference
+                    ) || contains_lookaround(config.search_text))
+                {
+                    detail.push_str("\n\n");
+                    detail.push_str(ADVANCED_REGEX_HINT);
+                }
+                error_handler.handle_search_text_error(kind.description(), &detail);
                 Ok(ValidationResult::ValidationErrors)
             } else {
                 Err(e)
@@ -180,24 +484,213 @@ fn parse_search_text_with_error_handler<H: ValidationErrorHandler>(
     }
 }
 
+/// Suggests advanced regex for patterns using features that the standard regex engine rejects
+const ADVANCED_REGEX_HINT: &str = "Lookaround, such as `(?=...)` and `(?<!...)`, and backreferences, such as `\\1`, \
+    are only supported with advanced regex: try again with --advanced-regex (-a)";
+
+/// A common reason for a search pattern failing to parse as a regex
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RegexErrorKind {
+    UnbalancedBracket,
+    UnbalancedParenthesis,
+    UnknownEscape,
+    Lookaround,
+    Backreference,
+    Other,
+}
+
+impl RegexErrorKind {
+    fn description(self) -> &'static str {
+        match self {
+            RegexErrorKind::UnbalancedBracket => "Unbalanced bracket in regex",
+            RegexErrorKind::UnbalancedParenthesis => "Unbalanced parenthesis in regex",
+            RegexErrorKind::UnknownEscape => "Unknown escape sequence in regex",
+            RegexErrorKind::Lookaround => "Lookaround requires advanced regex",
+            RegexErrorKind::Backreference => "Backreferences require advanced regex",
+            RegexErrorKind::Other => "Couldn't parse regex",
+        }
+    }
+}
+
+/// The kind of a `regex::Error` or `fancy_regex::Error`, from its message. Neither engine exposes the kind of
+/// a syntax error other than in the message
+fn regex_error_kind(message: &str) -> RegexErrorKind {
+    // Syntax errors from `regex` quote the pattern before the error itself, which shouldn't be matched
+    let message = message
+        .rsplit("error:")
+        .next()
+        .unwrap_or(message)
+        .to_lowercase();
+    if message.contains("look-around") {
+        RegexErrorKind::Lookaround
+    } else if message.contains("backreferences are not supported") {
+        RegexErrorKind::Backreference
+    } else if message.contains("unclosed character class") {
+        RegexErrorKind::UnbalancedBracket
+    } else if message.contains("unclosed group")
+        || message.contains("unopened group")
+        || message.contains("parenthesis")
+    {
+        RegexErrorKind::UnbalancedParenthesis
+    } else if message.contains("unrecognized escape") || message.contains("invalid escape") {
+        RegexErrorKind::UnknownEscape
+    } else {
+        RegexErrorKind::Other
+    }
+}
+
+/// Whether `pattern` appears to contain a lookahead or lookbehind, which are only supported with advanced regex
+fn contains_lookaround(pattern: &str) -> bool {
+    ["(?=", "(?!", "(?<=", "(?<!"]
+        .iter()
+        .any(|syntax| pattern.contains(syntax))
+}
+
+/// The capture group references in `replacement`, such as `$1`, `$name` and `${name}`, that don't refer to a
+/// capture group in `search`, formatted as they appear in the replacement. References are parsed as by
+/// `regex::Regex::replace`, so `$$` is a literal `$`, and names without braces extend as far as possible
+fn missing_capture_groups(search: &SearchType, replacement: &str) -> Vec<String> {
+    let (num_groups, names): (usize, Vec<&str>) = match search {
+        SearchType::Pattern(regex) => (
+            regex.captures_len(),
+            regex.capture_names().flatten().collect(),
+        ),
+        SearchType::PatternAdvanced(regex) => (
+            regex.captures_len(),
+            regex.capture_names().flatten().collect(),
+        ),
+        SearchType::Fixed(_) => return vec![],
+    };
+
+    let mut missing = vec![];
+    let mut rest = replacement;
+    while let Some(idx) = rest.find('$') {
+        rest = &rest[idx + 1..];
+        let (name, reference) = if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+            continue;
+        } else if let Some(braced) = rest.strip_prefix('{') {
+            let Some(end) = braced.find('}') else {
+                // An unterminated brace is inserted literally
+                continue;
+            };
+            rest = &braced[end + 1..];
+            (&braced[..end], format!("${{{}}}", &braced[..end]))
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let name = &rest[..end];
+            rest = &rest[end..];
+            (name, format!("${name}"))
+        };
+        let exists = match name.parse::<usize>() {
+            Ok(idx) => idx < num_groups,
+            Err(_) => name.is_empty() || names.contains(&name),
+        };
+        if !exists && !missing.contains(&reference) {
+            missing.push(reference);
+        }
+    }
+    missing
+}
+
+fn parse_additional_exprs<H: ValidationErrorHandler>(
+    config: &SearchConfig<'_>,
+    error_handler: &mut H,
+) -> anyhow::Result<ValidationResult<Vec<(SearchType, String)>>> {
+    let mut exprs = Vec::with_capacity(config.additional_exprs.len());
+    let mut success = true;
+    for (search_text, replacement_text) in config.additional_exprs {
+        let expr_config = SearchConfig {
+            search_text,
+            replacement_text,
+            ..config.clone()
+        };
+        match parse_search_text_with_error_handler(&expr_config, error_handler)? {
+            ValidationResult::Success(search) => exprs.push((search, replacement_text.clone())),
+            ValidationResult::ValidationErrors => success = false,
+        }
+    }
+    Ok(if success {
+        ValidationResult::Success(exprs)
+    } else {
+        ValidationResult::ValidationErrors
+    })
+}
+
 fn parse_overrides<H: ValidationErrorHandler>(
     dir_config: DirConfig<'_>,
     error_handler: &mut H,
 ) -> anyhow::Result<ValidationResult<ParsedDirConfig>> {
-    let mut overrides = OverrideBuilder::new(&dir_config.directory);
+    let Some(first_dir) = dir_config.directories.first() else {
+        bail!("At least one directory to search must be given");
+    };
+    let mut overrides = OverrideBuilder::new(first_dir);
+    let mut override_globs = vec![];
     let mut success = true;
 
-    if let Some(include_globs) = dir_config.include_globs
-        && let Err(e) = utils::add_overrides(&mut overrides, include_globs, "")
-    {
-        error_handler.handle_include_files_error("Couldn't parse glob pattern", &e.to_string());
-        success = false;
+    let include = (dir_config.include_globs, dir_config.include_types, "");
+    let exclude = (dir_config.exclude_globs, dir_config.exclude_types, "!");
+    // The last glob that matches a file decides whether it is included, so the globs that take precedence are
+    // added last
+    let ordered = match dir_config.glob_precedence {
+        GlobPrecedence::Include => [exclude, include],
+        GlobPrecedence::Exclude => [include, exclude],
+    };
+    for (globs, types, prefix) in ordered {
+        if let Some(globs) = globs
+            && let Err(e) = utils::add_overrides(
+                &mut overrides,
+                globs.split(','),
+                prefix,
+                &mut override_globs,
+            )
+        {
+            if prefix.is_empty() {
+                error_handler
+                    .handle_include_files_error("Couldn't parse glob pattern", &e.to_string());
+            } else {
+                error_handler
+                    .handle_exclude_files_error("Couldn't parse glob pattern", &e.to_string());
+            }
+            success = false;
+        }
+        for (error, detail) in
+            add_type_overrides(&mut overrides, types, prefix, &mut override_globs)
+        {
+            if prefix.is_empty() {
+                error_handler.handle_include_files_error(error, &detail);
+            } else {
+                error_handler.handle_exclude_files_error(error, &detail);
+            }
+            success = false;
+        }
     }
-    if let Some(exclude_globs) = dir_config.exclude_globs
-        && let Err(e) = utils::add_overrides(&mut overrides, exclude_globs, "!")
-    {
-        error_handler.handle_exclude_files_error("Couldn't parse glob pattern", &e.to_string());
-        success = false;
+    if let Some(glob_file) = dir_config.glob_file {
+        let contents = fs::read_to_string(glob_file)
+            .with_context(|| format!("Failed to read glob file '{}'", glob_file.display()))?;
+        if let Err(e) = utils::add_overrides(
+            &mut overrides,
+            utils::glob_file_patterns(&contents),
+            "",
+            &mut override_globs,
+        ) {
+            error_handler.handle_glob_file_error("Couldn't parse glob pattern", &e.to_string());
+            success = false;
+        }
+    }
+    let mut custom_ignore = GitignoreBuilder::new(first_dir);
+    for ignore_file in dir_config.ignore_files {
+        let contents = fs::read_to_string(ignore_file)
+            .with_context(|| format!("Failed to read ignore file '{}'", ignore_file.display()))?;
+        for line in contents.lines() {
+            if let Err(e) = custom_ignore.add_line(Some(ignore_file.clone()), line) {
+                error_handler
+                    .handle_ignore_file_error("Couldn't parse ignore pattern", &e.to_string());
+                success = false;
+            }
+        }
     }
     if !success {
         return Ok(ValidationResult::ValidationErrors);
@@ -205,11 +698,78 @@ fn parse_overrides<H: ValidationErrorHandler>(
 
     Ok(ValidationResult::Success(ParsedDirConfig {
         overrides: overrides.build()?,
-        root_dir: dir_config.directory,
+        override_globs,
+        root_dirs: dedup_directories(dir_config.directories),
         include_hidden: dir_config.include_hidden,
+        follow_symlinks: dir_config.follow_symlinks,
+        max_depth: dir_config.max_depth,
+        respect_ignore: dir_config.respect_ignore,
+        custom_ignore: custom_ignore.build()?,
+        extensions: dir_config.extensions.map(normalize_extensions),
     }))
 }
 
+/// Adds the globs for each of the file types in `types` to `overrides`, prepended with `prefix` as with
+/// `utils::add_overrides`. Returns the error and detail for each type that isn't known or whose globs couldn't
+/// be parsed
+fn add_type_overrides(
+    overrides: &mut OverrideBuilder,
+    types: &[String],
+    prefix: &str,
+    override_globs: &mut Vec<String>,
+) -> Vec<(&'static str, String)> {
+    let mut errors = vec![];
+    for name in types {
+        let Some(globs) = file_types::globs_for_type(name) else {
+            errors.push((
+                "Unknown file type",
+                format!(
+                    "'{name}' is not a known file type. Known types are: {}",
+                    file_types::type_names().collect::<Vec<_>>().join(", ")
+                ),
+            ));
+            continue;
+        };
+        if let Err(e) =
+            utils::add_overrides(overrides, globs.iter().copied(), prefix, override_globs)
+        {
+            errors.push(("Couldn't parse glob pattern", e.to_string()));
+        }
+    }
+    errors
+}
+
+/// Trims whitespace and any leading `.` from each extension, such as to match `.rs` as `rs`, removing any that
+/// are then empty
+fn normalize_extensions(extensions: Vec<String>) -> Vec<String> {
+    extensions
+        .into_iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_owned())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// Removes any directories that are inside, or the same as, another of `directories`, keeping the first of any
+/// duplicates, so that files aren't processed twice. Directories are compared by their canonical paths where
+/// possible, so that e.g. `src` and `./src` are treated as the same directory
+fn dedup_directories(directories: Vec<PathBuf>) -> Vec<PathBuf> {
+    let canonical = directories
+        .iter()
+        .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.clone()))
+        .collect::<Vec<_>>();
+    directories
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| {
+            !canonical.iter().enumerate().any(|(other_idx, other)| {
+                let dir = &canonical[*idx];
+                other_idx != *idx && dir.starts_with(other) && (dir != other || other_idx < *idx)
+            })
+        })
+        .map(|(_, dir)| dir)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,10 +781,162 @@ mod tests {
             fixed_strings: false,
             advanced_regex: false,
             match_whole_word: false,
+            match_whole_line: false,
             match_case: false,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         }
     }
 
+    #[test]
+    fn test_search_config_builder() {
+        assert_eq!(
+            SearchConfig::builder("test", "replacement").build(),
+            SearchConfig {
+                match_case: true,
+                ..create_search_test_config()
+            }
+        );
+
+        let exprs = [("foo".to_string(), "bar".to_string())];
+        let config = SearchConfig::builder("test", "replacement")
+            .fixed_strings(true)
+            .advanced_regex(true)
+            .match_whole_word(true)
+            .match_whole_line(true)
+            .match_case(false)
+            .multiline(true)
+            .dotall(true)
+            .additional_exprs(&exprs)
+            .word_chars("-")
+            .build();
+        assert_eq!(
+            config,
+            SearchConfig {
+                search_text: "test",
+                replacement_text: "replacement",
+                fixed_strings: true,
+                advanced_regex: true,
+                match_whole_word: true,
+                match_whole_line: true,
+                match_case: false,
+                multiline: true,
+                dotall: true,
+                additional_exprs: &exprs,
+                word_chars: "-",
+                strict_captures: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_word_chars() {
+        let is_match = |search: &SearchType, line| crate::search::contains_search(line, search);
+
+        for fixed_strings in [true, false] {
+            let config = SearchConfig::builder("foo", "bar")
+                .fixed_strings(fixed_strings)
+                .match_whole_word(true)
+                .build();
+            let search = parse_search_text(&config).unwrap();
+            assert!(is_match(&search, "foo-bar"));
+            assert!(is_match(&search, "foo.bar"));
+            assert!(!is_match(&search, "foobar"));
+
+            let config = SearchConfig {
+                word_chars: "-]",
+                strict_captures: false,
+                ..config
+            };
+            let search = parse_search_text(&config).unwrap();
+            assert!(!is_match(&search, "foo-bar"));
+            assert!(!is_match(&search, "[foo]"));
+            assert!(is_match(&search, "foo.bar"));
+            assert!(!is_match(&search, "foobar"));
+        }
+    }
+
+    #[test]
+    fn test_match_whole_word_unicode() {
+        let is_match = |search: &SearchType, line| crate::search::contains_search(line, search);
+
+        for fixed_strings in [true, false] {
+            let search = |search_text| {
+                parse_search_text(
+                    &SearchConfig::builder(search_text, "")
+                        .fixed_strings(fixed_strings)
+                        .match_whole_word(true)
+                        .build(),
+                )
+                .unwrap()
+            };
+
+            let cafe = search("café");
+            assert!(is_match(&cafe, "un café, s'il vous plaît"));
+            assert!(is_match(&cafe, "«café»"));
+            assert!(!is_match(&cafe, "cafés"));
+            assert!(!is_match(&search("caf"), "café"));
+
+            let naive = search("naïve");
+            assert!(is_match(&naive, "(naïve)"));
+            assert!(!is_match(&naive, "naïvety"));
+            assert!(!is_match(&search("na"), "naïve"));
+            assert!(!is_match(&search("ve"), "naïve"));
+
+            let japan = search("日本");
+            assert!(is_match(&japan, "日本。"));
+            assert!(is_match(&japan, "東京, 日本"));
+            assert!(!is_match(&japan, "日本語"));
+            assert!(!is_match(&japan, "x日本"));
+        }
+    }
+
+    #[test]
+    fn test_match_whole_line() {
+        let is_match = |search: &SearchType, line| crate::search::contains_search(line, search);
+
+        let config = SearchConfig::builder("debug = true", "debug = false")
+            .fixed_strings(true)
+            .match_whole_line(true)
+            .build();
+        let search = parse_search_text(&config).unwrap();
+        assert!(is_match(&search, "debug = true"));
+        assert!(is_match(&search, "  debug = true\t"));
+        assert!(!is_match(&search, "debug = true # enabled"));
+        assert!(!is_match(&search, "# debug = true"));
+
+        let config = SearchConfig::builder(r"port = \d+|host = .*", "")
+            .match_whole_line(true)
+            .build();
+        let search = parse_search_text(&config).unwrap();
+        assert!(is_match(&search, "port = 8080"));
+        assert!(is_match(&search, "host = localhost"));
+        assert!(!is_match(&search, "port = 8080 # default"));
+        assert!(!is_match(&search, " port = 8080"));
+
+        let config = SearchConfig::builder("foo", "bar")
+            .match_whole_line(true)
+            .build();
+        let search = parse_search_bytes(&config).unwrap().unwrap();
+        assert!(search.is_match(b"foo"));
+        assert!(!search.is_match(b"foo bar"));
+
+        // Whole files are searched when replacing in memory, so lines are matched within the text
+        let exprs = [(parse_search_text(&config).unwrap(), "baz".to_owned())];
+        assert_eq!(
+            crate::replace::replace_all_patterns("bar\nfoo\r\nfoo bar\nfoo", &exprs, false, None),
+            Some("bar\nbaz\r\nfoo bar\nbaz".to_owned())
+        );
+        let search = parse_search_bytes(&config).unwrap().unwrap();
+        assert_eq!(
+            search.replace_all(b"bar\nfoo\r\nfoo bar\nfoo", b"baz".as_slice()),
+            b"bar\nbaz\r\nfoo bar\nbaz".as_slice()
+        );
+    }
+
     #[test]
     fn test_valid_configuration() {
         let config = create_search_test_config();
@@ -254,14 +966,98 @@ mod tests {
         assert!(error_handler.errors[0].contains("Failed to parse search text"));
     }
 
+    #[test]
+    fn test_regex_error_kinds() {
+        let validate = |search_text, advanced_regex| {
+            let config = SearchConfig::builder(search_text, "replacement")
+                .advanced_regex(advanced_regex)
+                .build();
+            let mut error_handler = SimpleErrorHandler::new();
+            let result = validate_search_configuration(config, None, &mut error_handler).unwrap();
+            assert!(matches!(result, ValidationResult::ValidationErrors));
+            error_handler.errors.join("\n")
+        };
+
+        assert!(validate("[abc", false).contains("Unbalanced bracket in regex"));
+        assert!(validate("(abc", true).contains("Unbalanced parenthesis in regex"));
+
+        for (search_text, kind) in [
+            ("[abc", RegexErrorKind::UnbalancedBracket),
+            ("(abc", RegexErrorKind::UnbalancedParenthesis),
+            ("abc)", RegexErrorKind::UnbalancedParenthesis),
+            (r"\q", RegexErrorKind::UnknownEscape),
+            ("foo(?=bar)", RegexErrorKind::Lookaround),
+            ("(?<!foo)bar", RegexErrorKind::Lookaround),
+            (r"(a)\1", RegexErrorKind::Backreference),
+            ("a{2,1}", RegexErrorKind::Other),
+        ] {
+            let error = Regex::new(search_text).unwrap_err();
+            assert_eq!(regex_error_kind(&error.to_string()), kind, "{search_text}");
+        }
+
+        // Advanced regex is suggested for lookaround and backreferences, including when another error is
+        // reported first
+        assert!(validate("foo(?=bar)", false).contains("--advanced-regex"));
+        assert!(validate(r"(a)\1", false).contains("--advanced-regex"));
+        assert!(validate("[bar(?=foo)", false).contains("--advanced-regex"));
+        assert!(!validate("[abc", false).contains("--advanced-regex"));
+        assert!(!validate("[bar(?=foo)", true).contains("--advanced-regex"));
+    }
+
+    #[test]
+    fn test_strict_captures() {
+        let validate = |search_text, replacement_text, fixed_strings| {
+            let config = SearchConfig::builder(search_text, replacement_text)
+                .fixed_strings(fixed_strings)
+                .strict_captures(true)
+                .build();
+            let mut error_handler = SimpleErrorHandler::new();
+            let result = validate_search_configuration(config, None, &mut error_handler).unwrap();
+            (result, error_handler.errors)
+        };
+
+        let (result, errors) = validate(r"(\w+) (?<last>\w+)", "$2 $1 ${last} $last $$3 $0", false);
+        assert!(matches!(result, ValidationResult::Success(_)));
+        assert!(errors.is_empty());
+
+        let (result, errors) = validate(r"(\w+) (\w+)", "$3 ${first}_x $2 $1a $3", false);
+        assert!(matches!(result, ValidationResult::ValidationErrors));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Invalid replacement text"));
+        assert!(errors[0].contains("$3, ${first}, $1a"));
+
+        // Fixed strings are exempt, as their replacement text isn't expanded
+        let (result, errors) = validate("(a)", "$3", true);
+        assert!(matches!(result, ValidationResult::Success(_)));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_missing_capture_groups_advanced() {
+        let search = SearchType::PatternAdvanced(FancyRegex::new(r"(?<=x)(a)(?!b)").unwrap());
+        assert_eq!(
+            missing_capture_groups(&search, "$1 $2 ${name} ${unterminated"),
+            vec!["$2".to_string(), "${name}".to_string()]
+        );
+    }
+
     #[test]
     fn test_invalid_include_glob() {
         let search_config = create_search_test_config();
         let dir_config = DirConfig {
             include_globs: Some("[invalid"),
             exclude_globs: None,
-            directory: std::env::temp_dir(),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            directories: vec![std::env::temp_dir()],
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
         let mut error_handler = SimpleErrorHandler::new();
 
@@ -277,6 +1073,253 @@ mod tests {
         assert!(error_handler.errors[0].contains("Failed to parse include globs"));
     }
 
+    #[test]
+    fn test_glob_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let glob_file = temp_dir.path().join("globs.txt");
+        std::fs::write(&glob_file, "# Source files\n*.rs\n\n!target/**\n").unwrap();
+
+        let dir_config = DirConfig {
+            include_globs: Some("*.md"),
+            exclude_globs: None,
+            glob_file: Some(&glob_file),
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let mut error_handler = SimpleErrorHandler::new();
+        let result = validate_search_configuration(
+            create_search_test_config(),
+            Some(dir_config),
+            &mut error_handler,
+        )
+        .unwrap();
+        let overrides = match result {
+            ValidationResult::Success((_, Some(parsed_dir_config))) => parsed_dir_config.overrides,
+            result => panic!("Expected valid configuration, found {result:?}"),
+        };
+        assert!(overrides.matched("main.rs", false).is_whitelist());
+        assert!(overrides.matched("README.md", false).is_whitelist());
+        assert!(overrides.matched("target/debug/main.rs", false).is_ignore());
+        assert!(overrides.matched("Cargo.toml", false).is_ignore());
+
+        std::fs::write(&glob_file, "[invalid\n").unwrap();
+        let dir_config = DirConfig {
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: Some(&glob_file),
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let result = validate_search_configuration(
+            create_search_test_config(),
+            Some(dir_config),
+            &mut error_handler,
+        );
+        assert!(matches!(
+            result.unwrap(),
+            ValidationResult::ValidationErrors
+        ));
+        assert!(error_handler.errors[0].contains("Failed to parse glob file"));
+    }
+
+    #[test]
+    fn test_file_types() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let include_types = ["rust".to_string(), "py".to_string()];
+        let exclude_types = ["markdown".to_string()];
+        let dir_config = DirConfig {
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &include_types,
+            exclude_types: &exclude_types,
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let mut error_handler = SimpleErrorHandler::new();
+        let result = validate_search_configuration(
+            create_search_test_config(),
+            Some(dir_config),
+            &mut error_handler,
+        )
+        .unwrap();
+        let overrides = match result {
+            ValidationResult::Success((_, Some(parsed_dir_config))) => parsed_dir_config.overrides,
+            result => panic!("Expected valid configuration, found {result:?}"),
+        };
+        assert!(overrides.matched("main.rs", false).is_whitelist());
+        assert!(overrides.matched("stubs.pyi", false).is_whitelist());
+        assert!(overrides.matched("README.md", false).is_ignore());
+        assert!(overrides.matched("Cargo.toml", false).is_ignore());
+
+        let exclude_types = ["cobol".to_string()];
+        let dir_config = DirConfig {
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &exclude_types,
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let result = validate_search_configuration(
+            create_search_test_config(),
+            Some(dir_config),
+            &mut error_handler,
+        );
+        assert!(matches!(
+            result.unwrap(),
+            ValidationResult::ValidationErrors
+        ));
+        assert!(error_handler.errors[0].contains("Failed to parse exclude globs"));
+        assert!(error_handler.errors[0].contains("'cobol' is not a known file type"));
+    }
+
+    #[test]
+    fn test_ignore_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let ignore_file = temp_dir.path().join("custom-ignore");
+        std::fs::write(
+            &ignore_file,
+            "# Generated code\n/generated/\n*.log\n!keep.log\n",
+        )
+        .unwrap();
+        let ignore_files = [ignore_file.clone()];
+
+        let dir_config = DirConfig {
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &ignore_files,
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: false,
+        };
+        let mut error_handler = SimpleErrorHandler::new();
+        let result = validate_search_configuration(
+            create_search_test_config(),
+            Some(dir_config),
+            &mut error_handler,
+        )
+        .unwrap();
+        let custom_ignore = match result {
+            ValidationResult::Success((_, Some(parsed_dir_config))) => {
+                parsed_dir_config.custom_ignore
+            }
+            result => panic!("Expected valid configuration, found {result:?}"),
+        };
+        let dir = temp_dir.path();
+        assert!(
+            custom_ignore
+                .matched(dir.join("generated"), true)
+                .is_ignore()
+        );
+        assert!(
+            custom_ignore
+                .matched(dir.join("src/generated"), true)
+                .is_none()
+        );
+        assert!(
+            custom_ignore
+                .matched(dir.join("src/debug.log"), false)
+                .is_ignore()
+        );
+        assert!(
+            custom_ignore
+                .matched(dir.join("keep.log"), false)
+                .is_whitelist()
+        );
+        assert!(custom_ignore.matched(dir.join("main.rs"), false).is_none());
+
+        // Unclosed character classes are allowed in gitignore syntax, but invalid ranges are not
+        std::fs::write(&ignore_file, "[z-a]\n").unwrap();
+        let dir_config = DirConfig {
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &ignore_files,
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let result = validate_search_configuration(
+            create_search_test_config(),
+            Some(dir_config),
+            &mut error_handler,
+        );
+        assert!(matches!(
+            result.unwrap(),
+            ValidationResult::ValidationErrors
+        ));
+        assert!(error_handler.errors[0].contains("Failed to parse ignore file"));
+    }
+
+    #[test]
+    fn test_dedup_directories() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("src/nested")).unwrap();
+        std::fs::create_dir_all(dir.join("tests")).unwrap();
+
+        assert_eq!(
+            dedup_directories(vec![
+                dir.join("src/nested"),
+                dir.join("tests"),
+                dir.join("src"),
+                dir.join("tests/../tests"),
+            ]),
+            vec![dir.join("tests"), dir.join("src")]
+        );
+        assert_eq!(
+            dedup_directories(vec![dir.join("tests"), dir.to_path_buf()]),
+            vec![dir.to_path_buf()]
+        );
+        assert_eq!(
+            dedup_directories(vec![dir.join("src"), dir.join("srcs")]),
+            vec![dir.join("src"), dir.join("srcs")]
+        );
+    }
+
     #[test]
     fn test_fixed_strings_mode() {
         let mut config = create_search_test_config();
@@ -319,15 +1362,18 @@ mod tests {
                 replacement_text: "",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: true,
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let converted = parse_search_text(&search_config).unwrap();
 
-            test_helpers::assert_pattern_contains(
-                &converted,
-                &["(?<![a-zA-Z0-9_])", "(?![a-zA-Z0-9_])", "test"],
-            );
+            test_helpers::assert_pattern_contains(&converted, &[r"(?<![\w])", r"(?![\w])", "test"]);
         }
 
         #[test]
@@ -337,8 +1383,14 @@ mod tests {
                 replacement_text: "",
                 fixed_strings: true,
                 match_whole_word: false,
+                match_whole_line: false,
                 match_case: false,
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let converted = parse_search_text(&search_config).unwrap();
 
@@ -352,14 +1404,20 @@ mod tests {
                 replacement_text: "",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let converted = parse_search_text(&search_config).unwrap();
 
             test_helpers::assert_pattern_contains(
                 &converted,
-                &["(?<![a-zA-Z0-9_])", "(?![a-zA-Z0-9_])", "(?i)", "Test"],
+                &[r"(?<![\w])", r"(?![\w])", "(?i)", "Test"],
             );
         }
 
@@ -370,8 +1428,14 @@ mod tests {
                 replacement_text: "",
                 fixed_strings: true,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: true,
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let converted = parse_search_text(&search_config).unwrap();
 
@@ -385,14 +1449,20 @@ mod tests {
                 replacement_text: "",
                 fixed_strings: false,
                 match_whole_word: true,
+                match_whole_line: false,
                 match_case: false,
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let converted = parse_search_text(&search_config).unwrap();
 
             test_helpers::assert_pattern_contains(
                 &converted,
-                &["(?<![a-zA-Z0-9_])", "(?![a-zA-Z0-9_])", "(?i)", r"\d+"],
+                &[r"(?<![\w])", r"(?![\w])", "(?i)", r"\d+"],
             );
         }
 
@@ -403,8 +1473,14 @@ mod tests {
                 replacement_text: "",
                 fixed_strings: true,
                 match_whole_word: false,
+                match_whole_line: false,
                 match_case: false, // forces regex wrapping
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let converted = parse_search_text(&search_config).unwrap();
             test_helpers::assert_pattern_contains(&converted, &[r"\(foo", "(?i)"]);
@@ -417,8 +1493,14 @@ mod tests {
                 replacement_text: "",
                 fixed_strings: true,
                 match_whole_word: false,
+                match_whole_line: false,
                 match_case: false, // forces regex wrapping
                 advanced_regex: false,
+                multiline: false,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
             };
             let converted = parse_search_text(&search_config).unwrap();
             test_helpers::assert_pattern_contains(
@@ -1,8 +1,22 @@
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
 use indoc::indoc;
+use std::io::{Read, Write};
+use std::num::NonZero;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use frep_core::{
-    run::{find_and_replace, find_and_replace_text},
-    validation::{DirConfig, SearchConfig},
+    replace::{PostCommand, ReplaceCommand, ReplacementFunctions},
+    run::{
+        CompiledSearch, UnexpectedCountError, count_matches, count_matches_with_config,
+        explain_filters, find_and_replace, find_and_replace_bytes, find_and_replace_streaming,
+        find_and_replace_text, find_and_replace_text_with_config, find_and_replace_with_config,
+        find_and_replace_with_stats, find_matches, find_matching_paths, list_files, replace_file,
+    },
+    search::{FileSearcherConfig, FilterDecision},
+    store::MemoryStore,
+    undo::{self, UndoJournal},
+    validation::{DirConfig, GlobPrecedence, SearchConfig, parse_search_text},
 };
 
 mod utils;
@@ -33,13 +47,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -93,13 +122,28 @@ test_with_both_regex_modes!(
             fixed_strings: false,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -147,13 +191,28 @@ test_with_both_regex_modes!(
             fixed_strings: false,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some(""),
             exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -166,13 +225,28 @@ test_with_both_regex_modes!(
             fixed_strings: false,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("logs.txt"),
             exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -217,61 +291,45 @@ async fn test_headless_advanced_regex_features() -> anyhow::Result<()> {
         ),
     );
 
-    // Negative lookahead - match 'let' but not 'let mut'
-    let search_config = SearchConfig {
-        search_text: r"let(?!\s+mut)",
-        replacement_text: "const",
-        fixed_strings: false,
-        match_case: true,
-        match_whole_word: false,
-        advanced_regex: true,
-    };
-    let dir_config = DirConfig {
-        directory: temp_dir.path().to_path_buf(),
-        include_globs: Some("code.rs"),
-        exclude_globs: None,
+    let dir_config = |include_globs, exclude_globs| DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: Some(include_globs),
+        exclude_globs,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
         include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
     };
 
-    let result = find_and_replace(search_config, dir_config);
+    // Negative lookahead - match 'let' but not 'let mut'
+    let search_config = SearchConfig::builder(r"let(?!\s+mut)", "const")
+        .advanced_regex(true)
+        .build();
+
+    let result = find_and_replace(search_config, dir_config("code.rs", None));
     assert_eq!(result.unwrap(), "Success: 1 file updated\n".to_string());
 
     // Positive lookbehind - match numbers after headings
-    let search_config = SearchConfig {
-        search_text: r"(?<=# )[A-Za-z]+\s+(\d+)",
-        replacement_text: "Section $1",
-        fixed_strings: false,
-        match_case: true,
-        match_whole_word: false,
-        advanced_regex: true,
-    };
-    let dir_config = DirConfig {
-        directory: temp_dir.path().to_path_buf(),
-        include_globs: Some("*.md"),
-        exclude_globs: Some(""),
-        include_hidden: false,
-    };
+    let search_config = SearchConfig::builder(r"(?<=# )[A-Za-z]+\s+(\d+)", "Section $1")
+        .advanced_regex(true)
+        .build();
 
-    let result = find_and_replace(search_config, dir_config);
+    let result = find_and_replace(search_config, dir_config("*.md", Some("")));
     assert_eq!(result.unwrap(), "Success: 1 file updated\n".to_string());
 
     // Add spaces after commas in CSV file
-    let search_config = SearchConfig {
-        search_text: ",",
-        replacement_text: ", ",
-        fixed_strings: true,
-        match_case: true,
-        match_whole_word: false,
-        advanced_regex: true,
-    };
-    let dir_config = DirConfig {
-        directory: temp_dir.path().to_path_buf(),
-        include_globs: Some("*.csv"),
-        exclude_globs: None,
-        include_hidden: false,
-    };
+    let search_config = SearchConfig::builder(",", ", ")
+        .fixed_strings(true)
+        .advanced_regex(true)
+        .build();
 
-    let result = find_and_replace(search_config, dir_config);
+    let result = find_and_replace(search_config, dir_config("*.csv", None));
     assert_eq!(result.unwrap(), "Success: 1 file updated\n".to_string());
 
     assert_test_files!(
@@ -331,13 +389,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("**/*.rs"),
             exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -375,13 +448,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("**/*.rs"),
             exclude_globs: Some("tests/**"),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -419,13 +507,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("**/*.md,**/*.txt"),
             exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -481,13 +584,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: true,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some(""),
             exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -511,6 +629,69 @@ test_with_both_regex_modes_and_fixed_strings!(
     }
 );
 
+test_with_both_regex_modes_and_fixed_strings!(
+    test_headless_match_whole_line,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "config.toml" => text!(
+                "debug = true",
+                "debug = true # enabled",
+                "# debug = true",
+                "debug = true",
+            ),
+            "no_newline.toml" => b"name = \"app\"\ndebug = true",
+            "crlf.toml" => b"debug = true\r\nport = 8080\r\n",
+        );
+
+        let search_config = SearchConfig {
+            search_text: "debug = true",
+            replacement_text: "debug = false",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: true,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: Some(""),
+            exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 3 files updated\n".to_string());
+
+        assert_test_files!(
+            &temp_dir,
+            "config.toml" => text!(
+                "debug = false",
+                "debug = true # enabled",
+                "# debug = true",
+                "debug = false",
+            ),
+            "no_newline.toml" => b"name = \"app\"\ndebug = false",
+            "crlf.toml" => b"debug = false\r\nport = 8080\r\n",
+        );
+
+        Ok(())
+    }
+);
+
 test_with_both_regex_modes_and_fixed_strings!(
     test_headless_case_sensitivity,
     |advanced_regex, fixed_strings| async move {
@@ -533,13 +714,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir1.path().to_path_buf(),
+            directories: vec![temp_dir1.path().to_path_buf()],
             include_globs: Some(""),
             exclude_globs: Some(""),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -578,13 +774,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: false,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir2.path().to_path_buf(),
+            directories: vec![temp_dir2.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -625,13 +836,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -675,13 +901,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -719,13 +960,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: true,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -768,13 +1024,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false, // Default behavior
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -802,13 +1073,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: true, // Include hidden files
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -848,13 +1134,28 @@ test_with_both_regex_modes!(
             fixed_strings: false,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -888,13 +1189,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("{{"), // Invalid glob pattern
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -960,13 +1276,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("*.txt"),
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -1018,13 +1349,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("*.txt"),
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
         let result = find_and_replace(search_config, dir_config);
         assert!(result.is_ok());
@@ -1081,13 +1427,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: Some("*.txt"),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -1227,13 +1588,28 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: Some("**/*.rs"),
             exclude_globs: Some("tests/**"),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
@@ -1363,18 +1739,39 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
         let dir_config = DirConfig {
-            directory: temp_dir.path().to_path_buf(),
+            directories: vec![temp_dir.path().to_path_buf()],
             include_globs: None,
             exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
             include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
         };
 
         let result = find_and_replace(search_config, dir_config);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "Success: 2 files updated\n".to_string());
+        assert_eq!(
+            result.unwrap(),
+            format!(
+                "Success: 2 files updated\nWarning: {}: invalid UTF-8 at byte offset 36, so lines that aren't valid UTF-8 were left unchanged\nSkipped 1 binary file\n",
+                temp_dir.path().join("contains_binary.txt").display()
+            )
+        );
 
         assert_test_files!(
             &temp_dir,
@@ -1410,6 +1807,141 @@ test_with_both_regex_modes_and_fixed_strings!(
     }
 );
 
+test_with_both_regex_modes_and_fixed_strings!(
+    test_headless_include_binary,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "text.txt" => text!(
+                "This is a text file with PATTERN."
+            ),
+            "data.dat" => binary!(
+                b"\x00\x01PATTERN\x02",
+                b"\x00PATTERN",
+            ),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "PATTERN",
+            replacement_text: "REPLACED",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let searcher_config = FileSearcherConfig {
+            include_binary: true,
+            ..FileSearcherConfig::default()
+        };
+
+        let result = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+        assert_eq!(result.num_files_replaced, 2);
+        assert_eq!(result.num_binary_files_skipped, 0);
+
+        assert_test_files!(
+            &temp_dir,
+            "text.txt" => text!(
+                "This is a text file with REPLACED."
+            ),
+            "data.dat" => binary!(
+                b"\x00\x01REPLACED\x02",
+                b"\x00REPLACED",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes!(test_errors_reported, |advanced_regex| async move {
+    let temp_dir = create_test_files!(
+        "small.txt" => text!("PATTERN"),
+        "large.txt" => text!(
+            "This file is larger than the maximum in-memory size,",
+            "so can't be replaced in multiline mode: PATTERN",
+        ),
+    );
+
+    let search_config = SearchConfig {
+        search_text: "PATTERN",
+        replacement_text: "REPLACED",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline: true,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        max_in_memory_size: Some(20),
+        ..FileSearcherConfig::default()
+    };
+
+    let summary = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(summary.errors.len(), 1);
+    let (path, error) = &summary.errors[0];
+    assert_eq!(path, &temp_dir.path().join("large.txt"));
+    assert!(error.contains("maximum in-memory size"));
+    assert_eq!(
+        summary.to_string(),
+        format!(
+            "Success: 1 file updated\nFailed to process 1 file:\n  {}: {error}\n",
+            path.display()
+        )
+    );
+
+    assert_test_files!(
+        &temp_dir,
+        "small.txt" => text!("REPLACED"),
+        "large.txt" => text!(
+            "This file is larger than the maximum in-memory size,",
+            "so can't be replaced in multiline mode: PATTERN",
+        ),
+    );
+
+    Ok(())
+});
+
 test_with_both_regex_modes!(test_no_multiline_matches, |advanced_regex| async move {
     let temp_dir = create_test_files!(
         "multiline.txt" => text!(
@@ -1430,13 +1962,28 @@ test_with_both_regex_modes!(test_no_multiline_matches, |advanced_regex| async mo
         fixed_strings: false,
         match_case: true,
         match_whole_word: false,
+        match_whole_line: false,
         advanced_regex,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
     };
     let dir_config = DirConfig {
-        directory: temp_dir.path().to_path_buf(),
+        directories: vec![temp_dir.path().to_path_buf()],
         include_globs: None,
         exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
         include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
     };
 
     let result = find_and_replace(search_config, dir_config);
@@ -1461,13 +2008,21 @@ test_with_both_regex_modes!(test_no_multiline_matches, |advanced_regex| async mo
 });
 
 test_with_both_regex_modes_and_fixed_strings!(
-    test_text_basic_replacement,
+    test_dry_run_does_not_modify_files,
     |advanced_regex, fixed_strings| async move {
-        let input_text = indoc! {"
-            This is a test text.
-            It contains TEST_PATTERN that should be replaced.
-            Multiple lines with TEST_PATTERN here."
-        };
+        let temp_dir = create_test_files!(
+            "file1.txt" => text!(
+                "This is a test file.",
+                "It contains TEST_PATTERN that should be replaced.",
+                "Multiple lines with TEST_PATTERN here.",
+            ),
+            "subdir/file2.txt" => text!(
+                "Nested file with TEST_PATTERN.",
+            ),
+            "file3.txt" => text!(
+                "No matches here.",
+            ),
+        );
 
         let search_config = SearchConfig {
             search_text: "TEST_PATTERN",
@@ -1475,93 +2030,3830 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let searcher_config = FileSearcherConfig {
+            dry_run: true,
+            ..FileSearcherConfig::default()
         };
 
-        let result = find_and_replace_text(input_text, search_config);
+        let result = find_and_replace_with_config(search_config, dir_config, searcher_config);
         assert!(result.is_ok());
         assert_eq!(
-            result.unwrap(),
-            indoc! {"
-                This is a test text.
-                It contains REPLACEMENT that should be replaced.
-                Multiple lines with REPLACEMENT here."
-            }
+            result.unwrap().to_string(),
+            "Dry run: 2 files, 3 matches would be updated\n".to_string()
+        );
+
+        assert_test_files!(
+            &temp_dir,
+            "file1.txt" => text!(
+                "This is a test file.",
+                "It contains TEST_PATTERN that should be replaced.",
+                "Multiple lines with TEST_PATTERN here.",
+            ),
+            "subdir/file2.txt" => text!(
+                "Nested file with TEST_PATTERN.",
+            ),
+            "file3.txt" => text!(
+                "No matches here.",
+            ),
         );
 
         Ok(())
     }
 );
 
-test_with_both_regex_modes!(test_text_regex_replacement, |advanced_regex| async move {
-    let input_text = indoc! {"
-            Numbers: 123, 456, and 789.
-            Phone: (555) 123-4567
-            IP: 192.168.1.1"
-    };
-
-    let search_config = SearchConfig {
-        search_text: r"\d{3}",
-        replacement_text: "XXX",
-        fixed_strings: false,
-        match_case: true,
-        match_whole_word: false,
-        advanced_regex,
-    };
-
-    let result = find_and_replace_text(input_text, search_config);
-    assert!(result.is_ok());
-    assert_eq!(
-        result.unwrap(),
-        indoc! {"
-                Numbers: XXX, XXX, and XXX.
-                Phone: (XXX) XXX-XXX7
-                IP: XXX.XXX.1.1"
-        }
-    );
-
-    Ok(())
-});
-
-test_with_both_regex_modes!(
-    test_text_regex_with_capture_groups,
-    |advanced_regex| async move {
-        let input_text = indoc! {"
-            username: john_doe, email: john@example.com
-            username: jane_smith, email: jane@example.com"
-        };
+test_with_both_regex_modes_and_fixed_strings!(
+    test_diff_output,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "file1.txt" => text!(
+                "This is a test file.",
+                "It contains TEST_PATTERN that should be replaced.",
+            ),
+            "file2.txt" => text!(
+                "No matches here.",
+            ),
+            "binary.bin" => &[10, 19, 3, 92],
+        );
 
         let search_config = SearchConfig {
-            search_text: r"username: (\w+), email: ([^@]+)@",
-            replacement_text: "user: $1 (contact: $2 at",
-            fixed_strings: false,
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let searcher_config = FileSearcherConfig {
+            diff: true,
+            ..FileSearcherConfig::default()
         };
 
-        let result = find_and_replace_text(input_text, search_config);
-        assert!(result.is_ok());
+        let result = find_and_replace_with_config(search_config, dir_config, searcher_config);
+        let path = temp_dir.path().join("file1.txt");
         assert_eq!(
-            result.unwrap(),
-            indoc! {"
-                user: john_doe (contact: john atexample.com
-                user: jane_smith (contact: jane atexample.com"
-            }
+            result?.to_string(),
+            format!(
+                "--- {p}\n+++ {p}\n@@ -1,2 +1,2 @@\n This is a test file.\n-It contains TEST_PATTERN that should be replaced.\n+It contains REPLACEMENT that should be replaced.\n",
+                p = path.display()
+            )
         );
 
-        let input_text2 = indoc! {"
-            [2023-01-15] INFO: System started
-            [2023-02-20] ERROR: Connection failed"
-        };
-
-        let search_config2 = SearchConfig {
-            search_text: r"\[(\d{4})-(\d{2})-(\d{2})\]",
-            replacement_text: "[$3/$2/$1]",
+        assert_test_files!(
+            &temp_dir,
+            "file1.txt" => text!(
+                "This is a test file.",
+                "It contains REPLACEMENT that should be replaced.",
+            ),
+            "file2.txt" => text!(
+                "No matches here.",
+            ),
+            "binary.bin" => &[10, 19, 3, 92],
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_json_output,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "file1.txt" => text!(
+                "First \"TEST_PATTERN\" here.",
+                "No match.",
+                "Then TEST_PATTERN\tagain.",
+            ),
+            "file2.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let searcher_config = FileSearcherConfig {
+            collect_line_results: true,
+            ..FileSearcherConfig::default()
+        };
+
+        let summary = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+        assert_eq!(summary.to_string(), "Success: 1 file updated\n");
+        assert_eq!(
+            summary.to_json(),
+            format!(
+                r#"{{"num_files_replaced":1,"num_matches":2,"dry_run":false,"files":[{{"path":"{path}","num_matches":2,"lines":[{{"line_number":1,"before":"First \"TEST_PATTERN\" here.","after":"First \"REPLACEMENT\" here.","error":null}},{{"line_number":3,"before":"Then TEST_PATTERN\tagain.","after":"Then REPLACEMENT\tagain.","error":null}}]}}],"errors":[],"skipped":[],"warnings":[]}}"#,
+                path = temp_dir.path().join("file1.txt").display()
+            )
+        );
+
+        assert_test_files!(
+            &temp_dir,
+            "file1.txt" => text!(
+                "First \"REPLACEMENT\" here.",
+                "No match.",
+                "Then REPLACEMENT\tagain.",
+            ),
+            "file2.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_find_and_replace_with_stats,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "file1.txt" => text!(
+                "TEST_PATTERN on the first line.",
+                "No match.",
+                "TEST_PATTERN twice, TEST_PATTERN.",
+            ),
+            "subdir/file2.txt" => text!(
+                "Nested TEST_PATTERN.",
+            ),
+            "file3.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let summary = find_and_replace_with_stats(search_config, dir_config)?;
+        assert_eq!(summary.num_files_replaced, 2);
+        assert_eq!(summary.num_matches, 4);
+        assert_eq!(summary.stats.num_successes, 3);
+        assert!(summary.stats.errors.is_empty());
+        assert_eq!(summary.to_string(), "Success: 2 files updated\n");
+
+        assert_test_files!(
+            &temp_dir,
+            "file1.txt" => text!(
+                "REPLACEMENT on the first line.",
+                "No match.",
+                "REPLACEMENT twice, REPLACEMENT.",
+            ),
+            "subdir/file2.txt" => text!(
+                "Nested REPLACEMENT.",
+            ),
+            "file3.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_modified_paths,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "b.txt" => text!("TEST_PATTERN"),
+            "subdir/c.txt" => text!("Nested TEST_PATTERN"),
+            "a.txt" => text!("TEST_PATTERN"),
+            "d.txt" => text!("No match"),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        // Nothing is modified in a dry run
+        let summary = find_and_replace_with_config(
+            search_config.clone(),
+            dir_config.clone(),
+            FileSearcherConfig {
+                dry_run: true,
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.num_files_replaced, 3);
+        assert!(summary.modified_paths.is_empty());
+
+        let summary =
+            find_and_replace_with_config(search_config, dir_config, FileSearcherConfig::default())?;
+        assert_eq!(
+            summary.modified_paths,
+            vec![
+                temp_dir.path().join("a.txt"),
+                temp_dir.path().join("b.txt"),
+                temp_dir.path().join("subdir/c.txt"),
+            ]
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_replacement_counts,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "b.txt" => text!(
+                "TEST_PATTERN",
+                "Other line",
+                "TEST_PATTERN and TEST_PATTERN",
+            ),
+            "subdir/c.txt" => text!("Nested TEST_PATTERN"),
+            "a.txt" => text!("TEST_PATTERN"),
+            "d.txt" => text!("No match"),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let expected_counts = vec![
+            (temp_dir.path().join("a.txt"), 1),
+            (temp_dir.path().join("b.txt"), 3),
+            (temp_dir.path().join("subdir/c.txt"), 1),
+        ];
+
+        // Counts are reported in a dry run, as well as when replacing
+        let summary = find_and_replace_with_config(
+            search_config.clone(),
+            dir_config.clone(),
+            FileSearcherConfig {
+                dry_run: true,
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.replacement_counts, expected_counts);
+
+        let summary =
+            find_and_replace_with_config(search_config, dir_config, FileSearcherConfig::default())?;
+        assert_eq!(summary.replacement_counts, expected_counts);
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_rename,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "TOKEN_dir/TOKEN_file.txt" => text!("TOKEN inside"),
+            "TOKEN_dir/other.txt" => text!("No match"),
+            "plain.txt" => text!("TOKEN"),
+            "TOKEN_a.txt" => text!("Collides"),
+            "NEW_a.txt" => text!("Existing"),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TOKEN",
+            replacement_text: "NEW",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let expected_renamed = vec![
+            (
+                temp_dir.path().join("TOKEN_dir"),
+                temp_dir.path().join("NEW_dir"),
+            ),
+            (
+                temp_dir.path().join("TOKEN_dir/TOKEN_file.txt"),
+                temp_dir.path().join("NEW_dir/NEW_file.txt"),
+            ),
+        ];
+
+        // Renames are reported but not made in a dry run
+        let summary = find_and_replace_with_config(
+            search_config.clone(),
+            dir_config.clone(),
+            FileSearcherConfig {
+                dry_run: true,
+                rename: true,
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.renamed, expected_renamed);
+        assert_eq!(summary.errors.len(), 1);
+        assert_test_files!(
+            &temp_dir,
+            "TOKEN_dir/TOKEN_file.txt" => text!("TOKEN inside"),
+            "TOKEN_dir/other.txt" => text!("No match"),
+            "plain.txt" => text!("TOKEN"),
+            "TOKEN_a.txt" => text!("Collides"),
+            "NEW_a.txt" => text!("Existing"),
+        );
+
+        let summary = find_and_replace_with_config(
+            search_config,
+            dir_config,
+            FileSearcherConfig {
+                rename: true,
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.renamed, expected_renamed);
+        assert_eq!(
+            summary.errors,
+            vec![(
+                temp_dir.path().join("TOKEN_a.txt"),
+                format!(
+                    "Failed to rename: {} already exists",
+                    temp_dir.path().join("NEW_a.txt").display()
+                )
+            )]
+        );
+        assert_eq!(
+            summary.modified_paths,
+            vec![
+                temp_dir.path().join("NEW_dir/NEW_file.txt"),
+                temp_dir.path().join("plain.txt"),
+            ]
+        );
+        assert_test_files!(
+            &temp_dir,
+            "NEW_dir/NEW_file.txt" => text!("NEW inside"),
+            "NEW_dir/other.txt" => text!("No match"),
+            "plain.txt" => text!("NEW"),
+            "TOKEN_a.txt" => text!("Collides"),
+            "NEW_a.txt" => text!("Existing"),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_find_matches,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "b.txt" => text!(
+                "No match.",
+                "TEST_PATTERN twice, TEST_PATTERN.",
+                "TEST_PATTERN again.",
+            ),
+            "a.txt" => text!(
+                "Just TEST_PATTERN.",
+            ),
+            "c.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let results = find_matches(search_config, dir_config)?;
+        assert_eq!(
+            results
+                .iter()
+                .map(|r| (
+                    r.path.clone().unwrap(),
+                    r.line_number,
+                    r.line.as_str(),
+                    r.match_count
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (temp_dir.path().join("a.txt"), 1, "Just TEST_PATTERN.", 1),
+                (
+                    temp_dir.path().join("b.txt"),
+                    2,
+                    "TEST_PATTERN twice, TEST_PATTERN.",
+                    2
+                ),
+                (temp_dir.path().join("b.txt"), 3, "TEST_PATTERN again.", 1),
+            ]
+        );
+
+        assert_test_files!(
+            &temp_dir,
+            "b.txt" => text!(
+                "No match.",
+                "TEST_PATTERN twice, TEST_PATTERN.",
+                "TEST_PATTERN again.",
+            ),
+            "a.txt" => text!(
+                "Just TEST_PATTERN.",
+            ),
+            "c.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_count_matches,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "b.txt" => text!(
+                "TEST_PATTERN once.",
+                "TEST_PATTERN twice, TEST_PATTERN.",
+                "No match.",
+            ),
+            "a.txt" => text!(
+                "Just TEST_PATTERN.",
+            ),
+            "c.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let summary = count_matches(search_config, dir_config)?;
+        assert_eq!(
+            summary.file_counts,
+            vec![
+                (temp_dir.path().join("a.txt"), 1),
+                (temp_dir.path().join("b.txt"), 2),
+            ]
+        );
+        assert_eq!(summary.total(), 3);
+        // The line containing the pattern twice counts as two matches
+        assert_eq!(summary.num_matches, 4);
+        assert_eq!(
+            summary.to_string(),
+            format!(
+                "{}:1\n{}:2\nTotal: 4 matches across 3 lines in 2 files\n",
+                temp_dir.path().join("a.txt").display(),
+                temp_dir.path().join("b.txt").display(),
+            )
+        );
+
+        assert_test_files!(
+            &temp_dir,
+            "b.txt" => text!(
+                "TEST_PATTERN once.",
+                "TEST_PATTERN twice, TEST_PATTERN.",
+                "No match.",
+            ),
+            "a.txt" => text!(
+                "Just TEST_PATTERN.",
+            ),
+            "c.txt" => text!(
+                "No matches here.",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_context_lines,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "a.txt" => text!(
+                "one",
+                "TEST_PATTERN",
+                "three",
+                "four",
+                "five",
+                "TEST_PATTERN",
+            ),
+        );
+        let search_config = || SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = || DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let p = temp_dir.path().join("a.txt");
+        let p = p.display();
+        let expected_context = format!(
+            "{p}-1-one\n{p}:2:TEST_PATTERN\n{p}-3-three\n--\n{p}-5-five\n{p}:6:TEST_PATTERN\n"
+        );
+
+        let summary = count_matches_with_config(
+            search_config(),
+            dir_config(),
+            FileSearcherConfig {
+                before_context: Some(1),
+                after_context: Some(1),
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.total(), 2);
+        assert_eq!(
+            summary.to_string(),
+            format!("{expected_context}{p}:2\nTotal: 2 matches across 2 lines in 1 file\n")
+        );
+
+        let summary = find_and_replace_with_config(
+            search_config(),
+            dir_config(),
+            FileSearcherConfig {
+                dry_run: true,
+                before_context: Some(1),
+                after_context: Some(1),
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(
+            summary.to_string(),
+            format!("{expected_context}Dry run: 1 file, 2 matches would be updated\n")
+        );
+
+        assert_test_files!(
+            &temp_dir,
+            "a.txt" => text!(
+                "one",
+                "TEST_PATTERN",
+                "three",
+                "four",
+                "five",
+                "TEST_PATTERN",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_follow_symlinks() -> anyhow::Result<()> {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = create_test_files!(
+        "file.txt" => text!("Top-level TEST_PATTERN"),
+        "dir/nested.txt" => text!("Nested TEST_PATTERN"),
+    );
+    let linked_dir = create_test_files!(
+        "linked.txt" => text!("Linked TEST_PATTERN"),
+    );
+    let linked_file_dir = create_test_files!(
+        "target.txt" => text!("Target TEST_PATTERN"),
+    );
+    // A self-referential symlink, which must not cause the walker to recurse infinitely
+    symlink(temp_dir.path(), temp_dir.path().join("dir/loop"))?;
+    symlink(linked_dir.path(), temp_dir.path().join("link"))?;
+    symlink(
+        linked_file_dir.path().join("target.txt"),
+        temp_dir.path().join("file_link.txt"),
+    )?;
+
+    let search_config = SearchConfig {
+        search_text: "TEST_PATTERN",
+        replacement_text: "REPLACEMENT",
+        fixed_strings: true,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex: false,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let dir_config = |follow_symlinks| DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    // Symlinks are not followed by default
+    let result = find_and_replace(search_config.clone(), dir_config(false))?;
+    assert_eq!(result, "Success: 2 files updated\n");
+    assert_eq!(
+        std::fs::read_to_string(linked_dir.path().join("linked.txt"))?,
+        "Linked TEST_PATTERN\n"
+    );
+
+    std::fs::write(temp_dir.path().join("file.txt"), "Top-level TEST_PATTERN\n")?;
+    let result = find_and_replace(search_config, dir_config(true))?;
+    assert_eq!(result, "Success: 3 files updated\n");
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.path().join("file.txt"))?,
+        "Top-level REPLACEMENT\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(linked_dir.path().join("linked.txt"))?,
+        "Linked REPLACEMENT\n"
+    );
+    // The target of a file symlink is updated, rather than the link being replaced
+    assert_eq!(
+        std::fs::read_to_string(linked_file_dir.path().join("target.txt"))?,
+        "Target REPLACEMENT\n"
+    );
+    assert!(
+        std::fs::symlink_metadata(temp_dir.path().join("file_link.txt"))?
+            .file_type()
+            .is_symlink()
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_no_follow_symlink_writes() -> anyhow::Result<()> {
+    use std::os::unix::fs::symlink;
+
+    let temp_dir = create_test_files!(
+        "file.txt" => text!("Top-level TEST_PATTERN"),
+    );
+    let linked_file_dir = create_test_files!(
+        "target.txt" => text!("Target TEST_PATTERN"),
+    );
+    symlink(
+        linked_file_dir.path().join("target.txt"),
+        temp_dir.path().join("file_link.txt"),
+    )?;
+
+    let search_config = SearchConfig {
+        search_text: "TEST_PATTERN",
+        replacement_text: "REPLACEMENT",
+        fixed_strings: true,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex: false,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: true,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        no_follow_symlink_writes: true,
+        ..FileSearcherConfig::default()
+    };
+
+    let summary = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(summary.errors.len(), 1);
+    let (path, error) = &summary.errors[0];
+    assert_eq!(path, &temp_dir.path().join("file_link.txt"));
+    assert!(error.contains("is a symlink"));
+
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.path().join("file.txt"))?,
+        "Top-level REPLACEMENT\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(linked_file_dir.path().join("target.txt"))?,
+        "Target TEST_PATTERN\n"
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_replace_command() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "file1.txt" => text!(
+            "id: aGVsbG8=",
+            "no match",
+            "ids: d29ybGQ= aGVsbG8=",
+        ),
+        "file2.txt" => text!("No match"),
+    );
+
+    let search_config = SearchConfig::builder(r"[A-Za-z0-9+/]{4,}=", "").build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        replace_command: Some(ReplaceCommand::new("base64 -d")),
+        ..FileSearcherConfig::default()
+    };
+
+    // Dry runs use the command output too
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            dry_run: true,
+            ..searcher_config.clone()
+        },
+    )?;
+    assert_eq!(
+        summary.files[0]
+            .replacements
+            .iter()
+            .map(|r| r.replacement.as_str())
+            .collect::<Vec<_>>(),
+        vec!["id: hello", "ids: world hello"]
+    );
+
+    let summary = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_test_files!(
+        &temp_dir,
+        "file1.txt" => text!(
+            "id: hello",
+            "no match",
+            "ids: world hello",
+        ),
+        "file2.txt" => text!("No match"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_stats() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "file1.txt" => text!("needle", "hay", "needle"),
+        "file2.txt" => text!("hay"),
+        "image.png" => text!("needle"),
+        "data.dat" => b"needle\0",
+        "large.txt" => &[b'a'; 200],
+    );
+
+    let search_config = SearchConfig::builder("needle", "pin").build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        size_filter: Some((None, Some(100))),
+        ..FileSearcherConfig::default()
+    };
+
+    let stats = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        searcher_config.clone(),
+    )?
+    .stats;
+    assert_eq!(stats.num_files_searched, 2);
+    assert_eq!(stats.num_files_skipped, 3);
+    assert_eq!(stats.num_files_found(), 5);
+    assert_eq!(stats.num_files_replaced, 1);
+    assert_eq!(stats.num_matches, 2);
+
+    let output = stats.to_string();
+    assert!(output.starts_with(
+        "Files searched: 2\nFiles skipped: 3\nFiles with replacements: 1\nMatches: 2\nElapsed: "
+    ));
+
+    // Files excluded by globs aren't found at all
+    let dir_config = DirConfig {
+        include_globs: Some("*.rss"),
+        ..dir_config
+    };
+    let stats = find_and_replace_with_config(search_config, dir_config, searcher_config)?.stats;
+    assert_eq!(stats.num_files_found(), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_preserve_indent() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "config.toml" => text!(
+            "debug = true",
+            "    debug = true",
+            "\tdebug = true",
+            "\t  debug = true",
+        ),
+    );
+
+    let search_config = SearchConfig::builder(r"\s*debug = true", "debug = false")
+        .match_whole_line(true)
+        .build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        preserve_indent: true,
+        ..FileSearcherConfig::default()
+    };
+
+    // Dry runs show the replacements with the indentation restored
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            dry_run: true,
+            ..searcher_config.clone()
+        },
+    )?;
+    assert_eq!(
+        summary.files[0]
+            .replacements
+            .iter()
+            .map(|r| r.replacement.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "debug = false",
+            "    debug = false",
+            "\tdebug = false",
+            "\t  debug = false"
+        ]
+    );
+
+    let summary =
+        find_and_replace_with_config(search_config.clone(), dir_config, searcher_config.clone())?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_test_files!(
+        &temp_dir,
+        "config.toml" => text!(
+            "debug = false",
+            "    debug = false",
+            "\tdebug = false",
+            "\t  debug = false",
+        ),
+    );
+
+    let result = find_and_replace_text_with_config(
+        "  debug = true\n\tdebug = true\n",
+        search_config,
+        &searcher_config,
+    )?;
+    assert_eq!(result, "  debug = false\n\tdebug = false\n");
+
+    Ok(())
+}
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_template,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "src/main.rs" => text!(
+                "// TODO",
+                "fn main() {}",
+                "// TODO {lineno}",
+            ),
+            "notes.txt" => text!(
+                "TODO",
+            ),
+        );
+
+        let search_config = SearchConfig::builder("TODO", "TODO({filename}:{lineno})")
+            .fixed_strings(fixed_strings)
+            .advanced_regex(advanced_regex)
+            .build();
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+        let searcher_config = FileSearcherConfig {
+            template: true,
+            ..FileSearcherConfig::default()
+        };
+
+        let summary = find_and_replace_with_config(
+            search_config.clone(),
+            dir_config.clone(),
+            FileSearcherConfig {
+                dry_run: true,
+                ..searcher_config.clone()
+            },
+        )?;
+        assert_eq!(summary.num_matches(), 3);
+        assert_eq!(
+            summary.files[1].replacements[1].replacement,
+            "// TODO(main.rs:3) {lineno}"
+        );
+
+        // Without line-level results, files are replaced line by line
+        let summary = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+        assert_eq!(summary.num_files_replaced, 2);
+        assert_test_files!(
+            &temp_dir,
+            "src/main.rs" => text!(
+                "// TODO(main.rs:1)",
+                "fn main() {}",
+                "// TODO(main.rs:3) {lineno}",
+            ),
+            "notes.txt" => text!(
+                "TODO(notes.txt:1)",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+#[tokio::test]
+async fn test_multiple_directories() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "src/lib.rs" => text!("old"),
+        "src/nested/mod.rs" => text!("old"),
+        "tests/run.rs" => text!("old"),
+        "docs/guide.md" => text!("old"),
+    );
+
+    let search_config = SearchConfig::builder("old", "new").build();
+    let dir_config = DirConfig {
+        directories: vec![
+            temp_dir.path().join("src/nested"),
+            temp_dir.path().join("src"),
+            temp_dir.path().join("tests"),
+        ],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    // Files in overlapping directories are only replaced once
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            dry_run: true,
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 3);
+    assert_eq!(summary.num_matches(), 3);
+
+    let summary =
+        find_and_replace_with_config(search_config, dir_config, FileSearcherConfig::default())?;
+    assert_eq!(summary.num_files_replaced, 3);
+    assert_test_files!(
+        &temp_dir,
+        "src/lib.rs" => text!("new"),
+        "src/nested/mod.rs" => text!("new"),
+        "tests/run.rs" => text!("new"),
+        "docs/guide.md" => text!("old"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_explain_filters() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        ".ignore" => text!("generated/"),
+        "custom-ignore" => text!("*.bak.rs"),
+        "src/lib.rs" => text!("old"),
+        "src/lib.bak.rs" => text!("old"),
+        "src/vendor/dep.rs" => text!("old"),
+        "generated/out.rs" => text!("old"),
+        "docs/guide.md" => text!("old"),
+        "notes.txt" => text!("old"),
+    );
+    let root = temp_dir.path().canonicalize()?;
+
+    let decisions = explain_filters(
+        SearchConfig::builder("old", "new").build(),
+        DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: Some("*.rs,*.md"),
+            exclude_globs: Some("vendor"),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[temp_dir.path().join("custom-ignore")],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        },
+    )?;
+    let decisions = decisions
+        .into_iter()
+        .map(|(path, decision)| {
+            let path = path
+                .strip_prefix(&root)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            (path, decision)
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        decisions
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "custom-ignore",
+            "docs/guide.md",
+            "generated/out.rs",
+            "notes.txt",
+            "src/lib.bak.rs",
+            "src/lib.rs",
+            "src/vendor/dep.rs",
+        ]
+    );
+    let decision = |path: &str| {
+        decisions
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, decision)| decision.clone())
+            .unwrap()
+    };
+    assert_eq!(
+        decision("custom-ignore"),
+        FilterDecision::ExcludedByGlob(None)
+    );
+    assert_eq!(
+        decision("docs/guide.md"),
+        FilterDecision::Included(Some("*.md".to_owned()))
+    );
+    assert_eq!(
+        decision("generated/out.rs"),
+        FilterDecision::ExcludedByIgnoreRules
+    );
+    assert_eq!(decision("notes.txt"), FilterDecision::ExcludedByGlob(None));
+    assert!(matches!(
+        decision("src/lib.bak.rs"),
+        FilterDecision::ExcludedByIgnoreFile { pattern, .. } if pattern == "*.bak.rs"
+    ));
+    assert_eq!(
+        decision("src/lib.rs"),
+        FilterDecision::Included(Some("*.rs".to_owned()))
+    );
+    assert_eq!(
+        decision("src/vendor/dep.rs"),
+        FilterDecision::ExcludedByGlob(Some("!vendor".to_owned()))
+    );
+    assert_eq!(
+        decision("src/vendor/dep.rs").to_string(),
+        "excluded by glob '!vendor'"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_matching_paths() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "src/user_service.rs" => text!("user"),
+        "src/order.rs" => text!("user"),
+        "user/mod.rs" => text!("other"),
+        "docs/user.md" => text!("other"),
+    );
+    let root = temp_dir.path().canonicalize()?;
+
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: Some(vec!["rs".to_string()]),
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let paths = find_matching_paths(
+        SearchConfig::builder("^user|/user", "").build(),
+        dir_config.clone(),
+        FileSearcherConfig::default(),
+    )?;
+    assert_eq!(
+        paths,
+        vec![root.join("src/user_service.rs"), root.join("user/mod.rs")]
+    );
+
+    let paths = find_matching_paths(
+        SearchConfig::builder("user", "")
+            .match_whole_word(true)
+            .build(),
+        dir_config,
+        FileSearcherConfig::default(),
+    )?;
+    assert_eq!(paths, vec![root.join("user/mod.rs")]);
+
+    // File contents are left unchanged
+    assert_test_files!(
+        &temp_dir,
+        "src/user_service.rs" => text!("user"),
+        "src/order.rs" => text!("user"),
+        "user/mod.rs" => text!("other"),
+        "docs/user.md" => text!("other"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_glob_precedence() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "src/main.rs" => text!("old"),
+        "src/notes.md" => text!("old"),
+        "tests/run.rs" => text!("old"),
+    );
+
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: Some("src/**"),
+        exclude_globs: Some("*.rs"),
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    // src/main.rs matches both globs, so is only listed when include globs take precedence
+    let paths = list_files(
+        SearchConfig::builder("", "").build(),
+        dir_config.clone(),
+        FileSearcherConfig::default(),
+    )?;
+    assert_eq!(paths, vec![PathBuf::from("src/notes.md")]);
+
+    let paths = list_files(
+        SearchConfig::builder("", "").build(),
+        DirConfig {
+            glob_precedence: GlobPrecedence::Include,
+            ..dir_config
+        },
+        FileSearcherConfig::default(),
+    )?;
+    assert_eq!(
+        paths,
+        vec![PathBuf::from("src/main.rs"), PathBuf::from("src/notes.md")]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_files() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        ".ignore" => text!("generated/"),
+        ".hidden.txt" => text!("old"),
+        "src/main.rs" => text!("old"),
+        "src/lib.rs" => text!("other"),
+        "src/notes.md" => text!("old"),
+        "generated/out.rs" => text!("old"),
+        "image.png" => text!("old"),
+    );
+
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: Some("*.md"),
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    // Files are listed whether or not their contents match
+    let paths = list_files(
+        SearchConfig::builder("", "").build(),
+        dir_config.clone(),
+        FileSearcherConfig::default(),
+    )?;
+    assert_eq!(
+        paths,
+        vec![PathBuf::from("src/lib.rs"), PathBuf::from("src/main.rs")]
+    );
+
+    let paths = list_files(
+        SearchConfig::builder("old", "new").build(),
+        DirConfig {
+            include_hidden: true,
+            respect_ignore: false,
+            ..dir_config
+        },
+        FileSearcherConfig {
+            include_binary: true,
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from(".hidden.txt"),
+            PathBuf::from(".ignore"),
+            PathBuf::from("generated/out.rs"),
+            PathBuf::from("image.png"),
+            PathBuf::from("src/lib.rs"),
+            PathBuf::from("src/main.rs"),
+        ]
+    );
+
+    // File contents are left unchanged
+    assert_test_files!(
+        &temp_dir,
+        ".ignore" => text!("generated/"),
+        ".hidden.txt" => text!("old"),
+        "src/main.rs" => text!("old"),
+        "src/lib.rs" => text!("other"),
+        "src/notes.md" => text!("old"),
+        "generated/out.rs" => text!("old"),
+        "image.png" => text!("old"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_collapse_duplicate_lines() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "file1.txt" => text!("a", "--", "--", "--", "b", "--", "c", "c", "--", "--"),
+        "file2.txt" => text!("a", "", "", "", "b", ""),
+    );
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    // Identical lines that don't match, such as `c`, are kept
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("^-+$", "---").build(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            collapse_duplicate_lines: true,
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_test_files!(
+        &temp_dir,
+        "file1.txt" => text!("a", "---", "b", "---", "c", "c", "---"),
+        "file2.txt" => text!("a", "", "", "", "b", ""),
+    );
+
+    // Lines are collapsed even if the replacement leaves them unchanged
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("^$", "").build(),
+        dir_config,
+        FileSearcherConfig {
+            collapse_duplicate_lines: true,
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(summary.stats.num_matches, 2);
+    assert_test_files!(
+        &temp_dir,
+        "file1.txt" => text!("a", "---", "b", "---", "c", "c", "---"),
+        "file2.txt" => text!("a", "", "b", ""),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_expect_count() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "file1.txt" => text!("old 1", "old 2"),
+        "file2.txt" => text!("old 3 old", "other"),
+    );
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    // Each match is counted, rather than each line
+    for (expect_count, dry_run) in [(3, false), (5, false), (3, true)] {
+        let err = find_and_replace_with_config(
+            SearchConfig::builder("old", "new").build(),
+            dir_config.clone(),
+            FileSearcherConfig {
+                expect_count: Some(expect_count),
+                dry_run,
+                ..FileSearcherConfig::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<UnexpectedCountError>(),
+            Some(&UnexpectedCountError {
+                expected: expect_count,
+                found: 4,
+            })
+        );
+        assert_test_files!(
+            &temp_dir,
+            "file1.txt" => text!("old 1", "old 2"),
+            "file2.txt" => text!("old 3 old", "other"),
+        );
+    }
+
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        dir_config,
+        FileSearcherConfig {
+            expect_count: Some(4),
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.stats.num_matches, 4);
+    assert_test_files!(
+        &temp_dir,
+        "file1.txt" => text!("new 1", "new 2"),
+        "file2.txt" => text!("new 3 new", "other"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_and_replace_streaming() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "file1.txt" => text!("old 1", "old 2"),
+        "file2.txt" => text!("other"),
+        "image.png" => text!("old"),
+    );
+
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let mut lines = vec![];
+    find_and_replace_streaming(
+        SearchConfig::builder("old", "new").build(),
+        dir_config.clone(),
+        FileSearcherConfig::default(),
+        |outcome| {
+            lines.extend(outcome.to_json(false));
+            Ok(())
+        },
+    )?;
+    lines.sort();
+    let path =
+        |name: &str| frep_core::utils::json_string(&temp_dir.path().join(name).to_string_lossy());
+    // Binary files are skipped, so aren't included
+    assert_eq!(
+        lines,
+        vec![
+            format!(
+                r#"{{"path":{},"matches":2,"modified":true}}"#,
+                path("file1.txt")
+            ),
+            format!(
+                r#"{{"path":{},"matches":0,"modified":false}}"#,
+                path("file2.txt")
+            ),
+        ]
+    );
+    assert_test_files!(
+        &temp_dir,
+        "file1.txt" => text!("new 1", "new 2"),
+        "file2.txt" => text!("other"),
+        "image.png" => text!("old"),
+    );
+
+    // Errors returned from the callback stop processing and are returned
+    let result = find_and_replace_streaming(
+        SearchConfig::builder("new", "old").build(),
+        dir_config,
+        FileSearcherConfig::default(),
+        |_| anyhow::bail!("Callback failed"),
+    );
+    assert_eq!(result.unwrap_err().to_string(), "Callback failed");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_post_command() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "passes.txt" => text!("old ok"),
+        "fails.txt" => text!("old"),
+        "unchanged.txt" => text!("ok"),
+    );
+
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        post_command: Some(PostCommand::new("grep -q ok {} && touch {}.done")),
+        ..FileSearcherConfig::default()
+    };
+
+    // The command is only run on files with replacements, and failures don't stop other files being processed
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        dir_config,
+        searcher_config,
+    )?;
+    assert_eq!(summary.num_files_replaced, 2);
+    assert_eq!(summary.errors.len(), 1);
+    let (path, error) = &summary.errors[0];
+    assert_eq!(path, &temp_dir.path().join("fails.txt"));
+    assert!(error.contains("Post-replacement command"), "{error}");
+
+    assert!(temp_dir.path().join("passes.txt.done").exists());
+    assert!(!temp_dir.path().join("fails.txt.done").exists());
+    assert!(!temp_dir.path().join("unchanged.txt.done").exists());
+    assert_eq!(
+        std::fs::read_to_string(temp_dir.path().join("fails.txt"))?,
+        "new\n"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_undo_journal() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "first.txt" => text!("old 1", "old 2"),
+        "nested/second.txt" => text!("old"),
+        "unchanged.txt" => text!("other"),
+    );
+    let journals_dir = tempfile::TempDir::new()?;
+
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        undo_journal: Some(UndoJournal::new(journals_dir.path())),
+        ..FileSearcherConfig::default()
+    };
+
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        dir_config,
+        searcher_config,
+    )?;
+    assert_eq!(summary.num_files_replaced, 2);
+    assert_test_files!(
+        &temp_dir,
+        "first.txt" => text!("new 1", "new 2"),
+        "nested/second.txt" => text!("new"),
+        "unchanged.txt" => text!("other"),
+    );
+
+    let latest = undo::latest_journal(journals_dir.path())?.unwrap();
+    let summary = undo::undo(&latest)?;
+    assert_eq!(summary.restored.len(), 2);
+    assert!(summary.errors.is_empty());
+    assert_test_files!(
+        &temp_dir,
+        "first.txt" => text!("old 1", "old 2"),
+        "nested/second.txt" => text!("old"),
+        "unchanged.txt" => text!("other"),
+    );
+    assert_eq!(undo::latest_journal(journals_dir.path())?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replace_file() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "target.txt" => text!("old 1", "keep", "old 2"),
+        "other.txt" => text!("old"),
+        ".hidden.txt" => text!("old"),
+    );
+
+    assert!(replace_file(
+        &temp_dir.path().join("target.txt"),
+        SearchConfig::builder(r"old (\d)", "new $1").build(),
+    )?);
+    // Hidden files are replaced when given directly
+    assert!(replace_file(
+        &temp_dir.path().join(".hidden.txt"),
+        SearchConfig::builder("old", "new").build(),
+    )?);
+    assert!(!replace_file(
+        &temp_dir.path().join("other.txt"),
+        SearchConfig::builder("missing", "new").build(),
+    )?);
+    assert!(
+        replace_file(
+            &temp_dir.path().join("other.txt"),
+            SearchConfig::builder("(", "new").build(),
+        )
+        .is_err()
+    );
+    assert!(
+        replace_file(
+            &temp_dir.path().join("missing.txt"),
+            SearchConfig::builder("old", "new").build()
+        )
+        .is_err()
+    );
+
+    assert_test_files!(
+        &temp_dir,
+        "target.txt" => text!("new 1", "keep", "new 2"),
+        "other.txt" => text!("old"),
+        ".hidden.txt" => text!("new"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_file_store() -> anyhow::Result<()> {
+    // The directory isn't walked when a file store is set
+    let temp_dir = create_test_files!(
+        "on_disk.txt" => text!("old"),
+    );
+    let store = Arc::new(MemoryStore::new([
+        ("src/a.rs", "old 1\nold 2\n"),
+        ("src/b.txt", "old\n"),
+        ("src/unchanged.rs", "other\n"),
+        ("src/binary.rs", "old\0"),
+    ]));
+
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: Some("*.rs"),
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            file_store: Some(store.clone()),
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(summary.num_binary_files_skipped, 1);
+    assert_eq!(
+        store.files().into_iter().collect::<Vec<_>>(),
+        vec![
+            ("src/a.rs".into(), b"new 1\nnew 2\n".to_vec()),
+            ("src/b.txt".into(), b"old\n".to_vec()),
+            ("src/binary.rs".into(), b"old\0".to_vec()),
+            ("src/unchanged.rs".into(), b"other\n".to_vec()),
+        ]
+    );
+    assert_test_files!(
+        &temp_dir,
+        "on_disk.txt" => text!("old"),
+    );
+
+    // Options that replace line by line are supported too
+    let store = Arc::new(MemoryStore::new([("a.rs", "old old\nold\n")]));
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        DirConfig {
+            include_globs: None,
+            ..dir_config
+        },
+        FileSearcherConfig {
+            file_store: Some(store.clone()),
+            occurrence: NonZero::new(2),
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(
+        store.files().into_iter().collect::<Vec<_>>(),
+        vec![("a.rs".into(), b"old new\nold\n".to_vec())]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_search_compressed() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "plain.txt" => text!("old"),
+    );
+    let compressed_path = temp_dir.path().join("app.log.gz");
+    let mut encoder = GzEncoder::new(
+        std::fs::File::create(&compressed_path)?,
+        Compression::fast(),
+    );
+    encoder.write_all(b"old 1\nold 2\nother\n")?;
+    encoder.finish()?;
+    let read_compressed = || -> anyhow::Result<String> {
+        let mut contents = String::new();
+        GzDecoder::new(std::fs::File::open(&compressed_path)?).read_to_string(&mut contents)?;
+        Ok(contents)
+    };
+
+    let dir_config = || DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    // Compressed files are skipped as binary files unless enabled
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        dir_config(),
+        FileSearcherConfig::default(),
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(read_compressed()?, "old 1\nold 2\nother\n");
+
+    std::fs::write(temp_dir.path().join("plain.txt"), "old\n")?;
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        dir_config(),
+        FileSearcherConfig {
+            search_compressed: true,
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 2);
+    assert_eq!(read_compressed()?, "new 1\nnew 2\nother\n");
+    let compressed = std::fs::read(&compressed_path)?;
+    assert_test_files!(
+        &temp_dir,
+        "app.log.gz" => &compressed,
+        "plain.txt" => text!("new"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_warn_mixed_endings() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "crlf.txt" => text!("old\r", "old\r"),
+        "mixed.txt" => text!("old\r", "old"),
+        "unmatched.txt" => text!("other\r", "other"),
+    );
+
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("old", "new").build(),
+        DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        },
+        FileSearcherConfig {
+            warn_mixed_endings: true,
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 2);
+    assert_eq!(
+        summary.mixed_line_endings,
+        vec![temp_dir.path().join("mixed.txt")]
+    );
+    assert!(
+        summary
+            .to_string()
+            .contains("Warning: 1 file with mixed line endings:")
+    );
+    assert_test_files!(
+        &temp_dir,
+        "crlf.txt" => text!("new\r", "new\r"),
+        "mixed.txt" => text!("new\r", "new"),
+        "unmatched.txt" => text!("other\r", "other"),
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_first_only() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "Cargo.toml" => text!(
+            "version = \"1.0.0\"",
+            "dep = { version = \"1.0.0\" }",
+        ),
+        "docs/notes.txt" => text!(
+            "no versions here",
+            "version 1.0.0",
+            "version 1.0.0",
+            "version 1.0.0",
+        ),
+    );
+
+    let search_config = SearchConfig::builder("1.0.0", "1.1.0").build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        first_only: true,
+        ..FileSearcherConfig::default()
+    };
+
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            dry_run: true,
+            ..searcher_config.clone()
+        },
+    )?;
+    assert_eq!(
+        summary
+            .files
+            .iter()
+            .map(|file| (
+                file.path.clone(),
+                file.replacements
+                    .iter()
+                    .map(|r| r.search_result.line_number)
+                    .collect::<Vec<_>>()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (temp_dir.path().join("Cargo.toml"), vec![1]),
+            (temp_dir.path().join("docs/notes.txt"), vec![2]),
+        ]
+    );
+
+    let summary =
+        find_and_replace_with_config(search_config.clone(), dir_config, searcher_config.clone())?;
+    assert_eq!(summary.num_files_replaced, 2);
+    assert_eq!(summary.stats.num_matches, 2);
+    assert_test_files!(
+        &temp_dir,
+        "Cargo.toml" => text!(
+            "version = \"1.1.0\"",
+            "dep = { version = \"1.0.0\" }",
+        ),
+        "docs/notes.txt" => text!(
+            "no versions here",
+            "version 1.1.0",
+            "version 1.0.0",
+            "version 1.0.0",
+        ),
+    );
+
+    let result =
+        find_and_replace_text_with_config("1.0.0\n1.0.0\n", search_config, &searcher_config)?;
+    assert_eq!(result, "1.1.0\n1.0.0\n");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_only_matching_lines() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "config.txt" => text!(
+            "production port = 80",
+            "staging port = 80",
+            "production host = example.com",
+        ),
+        "staging.txt" => text!(
+            "port = 80",
+        ),
+    );
+
+    let search_config = SearchConfig::builder("port = 80", "port = 443").build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        only_matching_lines: Some(parse_search_text(
+            &SearchConfig::builder("^production", "").build(),
+        )?),
+        ..FileSearcherConfig::default()
+    };
+
+    // Lines matching the search text but not the guard pattern aren't reported
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            dry_run: true,
+            ..searcher_config.clone()
+        },
+    )?;
+    assert_eq!(
+        summary
+            .files
+            .iter()
+            .map(|file| (
+                file.path.clone(),
+                file.replacements
+                    .iter()
+                    .map(|r| r.search_result.line_number)
+                    .collect::<Vec<_>>()
+            ))
+            .collect::<Vec<_>>(),
+        vec![(temp_dir.path().join("config.txt"), vec![1])]
+    );
+
+    let summary =
+        find_and_replace_with_config(search_config.clone(), dir_config, searcher_config.clone())?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(summary.stats.num_matches, 1);
+    assert_test_files!(
+        &temp_dir,
+        "config.txt" => text!(
+            "production port = 443",
+            "staging port = 80",
+            "production host = example.com",
+        ),
+        "staging.txt" => text!(
+            "port = 80",
+        ),
+    );
+
+    let result = find_and_replace_text_with_config(
+        "staging port = 80\nproduction port = 80\n",
+        search_config,
+        &searcher_config,
+    )?;
+    assert_eq!(result, "staging port = 80\nproduction port = 443\n");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_skip_lines() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "main.rs" => text!(
+            "let x = old();",
+            "// let y = old();",
+            "    // old()",
+            "let z = old(); // old",
+        ),
+    );
+
+    let search_config = SearchConfig::builder("old", "new").build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        skip_lines: Some(parse_search_text(
+            &SearchConfig::builder(r"^\s*//", "").build(),
+        )?),
+        ..FileSearcherConfig::default()
+    };
+
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            dry_run: true,
+            ..searcher_config.clone()
+        },
+    )?;
+    assert_eq!(
+        summary.files[0]
+            .replacements
+            .iter()
+            .map(|r| r.search_result.line_number)
+            .collect::<Vec<_>>(),
+        vec![1, 4]
+    );
+
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        searcher_config.clone(),
+    )?;
+    assert_eq!(summary.stats.num_matches, 3);
+    assert_test_files!(
+        &temp_dir,
+        "main.rs" => text!(
+            "let x = new();",
+            "// let y = old();",
+            "    // old()",
+            "let z = new(); // new",
+        ),
+    );
+
+    // Lines matching both the guard and skip patterns are skipped
+    let searcher_config = FileSearcherConfig {
+        only_matching_lines: Some(parse_search_text(
+            &SearchConfig::builder("new", "").build(),
+        )?),
+        ..searcher_config
+    };
+    let summary = find_and_replace_with_config(
+        SearchConfig::builder("(old|new)", "updated").build(),
+        dir_config,
+        searcher_config.clone(),
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_test_files!(
+        &temp_dir,
+        "main.rs" => text!(
+            "let x = updated();",
+            "// let y = old();",
+            "    // old()",
+            "let z = updated(); // updated",
+        ),
+    );
+
+    let result = find_and_replace_text_with_config(
+        "// old new\nold new\nold\n",
+        SearchConfig::builder("old", "new").build(),
+        &searcher_config,
+    )?;
+    assert_eq!(result, "// old new\nnew new\nold\n");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replacement_functions() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "config.txt" => text!(
+            "user_name = alice",
+            "user_token = secret",
+        ),
+    );
+
+    let mut functions = ReplacementFunctions::default();
+    functions.register("upper", str::to_uppercase);
+    let searcher_config = FileSearcherConfig {
+        replacement_functions: Some(functions),
+        ..FileSearcherConfig::default()
+    };
+    let search_config =
+        SearchConfig::builder(r"user_(\w+) = (\w+)", r"\{upper:$1\} = \{base64:$2\}").build();
+
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        },
+        searcher_config.clone(),
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_test_files!(
+        &temp_dir,
+        "config.txt" => text!(
+            "NAME = YWxpY2U=",
+            "TOKEN = c2VjcmV0",
+        ),
+    );
+
+    let result =
+        find_and_replace_text_with_config("user_id = 1\n", search_config, &searcher_config)?;
+    assert_eq!(result, "ID = MQ==\n");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_delete_empty_lines() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "config.toml" => text!(
+            "debug = true",
+            "name = \"app\"",
+            "  debug = true",
+            "",
+            "port = 8080 # debug = true",
+            "debug = true",
+        ),
+    );
+
+    let search_config = SearchConfig::builder("debug = true", "")
+        .fixed_strings(true)
+        .match_whole_line(true)
+        .build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+    let searcher_config = FileSearcherConfig {
+        delete_empty_lines: true,
+        ..FileSearcherConfig::default()
+    };
+
+    let summary = find_and_replace_with_config(search_config, dir_config, searcher_config)?;
+    assert_eq!(summary.num_files_replaced, 1);
+    // Lines that were already empty are left in place
+    assert_test_files!(
+        &temp_dir,
+        "config.toml" => text!(
+            "name = \"app\"",
+            "",
+            "port = 8080 # debug = true",
+        ),
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_permission_denied_skipped() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = create_test_files!(
+        "readable.txt" => text!("Readable TEST_PATTERN"),
+        "unreadable.txt" => text!("Unreadable TEST_PATTERN"),
+        "locked/nested.txt" => text!("Nested TEST_PATTERN"),
+    );
+    let unreadable_file = temp_dir.path().join("unreadable.txt");
+    let locked_dir = temp_dir.path().join("locked");
+    let set_mode = |path: &std::path::Path, mode| {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+    };
+    set_mode(&unreadable_file, 0o000);
+    set_mode(&locked_dir, 0o000);
+
+    // Permissions aren't enforced when running as root
+    if std::fs::read(&unreadable_file).is_ok() {
+        set_mode(&locked_dir, 0o755);
+        return Ok(());
+    }
+
+    let search_config = SearchConfig {
+        search_text: "TEST_PATTERN",
+        replacement_text: "REPLACEMENT",
+        fixed_strings: true,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex: false,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    let summary =
+        find_and_replace_with_config(search_config, dir_config, FileSearcherConfig::default());
+    set_mode(&unreadable_file, 0o644);
+    set_mode(&locked_dir, 0o755);
+    let summary = summary?;
+
+    assert_eq!(summary.num_files_replaced, 1);
+    assert!(summary.errors.is_empty());
+    assert_eq!(
+        summary
+            .skipped
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>(),
+        vec![locked_dir, unreadable_file]
+    );
+    assert!(
+        summary
+            .to_string()
+            .contains("Skipped 2 files (permission denied):")
+    );
+    assert_test_files!(
+        &temp_dir,
+        "readable.txt" => text!("Readable REPLACEMENT"),
+        "unreadable.txt" => text!("Unreadable TEST_PATTERN"),
+        "locked/nested.txt" => text!("Nested TEST_PATTERN"),
+    );
+
+    Ok(())
+}
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_max_depth,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "top.txt" => text!("Top-level TEST_PATTERN"),
+            "a/one.txt" => text!("Depth two TEST_PATTERN"),
+            "a/b/two.txt" => text!("Depth three TEST_PATTERN"),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: Some(1),
+            respect_ignore: true,
+        };
+
+        let result = find_and_replace(search_config.clone(), dir_config.clone());
+        assert_eq!(result?, "Success: 1 file updated\n");
+        assert_test_files!(
+            &temp_dir,
+            "top.txt" => text!("Top-level REPLACEMENT"),
+            "a/one.txt" => text!("Depth two TEST_PATTERN"),
+            "a/b/two.txt" => text!("Depth three TEST_PATTERN"),
+        );
+
+        let dir_config = DirConfig {
+            max_depth: Some(2),
+            ..dir_config
+        };
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 1 file updated\n");
+        assert_test_files!(
+            &temp_dir,
+            "top.txt" => text!("Top-level REPLACEMENT"),
+            "a/one.txt" => text!("Depth two REPLACEMENT"),
+            "a/b/two.txt" => text!("Depth three TEST_PATTERN"),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_respect_ignore,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            ".git/HEAD" => text!("ref: refs/heads/main"),
+            ".gitignore" => text!("git_ignored.txt"),
+            ".ignore" => text!("ignored/"),
+            "included.txt" => text!("Included TEST_PATTERN"),
+            "git_ignored.txt" => text!("Git ignored TEST_PATTERN"),
+            "ignored/file.txt" => text!("Ignored TEST_PATTERN"),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let result = find_and_replace(search_config.clone(), dir_config.clone());
+        assert_eq!(result?, "Success: 1 file updated\n");
+        assert_test_files!(
+            &temp_dir,
+            ".git/HEAD" => text!("ref: refs/heads/main"),
+            ".gitignore" => text!("git_ignored.txt"),
+            ".ignore" => text!("ignored/"),
+            "included.txt" => text!("Included REPLACEMENT"),
+            "git_ignored.txt" => text!("Git ignored TEST_PATTERN"),
+            "ignored/file.txt" => text!("Ignored TEST_PATTERN"),
+        );
+
+        let dir_config = DirConfig {
+            respect_ignore: false,
+            ..dir_config
+        };
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 2 files updated\n");
+        assert_test_files!(
+            &temp_dir,
+            ".git/HEAD" => text!("ref: refs/heads/main"),
+            ".gitignore" => text!("git_ignored.txt"),
+            ".ignore" => text!("ignored/"),
+            "included.txt" => text!("Included REPLACEMENT"),
+            "git_ignored.txt" => text!("Git ignored REPLACEMENT"),
+            "ignored/file.txt" => text!("Ignored REPLACEMENT"),
+        );
+
+        Ok(())
+    }
+);
+
+#[tokio::test]
+async fn test_hidden_files_with_ignore_files() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        ".git/HEAD" => text!("ref: refs/heads/main"),
+        ".gitignore" => text!(".secrets", "build/"),
+        ".env" => text!("KEY=old"),
+        ".secrets" => text!("KEY=old"),
+        "build/out.txt" => text!("old"),
+        "file.txt" => text!("old"),
+    );
+
+    let search_config = SearchConfig::builder("old", "new").build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: true,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    // Including hidden files doesn't include those matched by ignore files
+    let result = find_and_replace(search_config.clone(), dir_config.clone());
+    assert_eq!(result?, "Success: 2 files updated\n");
+    assert_test_files!(
+        &temp_dir,
+        ".git/HEAD" => text!("ref: refs/heads/main"),
+        ".gitignore" => text!(".secrets", "build/"),
+        ".env" => text!("KEY=new"),
+        ".secrets" => text!("KEY=old"),
+        "build/out.txt" => text!("old"),
+        "file.txt" => text!("new"),
+    );
+
+    let result = find_and_replace(
+        search_config,
+        DirConfig {
+            respect_ignore: false,
+            ..dir_config
+        },
+    );
+    assert_eq!(result?, "Success: 2 files updated\n");
+    assert_test_files!(
+        &temp_dir,
+        ".git/HEAD" => text!("ref: refs/heads/main"),
+        ".gitignore" => text!(".secrets", "build/"),
+        ".env" => text!("KEY=new"),
+        ".secrets" => text!("KEY=new"),
+        "build/out.txt" => text!("new"),
+        "file.txt" => text!("new"),
+    );
+
+    Ok(())
+}
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_respect_frepignore,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "included.txt" => text!("Included TEST_PATTERN"),
+            "subdir/.frepignore" => text!("frep_ignored.txt"),
+            "subdir/frep_ignored.txt" => text!("Frep ignored TEST_PATTERN"),
+            "subdir/included.txt" => text!("Included TEST_PATTERN"),
+            "frep_ignored.txt" => text!("Not ignored outside subdir TEST_PATTERN"),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let result = find_and_replace(search_config.clone(), dir_config.clone());
+        assert_eq!(result?, "Success: 3 files updated\n");
+        assert_test_files!(
+            &temp_dir,
+            "included.txt" => text!("Included REPLACEMENT"),
+            "subdir/.frepignore" => text!("frep_ignored.txt"),
+            "subdir/frep_ignored.txt" => text!("Frep ignored TEST_PATTERN"),
+            "subdir/included.txt" => text!("Included REPLACEMENT"),
+            "frep_ignored.txt" => text!("Not ignored outside subdir REPLACEMENT"),
+        );
+
+        let dir_config = DirConfig {
+            respect_ignore: false,
+            ..dir_config
+        };
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 1 file updated\n");
+        assert_test_files!(
+            &temp_dir,
+            "included.txt" => text!("Included REPLACEMENT"),
+            "subdir/.frepignore" => text!("frep_ignored.txt"),
+            "subdir/frep_ignored.txt" => text!("Frep ignored REPLACEMENT"),
+            "subdir/included.txt" => text!("Included REPLACEMENT"),
+            "frep_ignored.txt" => text!("Not ignored outside subdir REPLACEMENT"),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_identical_replacement,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "file1.txt" => text!(
+                "foo",
+                "bar foo",
+            ),
+            "file2.txt" => text!("No match"),
+        );
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(temp_dir.path().join("file1.txt"))?
+            .set_modified(mtime)?;
+
+        let search_config = SearchConfig {
+            search_text: "foo",
+            replacement_text: "foo",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 0 files updated\n");
+        assert_test_files!(
+            &temp_dir,
+            "file1.txt" => text!(
+                "foo",
+                "bar foo",
+            ),
+            "file2.txt" => text!("No match"),
+        );
+        assert_eq!(
+            std::fs::metadata(temp_dir.path().join("file1.txt"))?.modified()?,
+            mtime
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_skip_in_strings,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "main.rs" => text!(
+                "// Calls old_name",
+                "let old_name = 1;",
+                "println!(\"old_name is {}\", old_name);",
+                "let raw = r#\"old_name",
+                "old_name\"#;",
+            ),
+            "script.py" => text!(
+                "old_name = 1  # 'old_name'",
+                "print('old_name', old_name)",
+                "\"\"\"",
+                "old_name",
+                "\"\"\"",
+            ),
+            "data.json" => text!(
+                "{\"old_name\": old_name}",
+            ),
+            "notes.txt" => text!(
+                "\"old_name\"",
+            ),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "old_name",
+            replacement_text: "new_name",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let summary = find_and_replace_with_config(
+            search_config.clone(),
+            dir_config.clone(),
+            FileSearcherConfig {
+                dry_run: true,
+                skip_in_strings: true,
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.num_files_replaced, 4);
+        // Matches inside string literals aren't counted, including in a line with other matches
+        assert_eq!(summary.num_matches(), 8);
+
+        let summary = find_and_replace_with_config(
+            search_config,
+            dir_config,
+            FileSearcherConfig {
+                skip_in_strings: true,
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.num_files_replaced, 4);
+        // Comments aren't string literals, so matches in them are replaced
+        assert_test_files!(
+            &temp_dir,
+            "main.rs" => text!(
+                "// Calls new_name",
+                "let new_name = 1;",
+                "println!(\"old_name is {}\", new_name);",
+                "let raw = r#\"old_name",
+                "old_name\"#;",
+            ),
+            "script.py" => text!(
+                "new_name = 1  # 'new_name'",
+                "print('old_name', new_name)",
+                "\"\"\"",
+                "old_name",
+                "\"\"\"",
+            ),
+            "data.json" => text!(
+                "{\"old_name\": new_name}",
+            ),
+            "notes.txt" => text!(
+                "\"new_name\"",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_extensions,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "main.rs" => text!("Rust TEST_PATTERN"),
+            "Cargo.toml" => text!("TOML TEST_PATTERN"),
+            "README.md" => text!("Markdown TEST_PATTERN"),
+            "src/lib.rs" => text!("Nested Rust TEST_PATTERN"),
+            "rs" => text!("No extension TEST_PATTERN"),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: Some("src/**"),
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: Some(vec!["rs".to_string(), ".toml".to_string()]),
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 2 files updated\n");
+        assert_test_files!(
+            &temp_dir,
+            "main.rs" => text!("Rust REPLACEMENT"),
+            "Cargo.toml" => text!("TOML REPLACEMENT"),
+            "README.md" => text!("Markdown TEST_PATTERN"),
+            "src/lib.rs" => text!("Nested Rust TEST_PATTERN"),
+            "rs" => text!("No extension TEST_PATTERN"),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_size_filter,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "small.txt" => text!("TEST_PATTERN"),
+            "medium.txt" => text!("A medium sized TEST_PATTERN"),
+            "large.txt" => text!(
+                "A much larger file, containing TEST_PATTERN",
+                "along with several other lines of text",
+                "that push it over the maximum size",
+            ),
+        );
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let summary = find_and_replace_with_config(
+            search_config.clone(),
+            dir_config.clone(),
+            FileSearcherConfig {
+                size_filter: Some((Some(20), Some(50))),
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.num_files_replaced, 1);
+        assert_test_files!(
+            &temp_dir,
+            "small.txt" => text!("TEST_PATTERN"),
+            "medium.txt" => text!("A medium sized REPLACEMENT"),
+            "large.txt" => text!(
+                "A much larger file, containing TEST_PATTERN",
+                "along with several other lines of text",
+                "that push it over the maximum size",
+            ),
+        );
+
+        // Either bound can be omitted
+        let summary = find_and_replace_with_config(
+            search_config,
+            dir_config,
+            FileSearcherConfig {
+                size_filter: Some((Some(20), None)),
+                ..FileSearcherConfig::default()
+            },
+        )?;
+        assert_eq!(summary.num_files_replaced, 1);
+        assert_test_files!(
+            &temp_dir,
+            "small.txt" => text!("TEST_PATTERN"),
+            "medium.txt" => text!("A medium sized REPLACEMENT"),
+            "large.txt" => text!(
+                "A much larger file, containing REPLACEMENT",
+                "along with several other lines of text",
+                "that push it over the maximum size",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+#[tokio::test]
+async fn test_modified_filter() -> anyhow::Result<()> {
+    let temp_dir = create_test_files!(
+        "old.txt" => text!("TEST_PATTERN"),
+        "recent.txt" => text!("TEST_PATTERN"),
+        "new.txt" => text!("TEST_PATTERN"),
+    );
+    let from_epoch =
+        |secs| std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    for (name, mtime) in [
+        ("old.txt", from_epoch(1_000_000)),
+        ("recent.txt", from_epoch(2_000_000)),
+    ] {
+        std::fs::File::options()
+            .write(true)
+            .open(temp_dir.path().join(name))?
+            .set_modified(mtime)?;
+    }
+
+    let search_config = SearchConfig::builder("TEST_PATTERN", "REPLACEMENT").build();
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    let summary = find_and_replace_with_config(
+        search_config.clone(),
+        dir_config.clone(),
+        FileSearcherConfig {
+            modified_filter: Some((Some(from_epoch(1_500_000)), Some(from_epoch(3_000_000)))),
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_eq!(summary.stats.num_files_skipped, 2);
+    assert_test_files!(
+        &temp_dir,
+        "old.txt" => text!("TEST_PATTERN"),
+        "recent.txt" => text!("REPLACEMENT"),
+        "new.txt" => text!("TEST_PATTERN"),
+    );
+
+    // Either bound can be omitted
+    let summary = find_and_replace_with_config(
+        search_config,
+        dir_config,
+        FileSearcherConfig {
+            modified_filter: Some((None, Some(from_epoch(1_500_000)))),
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(summary.num_files_replaced, 1);
+    assert_test_files!(
+        &temp_dir,
+        "old.txt" => text!("REPLACEMENT"),
+        "recent.txt" => text!("REPLACEMENT"),
+        "new.txt" => text!("TEST_PATTERN"),
+    );
+
+    Ok(())
+}
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_ignore_files,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "included.txt" => text!("Included TEST_PATTERN"),
+            "vendor/lib.txt" => text!("Vendored TEST_PATTERN"),
+            "vendor/nested/lib.txt" => text!("Nested vendored TEST_PATTERN"),
+            "src/vendor/file.txt" => text!("Not vendored TEST_PATTERN"),
+        );
+        let ignore_dir = create_test_files!(
+            "custom-ignore" => text!(
+                "# Third-party code",
+                "/vendor/",
+            ),
+        );
+        let ignore_files = [ignore_dir.path().join("custom-ignore")];
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &ignore_files,
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: false,
+        };
+
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 2 files updated\n");
+        assert_test_files!(
+            &temp_dir,
+            "included.txt" => text!("Included REPLACEMENT"),
+            "vendor/lib.txt" => text!("Vendored TEST_PATTERN"),
+            "vendor/nested/lib.txt" => text!("Nested vendored TEST_PATTERN"),
+            "src/vendor/file.txt" => text!("Not vendored REPLACEMENT"),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes!(test_multiline, |advanced_regex| async move {
+    let temp_dir = create_test_files!(
+        "code.rs" => text!(
+            "fn first()",
+            "{",
+            "    body();",
+            "}",
+            "fn second() {",
+            "}",
+        ),
+        "other.txt" => text!(
+            "No (match) here",
+        ),
+    );
+
+    let search_config = SearchConfig {
+        search_text: r"fn (\w+)\(\)\n\{\n(.*?)^\}",
+        replacement_text: "fn $1() {\n$2}",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline: true,
+        dotall: true,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let dir_config = DirConfig {
+        directories: vec![temp_dir.path().to_path_buf()],
+        include_globs: None,
+        exclude_globs: None,
+        glob_file: None,
+        include_types: &[],
+        exclude_types: &[],
+        ignore_files: &[],
+        glob_precedence: GlobPrecedence::Exclude,
+        extensions: None,
+        include_hidden: false,
+        follow_symlinks: false,
+        max_depth: None,
+        respect_ignore: true,
+    };
+
+    let result = find_and_replace(search_config, dir_config);
+    assert_eq!(result?, "Success: 1 file updated\n");
+
+    assert_test_files!(
+        &temp_dir,
+        "code.rs" => text!(
+            "fn first() {",
+            "    body();",
+            "}",
+            "fn second() {",
+            "}",
+        ),
+        "other.txt" => text!(
+            "No (match) here",
+        ),
+    );
+
+    Ok(())
+});
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_additional_exprs,
+    |advanced_regex, fixed_strings| async move {
+        let temp_dir = create_test_files!(
+            "file1.txt" => text!(
+                "alpha beta",
+                "gamma",
+            ),
+            "file2.txt" => text!(
+                "beta only",
+            ),
+            "file3.txt" => text!(
+                "no matches here",
+            ),
+        );
+
+        let additional_exprs = [
+            ("beta".to_string(), "delta".to_string()),
+            ("delta".to_string(), "epsilon".to_string()),
+        ];
+        let search_config = SearchConfig {
+            search_text: "alpha",
+            replacement_text: "beta",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &additional_exprs,
+            word_chars: "",
+            strict_captures: false,
+        };
+        let dir_config = DirConfig {
+            directories: vec![temp_dir.path().to_path_buf()],
+            include_globs: None,
+            exclude_globs: None,
+            glob_file: None,
+            include_types: &[],
+            exclude_types: &[],
+            ignore_files: &[],
+            glob_precedence: GlobPrecedence::Exclude,
+            extensions: None,
+            include_hidden: false,
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore: true,
+        };
+
+        let result = find_and_replace(search_config, dir_config);
+        assert_eq!(result?, "Success: 2 files updated\n");
+
+        assert_test_files!(
+            &temp_dir,
+            "file1.txt" => text!(
+                "epsilon epsilon",
+                "gamma",
+            ),
+            "file2.txt" => text!(
+                "epsilon only",
+            ),
+            "file3.txt" => text!(
+                "no matches here",
+            ),
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_text_additional_exprs_without_search_text,
+    |advanced_regex, fixed_strings| async move {
+        let additional_exprs = [
+            ("foo".to_string(), "bar".to_string()),
+            ("bar".to_string(), "baz".to_string()),
+        ];
+        let search_config = SearchConfig {
+            search_text: "",
+            replacement_text: "",
+            fixed_strings,
+            match_case: false,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &additional_exprs,
+            word_chars: "",
+            strict_captures: false,
+        };
+
+        let result = find_and_replace_text("foo\nqux\nBAR\n", search_config);
+        assert_eq!(result?, "baz\nqux\nbaz\n");
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_text_basic_replacement,
+    |advanced_regex, fixed_strings| async move {
+        let input_text = indoc! {"
+            This is a test text.
+            It contains TEST_PATTERN that should be replaced.
+            Multiple lines with TEST_PATTERN here."
+        };
+
+        let search_config = SearchConfig {
+            search_text: "TEST_PATTERN",
+            replacement_text: "REPLACEMENT",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+
+        let result = find_and_replace_text(input_text, search_config);
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            indoc! {"
+                This is a test text.
+                It contains REPLACEMENT that should be replaced.
+                Multiple lines with REPLACEMENT here."
+            }
+        );
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_text_multiline,
+    |advanced_regex, fixed_strings| async move {
+        let input_text = "first line\nsecond line\nthird line\n";
+
+        let search_config = SearchConfig {
+            search_text: "line\nsecond",
+            replacement_text: "+ second",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: true,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+
+        let result = find_and_replace_text(input_text, search_config);
+        assert_eq!(result?, "first + second line\nthird line\n");
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes!(test_text_dotall, |advanced_regex| async move {
+    let input_text = "start\nmiddle\nend\n";
+
+    let search_config = |multiline, dotall| SearchConfig {
+        search_text: "start.*end",
+        replacement_text: "replaced",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline,
+        dotall,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+
+    // `.` only spans lines when matching the whole text with dotall enabled
+    let result = find_and_replace_text(input_text, search_config(true, true));
+    assert_eq!(result?, "replaced\n");
+    let result = find_and_replace_text(input_text, search_config(true, false));
+    assert_eq!(result?, input_text);
+    let result = find_and_replace_text(input_text, search_config(false, true));
+    assert_eq!(result?, input_text);
+
+    Ok(())
+});
+
+test_with_both_regex_modes_and_fixed_strings!(
+    test_bytes_replacement,
+    |advanced_regex, fixed_strings| async move {
+        // Latin-1 encoded text, which isn't valid UTF-8
+        let input = b"caf\xe9 ERROR\r\nok\nERROR: na\xefve $1\xff";
+
+        let search_config = SearchConfig {
+            search_text: "ERROR",
+            replacement_text: "WARN",
+            fixed_strings,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[("$1".to_owned(), "$$".to_owned())],
+            word_chars: "",
+            strict_captures: false,
+        };
+
+        let result = find_and_replace_bytes(input, &search_config)?;
+        if fixed_strings {
+            assert_eq!(result, b"caf\xe9 WARN\r\nok\nWARN: na\xefve $$\xff");
+        } else {
+            // `$1` is an end-of-line anchor followed by `1` as a regex, so never matches
+            assert_eq!(result, b"caf\xe9 WARN\r\nok\nWARN: na\xefve $1\xff");
+        }
+
+        Ok(())
+    }
+);
+
+test_with_both_regex_modes!(test_bytes_regex, |advanced_regex| async move {
+    let input = b"id=\xe9\xe8 x\nid=ab x\n";
+
+    let search_config = SearchConfig {
+        search_text: r"(?-u)id=(.+) x",
+        replacement_text: "$1",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    assert_eq!(
+        find_and_replace_bytes(input, &search_config)?,
+        b"\xe9\xe8\nab\n"
+    );
+
+    // Lookarounds and whole-word matching aren't supported by byte regexes
+    let result = find_and_replace_bytes(
+        input,
+        &SearchConfig {
+            search_text: r"id(?==)",
+            ..search_config.clone()
+        },
+    );
+    assert!(result.is_err());
+    let result = find_and_replace_bytes(
+        input,
+        &SearchConfig {
+            match_whole_word: true,
+            match_whole_line: false,
+            ..search_config
+        },
+    );
+    assert!(result.is_err());
+
+    Ok(())
+});
+
+test_with_both_regex_modes!(test_text_case_transforms, |advanced_regex| async move {
+    let input_text = "let user_id = 1;\nlet group_id = 2;\n";
+
+    let search_config = SearchConfig {
+        search_text: r"(\w+)_id",
+        replacement_text: r"\U$1\E_ID",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let searcher_config = FileSearcherConfig {
+        case_transforms: true,
+        ..FileSearcherConfig::default()
+    };
+
+    let result = find_and_replace_text_with_config(input_text, search_config, &searcher_config);
+    assert_eq!(result?, "let USER_ID = 1;\nlet GROUP_ID = 2;\n");
+
+    Ok(())
+});
+
+test_with_both_regex_modes!(test_text_literal_replacement, |advanced_regex| async move {
+    let input_text = "price: 10\ntotal: 20\n";
+
+    let search_config = SearchConfig {
+        search_text: r"(\d+)",
+        replacement_text: "$1 ${1} $$",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let searcher_config = FileSearcherConfig {
+        literal_replacement: true,
+        ..FileSearcherConfig::default()
+    };
+
+    let result = find_and_replace_text_with_config(input_text, search_config, &searcher_config);
+    assert_eq!(result?, "price: $1 ${1} $$\ntotal: $1 ${1} $$\n");
+
+    Ok(())
+});
+
+test_with_both_regex_modes!(test_text_occurrence, |advanced_regex| async move {
+    let input_text = "a-b-c-d\na-b\n";
+
+    let search_config = SearchConfig {
+        search_text: r"-(\w)",
+        replacement_text: "+$1",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+    let searcher_config = FileSearcherConfig {
+        occurrence: NonZero::new(2),
+        ..FileSearcherConfig::default()
+    };
+
+    let result = find_and_replace_text_with_config(input_text, search_config, &searcher_config);
+    assert_eq!(result?, "a-b+c-d\na-b\n");
+
+    Ok(())
+});
+
+test_with_both_regex_modes!(test_compiled_search, |advanced_regex| async move {
+    let additional_exprs = [("world".to_string(), "earth".to_string())];
+    let search_config = SearchConfig::builder(r"(\w+)_id", "${1}Id")
+        .advanced_regex(advanced_regex)
+        .additional_exprs(&additional_exprs)
+        .build();
+    let compiled = CompiledSearch::new(search_config.clone())?;
+    assert_eq!(compiled.exprs().len(), 2);
+
+    assert!(compiled.matches("let user_id = 1;"));
+    assert!(compiled.matches("hello world"));
+    assert!(!compiled.matches("nothing here"));
+    assert_eq!(
+        compiled.replace_line("user_id, world"),
+        Some("userId, earth".to_string())
+    );
+    assert_eq!(compiled.replace_line("nothing here"), None);
+
+    let compiled = CompiledSearch::with_config(
+        search_config,
+        &FileSearcherConfig {
+            literal_replacement: true,
+            occurrence: NonZero::new(2),
+            ..FileSearcherConfig::default()
+        },
+    )?;
+    assert_eq!(
+        compiled.replace_line("a_id b_id"),
+        Some("a_id ${1}Id".to_string())
+    );
+
+    let invalid = SearchConfig::builder("(", "").build();
+    assert!(CompiledSearch::new(invalid).is_err());
+
+    Ok(())
+});
+
+test_with_both_regex_modes!(test_text_regex_replacement, |advanced_regex| async move {
+    let input_text = indoc! {"
+            Numbers: 123, 456, and 789.
+            Phone: (555) 123-4567
+            IP: 192.168.1.1"
+    };
+
+    let search_config = SearchConfig {
+        search_text: r"\d{3}",
+        replacement_text: "XXX",
+        fixed_strings: false,
+        match_case: true,
+        match_whole_word: false,
+        match_whole_line: false,
+        advanced_regex,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
+    };
+
+    let result = find_and_replace_text(input_text, search_config);
+    assert!(result.is_ok());
+    assert_eq!(
+        result.unwrap(),
+        indoc! {"
+                Numbers: XXX, XXX, and XXX.
+                Phone: (XXX) XXX-XXX7
+                IP: XXX.XXX.1.1"
+        }
+    );
+
+    Ok(())
+});
+
+test_with_both_regex_modes!(
+    test_text_regex_with_capture_groups,
+    |advanced_regex| async move {
+        let input_text = indoc! {"
+            username: john_doe, email: john@example.com
+            username: jane_smith, email: jane@example.com"
+        };
+
+        let search_config = SearchConfig {
+            search_text: r"username: (\w+), email: ([^@]+)@",
+            replacement_text: "user: $1 (contact: $2 at",
+            fixed_strings: false,
+            match_case: true,
+            match_whole_word: false,
+            match_whole_line: false,
+            advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
+        };
+
+        let result = find_and_replace_text(input_text, search_config);
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            indoc! {"
+                user: john_doe (contact: john atexample.com
+                user: jane_smith (contact: jane atexample.com"
+            }
+        );
+
+        let input_text2 = indoc! {"
+            [2023-01-15] INFO: System started
+            [2023-02-20] ERROR: Connection failed"
+        };
+
+        let search_config2 = SearchConfig {
+            search_text: r"\[(\d{4})-(\d{2})-(\d{2})\]",
+            replacement_text: "[$3/$2/$1]",
             fixed_strings: false,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result2 = find_and_replace_text(input_text2, search_config2);
@@ -1593,7 +5885,13 @@ async fn test_text_advanced_regex_features() -> anyhow::Result<()> {
         fixed_strings: false,
         match_case: true,
         match_whole_word: false,
+        match_whole_line: false,
         advanced_regex: true,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
     };
 
     let result = find_and_replace_text(input_text, search_config);
@@ -1620,7 +5918,13 @@ async fn test_text_advanced_regex_features() -> anyhow::Result<()> {
         fixed_strings: false,
         match_case: true,
         match_whole_word: false,
+        match_whole_line: false,
         advanced_regex: true,
+        multiline: false,
+        dotall: false,
+        additional_exprs: &[],
+        word_chars: "",
+        strict_captures: false,
     };
 
     let result2 = find_and_replace_text(input_text2, search_config2);
@@ -1651,7 +5955,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: true,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(input_text, search_config);
@@ -1668,6 +5978,58 @@ test_with_both_regex_modes_and_fixed_strings!(
     }
 );
 
+test_with_both_regex_modes_and_fixed_strings!(
+    test_text_match_whole_line,
+    |advanced_regex, fixed_strings| async move {
+        let input_text = indoc! {"
+            [server]
+            debug = true
+              debug = true
+            # debug = true
+            debug = true # for now"
+        };
+
+        for multiline in [false, true] {
+            let search_config = SearchConfig {
+                search_text: "debug = true",
+                replacement_text: "debug = false",
+                fixed_strings,
+                match_case: true,
+                match_whole_word: false,
+                match_whole_line: true,
+                advanced_regex,
+                multiline,
+                dotall: false,
+                additional_exprs: &[],
+                word_chars: "",
+                strict_captures: false,
+            };
+
+            // Surrounding whitespace is only ignored when searching with fixed strings
+            let expected = if fixed_strings {
+                indoc! {"
+                    [server]
+                    debug = false
+                    debug = false
+                    # debug = true
+                    debug = true # for now"
+                }
+            } else {
+                indoc! {"
+                    [server]
+                    debug = false
+                      debug = true
+                    # debug = true
+                    debug = true # for now"
+                }
+            };
+            assert_eq!(find_and_replace_text(input_text, search_config)?, expected);
+        }
+
+        Ok(())
+    }
+);
+
 test_with_both_regex_modes_and_fixed_strings!(
     test_text_case_sensitivity,
     |advanced_regex, fixed_strings| async move {
@@ -1683,7 +6045,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result_sensitive = find_and_replace_text(input_text, search_config_sensitive);
@@ -1703,7 +6071,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: false,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result_insensitive = find_and_replace_text(input_text, search_config_insensitive);
@@ -1731,7 +6105,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(empty_text, search_config);
@@ -1746,7 +6126,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(single_line, search_config);
@@ -1761,7 +6147,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(single_line_no_match, search_config);
@@ -1786,7 +6178,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(input_text, search_config);
@@ -1814,7 +6212,13 @@ test_with_both_regex_modes!(
             fixed_strings: false,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(input_text, search_config);
@@ -1846,7 +6250,13 @@ test_with_both_regex_modes!(
             fixed_strings: false,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(input_text, search_config);
@@ -1879,7 +6289,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result_lf = find_and_replace_text(input_lf, search_config);
@@ -1897,7 +6313,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result_crlf = find_and_replace_text(input_crlf, search_config_crlf);
@@ -1915,7 +6337,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result_mixed = find_and_replace_text(input_mixed, search_config_mixed);
@@ -1933,7 +6361,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result_no_trailing =
@@ -1953,7 +6387,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result_empty_lines = find_and_replace_text(input_empty_lines, search_config_empty);
@@ -1982,7 +6422,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(input_text, search_config);
@@ -2013,7 +6459,13 @@ test_with_both_regex_modes_and_fixed_strings!(
             fixed_strings,
             match_case: true,
             match_whole_word: false,
+            match_whole_line: false,
             advanced_regex,
+            multiline: false,
+            dotall: false,
+            additional_exprs: &[],
+            word_chars: "",
+            strict_captures: false,
         };
 
         let result = find_and_replace_text(&input_text, search_config);
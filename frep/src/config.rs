@@ -0,0 +1,185 @@
+use anyhow::Context;
+use etcetera::base_strategy::{BaseStrategy, choose_base_strategy};
+use serde::Deserialize;
+use std::{
+    io,
+    num::NonZero,
+    path::{Path, PathBuf},
+};
+
+/// The name of the config file, which is read from the working directory and from the config directory
+pub const CONFIG_FILENAME: &str = ".freprc";
+
+/// Default values for command-line options, read from a TOML config file. Options that aren't set are left
+/// to the next config file, and then to the built-in defaults, and options given on the command line take
+/// precedence over all config files
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub hidden: Option<bool>,
+    pub follow: Option<bool>,
+    pub no_ignore: Option<bool>,
+    pub case_insensitive: Option<bool>,
+    pub advanced_regex: Option<bool>,
+    pub include_files: Option<String>,
+    pub exclude_files: Option<String>,
+    pub threads: Option<NonZero<usize>>,
+}
+
+impl Config {
+    /// Parses a config from the contents of a config file
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Reads the config file at `path`, returning `None` if it doesn't exist
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Self::parse(&contents)
+            .map(Some)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// The config without the options that only apply to files, for processing stdin, where they aren't
+    /// available
+    pub fn without_file_options(self) -> Self {
+        Self {
+            case_insensitive: self.case_insensitive,
+            advanced_regex: self.advanced_regex,
+            ..Self::default()
+        }
+    }
+
+    /// Combines two configs, with the options set in `self` taking precedence over those in `fallback`
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            hidden: self.hidden.or(fallback.hidden),
+            follow: self.follow.or(fallback.follow),
+            no_ignore: self.no_ignore.or(fallback.no_ignore),
+            case_insensitive: self.case_insensitive.or(fallback.case_insensitive),
+            advanced_regex: self.advanced_regex.or(fallback.advanced_regex),
+            include_files: self.include_files.or(fallback.include_files),
+            exclude_files: self.exclude_files.or(fallback.exclude_files),
+            threads: self.threads.or(fallback.threads),
+        }
+    }
+}
+
+/// The path of the global config file, within `$XDG_CONFIG_HOME` (or the platform's equivalent)
+pub fn global_config_path() -> Option<PathBuf> {
+    choose_base_strategy()
+        .ok()
+        .map(|strategy| strategy.config_dir().join(CONFIG_FILENAME))
+}
+
+/// The config from the config file in the working directory, falling back to the global config file for
+/// options it doesn't set
+pub fn load() -> anyhow::Result<Config> {
+    let local = Config::load(Path::new(CONFIG_FILENAME))?.unwrap_or_default();
+    let global = match global_config_path() {
+        Some(path) => Config::load(&path)?.unwrap_or_default(),
+        None => Config::default(),
+    };
+    Ok(local.or(global))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+hidden = true
+exclude_files = "*.lock,target/**"
+threads = 4
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                hidden: Some(true),
+                exclude_files: Some("*.lock,target/**".to_string()),
+                threads: NonZero::new(4),
+                ..Config::default()
+            }
+        );
+
+        assert!(Config::parse("hiden = true").is_err());
+        assert!(Config::parse("threads = 0").is_err());
+        assert!(Config::parse("hidden = \"yes\"").is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(CONFIG_FILENAME);
+        assert_eq!(Config::load(&path).unwrap(), None);
+
+        std::fs::write(&path, "advanced_regex = true\n").unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap(),
+            Some(Config {
+                advanced_regex: Some(true),
+                ..Config::default()
+            })
+        );
+
+        std::fs::write(&path, "advanced_regex = \n").unwrap();
+        assert!(
+            Config::load(&path)
+                .unwrap_err()
+                .to_string()
+                .contains("Failed to parse config file")
+        );
+    }
+
+    #[test]
+    fn test_or() {
+        let local = Config {
+            hidden: Some(false),
+            threads: NonZero::new(2),
+            ..Config::default()
+        };
+        let global = Config {
+            hidden: Some(true),
+            follow: Some(true),
+            threads: NonZero::new(8),
+            ..Config::default()
+        };
+        assert_eq!(
+            local.or(global),
+            Config {
+                hidden: Some(false),
+                follow: Some(true),
+                threads: NonZero::new(2),
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_without_file_options() {
+        let config = Config {
+            hidden: Some(true),
+            case_insensitive: Some(true),
+            exclude_files: Some("*.lock".to_string()),
+            threads: NonZero::new(2),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.without_file_options(),
+            Config {
+                case_insensitive: Some(true),
+                ..Config::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use crossterm::style::Stylize;
+use frep_core::{
+    replace,
+    search::{FileReplacements, FileSearcherConfig, SearchResultWithReplacement},
+};
+use std::{
+    collections::HashSet,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+/// A response to the confirmation prompt for a single replacement
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Answer {
+    /// Make this replacement
+    Yes,
+    /// Skip this replacement
+    No,
+    /// Make this and all remaining replacements without prompting
+    All,
+    /// Skip this and all remaining replacements, keeping those already confirmed
+    Quit,
+}
+
+impl Answer {
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Some(Answer::Yes),
+            "n" | "no" => Some(Answer::No),
+            "a" | "all" => Some(Answer::All),
+            "q" | "quit" => Some(Answer::Quit),
+            _ => None,
+        }
+    }
+}
+
+/// Displays a replacement and prompts until a valid answer is given. End of input is treated as quitting
+fn prompt(
+    result: &SearchResultWithReplacement,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Answer> {
+    writeln!(
+        output,
+        "{path}:{line_number}",
+        path = result
+            .search_result
+            .path
+            .clone()
+            .unwrap_or_default()
+            .display(),
+        line_number = result.search_result.line_number,
+    )?;
+    writeln!(
+        output,
+        "{}",
+        format!("- {}", result.search_result.line).red()
+    )?;
+    writeln!(output, "{}", format!("+ {}", result.replacement).green())?;
+
+    loop {
+        write!(output, "Replace? [y/n/a/q] ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(Answer::Quit);
+        }
+        if let Some(answer) = Answer::parse(&answer) {
+            return Ok(answer);
+        }
+    }
+}
+
+/// Prompts for confirmation of each replacement in `files`, reading answers from `input`, and writes the
+/// confirmed replacements to disk. Any warnings about a file, such as it having been overwritten in place, are
+/// written to `output` once its replacements have been made.
+///
+/// Returns the paths of the files that had replacements performed in them, along with the number of
+/// matches replaced in each.
+pub fn confirm_and_replace(
+    files: Vec<FileReplacements>,
+    config: &FileSearcherConfig,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> anyhow::Result<Vec<(PathBuf, usize)>> {
+    let mut confirm_all = false;
+    let mut quit = false;
+    let mut replacement_counts = vec![];
+
+    for mut file in files {
+        let mut confirmed = HashSet::new();
+        let mut num_replacements = 0;
+        for result in &file.replacements {
+            let answer = if confirm_all {
+                Answer::Yes
+            } else {
+                prompt(result, input, output)?
+            };
+            match answer {
+                Answer::Yes => {}
+                Answer::No => continue,
+                Answer::All => confirm_all = true,
+                Answer::Quit => {
+                    quit = true;
+                    break;
+                }
+            }
+            confirmed.insert(result.search_result.line_number);
+            num_replacements += config.num_replacements_in(result.search_result.match_count);
+        }
+
+        if !confirmed.is_empty() {
+            let warnings = replace::replace_in_file(&mut file.replacements, config, |res| {
+                confirmed.contains(&res.search_result.line_number)
+            })?;
+            for warning in warnings {
+                writeln!(output, "Warning: {}: {warning}", file.path.display())?;
+            }
+            replacement_counts.push((file.path, num_replacements));
+        }
+        if quit {
+            break;
+        }
+    }
+
+    Ok(replacement_counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frep_core::{line_reader::LineEnding, search::SearchResult};
+    use std::{io::Cursor, path::Path};
+    use tempfile::TempDir;
+
+    fn file_replacements(path: &Path, lines: &[(&str, &str)]) -> FileReplacements {
+        FileReplacements {
+            path: path.to_path_buf(),
+            replacements: lines
+                .iter()
+                .enumerate()
+                .map(|(idx, (line, replacement))| SearchResultWithReplacement {
+                    search_result: SearchResult {
+                        path: Some(path.to_path_buf()),
+                        line_number: idx + 1,
+                        line: (*line).to_string(),
+                        line_ending: LineEnding::Lf,
+                        included: true,
+                        match_count: 1,
+                    },
+                    replacement: (*replacement).to_string(),
+                    replace_result: None,
+                })
+                .collect(),
+            num_replacements: lines.len(),
+            diff: None,
+            context: None,
+            num_over_limit: 0,
+        }
+    }
+
+    #[test]
+    fn test_answer_parse() {
+        assert_eq!(Answer::parse("y\n"), Some(Answer::Yes));
+        assert_eq!(Answer::parse(" No "), Some(Answer::No));
+        assert_eq!(Answer::parse("A"), Some(Answer::All));
+        assert_eq!(Answer::parse("quit"), Some(Answer::Quit));
+        assert_eq!(Answer::parse(""), None);
+        assert_eq!(Answer::parse("maybe"), None);
+    }
+
+    #[test]
+    fn test_confirm_and_replace() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first.txt");
+        let second = temp_dir.path().join("second.txt");
+        let third = temp_dir.path().join("third.txt");
+        std::fs::write(&first, "old 1\nold 2\nold 3\n").unwrap();
+        std::fs::write(&second, "old 1\nold 2\n").unwrap();
+        std::fs::write(&third, "old 1\n").unwrap();
+
+        let files = vec![
+            file_replacements(
+                &first,
+                &[("old 1", "new 1"), ("old 2", "new 2"), ("old 3", "new 3")],
+            ),
+            file_replacements(&second, &[("old 1", "new 1"), ("old 2", "new 2")]),
+            file_replacements(&third, &[("old 1", "new 1")]),
+        ];
+
+        // Invalid answers are asked again
+        let mut input = Cursor::new("y\nwhat\nn\ny\nn\nq\n");
+        let mut output = vec![];
+        let replacement_counts = confirm_and_replace(
+            files,
+            &FileSearcherConfig::default(),
+            &mut input,
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(replacement_counts, vec![(first.clone(), 2)]);
+        assert_eq!(
+            std::fs::read_to_string(&first).unwrap(),
+            "new 1\nold 2\nnew 3\n"
+        );
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "old 1\nold 2\n");
+        assert_eq!(std::fs::read_to_string(&third).unwrap(), "old 1\n");
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .matches("Replace? [y/n/a/q]")
+                .count(),
+            6
+        );
+    }
+
+    #[test]
+    fn test_confirm_and_replace_all() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("first.txt");
+        let second = temp_dir.path().join("second.txt");
+        std::fs::write(&first, "old 1\nold 2\n").unwrap();
+        std::fs::write(&second, "old 1\n").unwrap();
+
+        let files = vec![
+            file_replacements(&first, &[("old 1", "new 1"), ("old 2", "new 2")]),
+            file_replacements(&second, &[("old 1", "new 1")]),
+        ];
+
+        let mut input = Cursor::new("n\na\n");
+        let replacement_counts = confirm_and_replace(
+            files,
+            &FileSearcherConfig::default(),
+            &mut input,
+            &mut io::sink(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            replacement_counts,
+            vec![(first.clone(), 1), (second.clone(), 1)]
+        );
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "old 1\nnew 2\n");
+        assert_eq!(std::fs::read_to_string(&second).unwrap(), "new 1\n");
+    }
+}
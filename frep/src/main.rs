@@ -1,42 +1,187 @@
-use anyhow::bail;
-use clap::Parser;
-use frep_core::validation::{DirConfig, SearchConfig};
+use anyhow::{Context, bail};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum, parser::ValueSource};
+use frep_core::{
+    encoding::Encoding,
+    line_reader::LineEnding,
+    replace::{PostCommand, ReplaceCommand, ReplacementFunction, ReplacementFunctions},
+    search::{FileOrder, FileReplaceResult, FileSearcherConfig, SearchType},
+    undo::{self, UndoJournal},
+    validation::{self, DirConfig, GlobPrecedence, SearchConfig},
+};
 use simple_log::LevelFilter;
 use std::{
-    io::{self, IsTerminal, Read},
-    path::PathBuf,
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    num::NonZero,
+    path::{Path, PathBuf},
+    process::ExitCode,
     str::FromStr,
+    time::{Duration, SystemTime},
 };
+use tempfile::NamedTempFile;
 
 use frep_core::run;
 
+mod config;
+mod interactive;
 mod logging;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable summary
+    Text,
+    /// JSON containing the replacements made in each file
+    Json,
+    /// A line of JSON for each file as soon as it has been processed, with the number of replacements made in
+    /// it, or the error if it couldn't be processed
+    Ndjson,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    /// Colour output when stdout is a terminal
+    Auto,
+    /// Always colour output
+    Always,
+    /// Never colour output
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stdout().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LineEndingChoice {
+    /// Preserve the line ending of each line
+    Keep,
+    /// Unix line endings (\n)
+    Lf,
+    /// Windows line endings (\r\n)
+    Crlf,
+}
+
+impl LineEndingChoice {
+    fn line_ending(self) -> Option<LineEnding> {
+        match self {
+            LineEndingChoice::Keep => None,
+            LineEndingChoice::Lf => Some(LineEnding::Lf),
+            LineEndingChoice::Crlf => Some(LineEnding::CrLf),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum GlobPrecedenceChoice {
+    /// Process files matching an include glob, even if they also match an exclude glob
+    Include,
+    /// Skip files matching an exclude glob, even if they also match an include glob
+    Exclude,
+}
+
+impl GlobPrecedenceChoice {
+    fn glob_precedence(self) -> GlobPrecedence {
+        match self {
+            GlobPrecedenceChoice::Include => GlobPrecedence::Include,
+            GlobPrecedenceChoice::Exclude => GlobPrecedence::Exclude,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SortChoice {
+    /// By path
+    Path,
+    /// By size, smallest first
+    Size,
+    /// By last modification time, oldest first
+    Mtime,
+}
+
+impl SortChoice {
+    fn file_order(self) -> FileOrder {
+        match self {
+            SortChoice::Path => FileOrder::Path,
+            SortChoice::Size => FileOrder::Size,
+            SortChoice::Mtime => FileOrder::Modified,
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(about = "Find and replace CLI. Processes files in a directory or text from stdin.")]
 #[command(version)]
+#[command(
+    after_help = "Exit codes:\n  0  Replacements were made (or would be made with --dry-run, or matches were found with --count or --name-only)\n  1  No matches were found\n  2  An error occurred, including when any files couldn't be processed"
+)]
 #[allow(clippy::struct_excessive_bools)]
 struct Args {
     /// Text to search with. This will be regex, unless --fixed-strings is used in which case this is a string literal
-    #[arg(index = 1)]
-    search_text: String,
+    #[arg(index = 1, required_unless_present_any = ["search_file", "exprs", "undo", "list_files"])]
+    search_text: Option<String>,
 
     /// Text to replace the search text with. This can include capture groups if using search regex. If left blank (and --delete is used) then the search text will be deleted
     #[arg(index = 2)]
     replace_text: Option<String>,
 
-    /// Directory in which to search
+    /// Text to replace the search text with, as an alternative to giving it as the second argument. `--replace ''` replaces matches with empty text, which has the same effect as --delete
+    #[arg(long = "replace", value_name = "TEXT")]
+    replace_option: Option<String>,
+
+    /// Read the search text from a file rather than the command line. A single trailing newline is removed
+    #[arg(long, value_name = "PATH")]
+    search_file: Option<PathBuf>,
+
+    /// Read the replacement text from a file rather than the command line. A single trailing newline is removed
+    #[arg(long, value_name = "PATH")]
+    replace_file: Option<PathBuf>,
+
+    /// Read the replacement text from stdin rather than the command line, such as multiline text produced by another program. A single trailing newline is removed. The files in the directory are then processed as usual, rather than the text piped to stdin
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    replace_stdin: bool,
+
+    /// Replace each match with the output of this shell command, which is passed the matched text on stdin, e.g. `tr a-z A-Z`. A single trailing newline is removed from the output. The command is run once for each distinct match, so this is much slower than replacement text
+    #[arg(long, value_name = "COMMAND")]
+    replace_command: Option<String>,
+
+    /// An additional search and replacement pair, separated by the first equals sign (=). Can be repeated, and expressions are applied in order after the search and replacement text (if given), so each sees the output of the replacements before it
+    #[arg(short = 'e', long = "expr", value_name = "SEARCH=REPLACE", value_parser = parse_expr)]
+    exprs: Vec<(String, String)>,
+
+    /// Directory in which to search. Can be repeated to search several directories, in which case directories inside another of them are only searched once
     #[arg(short, long, value_parser = parse_directory, default_value = ".")]
-    directory: PathBuf,
+    directory: Vec<PathBuf>,
 
     /// Search with plain strings, rather than regex
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     fixed_strings: bool,
 
+    /// Interpret the escape sequences \n, \t, \r, \0 and \\ in the search and replacement text (including in --expr and pattern files) as a newline, tab, carriage return, NUL and backslash respectively. Any other backslash, including a trailing one, is an error. Requires --fixed-strings
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    interpret_escapes: bool,
+
     /// Only match when the search string forms an entire word, and not a substring in a larger word
     #[arg(short = 'w', long, action = clap::ArgAction::SetTrue)]
     match_whole_word: bool,
 
+    /// Characters to treat as part of words when matching whole words, in addition to letters, digits and underscores. For instance, `--word-chars -` stops `foo` from matching within `foo-bar`
+    #[arg(long, value_name = "CHARS")]
+    word_chars: Option<String>,
+
+    /// Only match when the search string matches an entire line, in which case the whole line is replaced. When searching with plain strings, leading and trailing whitespace on the line is ignored
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    line_match: bool,
+
+    /// Keep the leading whitespace of each line with a replacement, restoring it if the replacement doesn't start with it. Useful when replacing whole lines with --line-match or a regex such as `^\s*key = .*`
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    preserve_indent: bool,
+
     /// Ignore case when matching the search string
     #[arg(short = 'i', long, action = clap::ArgAction::SetTrue)]
     case_insensitive: bool,
@@ -49,10 +194,118 @@ struct Args {
     #[arg(short = 'E', long)]
     exclude_files: Option<String>,
 
-    /// Include hidden files and directories, such as those whose name starts with a dot (.)
+    /// Whether include or exclude globs win for files matching both, from --include-files and --type or from --exclude-files and --type-not, e.g. `-I 'src/**' -E '*.rs' --glob-precedence include` to also process Rust files in src. Patterns in --glob-file take precedence over both
+    #[arg(long, value_enum, default_value_t = GlobPrecedenceChoice::Exclude)]
+    glob_precedence: GlobPrecedenceChoice,
+
+    /// File containing glob patterns, one per line, that file paths must match. Patterns starting with ! are excluded instead, and lines starting with # are ignored. Combined with --include-files and --exclude-files
+    #[arg(long, value_name = "PATH")]
+    glob_file: Option<PathBuf>,
+
+    /// Only process files of this type, such as rust or py. Can be repeated to process files of any of the given types
+    #[arg(short = 't', long = "type", value_name = "TYPE")]
+    types: Vec<String>,
+
+    /// Don't process files of this type. Can be repeated
+    #[arg(short = 'T', long = "type-not", value_name = "TYPE")]
+    types_not: Vec<String>,
+
+    /// Only process files with one of these extensions, separated by commas (,), such as rs,toml. This is checked before glob matching, so is faster than the equivalent --include-files patterns in large directories
+    #[arg(long, value_delimiter = ',', value_name = "EXT")]
+    ext: Option<Vec<String>>,
+
+    /// Match against the entire contents of each file rather than line by line, so that matches can span multiple lines. Regex patterns are compiled so that `^` and `$` match at line boundaries (see --dotall to also have `.` match newlines), and capture groups can capture text across lines, including the newlines themselves
+    #[arg(short = 'U', long, action = clap::ArgAction::SetTrue)]
+    multiline: bool,
+
+    /// With --multiline, search each file this many consecutive lines at a time rather than reading it into memory as a whole, so that matches can span at most this many lines. Files of any size can be replaced in this mode, but only a single search pattern is supported
+    #[arg(long, value_name = "NUM")]
+    join_lines: Option<NonZero<usize>>,
+
+    /// Make `.` match newlines in regex patterns. Only has an effect with --multiline, as lines are otherwise matched one at a time
+    #[arg(short = 's', long, action = clap::ArgAction::SetTrue)]
+    dotall: bool,
+
+    /// Apply case-transform escapes in the replacement text when searching with regex: \U and \L uppercase or lowercase the following text until \E, and \u and \l uppercase or lowercase the next character. For instance, replacing `(\w+)_id` with `\U$1\E_ID` turns `user_id` into `USER_ID`
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    case_transforms: bool,
+
+    /// Apply calls to named functions in the replacement text when searching with regex, written as `\{name:text\}`, after capture groups have been expanded. The built-in functions are uppercase, lowercase, trim, reverse and base64, so replacing `(\w+)` with `\{uppercase:$1\}` uppercases each word
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    functions: bool,
+
+    /// Another name for a built-in replacement function, separated by an equals sign (=), such as `upper=uppercase` to allow `\{upper:$1\}`. Can be repeated, and requires --functions
+    #[arg(long = "fn", value_name = "NAME=FUNCTION", value_parser = parse_function)]
+    function_names: Vec<(String, ReplacementFunction)>,
+
+    /// Insert the replacement text verbatim when searching with regex, so that `$` doesn't need escaping and capture group references such as `$1` aren't expanded
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    literal_replacement: bool,
+
+    /// Check that each capture group referenced in the replacement text, such as `$3` or `${name}`, exists in the search pattern, failing before any files are processed otherwise. Without this, references to missing groups are replaced with empty text. Has no effect with --fixed-strings
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    strict_captures: bool,
+
+    /// Expand {path}, {filename} and {lineno} in the replacement text with the path and name of each file and the number of each matching line, e.g. replacing `TODO` with `TODO({filename}:{lineno})`. Other braces are left unchanged
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    template: bool,
+
+    /// Replace only the Nth match in each line, counting from 1, leaving lines with fewer matches unchanged. 0 replaces all matches, as when omitted
+    #[arg(long, value_name = "N")]
+    occurrence: Option<usize>,
+
+    /// Include hidden files and directories, such as those whose name starts with a dot (.). Files matched by ignore files are still skipped unless --no-ignore is also given
     #[arg(short = '.', long, action = clap::ArgAction::SetTrue)]
     hidden: bool,
 
+    /// Follow symbolic links when searching directories
+    #[arg(short = 'L', long, action = clap::ArgAction::SetTrue)]
+    follow: bool,
+
+    /// Error when a file with replacements is a symbolic link, rather than writing the replacements to the file it links to
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_follow_symlink_writes: bool,
+
+    /// Before writing replacements to a file, check that its size and modification time haven't changed since it was read, leaving it untouched with an error otherwise. Useful when other processes may be modifying the same files
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    check_unchanged: bool,
+
+    /// Leave matches inside string literals unchanged, such as to rename an identifier without changing text that mentions it. Supported for Rust (.rs), Python (.py and .pyi) and JSON (.json) files; matches in other files are replaced as usual
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    skip_in_strings: bool,
+
+    /// Also replace matches in the names of files and directories, renaming them. Names are matched in full, rather than as paths, and renames that would overwrite an existing path are reported as errors
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    rename: bool,
+
+    /// Limit the depth of directory traversal to this many levels, where 1 only includes files directly in the directory
+    #[arg(long, value_name = "NUM")]
+    max_depth: Option<usize>,
+
+    /// Print each file in the directory to stderr before searching, along with whether it will be searched and the glob, extension or ignore rule responsible. Every file is listed, including those in ignored directories
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    debug_globs: bool,
+
+    /// List the files that would be searched, relative to the directory, after applying the glob, ignore, hidden, extension and size filters, and exit. File contents aren't read, and the search text is optional
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    list_files: bool,
+
+    /// Don't respect ignore files, such as .gitignore, .ignore, .frepignore and the global gitignore
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_ignore: bool,
+
+    /// Additional ignore file, in .gitignore syntax, whose patterns are matched relative to the directory being searched. Can be repeated, and applies even with --no-ignore
+    #[arg(long, value_name = "PATH")]
+    ignore_file: Vec<PathBuf>,
+
+    /// Process files that appear to be binary, such as those with NUL bytes near the start or with binary extensions like .png, rather than skipping them
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    text: bool,
+
+    /// Fail with a non-zero exit code if any of the files to process appear to be binary, rather than skipping them, such as to catch binary files accidentally matched by --include-files. Each binary file is listed as an error
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    error_on_binary: bool,
+
     /// Log level (trace, debug, info, warn, error)
     #[arg(
         long,
@@ -61,6 +314,10 @@ struct Args {
     )]
     log_level: LevelFilter,
 
+    /// Ignore the config files, which otherwise set defaults for options not given on the command line. A .freprc file in the working directory takes precedence over one in `$XDG_CONFIG_HOME` (or the platform's equivalent)
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    no_config: bool,
+
     /// Use advanced regex features (including negative look-ahead), at the cost of performance
     #[arg(short = 'a', long, action = clap::ArgAction::SetTrue)]
     advanced_regex: bool,
@@ -68,6 +325,194 @@ struct Args {
     /// Delete matches
     #[arg(short = 'D', long, action = clap::ArgAction::SetTrue)]
     delete: bool,
+
+    /// When deleting matches, remove lines that are left empty entirely, including their line endings, rather than leaving blank lines behind. Requires --delete, and is implied by --delete with --line-match
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    delete_line: bool,
+
+    /// Collapse runs of consecutive identical lines that match the search text into a single line, such as `frep '^$' '' --collapse-duplicate-lines` to collapse runs of blank lines into one. Lines are collapsed even if the replacement leaves them unchanged. The first line of each run is kept, with the replacement made in it
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    collapse_duplicate_lines: bool,
+
+    /// Report the changes that would be made, without modifying any files
+    #[arg(short = 'n', long, action = clap::ArgAction::SetTrue)]
+    dry_run: bool,
+
+    /// Print a unified diff of each file with replacements, rather than a summary
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    diff: bool,
+
+    /// Before overwriting a file, copy its original contents to a backup file with the given suffix appended to its path
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak"
+    )]
+    backup: Option<String>,
+
+    /// Run this shell command on each file after replacements are made in it, such as to format it, e.g. `rustfmt {}`. Each {} is replaced with the path of the file, which is appended to the command if there are none. Files for which the command fails are reported as errors, without stopping other files from being processed
+    #[arg(long, value_name = "COMMAND")]
+    post_command: Option<String>,
+
+    /// Record the original contents of each file before it is modified in an undo journal, so that the changes can be reverted with --undo
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    undo_log: bool,
+
+    /// Restore the files modified by the most recent run with --undo-log to their original contents. Files that can't be restored are reported, and the journal is kept so that this can be retried
+    #[arg(long, exclusive = true, action = clap::ArgAction::SetTrue)]
+    undo: bool,
+
+    /// Write the result of replacing in the text piped to stdin to this file, rather than printing it to stdout. The file is written atomically, keeping its permissions if it already exists
+    #[arg(long, value_name = "PATH")]
+    stdin_to: Option<PathBuf>,
+
+    /// Format of the output
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// When to highlight output with colours: file paths are shown in bold, matches in red, and replacements in green. Only applies to the output of --count, --dry-run and --diff, and never to --format json
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Count the matching lines in each file, along with the total number of matches across all files, without performing any replacements
+    #[arg(short = 'c', long, action = clap::ArgAction::SetTrue)]
+    count: bool,
+
+    /// Print the paths of the files whose paths, relative to the directory, match the search text, without reading or replacing file contents
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    name_only: bool,
+
+    /// Encoding of the files to process (utf-8, utf-16le or utf-16be). If not set, this is detected from each file's byte order mark, falling back to utf-8. UTF-16 files are reported as errors with options that replace line by line, such as --dry-run, --diff and --occurrence
+    #[arg(long, value_parser = parse_encoding)]
+    encoding: Option<Encoding>,
+
+    /// Search lines that aren't valid UTF-8 after replacing invalid bytes with U+FFFD, which is written in their place on lines with replacements. By default, lines that aren't valid UTF-8 are left unchanged, and a warning is printed with the offset of the first invalid byte in files with replacements
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    lossy: bool,
+
+    /// Search and replace within gzip-compressed files (detected from their .gz extension or their contents), which are decompressed, replaced as a whole and then compressed again. --max-in-memory-size applies to the decompressed size. Options that replace line by line, such as --diff or --max-count, aren't supported for compressed files, which are reported as errors in that case
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    search_compressed: bool,
+
+    /// With --search-compressed, the gzip compression level to compress files with again, from 0 (no compression) to 9 (best compression)
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=9))]
+    compression_level: Option<u32>,
+
+    /// Line ending to rewrite every line with in files that have replacements made in them (or in stdin), including lines without replacements
+    #[arg(long, value_enum, default_value_t = LineEndingChoice::Keep)]
+    line_ending: LineEndingChoice,
+
+    /// Make each file that has replacements made in it end with exactly one line ending, adding one if it is missing and collapsing multiple into one. Uses --line-ending if set, or otherwise the file's last line ending
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    ensure_trailing_newline: bool,
+
+    /// Files larger than this are replaced line by line rather than being read into memory. Accepts sizes such as 512K, 10M or 1G [default: 100M]
+    #[arg(long, value_parser = parse_size)]
+    max_in_memory_size: Option<u64>,
+
+    /// Only process files at least this large. Accepts sizes such as 512K, 10M or 1G
+    #[arg(long, value_parser = parse_size)]
+    min_size: Option<u64>,
+
+    /// Only process files at most this large. Accepts sizes such as 512K, 10M or 1G
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Only process files modified within this long before now. Accepts durations such as 30s, 15m, 2h, 7d or 1w
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    changed_within: Option<Duration>,
+
+    /// Only process files last modified before this date, in UTC. Accepts dates such as 2024-01-31, optionally followed by a time such as 2024-01-31T14:30 or 2024-01-31T14:30:00
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    changed_before: Option<SystemTime>,
+
+    /// Number of threads to process files with. Defaults to the number of logical CPUs
+    #[arg(short = 'j', long, value_name = "NUM")]
+    threads: Option<NonZero<usize>>,
+
+    /// Process files one at a time in this order, so that runs are reproducible, such as which files --max-count replaces in. Every file is found before any are processed, which is slower than the default of processing files in parallel as they are found, and --threads is ignored
+    #[arg(long, value_enum, value_name = "ORDER")]
+    sort: Option<SortChoice>,
+
+    /// Preserve the modification time of files that have replacements made in them
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    keep_mtime: bool,
+
+    /// Directory to write temporary files to before they replace the files being modified. Defaults to the directory of each file, falling back to the system temporary directory if that isn't writable. Files are overwritten in place, with a warning, if the temporary file can't be moved over them, such as when it is on a different filesystem
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Overwrite each file in place rather than moving a temporary file over it, such as on networked filesystems where this is slow or fails. This isn't atomic: if frep is interrupted while writing a file, the file is left incomplete and its original contents are lost (use --backup to keep a copy)
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    in_place: bool,
+
+    /// Warn about files with replacements that mix \n and \r\n line endings, which may indicate an inconsistently edited file. Each file is read again to check its line endings
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    warn_mixed_endings: bool,
+
+    /// Prompt for confirmation of each replacement, showing the line before and after: answer y to make the replacement, n to skip it, a to make it along with all remaining replacements, or q to skip all remaining replacements. Requires stdin to be a terminal
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    interactive: bool,
+
+    /// Stop after replacing this many matches in total across all files. Lines are replaced as a whole, so a line is left unchanged if its matches would exceed the limit. Which files are replaced first is only deterministic with --sort or --threads 1
+    #[arg(short = 'm', long, value_name = "NUM")]
+    max_count: Option<usize>,
+
+    /// Replace at most this many matches in each file, leaving any further matches in the file unchanged. Lines are replaced as a whole, so replacing stops at the first line whose matches would exceed the limit. Files that reach the limit are listed with --dry-run and --verbose
+    #[arg(long, value_name = "NUM")]
+    max_per_file: Option<usize>,
+
+    /// Fail without modifying any files unless exactly this many matches would be replaced across all files, such as to catch a pattern that matches more or fewer times than intended. The files are searched once to count the replacements before any are made
+    #[arg(long, value_name = "NUM")]
+    expect: Option<usize>,
+
+    /// Replace only the first matching line in each file, such as a version number near the top, leaving any later matches unchanged. The rest of the file is copied without being searched. With --verbose, the number of the replaced line is listed for each file
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    first_only: bool,
+
+    /// Only replace in lines that also match this regex, such as to replace `port` only on lines containing `production`. Other lines are left unchanged, even if they match the search text
+    #[arg(long, value_name = "PATTERN", value_parser = parse_line_pattern)]
+    only_matching_lines: Option<SearchType>,
+
+    /// Don't replace in lines that match this regex, such as `^\s*//` to leave commented-out lines unchanged. Takes precedence over --only-matching-lines
+    #[arg(long, value_name = "PATTERN", value_parser = parse_line_pattern)]
+    skip_lines: Option<SearchType>,
+
+    /// Abort with an error if searching a single file takes longer than this many milliseconds, such as when an advanced regex backtracks catastrophically. Each file is searched on a separate thread before being replaced, so this adds the cost of an extra search. Files where an advanced regex exceeds its backtracking limit are also reported with an error
+    #[arg(long, value_name = "MS")]
+    timeout: Option<NonZero<u64>>,
+
+    /// Print a running count of the files processed and replacements made to stderr
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    progress: bool,
+
+    /// List each file that had replacements made in it, along with the number of matches replaced, before the summary
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    verbose: bool,
+
+    /// Print statistics to stderr once finished: the number of files searched, skipped and with replacements, the total number of replacements, and the time taken
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    stats: bool,
+
+    /// Number of lines of context to show around each match with --count or --dry-run, and in each hunk with --diff
+    #[arg(short = 'C', long, value_name = "NUM")]
+    context: Option<usize>,
+
+    /// Number of lines of context to show after each match. Overrides --context
+    #[arg(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
+
+    /// Number of lines of context to show before each match. Overrides --context
+    #[arg(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
+}
+
+impl Args {
+    fn has_context(&self) -> bool {
+        self.context.is_some() || self.after_context.is_some() || self.before_context.is_some()
+    }
 }
 
 fn detect_and_read_stdin() -> anyhow::Result<Option<String>> {
@@ -81,249 +526,3171 @@ fn detect_and_read_stdin() -> anyhow::Result<Option<String>> {
 }
 
 fn validate_args(args: &Args, stdin_content: Option<&String>) -> anyhow::Result<()> {
-    if args.search_text.is_empty() {
-        bail!("Search text must not be empty");
+    validate_replacement_args(args)?;
+    validate_mode_args(args)?;
+    validate_pattern_args(args)?;
+    validate_line_args(args)?;
+    validate_output_args(args)?;
+    validate_file_args(args)?;
+
+    if args.replace_stdin {
+        // Stdin holds the replacement text, so files are processed as usual
+        if stdin_content.is_none() {
+            bail!("Cannot use --replace-stdin without text piped to stdin");
+        }
+        if args.stdin_to.is_some() || args.interactive {
+            bail!("Cannot use --stdin-to or --interactive with --replace-stdin");
+        }
+    } else if stdin_content.is_some() {
+        validate_stdin_args(args)?;
+    } else if args.stdin_to.is_some() {
+        bail!("Cannot use --stdin-to without text piped to stdin");
     }
 
-    if args.replace_text.is_none() && !args.delete {
-        bail!(
-            "You must specify either replacement text (`frep \"before\" \"after\"`) or use --delete to delete matches `(frep \"before\" --delete)`"
-        );
+    Ok(())
+}
+
+/// Rejects conflicting ways of giving the search and replacement text
+fn validate_replacement_args(args: &Args) -> anyhow::Result<()> {
+    if args.search_file.is_some() {
+        if args.search_text.is_some() {
+            bail!("Cannot specify both search text and --search-file");
+        }
+    } else if !args.list_files
+        && args
+            .search_text
+            .as_ref()
+            .map_or(args.exprs.is_empty(), String::is_empty)
+    {
+        bail!("Search text must not be empty");
     }
-    if args.replace_text.is_some() && args.delete {
-        bail!(
-            "You cannot specify both replacement text and the --delete flag. Use either replacement text (`frep \"before\" \"after\"`) or the --delete flag (`frep \"before\" --delete`)"
-        );
+    if args.replace_option.is_some() && args.replace_text.is_some() {
+        bail!("Cannot specify both replacement text and --replace");
+    }
+    if args.replace_file.is_some() && (args.replace_text.is_some() || args.replace_option.is_some())
+    {
+        bail!("Cannot specify both replacement text and --replace-file");
+    }
+    if args.replace_stdin
+        && (args.replace_text.is_some()
+            || args.replace_option.is_some()
+            || args.replace_file.is_some())
+    {
+        bail!("Cannot specify both replacement text and --replace-stdin");
+    }
+    if args.replace_command.is_some() {
+        if args.replace_text.is_some()
+            || args.replace_option.is_some()
+            || args.replace_file.is_some()
+            || args.replace_stdin
+        {
+            bail!("Cannot specify both replacement text and --replace-command");
+        }
+        if !args.exprs.is_empty()
+            || args.multiline
+            || args.occurrence.is_some()
+            || args.skip_in_strings
+            || args.case_transforms
+            || args.literal_replacement
+        {
+            bail!(
+                "Cannot use --replace-command with --expr, --multiline, --occurrence, --skip-in-strings, --case-transforms or --literal-replacement"
+            );
+        }
     }
+    if args.strict_captures && (args.literal_replacement || args.replace_command.is_some()) {
+        bail!("Cannot use --strict-captures with --literal-replacement or --replace-command");
+    }
+
+    Ok(())
+}
 
-    if stdin_content.is_some() {
-        if args.hidden {
-            bail!("Cannot use --hidden flag when processing stdin");
+/// Rejects arguments that don't apply to the mode of the run, such as replacement text when counting matches
+fn validate_mode_args(args: &Args) -> anyhow::Result<()> {
+    let has_search = args.search_text.is_some() || args.search_file.is_some();
+    let has_replacement = args.replace_text.is_some()
+        || args.replace_option.is_some()
+        || args.replace_file.is_some()
+        || args.replace_stdin
+        || args.replace_command.is_some();
+
+    if args.count && args.name_only {
+        bail!("Cannot use --count with --name-only");
+    }
+    if args.list_files {
+        if args.count || args.name_only || args.interactive || args.diff || args.rename {
+            bail!(
+                "Cannot use --list-files with --count, --name-only, --interactive, --diff or --rename"
+            );
+        }
+    } else if args.count || args.name_only {
+        let flag = if args.count { "--count" } else { "--name-only" };
+        if has_replacement || args.delete || !args.exprs.is_empty() {
+            bail!("Cannot use {flag} with replacement text, --expr or the --delete flag");
+        }
+        if args.dry_run || args.diff || args.backup.is_some() || args.keep_mtime || args.progress {
+            bail!("Cannot use {flag} with --dry-run, --diff, --backup, --keep-mtime or --progress");
+        }
+        if args.format == OutputFormat::Json {
+            bail!("Cannot use {flag} with --format json");
+        }
+    } else if !has_search {
+        if has_replacement || args.delete {
+            bail!("Cannot specify replacement text or the --delete flag without search text");
         }
-        if args.include_files.is_some() {
-            bail!("Cannot use --include-files when processing stdin");
+    } else {
+        if !has_replacement && !args.delete {
+            bail!(
+                "You must specify either replacement text (`frep \"before\" \"after\"`) or use --delete to delete matches `(frep \"before\" --delete)`"
+            );
         }
-        if args.exclude_files.is_some() {
-            bail!("Cannot use --exclude-files when processing stdin");
+        if has_replacement && args.delete {
+            bail!(
+                "You cannot specify both replacement text and the --delete flag. Use either replacement text (`frep \"before\" \"after\"`) or the --delete flag (`frep \"before\" --delete`)"
+            );
         }
     }
+    if args.multiline
+        && (args.count || args.dry_run || args.diff || args.format == OutputFormat::Json)
+    {
+        bail!("Cannot use --multiline with --count, --dry-run, --diff or --format json");
+    }
 
     Ok(())
 }
 
-fn parse_log_level(level: &str) -> Result<LevelFilter, String> {
-    LevelFilter::from_str(level).map_err(|_| format!("Invalid log level: {level}"))
+/// Rejects conflicting options for how the search patterns are matched and replaced
+fn validate_pattern_args(args: &Args) -> anyhow::Result<()> {
+    if args.case_transforms && args.fixed_strings {
+        bail!("Cannot use --case-transforms with --fixed-strings");
+    }
+    if !args.function_names.is_empty() && !args.functions {
+        bail!("Cannot use --fn without --functions");
+    }
+    if args.functions
+        && (args.fixed_strings
+            || args.multiline
+            || args.occurrence.is_some()
+            || args.skip_in_strings
+            || args.case_transforms
+            || args.literal_replacement
+            || args.replace_command.is_some())
+    {
+        bail!(
+            "Cannot use --functions with --fixed-strings, --multiline, --occurrence, --skip-in-strings, --case-transforms, --literal-replacement or --replace-command"
+        );
+    }
+    if args.interpret_escapes && !args.fixed_strings {
+        bail!("Cannot use --interpret-escapes without --fixed-strings");
+    }
+    if args.word_chars.is_some() && !args.match_whole_word {
+        bail!("Cannot use --word-chars without --match-whole-word");
+    }
+    if args.lossy && args.multiline {
+        bail!("Cannot use --lossy with --multiline");
+    }
+    if args.join_lines.is_some() {
+        if !args.multiline {
+            bail!("Cannot use --join-lines without --multiline");
+        }
+        if !args.exprs.is_empty() {
+            bail!("Cannot use --join-lines with --expr");
+        }
+    }
+    if args.timeout.is_some() && args.count {
+        bail!("Cannot use --timeout with --count");
+    }
+
+    Ok(())
 }
 
-fn parse_directory(dir: &str) -> anyhow::Result<PathBuf> {
-    let path = PathBuf::from(dir);
-    if path.exists() {
-        Ok(path)
-    } else {
-        bail!("'{dir}' does not exist. Please provide a valid path.")
+/// Rejects options for replacing line by line that conflict with multiline mode or counting matches
+fn validate_line_args(args: &Args) -> anyhow::Result<()> {
+    if args.preserve_indent && args.multiline {
+        bail!("Cannot use --preserve-indent with --multiline");
+    }
+    if args.delete_line {
+        if !args.delete {
+            bail!("Cannot use --delete-line without --delete");
+        }
+        if args.multiline {
+            bail!("Cannot use --delete-line with --multiline");
+        }
+    }
+    if args.collapse_duplicate_lines
+        && (args.multiline || args.count || args.name_only || args.dry_run || args.diff)
+    {
+        bail!(
+            "Cannot use --collapse-duplicate-lines with --multiline, --count, --name-only, --dry-run or --diff"
+        );
+    }
+    if args.occurrence.is_some() && (args.multiline || args.count) {
+        bail!("Cannot use --occurrence with --multiline or --count");
+    }
+    if args.max_count.is_some()
+        && (args.count || args.dry_run || args.diff || args.multiline || args.interactive)
+    {
+        bail!(
+            "Cannot use --max-count with --count, --dry-run, --diff, --multiline or --interactive"
+        );
+    }
+    if args.max_per_file.is_some() && (args.count || args.multiline) {
+        bail!("Cannot use --max-per-file with --count or --multiline");
+    }
+    if args.first_only && (args.count || args.multiline) {
+        bail!("Cannot use --first-only with --count or --multiline");
+    }
+    if args.only_matching_lines.is_some() && (args.multiline || args.count || args.name_only) {
+        bail!("Cannot use --only-matching-lines with --multiline, --count or --name-only");
+    }
+    if args.skip_lines.is_some() && (args.multiline || args.count || args.name_only) {
+        bail!("Cannot use --skip-lines with --multiline, --count or --name-only");
+    }
+    if args.skip_in_strings && (args.multiline || args.count) {
+        bail!("Cannot use --skip-in-strings with --multiline or --count");
+    }
+    if args.template
+        && (args.count || args.multiline || args.replace_command.is_some() || args.rename)
+    {
+        bail!("Cannot use --template with --count, --multiline, --replace-command or --rename");
     }
+
+    Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-    let stdin_content = detect_and_read_stdin()?;
+/// Rejects conflicting options for how the results are reported
+fn validate_output_args(args: &Args) -> anyhow::Result<()> {
+    if args.has_context() {
+        if !(args.count || args.dry_run || args.diff) {
+            bail!(
+                "Cannot use --context, --after-context or --before-context without --count, --dry-run or --diff"
+            );
+        }
+        if args.format == OutputFormat::Json {
+            bail!("Cannot use --context, --after-context or --before-context with --format json");
+        }
+    }
+    if args.format == OutputFormat::Json && args.diff {
+        bail!("Cannot use --diff flag with --format json");
+    }
+    if args.format == OutputFormat::Ndjson
+        && (args.count
+            || args.name_only
+            || args.diff
+            || args.interactive
+            || args.verbose
+            || args.stats
+            || args.progress
+            || args.rename
+            || args.post_command.is_some()
+            || args.has_context())
+    {
+        bail!(
+            "Cannot use --format ndjson with --count, --name-only, --diff, --interactive, --verbose, --stats, --progress, --rename, --post-command or --context"
+        );
+    }
 
-    validate_args(&args, stdin_content.as_ref())?;
-    logging::setup_logging(args.log_level)?;
+    if args.interactive
+        && (args.count
+            || args.dry_run
+            || args.diff
+            || args.multiline
+            || args.progress
+            || args.format == OutputFormat::Json)
+    {
+        bail!(
+            "Cannot use --interactive with --count, --dry-run, --diff, --multiline, --progress or --format json"
+        );
+    }
+    if args.expect.is_some()
+        && (args.count
+            || args.name_only
+            || args.list_files
+            || args.interactive
+            || args.multiline
+            || args.format == OutputFormat::Ndjson)
+    {
+        bail!(
+            "Cannot use --expect with --count, --name-only, --list-files, --interactive, --multiline or --format ndjson"
+        );
+    }
+    if args.rename && (args.count || args.interactive) {
+        bail!("Cannot use --rename with --count or --interactive");
+    }
+    if args.name_only && (args.multiline || args.interactive || args.rename) {
+        bail!("Cannot use --name-only with --multiline, --interactive or --rename");
+    }
+    if args.verbose && (args.count || args.diff || args.format == OutputFormat::Json) {
+        bail!("Cannot use --verbose with --count, --diff or --format json");
+    }
+    if args.stats && (args.count || args.interactive) {
+        bail!("Cannot use --stats with --count or --interactive");
+    }
 
-    let search_config = search_config_from_args(&args);
-    let results = if let Some(stdin_content) = stdin_content {
-        run::find_and_replace_text(&stdin_content, search_config)?
-    } else {
-        run::find_and_replace(search_config, dir_config_from_args(&args))?
-    };
-    print!("{results}");
     Ok(())
 }
 
-fn dir_config_from_args(args: &Args) -> DirConfig<'_> {
-    DirConfig {
-        include_globs: args.include_files.as_deref(),
-        exclude_globs: args.exclude_files.as_deref(),
-        include_hidden: args.hidden,
-        directory: args.directory.clone(),
+/// Rejects conflicting options for which files are processed and how they are written
+fn validate_file_args(args: &Args) -> anyhow::Result<()> {
+    if args.backup.as_ref().is_some_and(String::is_empty) {
+        bail!("Backup suffix must not be empty");
+    }
+    if args.error_on_binary && (args.text || args.count || args.name_only) {
+        bail!("Cannot use --error-on-binary with --text, --count or --name-only");
+    }
+    if args.warn_mixed_endings && (args.count || args.name_only) {
+        bail!("Cannot use --warn-mixed-endings with --count or --name-only");
+    }
+    if args.search_compressed && (args.count || args.name_only || args.interactive) {
+        bail!("Cannot use --search-compressed with --count, --name-only or --interactive");
     }
+    if args.compression_level.is_some() && !args.search_compressed {
+        bail!("Cannot use --compression-level without --search-compressed");
+    }
+    if args.undo_log && (args.count || args.name_only || args.dry_run || args.diff || args.rename) {
+        bail!("Cannot use --undo-log with --count, --name-only, --dry-run, --diff or --rename");
+    }
+    if args.post_command.is_some()
+        && (args.count || args.name_only || args.dry_run || args.diff || args.interactive)
+    {
+        bail!(
+            "Cannot use --post-command with --count, --name-only, --dry-run, --diff or --interactive"
+        );
+    }
+    if let (Some(min_size), Some(max_size)) = (args.min_size, args.max_size)
+        && min_size > max_size
+    {
+        bail!("--min-size must not be larger than --max-size");
+    }
+    if let (Some(changed_within), Some(changed_before)) = (args.changed_within, args.changed_before)
+        && SystemTime::now()
+            .checked_sub(changed_within)
+            .is_some_and(|changed_after| changed_after >= changed_before)
+    {
+        bail!("No files can match both --changed-within and --changed-before");
+    }
+
+    Ok(())
 }
 
-fn search_config_from_args(args: &Args) -> SearchConfig<'_> {
-    SearchConfig {
-        search_text: &args.search_text,
-        replacement_text: args.replace_text.as_deref().unwrap_or(""),
-        fixed_strings: args.fixed_strings,
-        advanced_regex: args.advanced_regex,
-        match_whole_word: args.match_whole_word,
-        match_case: !args.case_insensitive,
+/// Warnings about combinations of arguments that are valid but likely not what was intended
+fn arg_warnings(args: &Args) -> Vec<&'static str> {
+    let mut warnings = vec![];
+    if args.dotall && !args.multiline {
+        warnings.push(
+            "--dotall has no effect without --multiline, as lines are matched one at a time and so never contain newlines",
+        );
     }
+    warnings
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+/// A warning if files had to match include globs or extensions but none were found, which is likely because of a
+/// mistyped glob, as the run would otherwise just report that no files were updated
+fn no_files_found_warning(args: &Args, stats: &run::RunStats) -> Option<&'static str> {
+    let has_include_filters = args.include_files.is_some()
+        || !args.types.is_empty()
+        || args.glob_file.is_some()
+        || args.ext.is_some();
+    (has_include_filters && stats.num_files_found() == 0).then_some(
+        "No files matched --include-files, --type, --glob-file or --ext, so no files were searched. Check them for typos",
+    )
+}
 
-    fn setup_test_dir() -> TempDir {
-        TempDir::new().unwrap()
+/// Rejects flags that only apply when processing files in a directory
+fn validate_stdin_args(args: &Args) -> anyhow::Result<()> {
+    let directory_flag = [
+        (args.hidden, "--hidden flag"),
+        (args.follow, "--follow flag"),
+        (
+            args.no_follow_symlink_writes,
+            "--no-follow-symlink-writes flag",
+        ),
+        (args.rename, "--rename flag"),
+        (args.template, "--template flag"),
+        (args.debug_globs, "--debug-globs flag"),
+        (args.list_files, "--list-files flag"),
+        (args.check_unchanged, "--check-unchanged flag"),
+        (args.skip_in_strings, "--skip-in-strings flag"),
+        (args.delete_line, "--delete-line flag"),
+        (
+            args.collapse_duplicate_lines,
+            "--collapse-duplicate-lines flag",
+        ),
+        (args.max_depth.is_some(), "--max-depth"),
+        (args.no_ignore, "--no-ignore flag"),
+        (args.text, "--text flag"),
+        (args.error_on_binary, "--error-on-binary flag"),
+        (args.include_files.is_some(), "--include-files"),
+        (args.exclude_files.is_some(), "--exclude-files"),
+        (args.glob_file.is_some(), "--glob-file"),
+        (
+            args.glob_precedence != GlobPrecedenceChoice::Exclude,
+            "--glob-precedence",
+        ),
+        (
+            !args.types.is_empty() || !args.types_not.is_empty(),
+            "--type or --type-not",
+        ),
+        (args.ext.is_some(), "--ext"),
+        (!args.ignore_file.is_empty(), "--ignore-file"),
+        (args.dry_run, "--dry-run flag"),
+        (args.diff, "--diff flag"),
+        (args.backup.is_some(), "--backup"),
+        (
+            args.format != OutputFormat::Text,
+            "--format json or --format ndjson",
+        ),
+        (args.count, "--count flag"),
+        (args.name_only, "--name-only flag"),
+        (args.encoding.is_some(), "--encoding"),
+        (args.lossy, "--lossy flag"),
+        (args.post_command.is_some(), "--post-command"),
+        (
+            args.search_compressed || args.compression_level.is_some(),
+            "--search-compressed or --compression-level",
+        ),
+        (args.join_lines.is_some(), "--join-lines"),
+        (args.undo_log || args.undo, "--undo-log or --undo"),
+        (args.max_in_memory_size.is_some(), "--max-in-memory-size"),
+        (
+            args.min_size.is_some() || args.max_size.is_some(),
+            "--min-size or --max-size",
+        ),
+        (
+            args.changed_within.is_some() || args.changed_before.is_some(),
+            "--changed-within or --changed-before",
+        ),
+        (args.keep_mtime, "--keep-mtime flag"),
+        (args.temp_dir.is_some(), "--temp-dir"),
+        (args.in_place, "--in-place flag"),
+        (args.warn_mixed_endings, "--warn-mixed-endings flag"),
+        (
+            args.ensure_trailing_newline,
+            "--ensure-trailing-newline flag",
+        ),
+        (args.threads.is_some(), "--threads"),
+        (args.sort.is_some(), "--sort"),
+        (args.max_count.is_some(), "--max-count"),
+        (args.max_per_file.is_some(), "--max-per-file"),
+        (args.expect.is_some(), "--expect"),
+        (args.timeout.is_some(), "--timeout"),
+        (args.progress, "--progress flag"),
+        (args.verbose, "--verbose flag"),
+        (args.stats, "--stats flag"),
+    ]
+    .into_iter()
+    .find_map(|(set, flag)| set.then_some(flag));
+    if let Some(flag) = directory_flag {
+        bail!("Cannot use {flag} when processing stdin");
+    }
+    if args.interactive {
+        bail!("Cannot use --interactive flag when stdin is not a terminal");
     }
 
-    #[test]
-    fn test_validate_directory_exists() {
-        let temp_dir = setup_test_dir();
-        let dir_path = temp_dir.path().to_str().unwrap();
+    Ok(())
+}
 
-        let result = parse_directory(dir_path);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), PathBuf::from(dir_path));
+/// Reads search or replacement text from a file, removing a single trailing newline if present
+fn read_pattern_file(path: &Path) -> anyhow::Result<String> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read '{}'", path.display()))?;
+    Ok(without_trailing_newline(text))
+}
+
+/// Removes a single trailing `\n` or `\r\n` from `text`, such as the one ending the last line of a file
+fn without_trailing_newline(mut text: String) -> String {
+    if text.ends_with('\n') {
+        text.pop();
+        if text.ends_with('\r') {
+            text.pop();
+        }
     }
+    text
+}
 
-    #[test]
-    fn test_validate_directory_does_not_exist() {
+/// Sets each option in `config` that wasn't given on the command line, so that command-line options take
+/// precedence over the config files
+fn apply_config(args: &mut Args, config: config::Config, matches: &ArgMatches) {
+    let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    if let Some(hidden) = config.hidden
+        && !from_cli("hidden")
+    {
+        args.hidden = hidden;
+    }
+    if let Some(follow) = config.follow
+        && !from_cli("follow")
+    {
+        args.follow = follow;
+    }
+    if let Some(no_ignore) = config.no_ignore
+        && !from_cli("no_ignore")
+    {
+        args.no_ignore = no_ignore;
+    }
+    if let Some(case_insensitive) = config.case_insensitive
+        && !from_cli("case_insensitive")
+    {
+        args.case_insensitive = case_insensitive;
+    }
+    if let Some(advanced_regex) = config.advanced_regex
+        && !from_cli("advanced_regex")
+    {
+        args.advanced_regex = advanced_regex;
+    }
+    if config.include_files.is_some() && !from_cli("include_files") {
+        args.include_files = config.include_files;
+    }
+    if config.exclude_files.is_some() && !from_cli("exclude_files") {
+        args.exclude_files = config.exclude_files;
+    }
+    if config.threads.is_some() && !from_cli("threads") {
+        args.threads = config.threads;
+    }
+}
+
+/// Populates the search and replacement text from `--search-file`, and from `--replace` or `--replace-file`,
+/// if set
+fn load_pattern_files(args: &mut Args) -> anyhow::Result<()> {
+    if let Some(path) = &args.search_file {
+        let search_text = read_pattern_file(path)?;
+        if search_text.is_empty() {
+            bail!("Search text must not be empty");
+        }
+        args.search_text = Some(search_text);
+    }
+    if let Some(replace_text) = args.replace_option.take() {
+        args.replace_text = Some(replace_text);
+    }
+    if let Some(path) = &args.replace_file {
+        args.replace_text = Some(read_pattern_file(path)?);
+    }
+    Ok(())
+}
+
+/// Replaces the escape sequences `\n`, `\t`, `\r`, `\0` and `\\` in `text` with the characters they represent,
+/// erroring on any other escape sequence or a trailing backslash rather than passing it through
+fn interpret_escapes(text: &str) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some('\\') => result.push('\\'),
+            Some(other) => bail!(
+                "Invalid escape sequence '\\{other}' in '{text}': expected one of \\n, \\t, \\r, \\0 or \\\\"
+            ),
+            None => bail!("Invalid trailing backslash in '{text}'"),
+        }
+    }
+    Ok(result)
+}
+
+/// Interprets escape sequences in the search and replacement text and additional expressions. See
+/// `interpret_escapes`
+fn interpret_escapes_in_args(args: &mut Args) -> anyhow::Result<()> {
+    if let Some(search_text) = &args.search_text {
+        args.search_text = Some(interpret_escapes(search_text)?);
+    }
+    if let Some(replace_text) = &args.replace_text {
+        args.replace_text = Some(interpret_escapes(replace_text)?);
+    }
+    for (search, replace) in &mut args.exprs {
+        *search = interpret_escapes(search)?;
+        *replace = interpret_escapes(replace)?;
+    }
+    Ok(())
+}
+
+fn parse_expr(expr: &str) -> anyhow::Result<(String, String)> {
+    let Some((search, replace)) = expr.split_once('=') else {
+        bail!("Invalid expression '{expr}': expected SEARCH=REPLACE");
+    };
+    if search.is_empty() {
+        bail!("Invalid expression '{expr}': search text must not be empty");
+    }
+    Ok((search.to_owned(), replace.to_owned()))
+}
+
+fn parse_line_pattern(pattern: &str) -> anyhow::Result<SearchType> {
+    validation::parse_search_text(&SearchConfig::builder(pattern, "").build())
+}
+
+fn parse_function(definition: &str) -> anyhow::Result<(String, ReplacementFunction)> {
+    let Some((name, builtin)) = definition.split_once('=') else {
+        bail!("Invalid function '{definition}': expected NAME=FUNCTION");
+    };
+    if name.is_empty() || name.contains(':') {
+        bail!("Invalid function '{definition}': name must not be empty or contain a colon");
+    }
+    let builtins = ReplacementFunctions::default();
+    let Some(function) = builtins.get(builtin) else {
+        bail!(
+            "Unknown function '{builtin}': expected one of {}",
+            builtins.names().join(", ")
+        );
+    };
+    Ok((name.to_owned(), function))
+}
+
+fn parse_log_level(level: &str) -> Result<LevelFilter, String> {
+    LevelFilter::from_str(level).map_err(|_| format!("Invalid log level: {level}"))
+}
+
+fn parse_encoding(encoding: &str) -> Result<Encoding, String> {
+    Encoding::from_str(encoding).map_err(|e| e.to_string())
+}
+
+fn parse_size(size: &str) -> anyhow::Result<u64> {
+    let size = size.trim();
+    let digits_end = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(digits_end);
+    if number.is_empty() {
+        bail!(
+            "Invalid size '{size}': expected a number, optionally followed by a unit (K, M or G)"
+        );
+    }
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        _ => bail!("Invalid size unit '{unit}': expected one of K, M or G"),
+    };
+    number
+        .parse::<u64>()?
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow::anyhow!("Size '{size}' is too large"))
+}
+
+/// Parses a duration given as a whole number followed by a unit, such as 30s, 15m, 2h, 7d or 1w
+fn parse_duration(duration: &str) -> anyhow::Result<Duration> {
+    let duration = duration.trim();
+    let digits_end = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(digits_end);
+    if number.is_empty() || unit.trim().is_empty() {
+        bail!(
+            "Invalid duration '{duration}': expected a number followed by a unit (s, m, h, d or w)"
+        );
+    }
+    let seconds_per_unit: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!("Invalid duration unit '{unit}': expected one of s, m, h, d or w"),
+    };
+    number
+        .parse::<u64>()?
+        .checked_mul(seconds_per_unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| anyhow::anyhow!("Duration '{duration}' is too large"))
+}
+
+/// Parses a date in UTC, given as YYYY-MM-DD and optionally followed by a time as HH:MM or HH:MM:SS, separated
+/// from the date by `T` or a space
+fn parse_date(date: &str) -> anyhow::Result<SystemTime> {
+    let date = date.trim();
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid date '{date}': expected YYYY-MM-DD, optionally followed by a time such as T14:30 or T14:30:00"
+        )
+    };
+    let parse_parts = |s: &str, separator: char| {
+        s.split(separator)
+            .map(|part| {
+                if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                part.parse::<u64>().ok()
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)
+    };
+
+    let (day_part, time_part) = match date.split_once(['T', ' ']) {
+        Some((day_part, time_part)) => (day_part, Some(time_part)),
+        None => (date, None),
+    };
+    let [year, month, day] = parse_parts(day_part, '-')?[..] else {
+        return Err(invalid());
+    };
+    let (hour, minute, second) = match time_part.map(|t| parse_parts(t, ':')).transpose()? {
+        None => (0, 0, 0),
+        Some(time) => match time[..] {
+            [hour, minute] => (hour, minute, 0),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return Err(invalid()),
+        },
+    };
+    if !(1970..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(invalid());
+    }
+
+    let seconds =
+        days_since_epoch(year, month, day) * 24 * 60 * 60 + hour * 60 * 60 + minute * 60 + second;
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days from 1970-01-01 to the given date, which must not be before it. This uses Howard
+/// Hinnant's `days_from_civil` algorithm, which treats years as starting in March so that leap days come last
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn parse_directory(dir: &str) -> anyhow::Result<PathBuf> {
+    let path = PathBuf::from(dir);
+    if path.exists() {
+        Ok(path)
+    } else {
+        bail!("'{dir}' does not exist. Please provide a valid path.")
+    }
+}
+
+/// Exit code used when replacements were made (or would be, or matches were found when counting)
+const EXIT_CODE_CHANGED: u8 = 0;
+/// Exit code used when no matches were found
+const EXIT_CODE_NO_MATCHES: u8 = 1;
+/// Exit code used when an error occurred
+const EXIT_CODE_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::from(EXIT_CODE_CHANGED),
+        Ok(false) => ExitCode::from(EXIT_CODE_NO_MATCHES),
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(EXIT_CODE_ERROR)
+        }
+    }
+}
+
+/// Runs frep with the command-line arguments, returning whether any matches were found
+fn run() -> anyhow::Result<bool> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let mut stdin_content = detect_and_read_stdin()?;
+    if !args.no_config {
+        let mut config = config::load()?;
+        if stdin_content.is_some() && !args.replace_stdin {
+            config = config.without_file_options();
+        }
+        apply_config(&mut args, config, &matches);
+    }
+
+    validate_args(&args, stdin_content.as_ref())?;
+    for warning in arg_warnings(&args) {
+        eprintln!("Warning: {warning}");
+    }
+    load_pattern_files(&mut args)?;
+    if args.replace_stdin {
+        args.replace_text = stdin_content.take().map(without_trailing_newline);
+    }
+    if args.interpret_escapes {
+        interpret_escapes_in_args(&mut args)?;
+    }
+    logging::setup_logging(args.log_level)?;
+
+    if args.undo {
+        return undo_last_run();
+    }
+
+    let search_config = search_config_from_args(&args);
+    if args.debug_globs {
+        for (path, decision) in
+            run::explain_filters(search_config.clone(), dir_config_from_args(&args))?
+        {
+            eprintln!("{path}: {decision}", path = path.display());
+        }
+    }
+    if let Some(stdin_content) = stdin_content {
+        let result = run::find_and_replace_text_with_config(
+            &stdin_content,
+            search_config,
+            &searcher_config_from_args(&args),
+        )?;
+        if let Some(path) = &args.stdin_to {
+            write_atomically(path, &result)
+                .with_context(|| format!("Failed to write to {}", path.display()))?;
+        } else {
+            print!("{result}");
+        }
+        Ok(result != stdin_content)
+    } else if args.list_files {
+        let paths = run::list_files(
+            search_config,
+            dir_config_from_args(&args),
+            searcher_config_from_args(&args),
+        )?;
+        for path in &paths {
+            println!("{}", path.display());
+        }
+        Ok(!paths.is_empty())
+    } else if args.count {
+        let summary = run::count_matches_with_config(
+            search_config,
+            dir_config_from_args(&args),
+            searcher_config_from_args(&args),
+        )?;
+        print!("{summary}");
+        Ok(summary.total() > 0)
+    } else if args.name_only {
+        let paths = run::find_matching_paths(
+            search_config,
+            dir_config_from_args(&args),
+            searcher_config_from_args(&args),
+        )?;
+        for path in &paths {
+            println!("{}", path.display());
+        }
+        Ok(!paths.is_empty())
+    } else if args.interactive {
+        replace_interactively(search_config, &args)
+    } else if args.format == OutputFormat::Ndjson {
+        replace_printing_ndjson(search_config, &args)
+    } else {
+        replace_printing_summary(search_config, &args)
+    }
+}
+
+/// Finds the replacements to make in files, and then asks whether to make each of them before printing a
+/// summary. Returns whether any replacements were made
+fn replace_interactively(search_config: SearchConfig<'_>, args: &Args) -> anyhow::Result<bool> {
+    let searcher_config = searcher_config_from_args(args);
+    let summary = run::find_and_replace_with_config(
+        search_config,
+        dir_config_from_args(args),
+        FileSearcherConfig {
+            dry_run: true,
+            ..searcher_config.clone()
+        },
+    )?;
+    let replacement_counts = interactive::confirm_and_replace(
+        summary.files,
+        &searcher_config,
+        &mut io::stdin().lock(),
+        &mut io::stdout(),
+    )?;
+    let modified_paths = replacement_counts
+        .iter()
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    let summary = run::RunSummary {
+        num_files_replaced: modified_paths.len(),
+        files: vec![],
+        dry_run: false,
+        diff: false,
+        num_binary_files_skipped: summary.num_binary_files_skipped,
+        errors: summary.errors,
+        skipped: summary.skipped,
+        modified_paths,
+        replacement_counts,
+        over_limit: summary.over_limit,
+        renamed: vec![],
+        mixed_line_endings: summary.mixed_line_endings,
+        warnings: summary.warnings,
+        stats: summary.stats,
+    };
+    if args.verbose {
+        print_replacement_counts(&summary, args.first_only);
+    }
+    print!("{summary}");
+    if let Some(warning) = no_files_found_warning(args, &summary.stats) {
+        eprintln!("Warning: {warning}");
+    }
+    check_errors(&summary)?;
+    Ok(summary.has_replacements())
+}
+
+/// Replaces in files, printing a summary once all files have been processed, along with the progress so far
+/// while replacing if `--progress` is set. Returns whether any replacements were made
+fn replace_printing_summary(search_config: SearchConfig<'_>, args: &Args) -> anyhow::Result<bool> {
+    let mut progress = Progress::default();
+    let mut update_progress = |_: &Path, num_replacements| progress.update(num_replacements);
+    let summary = run::find_and_replace_with_progress(
+        search_config,
+        dir_config_from_args(args),
+        searcher_config_from_args(args),
+        if args.progress {
+            Some(&mut update_progress)
+        } else {
+            None
+        },
+    )?;
+    if args.progress {
+        eprintln!();
+    }
+    if args.verbose {
+        print_replacement_counts(&summary, args.first_only);
+    }
+    match args.format {
+        OutputFormat::Text => print!("{summary}"),
+        OutputFormat::Json => println!("{}", summary.to_json()),
+        OutputFormat::Ndjson => unreachable!("ndjson output is printed while replacing"),
+    }
+    if let Some(warning) = no_files_found_warning(args, &summary.stats) {
+        eprintln!("Warning: {warning}");
+    }
+    if args.stats {
+        eprint!("{}", summary.stats);
+    }
+    check_errors(&summary)?;
+    Ok(summary.has_replacements())
+}
+
+/// Replaces in files, printing a line of JSON for each file to stdout as soon as it has been processed.
+/// Returns whether any replacements were made
+fn replace_printing_ndjson(search_config: SearchConfig<'_>, args: &Args) -> anyhow::Result<bool> {
+    let mut has_replacements = false;
+    let mut num_errors = 0;
+    let mut stdout = io::stdout().lock();
+    run::find_and_replace_streaming(
+        search_config,
+        dir_config_from_args(args),
+        searcher_config_from_args(args),
+        |outcome| {
+            match &outcome.result {
+                FileReplaceResult::Replaced {
+                    num_replacements, ..
+                } => has_replacements |= *num_replacements > 0,
+                FileReplaceResult::Error(_) => num_errors += 1,
+                _ => {}
+            }
+            if let Some(json) = outcome.to_json(args.dry_run) {
+                // Flushed for each file, so that consumers see each result as soon as it is available
+                writeln!(stdout, "{json}")?;
+                stdout.flush()?;
+            }
+            Ok(())
+        },
+    )?;
+    if num_errors > 0 {
+        bail!(
+            "Failed to process {num_errors} file{suffix}",
+            suffix = if num_errors != 1 { "s" } else { "" },
+        );
+    }
+    Ok(has_replacements)
+}
+
+/// Prints each file that had replacements made in it along with the number of replacements, e.g.
+/// `src/main.rs: 4`, for `--verbose`. Matches left unchanged because of `--max-per-file` are noted alongside,
+/// as is the number of the replaced line with `--first-only`
+fn print_replacement_counts(summary: &run::RunSummary, first_only: bool) {
+    for (path, num_replacements) in &summary.replacement_counts {
+        if first_only && let Some(line_number) = replaced_line_number(summary, path) {
+            println!(
+                "{path}: {num_replacements} (line {line_number})",
+                path = path.display()
+            );
+            continue;
+        }
+        let num_over_limit = summary
+            .over_limit
+            .iter()
+            .find(|(over_limit_path, _)| over_limit_path == path)
+            .map_or(0, |(_, n)| *n);
+        if num_over_limit > 0 {
+            println!(
+                "{path}: {num_replacements} ({num_over_limit} skipped due to per-file limit)",
+                path = path.display()
+            );
+        } else {
+            println!("{path}: {num_replacements}", path = path.display());
+        }
+    }
+}
+
+/// The line number of the only replacement in the file at `path`, if line-level results were collected for
+/// it and it has exactly one replacement, as with `--first-only`
+fn replaced_line_number(summary: &run::RunSummary, path: &Path) -> Option<usize> {
+    let file = summary.files.iter().find(|file| file.path == path)?;
+    match file.replacements.as_slice() {
+        [replacement] => Some(replacement.search_result.line_number),
+        _ => None,
+    }
+}
+
+/// The directory containing the undo journals written with `--undo-log`
+fn undo_journals_dir() -> PathBuf {
+    logging::cache_dir().join("undo")
+}
+
+/// Writes `contents` to the file at `path` by renaming a temporary file over it, so that the file is never left
+/// partially written. The permissions of the file are kept if it already exists
+fn write_atomically(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut temp_file = NamedTempFile::new_in(parent)?;
+    temp_file.write_all(contents.as_bytes())?;
+    if let Ok(metadata) = fs::metadata(path) {
+        temp_file
+            .as_file()
+            .set_permissions(metadata.permissions())?;
+    }
+    temp_file.persist(path)?;
+    Ok(())
+}
+
+/// Restores the files recorded in the most recent undo journal, for `--undo`
+fn undo_last_run() -> anyhow::Result<bool> {
+    let Some(journal_dir) = undo::latest_journal(&undo_journals_dir())? else {
+        bail!("No runs to undo: run with --undo-log to record changes so that they can be undone");
+    };
+    let summary = undo::undo(&journal_dir)?;
+    print!("{summary}");
+    if !summary.errors.is_empty() {
+        bail!(
+            "Failed to restore {num_errors} file{suffix}, so the undo journal at {journal} has been kept",
+            num_errors = summary.errors.len(),
+            suffix = if summary.errors.len() != 1 { "s" } else { "" },
+            journal = journal_dir.display(),
+        );
+    }
+    Ok(!summary.restored.is_empty())
+}
+
+/// Returns an error if any files couldn't be processed, so that the exit code reflects the failure. The
+/// files and their errors are listed in the summary
+fn check_errors(summary: &run::RunSummary) -> anyhow::Result<()> {
+    if !summary.errors.is_empty() {
+        bail!(
+            "Failed to process {num_errors} file{suffix}",
+            num_errors = summary.errors.len(),
+            suffix = if summary.errors.len() != 1 { "s" } else { "" },
+        );
+    }
+    Ok(())
+}
+
+/// A running count of the files processed and replacements made, printed to stderr with `--progress`
+#[derive(Default)]
+struct Progress {
+    num_files: usize,
+    num_replacements: usize,
+}
+
+impl Progress {
+    fn update(&mut self, num_replacements: usize) {
+        self.num_files += 1;
+        self.num_replacements += num_replacements;
+        eprint!(
+            "\rProcessed {num_files} file{files_suffix}, {num_replacements} replacement{replacements_suffix}",
+            num_files = self.num_files,
+            files_suffix = if self.num_files != 1 { "s" } else { "" },
+            num_replacements = self.num_replacements,
+            replacements_suffix = if self.num_replacements != 1 { "s" } else { "" },
+        );
+    }
+}
+
+fn dir_config_from_args(args: &Args) -> DirConfig<'_> {
+    DirConfig {
+        include_globs: args.include_files.as_deref(),
+        exclude_globs: args.exclude_files.as_deref(),
+        glob_file: args.glob_file.as_deref(),
+        include_types: &args.types,
+        exclude_types: &args.types_not,
+        ignore_files: &args.ignore_file,
+        glob_precedence: args.glob_precedence.glob_precedence(),
+        extensions: args.ext.clone(),
+        include_hidden: args.hidden,
+        follow_symlinks: args.follow,
+        max_depth: args.max_depth,
+        respect_ignore: !args.no_ignore,
+        directories: args.directory.clone(),
+    }
+}
+
+fn searcher_config_from_args(args: &Args) -> FileSearcherConfig {
+    FileSearcherConfig {
+        dry_run: args.dry_run,
+        diff: args.diff,
+        backup_suffix: args.backup.clone(),
+        // The replaced line in each file is listed with --verbose
+        collect_line_results: args.format == OutputFormat::Json
+            || (args.verbose && args.first_only),
+        encoding: args.encoding,
+        max_in_memory_size: args.max_in_memory_size,
+        size_filter: (args.min_size.is_some() || args.max_size.is_some())
+            .then_some((args.min_size, args.max_size)),
+        modified_filter: (args.changed_within.is_some() || args.changed_before.is_some()).then(
+            || {
+                (
+                    args.changed_within
+                        .and_then(|changed_within| SystemTime::now().checked_sub(changed_within)),
+                    args.changed_before,
+                )
+            },
+        ),
+        keep_mtime: args.keep_mtime,
+        threads: args.threads,
+        sort: args.sort.map(SortChoice::file_order),
+        case_transforms: args.case_transforms,
+        before_context: args.before_context.or(args.context),
+        after_context: args.after_context.or(args.context),
+        max_count: args.max_count,
+        max_per_file: args.max_per_file,
+        expect_count: args.expect,
+        first_only: args.first_only,
+        only_matching_lines: args.only_matching_lines.clone(),
+        skip_lines: args.skip_lines.clone(),
+        occurrence: args.occurrence.and_then(NonZero::new),
+        include_binary: args.text,
+        error_on_binary: args.error_on_binary,
+        temp_dir: args.temp_dir.clone(),
+        in_place: args.in_place,
+        warn_mixed_endings: args.warn_mixed_endings,
+        color: args.format == OutputFormat::Text && args.color.enabled(),
+        literal_replacement: args.literal_replacement,
+        template: args.template,
+        line_ending: args.line_ending.line_ending(),
+        ensure_trailing_newline: args.ensure_trailing_newline,
+        no_follow_symlink_writes: args.no_follow_symlink_writes,
+        check_unchanged: args.check_unchanged,
+        skip_in_strings: args.skip_in_strings,
+        rename: args.rename,
+        timeout: args
+            .timeout
+            .map(|timeout| Duration::from_millis(timeout.get())),
+        replace_command: args.replace_command.as_deref().map(ReplaceCommand::new),
+        replacement_functions: args.functions.then(|| {
+            let mut functions = ReplacementFunctions::default();
+            for (name, function) in &args.function_names {
+                functions.register(name.clone(), *function);
+            }
+            functions
+        }),
+        delete_empty_lines: args.delete && (args.delete_line || args.line_match),
+        collapse_duplicate_lines: args.collapse_duplicate_lines,
+        preserve_indent: args.preserve_indent,
+        lossy: args.lossy,
+        post_command: args.post_command.as_deref().map(PostCommand::new),
+        join_lines: args.join_lines,
+        undo_journal: args
+            .undo_log
+            .then(|| UndoJournal::new(&undo_journals_dir())),
+        search_compressed: args.search_compressed,
+        compression_level: args.compression_level,
+        file_store: None,
+    }
+}
+
+fn search_config_from_args(args: &Args) -> SearchConfig<'_> {
+    SearchConfig {
+        search_text: args.search_text.as_deref().unwrap_or(""),
+        replacement_text: args.replace_text.as_deref().unwrap_or(""),
+        fixed_strings: args.fixed_strings,
+        advanced_regex: args.advanced_regex,
+        match_whole_word: args.match_whole_word,
+        match_whole_line: args.line_match,
+        match_case: !args.case_insensitive,
+        multiline: args.multiline,
+        dotall: args.dotall,
+        additional_exprs: &args.exprs,
+        word_chars: args.word_chars.as_deref().unwrap_or(""),
+        strict_captures: args.strict_captures,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup_test_dir() -> TempDir {
+        TempDir::new().unwrap()
+    }
+
+    #[test]
+    fn test_validate_directory_exists() {
+        let temp_dir = setup_test_dir();
+        let dir_path = temp_dir.path().to_str().unwrap();
+
+        let result = parse_directory(dir_path);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), PathBuf::from(dir_path));
+    }
+
+    #[test]
+    fn test_validate_directory_does_not_exist() {
         let nonexistent_path = "/path/that/definitely/does/not/exist/12345";
         let result = parse_directory(nonexistent_path);
 
-        assert!(result.is_err());
-        let err = result.unwrap_err().to_string();
-        assert!(err.contains("does not exist"));
-        assert!(err.contains(nonexistent_path));
+        assert!(result.is_err());
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("does not exist"));
+        assert!(err.contains(nonexistent_path));
+    }
+
+    #[test]
+    fn test_validate_directory_with_nested_structure() {
+        let temp_dir = setup_test_dir();
+        let nested_dir = temp_dir.path().join("nested").join("directory");
+        std::fs::create_dir_all(&nested_dir).expect("Failed to create nested directories");
+
+        let dir_path = nested_dir.to_str().unwrap();
+        let result = parse_directory(dir_path);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), nested_dir);
+    }
+
+    #[test]
+    fn test_validate_directory_with_special_chars() {
+        let temp_dir = setup_test_dir();
+        let special_dir = temp_dir.path().join("test with spaces and-symbols_!@#$");
+        std::fs::create_dir(&special_dir)
+            .expect("Failed to create directory with special characters");
+
+        let dir_path = special_dir.to_str().unwrap();
+        let result = parse_directory(dir_path);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), special_dir);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("123").unwrap(), 123);
+        assert_eq!(parse_size("512K").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("10M").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("10mb").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("2G").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("99999999999999999999G").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(15 * 60));
+        assert_eq!(
+            parse_duration("2h").unwrap(),
+            Duration::from_secs(2 * 60 * 60)
+        );
+        assert_eq!(
+            parse_duration("7D").unwrap(),
+            Duration::from_secs(7 * 24 * 60 * 60)
+        );
+        assert_eq!(
+            parse_duration("1w").unwrap(),
+            Duration::from_secs(7 * 24 * 60 * 60)
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("2y").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let from_epoch = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(parse_date("1970-01-01").unwrap(), from_epoch(0));
+        assert_eq!(parse_date("2000-03-01").unwrap(), from_epoch(951_868_800));
+        assert_eq!(parse_date("2024-02-29").unwrap(), from_epoch(1_709_164_800));
+        assert_eq!(
+            parse_date("2024-01-31T14:30").unwrap(),
+            from_epoch(1_706_711_400)
+        );
+        assert_eq!(
+            parse_date("2024-01-31 14:30:15").unwrap(),
+            from_epoch(1_706_711_415)
+        );
+        for invalid in [
+            "",
+            "2024-01",
+            "2024-13-01",
+            "2023-02-29",
+            "1969-12-31",
+            "2024-01-31T24:00",
+            "2024-01-31T14",
+            "2024-1-+1",
+            "yesterday",
+        ] {
+            assert!(parse_date(invalid).is_err(), "{invalid} should be invalid");
+        }
+    }
+
+    #[test]
+    fn test_read_pattern_file_trims_single_trailing_newline() {
+        let temp_dir = setup_test_dir();
+        let path = temp_dir.path().join("pattern.txt");
+        for (contents, expected) in [
+            ("foo", "foo"),
+            ("foo\n", "foo"),
+            ("foo\r\n", "foo"),
+            ("foo\nbar\n\n", "foo\nbar\n"),
+        ] {
+            std::fs::write(&path, contents).unwrap();
+            assert_eq!(read_pattern_file(&path).unwrap(), expected);
+        }
+        assert!(read_pattern_file(&temp_dir.path().join("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn test_load_pattern_files() {
+        let temp_dir = setup_test_dir();
+        let search_file = temp_dir.path().join("search.txt");
+        let replace_file = temp_dir.path().join("replace.txt");
+        std::fs::write(&search_file, "foo\\s+bar\n").unwrap();
+        std::fs::write(&replace_file, "baz\n").unwrap();
+
+        let mut args = Args {
+            search_text: None,
+            replace_text: None,
+            search_file: Some(search_file),
+            replace_file: Some(replace_file),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        load_pattern_files(&mut args).unwrap();
+        assert_eq!(args.search_text.as_deref(), Some("foo\\s+bar"));
+        assert_eq!(args.replace_text.as_deref(), Some("baz"));
+    }
+
+    #[test]
+    fn test_validate_args_replace_option() {
+        // An explicitly empty replacement is accepted without --delete
+        let mut args = Args {
+            replace_text: None,
+            replace_option: Some(String::new()),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        load_pattern_files(&mut args).unwrap();
+        assert_eq!(args.replace_text.as_deref(), Some(""));
+        assert_eq!(search_config_from_args(&args).replacement_text, "");
+
+        let args = Args {
+            replace_option: Some("other".to_string()),
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot specify both replacement text and --replace")
+        );
+
+        let args = Args {
+            replace_text: None,
+            replace_option: Some(String::new()),
+            delete: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("You cannot specify both replacement text and the --delete flag")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_pattern_files_conflict_with_positional_text() {
+        let args = Args {
+            search_file: Some(PathBuf::from("search.txt")),
+            ..test_args()
+        };
+        let res = validate_args(&args, None);
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("--search-file"));
+
+        let args = Args {
+            replace_file: Some(PathBuf::from("replace.txt")),
+            ..test_args()
+        };
+        let res = validate_args(&args, None);
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("--replace-file"));
+    }
+
+    #[test]
+    fn test_interpret_escapes() {
+        assert_eq!(
+            interpret_escapes(r"a\tb\nc\r\0d\\n").unwrap(),
+            "a\tb\nc\r\0d\\n"
+        );
+        assert_eq!(interpret_escapes("no escapes").unwrap(), "no escapes");
+        assert!(
+            interpret_escapes(r"foo\q")
+                .unwrap_err()
+                .to_string()
+                .contains("Invalid escape sequence '\\q'")
+        );
+        assert!(interpret_escapes("foo\\").is_err());
+    }
+
+    #[test]
+    fn test_interpret_escapes_in_args() {
+        let mut args = Args {
+            search_text: Some(r"a\tb".to_string()),
+            replace_text: Some(r"a\nb".to_string()),
+            exprs: vec![(r"\\".to_string(), r"\0".to_string())],
+            fixed_strings: true,
+            interpret_escapes: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        interpret_escapes_in_args(&mut args).unwrap();
+        assert_eq!(args.search_text.as_deref(), Some("a\tb"));
+        assert_eq!(args.replace_text.as_deref(), Some("a\nb"));
+        assert_eq!(args.exprs, vec![("\\".to_string(), "\0".to_string())]);
+
+        let args = Args {
+            interpret_escapes: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_expr() {
+        assert_eq!(
+            parse_expr("foo=bar").unwrap(),
+            ("foo".to_owned(), "bar".to_owned())
+        );
+        assert_eq!(
+            parse_expr("a=b=c").unwrap(),
+            ("a".to_owned(), "b=c".to_owned())
+        );
+        assert_eq!(
+            parse_expr("foo=").unwrap(),
+            ("foo".to_owned(), String::new())
+        );
+        assert!(parse_expr("foo").is_err());
+        assert!(parse_expr("=bar").is_err());
+    }
+
+    #[test]
+    fn test_validate_args_exprs_without_search_text() {
+        let args = Args {
+            search_text: None,
+            replace_text: None,
+            exprs: vec![("foo".to_owned(), "bar".to_owned())],
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let args = Args {
+            search_text: None,
+            replace_text: None,
+            delete: true,
+            exprs: vec![("foo".to_owned(), "bar".to_owned())],
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+
+        let args = Args {
+            replace_text: None,
+            count: true,
+            exprs: vec![("foo".to_owned(), "bar".to_owned())],
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    fn test_args() -> Args {
+        Args {
+            search_text: Some("search".to_string()),
+            replace_text: Some("replace".to_string()),
+            search_file: None,
+            replace_option: None,
+            replace_file: None,
+            replace_stdin: false,
+            replace_command: None,
+            post_command: None,
+            undo_log: false,
+            undo: false,
+            stdin_to: None,
+            join_lines: None,
+            exprs: vec![],
+            directory: vec![PathBuf::from(".")],
+            fixed_strings: false,
+            interpret_escapes: false,
+            match_whole_word: false,
+            word_chars: None,
+            line_match: false,
+            preserve_indent: false,
+            case_insensitive: false,
+            multiline: false,
+            dotall: false,
+            include_files: None,
+            exclude_files: None,
+            glob_precedence: GlobPrecedenceChoice::Exclude,
+            glob_file: None,
+            types: vec![],
+            types_not: vec![],
+            ext: None,
+            ignore_file: vec![],
+            hidden: false,
+            follow: false,
+            no_follow_symlink_writes: false,
+            check_unchanged: false,
+            skip_in_strings: false,
+            rename: false,
+            max_depth: None,
+            no_ignore: false,
+            text: false,
+            error_on_binary: false,
+            log_level: LevelFilter::Info,
+            no_config: false,
+            advanced_regex: false,
+            delete: false,
+            delete_line: false,
+            collapse_duplicate_lines: false,
+            dry_run: false,
+            diff: false,
+            backup: None,
+            format: OutputFormat::Text,
+            color: ColorChoice::Never,
+            count: false,
+            encoding: None,
+            lossy: false,
+            search_compressed: false,
+            compression_level: None,
+            line_ending: LineEndingChoice::Keep,
+            ensure_trailing_newline: false,
+            max_in_memory_size: None,
+            min_size: None,
+            max_size: None,
+            changed_within: None,
+            changed_before: None,
+            keep_mtime: false,
+            temp_dir: None,
+            in_place: false,
+            warn_mixed_endings: false,
+            threads: None,
+            sort: None,
+            case_transforms: false,
+            functions: false,
+            function_names: vec![],
+            literal_replacement: false,
+            strict_captures: false,
+            template: false,
+            debug_globs: false,
+            list_files: false,
+            name_only: false,
+            occurrence: None,
+            interactive: false,
+            max_count: None,
+            max_per_file: None,
+            expect: None,
+            first_only: false,
+            only_matching_lines: None,
+            skip_lines: None,
+            timeout: None,
+            progress: false,
+            verbose: false,
+            stats: false,
+            context: None,
+            after_context: None,
+            before_context: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_args_with_replacement_text() {
+        let args = Args {
+            replace_text: Some("replace".to_string()),
+            delete: false,
+            ..test_args()
+        };
+
+        let result = validate_args(&args, None);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validate_args_functions() {
+        let args = Args::try_parse_from([
+            "frep",
+            r"(\w+)",
+            r"\{upper:$1\}",
+            "--functions",
+            "--fn",
+            "upper=uppercase",
+        ])
+        .unwrap();
+        assert!(validate_args(&args, None).is_ok());
+        let functions = searcher_config_from_args(&args)
+            .replacement_functions
+            .unwrap();
+        assert_eq!(functions.apply(r"\{upper:abc\}"), "ABC");
+
+        assert!(Args::try_parse_from(["frep", "a", "b", "--fn", "upper=shout"]).is_err());
+        assert!(Args::try_parse_from(["frep", "a", "b", "--fn", "uppercase"]).is_err());
+
+        let args = Args {
+            functions: false,
+            ..args
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --fn without --functions")
+        );
+
+        let args = Args {
+            functions: true,
+            case_transforms: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --functions with --fixed-strings")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_replace_command() {
+        let args = Args {
+            replace_text: None,
+            replace_command: Some("tr a-z A-Z".to_string()),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(
+            searcher_config_from_args(&args)
+                .replace_command
+                .map(|command| command.command().to_owned()),
+            Some("tr a-z A-Z".to_string())
+        );
+
+        let args = Args {
+            replace_command: Some("tr a-z A-Z".to_string()),
+            ..test_args()
+        };
+        let res = validate_args(&args, None);
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot specify both replacement text and --replace-command")
+        );
+
+        let args = Args {
+            replace_text: None,
+            replace_command: Some("tr a-z A-Z".to_string()),
+            occurrence: Some(2),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+
+        let args = Args {
+            replace_text: None,
+            replace_command: Some("tr a-z A-Z".to_string()),
+            delete: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_args_with_delete_flag() {
+        let args = Args {
+            replace_text: None,
+            delete: true,
+            ..test_args()
+        };
+
+        let result = validate_args(&args, None);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validate_args_with_both_replacement_and_delete() {
+        let args = Args {
+            replace_text: Some("replace".to_string()),
+            delete: true,
+            ..test_args()
+        };
+
+        let result = validate_args(&args, None);
+        assert!(result.is_err());
+
+        let error_message = result.unwrap_err().to_string();
+        assert!(
+            error_message.contains("cannot specify both")
+                && error_message.contains("replacement text")
+                && error_message.contains("--delete"),
+            "Error message should explain that both options cannot be used together"
+        );
+    }
+
+    #[test]
+    fn test_validate_args_with_neither_replacement_nor_delete() {
+        let args = Args {
+            replace_text: None,
+            delete: false,
+            ..test_args()
+        };
+
+        let result = validate_args(&args, None);
+        assert!(result.is_err());
+
+        let error_message = result.unwrap_err().to_string();
+        assert!(
+            error_message.contains("must specify either")
+                && error_message.contains("replacement text")
+                && error_message.contains("--delete"),
+            "Error message should mention both replacement text and --delete option"
+        );
+    }
+
+    #[test]
+    fn test_validate_args_stdin_disallows_hidden() {
+        let args = Args {
+            hidden: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().contains("Cannot use --hidden"));
+    }
+
+    #[test]
+    fn test_validate_args_stdin_disallows_include_exclude() {
+        let args = Args {
+            include_files: Some("**/*.rs".into()),
+            exclude_files: Some("target/**".into()),
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        let msg = res.unwrap_err().to_string();
+        assert!(
+            msg.contains("Cannot use --include-files")
+                || msg.contains("Cannot use --exclude-files")
+        );
+    }
+
+    #[test]
+    fn test_glob_precedence() {
+        let args = Args::try_parse_from(["frep", "search", "replace"]).unwrap();
+        assert_eq!(
+            dir_config_from_args(&args).glob_precedence,
+            GlobPrecedence::Exclude
+        );
+
+        let args =
+            Args::try_parse_from(["frep", "search", "replace", "--glob-precedence", "include"])
+                .unwrap();
+        assert_eq!(
+            dir_config_from_args(&args).glob_precedence,
+            GlobPrecedence::Include
+        );
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --glob-precedence when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_stdin_disallows_dry_run() {
+        let args = Args {
+            dry_run: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --dry-run")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_disallows_empty_backup_suffix() {
+        let args = Args {
+            backup: Some(String::new()),
+            ..test_args()
+        };
+        let res = validate_args(&args, None);
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Backup suffix must not be empty")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_stdin_to() {
+        let args = Args {
+            stdin_to: Some(PathBuf::from("out.txt")),
+            ..test_args()
+        };
+        let s = String::from("input");
+        assert!(validate_args(&args, Some(&s)).is_ok());
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --stdin-to without text piped to stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_replace_stdin() {
+        let args = Args {
+            replace_text: None,
+            replace_stdin: true,
+            ..test_args()
+        };
+        let s = String::from("replacement\n");
+        assert!(validate_args(&args, Some(&s)).is_ok());
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --replace-stdin without text piped to stdin")
+        );
+
+        let args = Args {
+            replace_text: Some("bar".to_string()),
+            replace_stdin: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot specify both replacement text and --replace-stdin")
+        );
+
+        // Options that only apply to files are allowed, as the files are processed rather than stdin
+        let args = Args {
+            replace_text: None,
+            replace_stdin: true,
+            include_files: Some("*.rs".to_string()),
+            ..test_args()
+        };
+        assert!(validate_args(&args, Some(&s)).is_ok());
+
+        let args = Args {
+            replace_text: None,
+            replace_stdin: true,
+            interactive: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, Some(&s)).is_err());
+    }
+
+    #[test]
+    fn test_regex_error_description() {
+        let args = Args::try_parse_from(["frep", "foo(?=bar)", "baz"]).unwrap();
+        let error = run::find_and_replace_text_with_config(
+            "foobar",
+            search_config_from_args(&args),
+            &searcher_config_from_args(&args),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("Failed to parse search text"));
+        assert!(error.contains("Lookaround requires advanced regex"));
+        assert!(error.contains("--advanced-regex"));
+
+        let args = Args::try_parse_from(["frep", "[abc", "x"]).unwrap();
+        let error = run::find_and_replace_text_with_config(
+            "abc",
+            search_config_from_args(&args),
+            &searcher_config_from_args(&args),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("Unbalanced bracket in regex"));
+        assert!(!error.contains("--advanced-regex"));
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.txt");
+        write_atomically(&path, "first\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first\n");
+
+        write_atomically(&path, "second\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        assert!(write_atomically(&temp_dir.path().join("missing/out.txt"), "").is_err());
+    }
+
+    #[test]
+    fn test_validate_args_stdin_disallows_json_format() {
+        let args = Args {
+            format: OutputFormat::Json,
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --format json")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_count_without_replacement() {
+        let args = Args {
+            replace_text: None,
+            count: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+    }
+
+    #[test]
+    fn test_validate_args_count_with_replacement() {
+        let args = Args {
+            count: true,
+            ..test_args()
+        };
+        let res = validate_args(&args, None);
+        assert!(res.unwrap_err().to_string().contains("Cannot use --count"));
+    }
+
+    #[test]
+    fn test_validate_args_name_only() {
+        let args = Args {
+            name_only: true,
+            replace_text: None,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let args = Args {
+            name_only: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --name-only with replacement text")
+        );
+
+        let args = Args {
+            name_only: true,
+            count: true,
+            replace_text: None,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --count with --name-only")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_context_requires_line_level_output() {
+        let args = Args {
+            context: Some(2),
+            ..test_args()
+        };
+        let res = validate_args(&args, None);
+        assert!(res.is_err());
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --context")
+        );
+
+        for args in [
+            Args {
+                context: Some(2),
+                dry_run: true,
+                ..test_args()
+            },
+            Args {
+                after_context: Some(1),
+                diff: true,
+                ..test_args()
+            },
+            Args {
+                before_context: Some(1),
+                count: true,
+                replace_text: None,
+                ..test_args()
+            },
+        ] {
+            assert!(validate_args(&args, None).is_ok());
+        }
+
+        let args = Args {
+            context: Some(2),
+            dry_run: true,
+            format: OutputFormat::Json,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_args_interactive() {
+        let args = Args {
+            interactive: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --interactive flag when stdin is not a terminal")
+        );
+
+        let args = Args {
+            interactive: true,
+            dry_run: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_searcher_config_color() {
+        let args = Args {
+            color: ColorChoice::Always,
+            ..test_args()
+        };
+        assert!(searcher_config_from_args(&args).color);
+
+        let args = Args {
+            color: ColorChoice::Always,
+            format: OutputFormat::Json,
+            ..test_args()
+        };
+        assert!(!searcher_config_from_args(&args).color);
+
+        assert!(!searcher_config_from_args(&test_args()).color);
+    }
+
+    #[test]
+    fn test_validate_args_occurrence() {
+        let args = Args {
+            occurrence: Some(2),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(searcher_config_from_args(&args).occurrence, NonZero::new(2));
+
+        let args = Args {
+            occurrence: Some(0),
+            ..test_args()
+        };
+        assert_eq!(searcher_config_from_args(&args).occurrence, None);
+
+        let args = Args {
+            occurrence: Some(2),
+            multiline: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_args_preserve_indent() {
+        let args = Args {
+            preserve_indent: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).preserve_indent);
+
+        let args = Args {
+            multiline: true,
+            ..args
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_args_collapse_duplicate_lines() {
+        let args = Args {
+            collapse_duplicate_lines: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).collapse_duplicate_lines);
+
+        let args = Args {
+            dry_run: true,
+            ..args
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --collapse-duplicate-lines with")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_delete_line() {
+        let args = Args {
+            replace_text: None,
+            delete: true,
+            delete_line: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).delete_empty_lines);
+
+        let args = Args {
+            delete_line: false,
+            line_match: true,
+            ..args
+        };
+        assert!(searcher_config_from_args(&args).delete_empty_lines);
+
+        let args = Args {
+            delete_line: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+
+        let args = Args {
+            replace_text: None,
+            delete: true,
+            delete_line: true,
+            multiline: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_args_word_chars() {
+        let args = Args {
+            match_whole_word: true,
+            word_chars: Some("-".to_string()),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(search_config_from_args(&args).word_chars, "-");
+
+        let args = Args {
+            word_chars: Some("-".to_string()),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+    }
+
+    #[test]
+    fn test_validate_args_strict_captures() {
+        let args = Args {
+            strict_captures: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(search_config_from_args(&args).strict_captures);
+
+        let args = Args {
+            strict_captures: true,
+            literal_replacement: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains(
+                    "Cannot use --strict-captures with --literal-replacement or --replace-command"
+                )
+        );
+    }
+
+    #[test]
+    fn test_validate_args_size_filter() {
+        let args = Args {
+            min_size: Some(10),
+            max_size: Some(1024),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(
+            searcher_config_from_args(&args).size_filter,
+            Some((Some(10), Some(1024)))
+        );
+        assert_eq!(searcher_config_from_args(&test_args()).size_filter, None);
+
+        let args = Args {
+            min_size: Some(1024),
+            max_size: Some(10),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+
+        let args = Args {
+            max_size: Some(1024),
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --min-size or --max-size when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_modified_filter() {
+        let changed_before = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let args = Args {
+            changed_before: Some(changed_before),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(
+            searcher_config_from_args(&args).modified_filter,
+            Some((None, Some(changed_before)))
+        );
+        assert_eq!(
+            searcher_config_from_args(&test_args()).modified_filter,
+            None
+        );
+
+        let args = Args {
+            changed_within: Some(Duration::from_secs(60 * 60)),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        let (changed_after, _) = searcher_config_from_args(&args).modified_filter.unwrap();
+        assert!(changed_after.unwrap() < SystemTime::now());
+
+        // Files can't have been modified in the last hour and before a date in the past
+        let args = Args {
+            changed_before: Some(changed_before),
+            ..args
+        };
+        assert!(validate_args(&args, None).is_err());
+
+        let args = Args {
+            changed_within: Some(Duration::from_secs(60 * 60)),
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --changed-within or --changed-before when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_skip_in_strings() {
+        let args = Args {
+            skip_in_strings: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).skip_in_strings);
+
+        let args = Args {
+            skip_in_strings: true,
+            multiline: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+
+        let args = Args {
+            skip_in_strings: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --skip-in-strings flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_verbose() {
+        let args = Args {
+            verbose: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let args = Args {
+            verbose: true,
+            dry_run: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        for args in [
+            Args {
+                replace_text: None,
+                verbose: true,
+                count: true,
+                ..test_args()
+            },
+            Args {
+                verbose: true,
+                diff: true,
+                ..test_args()
+            },
+            Args {
+                verbose: true,
+                format: OutputFormat::Json,
+                ..test_args()
+            },
+        ] {
+            let res = validate_args(&args, None);
+            assert!(
+                res.unwrap_err()
+                    .to_string()
+                    .contains("Cannot use --verbose with --count, --diff or --format json")
+            );
+        }
+
+        let args = Args {
+            verbose: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --verbose flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_stats() {
+        let args = Args {
+            stats: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        for args in [
+            Args {
+                stats: true,
+                count: true,
+                replace_text: None,
+                ..test_args()
+            },
+            Args {
+                stats: true,
+                interactive: true,
+                ..test_args()
+            },
+        ] {
+            let res = validate_args(&args, None);
+            assert!(
+                res.unwrap_err()
+                    .to_string()
+                    .contains("Cannot use --stats with --count or --interactive")
+            );
+        }
+
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --stats flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_rename() {
+        let args = Args {
+            rename: true,
+            dry_run: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).rename);
+
+        let args = Args {
+            rename: true,
+            count: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
+
+        let args = Args {
+            rename: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --rename flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_template() {
+        let args = Args {
+            template: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).template);
+
+        let args = Args {
+            template: true,
+            multiline: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --template with")
+        );
+
+        let args = Args {
+            template: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --template flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_in_place() {
+        let args = Args {
+            in_place: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).in_place);
+
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --in-place flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_ensure_trailing_newline() {
+        let args = Args {
+            ensure_trailing_newline: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).ensure_trailing_newline);
+
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --ensure-trailing-newline flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_warn_mixed_endings() {
+        let args = Args {
+            warn_mixed_endings: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).warn_mixed_endings);
+
+        let args = Args {
+            replace_text: None,
+            warn_mixed_endings: true,
+            count: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --warn-mixed-endings with --count or --name-only")
+        );
+
+        let args = Args {
+            warn_mixed_endings: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --warn-mixed-endings flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let config = config::Config {
+            hidden: Some(true),
+            advanced_regex: Some(true),
+            exclude_files: Some("*.lock".to_string()),
+            threads: NonZero::new(4),
+            ..config::Config::default()
+        };
+
+        let matches = Args::command()
+            .try_get_matches_from(["frep", "search", "replace", "--threads", "2"])
+            .unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        apply_config(&mut args, config, &matches);
+        assert!(args.hidden);
+        assert!(args.advanced_regex);
+        assert!(!args.follow);
+        assert_eq!(args.exclude_files.as_deref(), Some("*.lock"));
+        // Options given on the command line take precedence
+        assert_eq!(args.threads, NonZero::new(2));
+    }
+
+    #[test]
+    fn test_validate_args_error_on_binary() {
+        let args = Args {
+            error_on_binary: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).error_on_binary);
+
+        let args = Args {
+            error_on_binary: true,
+            text: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --error-on-binary with --text, --count or --name-only")
+        );
+
+        let args = Args {
+            error_on_binary: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --error-on-binary flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_debug_globs() {
+        let args = Args {
+            debug_globs: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --debug-globs flag when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_list_files() {
+        let args =
+            Args::try_parse_from(["frep", "--list-files", "--include-files", "*.rs"]).unwrap();
+        assert!(validate_args(&args, None).is_ok());
+
+        let args = Args {
+            search_text: Some("search".to_string()),
+            ..args
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --list-files flag when processing stdin")
+        );
+
+        let args = Args {
+            count: true,
+            ..args
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --list-files with --count")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_ndjson() {
+        let args =
+            Args::try_parse_from(["frep", "search", "replace", "--format", "ndjson"]).unwrap();
+        assert!(validate_args(&args, None).is_ok());
+
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --format json or --format ndjson when processing stdin")
+        );
+
+        let args = Args {
+            verbose: true,
+            ..args
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --format ndjson with")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_max_count() {
+        let args = Args {
+            max_count: Some(3),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --max-count when processing stdin")
+        );
+
+        let args = Args {
+            max_count: Some(3),
+            multiline: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
     }
 
     #[test]
-    fn test_validate_directory_with_nested_structure() {
-        let temp_dir = setup_test_dir();
-        let nested_dir = temp_dir.path().join("nested").join("directory");
-        std::fs::create_dir_all(&nested_dir).expect("Failed to create nested directories");
+    fn test_validate_args_sort() {
+        let args = Args::try_parse_from(["frep", "search", "replace", "--sort", "mtime"]).unwrap();
+        assert_eq!(args.sort, Some(SortChoice::Mtime));
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(
+            searcher_config_from_args(&args).sort,
+            Some(FileOrder::Modified)
+        );
 
-        let dir_path = nested_dir.to_str().unwrap();
-        let result = parse_directory(dir_path);
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --sort when processing stdin")
+        );
+        assert!(Args::try_parse_from(["frep", "search", "replace", "--sort", "name"]).is_err());
+    }
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), nested_dir);
+    #[test]
+    fn test_validate_args_expect() {
+        let args = Args {
+            expect: Some(3),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(searcher_config_from_args(&args).expect_count, Some(3));
+
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --expect when processing stdin")
+        );
+
+        let args = Args {
+            interactive: true,
+            ..args
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --expect with")
+        );
     }
 
     #[test]
-    fn test_validate_directory_with_special_chars() {
-        let temp_dir = setup_test_dir();
-        let special_dir = temp_dir.path().join("test with spaces and-symbols_!@#$");
-        std::fs::create_dir(&special_dir)
-            .expect("Failed to create directory with special characters");
+    fn test_validate_args_max_per_file() {
+        let args = Args {
+            max_per_file: Some(3),
+            dry_run: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(searcher_config_from_args(&args).max_per_file, Some(3));
 
-        let dir_path = special_dir.to_str().unwrap();
-        let result = parse_directory(dir_path);
+        let args = Args {
+            max_per_file: Some(3),
+            ..test_args()
+        };
+        let s = String::from("input");
+        let res = validate_args(&args, Some(&s));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --max-per-file when processing stdin")
+        );
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), special_dir);
+        let args = Args {
+            max_per_file: Some(3),
+            multiline: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
     }
 
-    fn test_args() -> Args {
-        Args {
-            search_text: "search".to_string(),
-            replace_text: Some("replace".to_string()),
-            directory: PathBuf::from("."),
-            fixed_strings: false,
-            match_whole_word: false,
-            case_insensitive: false,
-            include_files: None,
-            exclude_files: None,
-            hidden: false,
-            log_level: LevelFilter::Info,
-            advanced_regex: false,
-            delete: false,
-        }
+    #[test]
+    fn test_validate_args_undo_log() {
+        let args = Args {
+            undo_log: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        let undo_journal = searcher_config_from_args(&args).undo_journal.unwrap();
+        assert!(undo_journal.dir().starts_with(undo_journals_dir()));
+        assert!(
+            searcher_config_from_args(&test_args())
+                .undo_journal
+                .is_none()
+        );
+
+        let args = Args {
+            undo_log: true,
+            dry_run: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --undo-log with")
+        );
+
+        let args = Args {
+            undo_log: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --undo-log or --undo when processing stdin")
+        );
     }
 
     #[test]
-    fn test_validate_args_with_replacement_text() {
+    fn test_validate_args_join_lines() {
         let args = Args {
-            replace_text: Some("replace".to_string()),
-            delete: false,
+            join_lines: NonZero::new(5),
+            multiline: true,
             ..test_args()
         };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(searcher_config_from_args(&args).join_lines, NonZero::new(5));
 
-        let result = validate_args(&args, None);
-        assert!(result.is_ok());
+        let args = Args {
+            join_lines: NonZero::new(5),
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --join-lines without --multiline")
+        );
+
+        let args = Args {
+            join_lines: NonZero::new(5),
+            multiline: true,
+            exprs: vec![("a".to_string(), "b".to_string())],
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --join-lines with --expr")
+        );
     }
 
     #[test]
-    fn test_validate_args_with_delete_flag() {
+    fn test_validate_args_post_command() {
+        let args = Args {
+            post_command: Some("rustfmt {}".to_string()),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(
+            searcher_config_from_args(&args).post_command,
+            Some(PostCommand::new("rustfmt {}"))
+        );
+
+        let args = Args {
+            post_command: Some("rustfmt {}".to_string()),
+            dry_run: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --post-command with")
+        );
+
+        let args = Args {
+            post_command: Some("rustfmt {}".to_string()),
+            ..test_args()
+        };
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --post-command when processing stdin")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_search_compressed() {
+        let args = Args {
+            search_compressed: true,
+            compression_level: Some(9),
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        let config = searcher_config_from_args(&args);
+        assert!(config.search_compressed);
+        assert_eq!(config.compression_level, Some(9));
+
         let args = Args {
             replace_text: None,
-            delete: true,
+            search_compressed: true,
+            count: true,
             ..test_args()
         };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --search-compressed with")
+        );
 
-        let result = validate_args(&args, None);
-        assert!(result.is_ok());
+        let args = Args {
+            compression_level: Some(1),
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --compression-level without --search-compressed")
+        );
+
+        let args = Args {
+            search_compressed: true,
+            ..test_args()
+        };
+        let s = String::from("input");
+        assert!(
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains(
+                    "Cannot use --search-compressed or --compression-level when processing stdin"
+                )
+        );
     }
 
     #[test]
-    fn test_validate_args_with_both_replacement_and_delete() {
+    fn test_validate_args_lossy() {
         let args = Args {
-            replace_text: Some("replace".to_string()),
-            delete: true,
+            lossy: true,
             ..test_args()
         };
+        assert!(validate_args(&args, None).is_ok());
+        assert!(searcher_config_from_args(&args).lossy);
 
-        let result = validate_args(&args, None);
-        assert!(result.is_err());
+        let args = Args {
+            lossy: true,
+            multiline: true,
+            ..test_args()
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --lossy with --multiline")
+        );
 
-        let error_message = result.unwrap_err().to_string();
+        let args = Args {
+            lossy: true,
+            ..test_args()
+        };
+        let s = String::from("input");
         assert!(
-            error_message.contains("cannot specify both")
-                && error_message.contains("replacement text")
-                && error_message.contains("--delete"),
-            "Error message should explain that both options cannot be used together"
+            validate_args(&args, Some(&s))
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --lossy flag when processing stdin")
         );
     }
 
     #[test]
-    fn test_validate_args_with_neither_replacement_nor_delete() {
+    fn test_validate_args_first_only() {
+        let args = Args {
+            first_only: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        let s = String::from("input");
+        assert!(validate_args(&args, Some(&s)).is_ok());
+        let searcher_config = searcher_config_from_args(&args);
+        assert!(searcher_config.first_only);
+        assert!(!searcher_config.collect_line_results);
+
+        let args = Args {
+            verbose: true,
+            ..args
+        };
+        assert!(searcher_config_from_args(&args).collect_line_results);
+
         let args = Args {
             replace_text: None,
-            delete: false,
+            first_only: true,
+            count: true,
             ..test_args()
         };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --first-only with --count or --multiline")
+        );
+    }
 
-        let result = validate_args(&args, None);
-        assert!(result.is_err());
+    #[test]
+    fn test_validate_args_only_matching_lines() {
+        let args = Args::try_parse_from(["frep", "port", "8080", "--only-matching-lines", "prod"])
+            .unwrap();
+        assert!(validate_args(&args, None).is_ok());
+        let guard = searcher_config_from_args(&args)
+            .only_matching_lines
+            .unwrap();
+        assert_eq!(guard.as_str(), "prod");
 
-        let error_message = result.unwrap_err().to_string();
         assert!(
-            error_message.contains("must specify either")
-                && error_message.contains("replacement text")
-                && error_message.contains("--delete"),
-            "Error message should mention both replacement text and --delete option"
+            Args::try_parse_from(["frep", "port", "8080", "--only-matching-lines", "(prod"])
+                .is_err()
+        );
+
+        let args = Args {
+            multiline: true,
+            ..args
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains(
+                    "Cannot use --only-matching-lines with --multiline, --count or --name-only"
+                )
         );
     }
 
     #[test]
-    fn test_validate_args_stdin_disallows_hidden() {
+    fn test_validate_args_skip_lines() {
+        let args = Args::try_parse_from(["frep", "old", "new", "--skip-lines", r"^\s*//"]).unwrap();
+        assert!(validate_args(&args, None).is_ok());
+        let skip = searcher_config_from_args(&args).skip_lines.unwrap();
+        assert_eq!(skip.as_str(), r"^\s*//");
+
         let args = Args {
-            hidden: true,
+            replace_text: None,
+            name_only: true,
+            ..args
+        };
+        assert!(
+            validate_args(&args, None)
+                .unwrap_err()
+                .to_string()
+                .contains("Cannot use --skip-lines with --multiline, --count or --name-only")
+        );
+    }
+
+    #[test]
+    fn test_validate_args_timeout() {
+        let args = Args {
+            timeout: NonZero::new(500),
             ..test_args()
         };
+        assert!(validate_args(&args, None).is_ok());
+        assert_eq!(
+            searcher_config_from_args(&args).timeout,
+            Some(Duration::from_millis(500))
+        );
+
         let s = String::from("input");
         let res = validate_args(&args, Some(&s));
-        assert!(res.is_err());
-        assert!(res.unwrap_err().to_string().contains("Cannot use --hidden"));
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --timeout when processing stdin")
+        );
+
+        let args = Args {
+            timeout: NonZero::new(500),
+            count: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_err());
     }
 
     #[test]
-    fn test_validate_args_stdin_disallows_include_exclude() {
+    fn test_no_files_found_warning() {
+        let stats = run::RunStats::default();
+        assert_eq!(no_files_found_warning(&test_args(), &stats), None);
+
         let args = Args {
-            include_files: Some("**/*.rs".into()),
-            exclude_files: Some("target/**".into()),
+            include_files: Some("*.rss".to_string()),
             ..test_args()
         };
-        let s = String::from("input");
-        let res = validate_args(&args, Some(&s));
-        let msg = res.unwrap_err().to_string();
         assert!(
-            msg.contains("Cannot use --include-files")
-                || msg.contains("Cannot use --exclude-files")
+            no_files_found_warning(&args, &stats)
+                .unwrap()
+                .contains("No files matched --include-files")
+        );
+
+        let stats = run::RunStats {
+            num_files_skipped: 1,
+            ..run::RunStats::default()
+        };
+        assert_eq!(no_files_found_warning(&args, &stats), None);
+    }
+
+    #[test]
+    fn test_arg_warnings_dotall() {
+        assert!(arg_warnings(&test_args()).is_empty());
+
+        let args = Args {
+            dotall: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+        let warnings = arg_warnings(&args);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("--dotall has no effect without --multiline"));
+
+        let args = Args {
+            dotall: true,
+            multiline: true,
+            ..test_args()
+        };
+        assert!(arg_warnings(&args).is_empty());
+        assert!(search_config_from_args(&args).dotall);
+    }
+
+    #[test]
+    fn test_validate_args_multiline_disallows_line_level_output() {
+        let args = Args {
+            multiline: true,
+            ..test_args()
+        };
+        assert!(validate_args(&args, None).is_ok());
+
+        let args = Args {
+            multiline: true,
+            dry_run: true,
+            ..test_args()
+        };
+        let res = validate_args(&args, None);
+        assert!(
+            res.unwrap_err()
+                .to_string()
+                .contains("Cannot use --multiline")
         );
     }
 }